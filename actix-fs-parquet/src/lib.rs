@@ -0,0 +1,300 @@
+//! A Parquet sink for [`actix-fs`], writing records into size-rotated files
+//! on the threadpool.
+//!
+//! This is a separate crate so that `parquet`, and the recent `chrono` it
+//! needs, stay out of the dependency graph of `actix-fs`.
+//!
+//! [`actix-fs`]: https://docs.rs/actix-fs
+use futures::future::{self, Either};
+use futures::Future;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::record::RecordWriter;
+
+use std::fmt;
+use std::fs::{self, File as StdFile, OpenOptions as StdOpenOptions};
+use std::io::{self, ErrorKind};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Options which can be used to configure a [`ParquetSink`].
+///
+/// [`ParquetSink`]: struct.ParquetSink.html
+#[derive(Clone, Debug)]
+pub struct ParquetOptions {
+    row_group_size: usize,
+    max_file_size: u64,
+    prefix: String,
+    properties: Arc<WriterProperties>,
+}
+
+impl ParquetOptions {
+    /// Creates the default set of options: row groups of 8192 records, files
+    /// rotated once they reach 128 MiB and named `part-NNNNN.parquet`.
+    pub fn new() -> ParquetOptions {
+        ParquetOptions {
+            row_group_size: 8192,
+            max_file_size: 128 * 1024 * 1024,
+            prefix: "part".to_owned(),
+            properties: Arc::new(WriterProperties::new()),
+        }
+    }
+
+    /// Sets how many records are buffered in memory before they are written
+    /// out as a row group.
+    pub fn row_group_size(&mut self, records: usize) -> &mut ParquetOptions {
+        self.row_group_size = records.max(1);
+        self
+    }
+
+    /// Sets the size in bytes after which the current file is closed and a
+    /// new one is started.
+    ///
+    /// Files are only rotated between row groups, so a file may exceed this
+    /// size by up to one row group.
+    pub fn max_file_size(&mut self, bytes: u64) -> &mut ParquetOptions {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// Sets the prefix of the file names.
+    pub fn prefix<S>(&mut self, prefix: S) -> &mut ParquetOptions
+    where
+        S: Into<String>,
+    {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the writer properties, such as compression, used for every file.
+    pub fn properties(&mut self, properties: WriterProperties) -> &mut ParquetOptions {
+        self.properties = Arc::new(properties);
+        self
+    }
+
+    /// Creates a sink writing files into `dir` with the options specified by
+    /// `self`.
+    pub fn open<T, P>(&self, dir: P) -> ParquetSink<T>
+    where
+        P: Into<PathBuf>,
+    {
+        ParquetSink {
+            inner: Arc::new(Inner {
+                dir: dir.into(),
+                opts: self.clone(),
+                pending: Mutex::new(Vec::new()),
+                current: Mutex::new(Current {
+                    file: None,
+                    next: 0,
+                }),
+            }),
+        }
+    }
+}
+
+impl Default for ParquetOptions {
+    fn default() -> ParquetOptions {
+        ParquetOptions::new()
+    }
+}
+
+struct Inner<T> {
+    dir: PathBuf,
+    opts: ParquetOptions,
+    pending: Mutex<Vec<T>>,
+    current: Mutex<Current>,
+}
+
+struct Current {
+    file: Option<OpenFile>,
+    // Index to try first when naming the next file.
+    next: u64,
+}
+
+struct OpenFile {
+    writer: SerializedFileWriter<StdFile>,
+    tmp: PathBuf,
+    path: PathBuf,
+}
+
+/// Buffers typed records and writes them into a directory of Parquet files.
+///
+/// Records are collected in memory and written out as a row group once
+/// enough have accumulated, or when [`flush`] is called. Writing happens on
+/// the threadpool. A file is closed and a new one started once it grows past
+/// the configured size.
+///
+/// Records are described by parquet's `RecordWriter` trait, which can be
+/// derived with the `parquet_derive` crate.
+///
+/// Files being written carry a `.tmp` suffix that is only removed once their
+/// footer has been written, so readers listing the directory never see an
+/// incomplete file. Call [`close`] before dropping the sink, or buffered
+/// records are lost and the current file is left behind unfinished.
+///
+/// Cloning a `ParquetSink` produces another handle to the same sink.
+///
+/// This is only available with the `parquet` feature.
+///
+/// [`flush`]: #method.flush
+/// [`close`]: #method.close
+pub struct ParquetSink<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> ParquetSink<T>
+where
+    T: Send + 'static,
+    for<'a> &'a [T]: RecordWriter<T>,
+{
+    /// Creates a sink writing files into `dir` with the default options.
+    ///
+    /// See [`ParquetOptions`] for more control.
+    ///
+    /// [`ParquetOptions`]: struct.ParquetOptions.html
+    pub fn new<P>(dir: P) -> ParquetSink<T>
+    where
+        P: Into<PathBuf>,
+    {
+        ParquetOptions::new().open(dir)
+    }
+
+    /// Returns the directory files are written to.
+    pub fn dir(&self) -> &Path {
+        &self.inner.dir
+    }
+
+    /// Returns the number of records buffered in memory.
+    pub fn buffered(&self) -> usize {
+        self.inner.pending.lock().unwrap().len()
+    }
+
+    /// Buffers `record`, writing out a row group once enough records have
+    /// accumulated.
+    pub fn write(&self, record: T) -> impl Future<Item = (), Error = io::Error> {
+        let batch = {
+            let mut pending = self.inner.pending.lock().unwrap();
+            pending.push(record);
+            if pending.len() >= self.inner.opts.row_group_size {
+                Some(mem::take(&mut *pending))
+            } else {
+                None
+            }
+        };
+        match batch {
+            Some(batch) => Either::B(self.write_batch(batch, false).map(|_| ())),
+            None => Either::A(future::ok(())),
+        }
+    }
+
+    /// Writes all buffered records out as a row group.
+    ///
+    /// The current file stays open; see [`close`] to finish it.
+    ///
+    /// [`close`]: #method.close
+    pub fn flush(&self) -> impl Future<Item = (), Error = io::Error> {
+        let batch = mem::take(&mut *self.inner.pending.lock().unwrap());
+        self.write_batch(batch, false).map(|_| ())
+    }
+
+    /// Writes all buffered records and finishes the current file, resolving
+    /// to its path, or `None` if no file was open.
+    ///
+    /// The sink can still be used afterwards; the next write starts a new
+    /// file.
+    pub fn close(&self) -> impl Future<Item = Option<PathBuf>, Error = io::Error> {
+        let batch = mem::take(&mut *self.inner.pending.lock().unwrap());
+        self.write_batch(batch, true)
+    }
+
+    fn write_batch(
+        &self,
+        batch: Vec<T>,
+        close: bool,
+    ) -> impl Future<Item = Option<PathBuf>, Error = io::Error> {
+        let inner = self.inner.clone();
+        actix_fs::spawn_blocking_fs(move || {
+            let mut current = inner.current.lock().unwrap();
+            let full = !batch.is_empty()
+                && write_row_group(&inner, &mut current, &batch)? >= inner.opts.max_file_size;
+            if !(full || close) {
+                return Ok(None);
+            }
+            match current.file.take() {
+                Some(file) => finish(file).map(Some),
+                None => Ok(None),
+            }
+        })
+    }
+}
+
+impl<T> Clone for ParquetSink<T> {
+    fn clone(&self) -> ParquetSink<T> {
+        ParquetSink {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for ParquetSink<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ParquetSink")
+            .field("dir", &self.inner.dir)
+            .field("opts", &self.inner.opts)
+            .finish()
+    }
+}
+
+/// Writes `batch` to the current file, starting one if needed, and returns
+/// the number of bytes written to the file so far.
+fn write_row_group<T>(inner: &Inner<T>, current: &mut Current, batch: &[T]) -> io::Result<u64>
+where
+    for<'a> &'a [T]: RecordWriter<T>,
+{
+    if current.file.is_none() {
+        current.file = Some(create(inner, current, batch)?);
+    }
+    let file = current.file.as_mut().unwrap();
+    let mut row_group = file.writer.next_row_group()?;
+    batch.write_to_row_group(&mut row_group)?;
+    row_group.close()?;
+    Ok(file.writer.bytes_written() as u64)
+}
+
+/// Starts a new file under the first name that is not taken yet.
+fn create<T>(inner: &Inner<T>, current: &mut Current, batch: &[T]) -> io::Result<OpenFile>
+where
+    for<'a> &'a [T]: RecordWriter<T>,
+{
+    fs::create_dir_all(&inner.dir)?;
+    loop {
+        let name = format!("{}-{:05}.parquet", inner.opts.prefix, current.next);
+        current.next += 1;
+        let path = inner.dir.join(&name);
+        if path.exists() {
+            continue;
+        }
+        let tmp = inner.dir.join(format!("{}.tmp", name));
+        let file = match StdOpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)
+        {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        };
+        let schema = batch.schema()?;
+        let writer = SerializedFileWriter::new(file, schema, inner.opts.properties.clone())?;
+        return Ok(OpenFile { writer, tmp, path });
+    }
+}
+
+/// Writes the footer, syncs the file and moves it to its final name.
+fn finish(file: OpenFile) -> io::Result<PathBuf> {
+    let std = file.writer.into_inner()?;
+    std.sync_all()?;
+    fs::rename(&file.tmp, &file.path)?;
+    Ok(file.path)
+}
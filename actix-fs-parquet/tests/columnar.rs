@@ -0,0 +1,116 @@
+use actix_fs_parquet::*;
+use futures::Future;
+use parquet::data_type::Int64Type;
+use parquet::errors::ParquetError;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::writer::SerializedRowGroupWriter;
+use parquet::record::RecordWriter;
+use parquet::schema::parser::parse_message_type;
+use parquet::schema::types::TypePtr;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+mod rt;
+
+struct Event {
+    id: i64,
+}
+
+impl RecordWriter<Event> for &[Event] {
+    fn write_to_row_group<W: Write + Send>(
+        &self,
+        row_group: &mut SerializedRowGroupWriter<W>,
+    ) -> Result<(), ParquetError> {
+        let ids: Vec<i64> = self.iter().map(|event| event.id).collect();
+        let mut column = row_group.next_column()?.unwrap();
+        column.typed::<Int64Type>().write_batch(&ids, None, None)?;
+        column.close()
+    }
+
+    fn schema(&self) -> Result<TypePtr, ParquetError> {
+        Ok(Arc::new(parse_message_type(
+            "message event { REQUIRED INT64 id; }",
+        )?))
+    }
+}
+
+fn num_rows(path: &Path) -> i64 {
+    let reader = SerializedFileReader::new(fs::File::open(path).unwrap()).unwrap();
+    reader.metadata().file_metadata().num_rows()
+}
+
+fn names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn buffers_until_row_group_is_full() {
+    let base_dir = tempdir().unwrap();
+    let sink = ParquetOptions::new()
+        .row_group_size(2)
+        .open::<Event, _>(base_dir.path());
+
+    let dir = base_dir.path().to_owned();
+
+    rt::run({
+        let sink = sink.clone();
+        sink.write(Event { id: 1 })
+            .map(move |_| {
+                assert!(fs::read_dir(&dir).unwrap().next().is_none());
+                dir
+            })
+            .and_then(move |dir| sink.write(Event { id: 2 }).map(|_| dir))
+            .map(|dir| assert_eq!(names(&dir), vec!["part-00000.parquet.tmp"]))
+    });
+    assert_eq!(sink.buffered(), 0);
+}
+
+#[test]
+fn close_finishes_file() {
+    let base_dir = tempdir().unwrap();
+    let sink = ParquetSink::new(base_dir.path());
+
+    rt::run({
+        let sink = sink.clone();
+        sink.write(Event { id: 1 })
+            .and_then(move |_| sink.write(Event { id: 2 }).map(|_| sink))
+            .and_then(|sink| sink.close())
+            .map(|path| {
+                let path = path.unwrap();
+                assert_eq!(path.file_name().unwrap(), "part-00000.parquet");
+                assert_eq!(num_rows(&path), 2);
+            })
+    });
+}
+
+#[test]
+fn rotates_by_size() {
+    let base_dir = tempdir().unwrap();
+    let sink = ParquetOptions::new()
+        .row_group_size(1)
+        .max_file_size(1)
+        .prefix("events")
+        .open::<Event, _>(base_dir.path());
+
+    rt::run({
+        let sink = sink.clone();
+        sink.write(Event { id: 1 })
+            .and_then(move |_| sink.write(Event { id: 2 }))
+    });
+
+    let dir = base_dir.path();
+    assert_eq!(
+        names(dir),
+        vec!["events-00000.parquet", "events-00001.parquet"]
+    );
+    assert_eq!(num_rows(&dir.join("events-00000.parquet")), 1);
+    assert_eq!(num_rows(&dir.join("events-00001.parquet")), 1);
+}
@@ -0,0 +1,263 @@
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use tokio_timer::Delay;
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// What an [`Account`] has been charged for.
+///
+/// [`Account`]: struct.Account.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// The number of bytes read.
+    pub bytes_read: u64,
+    /// The number of bytes written.
+    pub bytes_written: u64,
+    /// The number of operations.
+    pub ops: u64,
+}
+
+/// A cap on how fast an [`Account`] may do I/O.
+///
+/// Each rate is enforced by a token bucket holding up to one second's worth,
+/// so short bursts go through at full speed.
+///
+/// [`Account`]: struct.Account.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    bytes_per_sec: Option<u64>,
+    ops_per_sec: Option<u64>,
+}
+
+impl RateLimit {
+    /// Creates a limit that does not cap anything.
+    pub fn new() -> RateLimit {
+        RateLimit::default()
+    }
+
+    /// Caps the bytes read and written per second.
+    pub fn bytes_per_sec(&mut self, bytes: u64) -> &mut RateLimit {
+        self.bytes_per_sec = Some(bytes);
+        self
+    }
+
+    /// Caps the operations per second.
+    pub fn ops_per_sec(&mut self, ops: u64) -> &mut RateLimit {
+        self.ops_per_sec = Some(ops);
+        self
+    }
+}
+
+/// Per-label accounting of filesystem work, for fairness between tenants
+/// or between requests and background jobs.
+///
+/// Each label, such as a tenant id, has an [`Account`] that counts the bytes
+/// read and written and the operations done, and can be capped with a
+/// [`RateLimit`]. Charge work to an account by opening files with
+/// [`OpenOptions::account`], or for requests, with
+/// [`FsContextOptions::accounting`].
+///
+/// Cloning an `Accounting` produces another handle to the same accounts.
+///
+/// [`Account`]: struct.Account.html
+/// [`RateLimit`]: struct.RateLimit.html
+/// [`OpenOptions::account`]: struct.OpenOptions.html#method.account
+/// [`FsContextOptions::accounting`]: struct.FsContextOptions.html#method.accounting
+#[derive(Clone, Debug, Default)]
+pub struct Accounting {
+    accounts: Arc<Mutex<HashMap<String, Account>>>,
+}
+
+impl Accounting {
+    /// Creates accounting without any accounts.
+    pub fn new() -> Accounting {
+        Accounting::default()
+    }
+
+    /// Returns the account for `label`, creating it without a limit if
+    /// needed.
+    pub fn account(&self, label: &str) -> Account {
+        let mut accounts = self.accounts.lock().unwrap();
+        if let Some(account) = accounts.get(label) {
+            return account.clone();
+        }
+        let account = Account {
+            inner: Arc::new(Inner {
+                label: label.to_owned(),
+                state: Mutex::new(State::default()),
+            }),
+        };
+        accounts.insert(label.to_owned(), account.clone());
+        account
+    }
+
+    /// Sets the limit of the account for `label`, replacing its previous
+    /// limit.
+    pub fn limit(&self, label: &str, limit: &RateLimit) -> &Accounting {
+        self.account(label).set_limit(limit);
+        self
+    }
+
+    /// Returns the usage of every account, sorted by label.
+    pub fn usage(&self) -> Vec<(String, Usage)> {
+        let accounts = self.accounts.lock().unwrap();
+        let mut usage: Vec<_> = accounts
+            .iter()
+            .map(|(label, account)| (label.clone(), account.usage()))
+            .collect();
+        usage.sort_by(|a, b| a.0.cmp(&b.0));
+        usage
+    }
+}
+
+/// The account of one label of an [`Accounting`].
+///
+/// Work is charged after it is done, and once an account exceeds its limit,
+/// [`ready`] waits until it is back within it. A single large read can
+/// therefore take an account over its limit, and the work after it waits
+/// for longer.
+///
+/// Cloning an `Account` produces another handle to the same account.
+///
+/// [`Accounting`]: struct.Accounting.html
+/// [`ready`]: #method.ready
+#[derive(Clone, Debug)]
+pub struct Account {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    label: String,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    usage: Usage,
+    bytes: Option<Bucket>,
+    ops: Option<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn new(rate: u64) -> Bucket {
+        let rate = rate.max(1) as f64;
+        Bucket {
+            rate,
+            tokens: rate,
+            refilled: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.refilled = now;
+    }
+
+    /// Returns how long until the bucket is out of debt.
+    fn wait(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        if self.tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+impl Account {
+    /// Returns the label of the account.
+    pub fn label(&self) -> &str {
+        &self.inner.label
+    }
+
+    /// Returns what the account has been charged for so far.
+    pub fn usage(&self) -> Usage {
+        self.inner.state.lock().unwrap().usage
+    }
+
+    /// Sets the limit of the account, replacing its previous limit.
+    pub fn set_limit(&self, limit: &RateLimit) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.bytes = limit.bytes_per_sec.map(Bucket::new);
+        state.ops = limit.ops_per_sec.map(Bucket::new);
+    }
+
+    /// Charges an operation that read `bytes`.
+    pub fn record_read(&self, bytes: u64) {
+        self.record(bytes, |usage| usage.bytes_read += bytes);
+    }
+
+    /// Charges an operation that wrote `bytes`.
+    pub fn record_write(&self, bytes: u64) {
+        self.record(bytes, |usage| usage.bytes_written += bytes);
+    }
+
+    /// Charges an operation that neither read nor wrote file contents, such
+    /// as a sync.
+    pub fn record_op(&self) {
+        self.record(0, |_| {});
+    }
+
+    fn record<F>(&self, bytes: u64, f: F)
+    where
+        F: FnOnce(&mut Usage),
+    {
+        let now = Instant::now();
+        let mut state = self.inner.state.lock().unwrap();
+        state.usage.ops += 1;
+        f(&mut state.usage);
+        if let Some(ref mut bucket) = state.bytes {
+            bucket.refill(now);
+            bucket.tokens -= bytes as f64;
+        }
+        if let Some(ref mut bucket) = state.ops {
+            bucket.refill(now);
+            bucket.tokens -= 1.0;
+        }
+    }
+
+    /// Resolves once the account is within its limit.
+    ///
+    /// Resolves immediately for an account without a limit.
+    pub fn ready(&self) -> impl Future<Item = (), Error = io::Error> {
+        let account = self.clone();
+        future::loop_fn((), move |()| {
+            let wait = account.wait();
+            if wait == Duration::from_secs(0) {
+                return Either::A(future::ok(Loop::Break(())));
+            }
+            Either::B(
+                Delay::new(Instant::now() + wait)
+                    .map_err(crate::blocking_err)
+                    .map(|()| Loop::Continue(())),
+            )
+        })
+    }
+
+    fn wait(&self) -> Duration {
+        let now = Instant::now();
+        let mut state = self.inner.state.lock().unwrap();
+        let State {
+            ref mut bytes,
+            ref mut ops,
+            ..
+        } = *state;
+        let bytes = bytes
+            .as_mut()
+            .map_or(Duration::from_secs(0), |b| b.wait(now));
+        let ops = ops.as_mut().map_or(Duration::from_secs(0), |b| b.wait(now));
+        bytes.max(ops)
+    }
+}
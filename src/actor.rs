@@ -0,0 +1,211 @@
+use actix::fut::wrap_future;
+use actix::{Actor, AsyncContext, Context, Handler, Message, ResponseFuture, Supervised};
+use futures::sync::oneshot;
+use futures::Future;
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::Pool;
+
+/// An actor that performs filesystem operations sent to it as messages.
+///
+/// Routing filesystem work through a mailbox lets supervised actor systems
+/// bound how much of it is queued, and restart the actor like any other.
+/// Operations run on the global Actix threadpool, or on a dedicated [`Pool`]
+/// given to [`with_pool`].
+///
+/// Handlers return as soon as their operation is started, so the
+/// [`mailbox_capacity`] alone does not bound how many operations run at
+/// once. Set [`max_in_flight`] to stop taking messages from the mailbox
+/// while that many are running.
+///
+/// `FsActor` keeps no state between messages, so restarting it under a
+/// [`Supervisor`] is always safe.
+///
+/// This is only available with the `actor` feature.
+///
+/// [`Pool`]: struct.Pool.html
+/// [`with_pool`]: #method.with_pool
+/// [`mailbox_capacity`]: #method.mailbox_capacity
+/// [`max_in_flight`]: #method.max_in_flight
+/// [`Supervisor`]: https://docs.rs/actix/0.8/actix/struct.Supervisor.html
+#[derive(Clone, Debug, Default)]
+pub struct FsActor {
+    pool: Option<Pool>,
+    mailbox_capacity: Option<usize>,
+    max_in_flight: Option<usize>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl FsActor {
+    /// Creates an actor that runs operations on the global threadpool.
+    pub fn new() -> FsActor {
+        FsActor::default()
+    }
+
+    /// Creates an actor that runs operations on `pool`.
+    pub fn with_pool(pool: Pool) -> FsActor {
+        FsActor {
+            pool: Some(pool),
+            ..FsActor::default()
+        }
+    }
+
+    /// Sets how many messages may wait in the mailbox before senders using
+    /// `send` have to wait.
+    pub fn mailbox_capacity(mut self, capacity: usize) -> FsActor {
+        self.mailbox_capacity = Some(capacity);
+        self
+    }
+
+    /// Sets how many operations may run at once.
+    ///
+    /// Once that many are running, the actor stops taking messages from its
+    /// mailbox until the last one started has finished, so further messages
+    /// wait in the mailbox, up to its capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is 0.
+    pub fn max_in_flight(mut self, max: usize) -> FsActor {
+        assert!(max > 0, "max_in_flight must be at least 1");
+        self.max_in_flight = Some(max);
+        self
+    }
+
+    fn run<F, I>(&self, ctx: &mut Context<Self>, f: F) -> ResponseFuture<I, io::Error>
+    where
+        F: FnOnce() -> io::Result<I> + Send + 'static,
+        I: Send + 'static,
+    {
+        let running = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut slot = InFlightGuard(self.in_flight.clone(), None);
+        if self.max_in_flight.is_some_and(|max| running >= max) {
+            let (tx, rx) = oneshot::channel();
+            slot.1 = Some(tx);
+            // Resolves once the operation is done or dropped, either way
+            // releasing its slot.
+            ctx.wait(wrap_future(rx.then(|_| Ok(()))));
+        }
+        Box::new(crate::blocking_on(self.pool.as_ref(), move || {
+            let _slot = slot;
+            f()
+        }))
+    }
+}
+
+impl Actor for FsActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        // Operations left running by a previous start do not count.
+        self.in_flight = Arc::default();
+        if let Some(capacity) = self.mailbox_capacity {
+            ctx.set_mailbox_capacity(capacity);
+        }
+    }
+}
+
+impl Supervised for FsActor {}
+
+/// Releases the slot of a running operation when dropped, and tells the
+/// actor if it is waiting for it.
+struct InFlightGuard(Arc<AtomicUsize>, Option<oneshot::Sender<()>>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+        if let Some(tx) = self.1.take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
+/// Reads the entire contents of a file.
+#[derive(Clone, Debug)]
+pub struct ReadFile {
+    /// The file to read.
+    pub path: PathBuf,
+}
+
+impl Message for ReadFile {
+    type Result = io::Result<Vec<u8>>;
+}
+
+impl Handler<ReadFile> for FsActor {
+    type Result = ResponseFuture<Vec<u8>, io::Error>;
+
+    fn handle(&mut self, msg: ReadFile, ctx: &mut Context<Self>) -> Self::Result {
+        self.run(ctx, move || fs::read(msg.path))
+    }
+}
+
+/// Writes a file, creating it if it does not exist and replacing its
+/// contents if it does.
+#[derive(Clone, Debug)]
+pub struct WriteFile {
+    /// The file to write.
+    pub path: PathBuf,
+    /// The new contents of the file.
+    pub data: Vec<u8>,
+}
+
+impl Message for WriteFile {
+    type Result = io::Result<()>;
+}
+
+impl Handler<WriteFile> for FsActor {
+    type Result = ResponseFuture<(), io::Error>;
+
+    fn handle(&mut self, msg: WriteFile, ctx: &mut Context<Self>) -> Self::Result {
+        self.run(ctx, move || fs::write(msg.path, msg.data))
+    }
+}
+
+/// Removes a file.
+#[derive(Clone, Debug)]
+pub struct RemoveFile {
+    /// The file to remove.
+    pub path: PathBuf,
+}
+
+impl Message for RemoveFile {
+    type Result = io::Result<()>;
+}
+
+impl Handler<RemoveFile> for FsActor {
+    type Result = ResponseFuture<(), io::Error>;
+
+    fn handle(&mut self, msg: RemoveFile, ctx: &mut Context<Self>) -> Self::Result {
+        self.run(ctx, move || fs::remove_file(msg.path))
+    }
+}
+
+/// Lists the entries of a directory, sorted by path.
+#[derive(Clone, Debug)]
+pub struct ListDir {
+    /// The directory to list.
+    pub path: PathBuf,
+}
+
+impl Message for ListDir {
+    type Result = io::Result<Vec<PathBuf>>;
+}
+
+impl Handler<ListDir> for FsActor {
+    type Result = ResponseFuture<Vec<PathBuf>, io::Error>;
+
+    fn handle(&mut self, msg: ListDir, ctx: &mut Context<Self>) -> Self::Result {
+        self.run(ctx, move || {
+            let mut paths = fs::read_dir(msg.path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<io::Result<Vec<_>>>()?;
+            paths.sort();
+            Ok(paths)
+        })
+    }
+}
@@ -0,0 +1,317 @@
+use futures::{stream, Future, Stream};
+
+use std::collections::VecDeque;
+use std::fs::{self, File as StdFile};
+use std::io::{self, ErrorKind, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::str;
+
+use crate::Root;
+
+const MAGIC: &[u8; 8] = b"ACTIXFS\x01";
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+const DIR: u8 = b'D';
+const FILE: u8 = b'F';
+const END: u8 = b'E';
+
+/// Streams the contents of the directory at `dir` as an archive that
+/// [`import`] can restore, on this host or another.
+///
+/// Every regular file and directory below `dir` is included, in sorted
+/// order. Files ending in `.tmp` are treated as incomplete writes and left
+/// out, as are symbolic links. Files are read one chunk at a time as the
+/// stream is polled, so the archive never has to fit in memory.
+///
+/// The archive is a simple sequence of length-prefixed entries, not a tar
+/// file. Its layout is versioned by the leading magic bytes.
+///
+/// [`import`]: fn.import.html
+pub fn export<P>(dir: P) -> impl Stream<Item = Vec<u8>, Error = io::Error>
+where
+    P: Into<PathBuf>,
+{
+    let dir = dir.into();
+    crate::blocking(move || {
+        let entries = walk(&dir)?;
+        Ok(Exporter {
+            dir,
+            entries: entries.into(),
+            current: None,
+            started: false,
+            finished: false,
+        })
+    })
+    .map(|exporter| {
+        stream::unfold(Some(exporter), |exporter| {
+            exporter.map(|mut exporter| {
+                crate::blocking(move || match exporter.next_chunk()? {
+                    Some(chunk) => Ok((Some(chunk), Some(exporter))),
+                    None => Ok((None, None)),
+                })
+            })
+        })
+        .filter_map(|chunk| chunk)
+    })
+    .flatten_stream()
+}
+
+/// Restores an archive produced by [`export`] into the directory at `dir`,
+/// resolving to the number of files restored.
+///
+/// Entry names are resolved like [`Root::resolve`], so an archive cannot
+/// write outside of `dir`. Each file is written to a temporary `.tmp` file,
+/// synced and renamed into place once complete, so an interrupted import
+/// never leaves a partially written file under its final name. Existing
+/// files with the same name are replaced.
+///
+/// A truncated or malformed archive fails with an error of kind
+/// `InvalidData`.
+///
+/// [`export`]: fn.export.html
+/// [`Root::resolve`]: struct.Root.html#method.resolve
+pub fn import<P, S>(dir: P, archive: S) -> impl Future<Item = usize, Error = io::Error>
+where
+    P: Into<PathBuf>,
+    S: Stream<Item = Vec<u8>, Error = io::Error>,
+{
+    let importer = Importer {
+        root: Root::new(dir),
+        buf: Vec::new(),
+        started: false,
+        finished: false,
+        current: None,
+        files: 0,
+    };
+    archive
+        .fold(importer, |mut importer, chunk| {
+            crate::blocking(move || {
+                importer.feed(&chunk)?;
+                Ok(importer)
+            })
+        })
+        .and_then(|importer| {
+            if importer.finished {
+                Ok(importer.files)
+            } else {
+                Err(invalid("truncated archive"))
+            }
+        })
+}
+
+/// Lists the directories and files below `dir` as `/` separated names, with
+/// `true` marking directories.
+fn walk(dir: &Path) -> io::Result<Vec<(String, bool)>> {
+    let mut entries = Vec::new();
+    let mut pending = vec![dir.to_owned()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let path = entry.path();
+            let is_dir = file_type.is_dir();
+            if !is_dir && (!file_type.is_file() || path.extension().is_some_and(|ext| ext == "tmp"))
+            {
+                continue;
+            }
+            let relative = path.strip_prefix(dir).unwrap_or(&path);
+            let parts: Option<Vec<&str>> = relative.iter().map(|part| part.to_str()).collect();
+            let name = parts
+                .ok_or_else(|| invalid("file name is not valid UTF-8"))?
+                .join("/");
+            if name.len() > u16::MAX as usize {
+                return Err(invalid("file name is too long"));
+            }
+            if is_dir {
+                pending.push(path);
+            }
+            entries.push((name, is_dir));
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+struct Exporter {
+    dir: PathBuf,
+    entries: VecDeque<(String, bool)>,
+    current: Option<(StdFile, u64)>,
+    started: bool,
+    finished: bool,
+}
+
+impl Exporter {
+    fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        if !self.started {
+            self.started = true;
+            return Ok(Some(MAGIC.to_vec()));
+        }
+        if let Some((ref mut file, ref mut remaining)) = self.current {
+            let mut chunk = vec![0; (*remaining).min(CHUNK_SIZE) as usize];
+            file.read_exact(&mut chunk)?;
+            *remaining -= chunk.len() as u64;
+            if *remaining == 0 {
+                self.current = None;
+            }
+            return Ok(Some(chunk));
+        }
+        let (name, is_dir) = match self.entries.pop_front() {
+            Some(entry) => entry,
+            None if self.finished => return Ok(None),
+            None => {
+                self.finished = true;
+                return Ok(Some(vec![END]));
+            }
+        };
+        let mut header = vec![if is_dir { DIR } else { FILE }];
+        header.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        header.extend_from_slice(name.as_bytes());
+        if !is_dir {
+            let file = StdFile::open(self.dir.join(&name))?;
+            let len = file.metadata()?.len();
+            header.extend_from_slice(&len.to_be_bytes());
+            if len > 0 {
+                self.current = Some((file, len));
+            }
+        }
+        Ok(Some(header))
+    }
+}
+
+struct Entry {
+    file: StdFile,
+    tmp: PathBuf,
+    path: PathBuf,
+    remaining: u64,
+}
+
+struct Importer {
+    root: Root,
+    buf: Vec<u8>,
+    started: bool,
+    finished: bool,
+    current: Option<Entry>,
+    files: usize,
+}
+
+impl Importer {
+    fn feed(&mut self, chunk: &[u8]) -> io::Result<()> {
+        let mut data = mem::take(&mut self.buf);
+        data.extend_from_slice(chunk);
+        let mut pos = 0;
+        while pos < data.len() {
+            let buf = &data[pos..];
+            let consumed = if let Some(entry) = self.current.as_mut() {
+                let n = entry.remaining.min(buf.len() as u64) as usize;
+                entry.file.write_all(&buf[..n])?;
+                entry.remaining -= n as u64;
+                if entry.remaining == 0 {
+                    self.finish_entry()?;
+                }
+                n
+            } else if self.finished {
+                return Err(invalid("trailing data after end of archive"));
+            } else if !self.started {
+                if buf.len() < MAGIC.len() {
+                    break;
+                }
+                if &buf[..MAGIC.len()] != MAGIC {
+                    return Err(invalid("not an archive"));
+                }
+                self.started = true;
+                MAGIC.len()
+            } else {
+                match self.header(buf)? {
+                    Some(consumed) => consumed,
+                    None => break,
+                }
+            };
+            pos += consumed;
+        }
+        data.drain(..pos);
+        self.buf = data;
+        Ok(())
+    }
+
+    /// Starts the entry whose header is at the beginning of `buf`, returning
+    /// the length of the header or `None` if it is incomplete.
+    fn header(&mut self, buf: &[u8]) -> io::Result<Option<usize>> {
+        let kind = buf[0];
+        if kind == END {
+            self.finished = true;
+            return Ok(Some(1));
+        }
+        if kind != DIR && kind != FILE {
+            return Err(invalid("unknown archive entry"));
+        }
+        if buf.len() < 3 {
+            return Ok(None);
+        }
+        let name_len = u16::from_be_bytes([buf[1], buf[2]]) as usize;
+        let header_len = 3 + name_len + if kind == FILE { 8 } else { 0 };
+        if buf.len() < header_len {
+            return Ok(None);
+        }
+        let name = str::from_utf8(&buf[3..3 + name_len])
+            .map_err(|_| invalid("entry name is not valid UTF-8"))?;
+        let path = self.root.resolve(name)?;
+        if path == self.root.path() {
+            return Err(invalid("archive entry has an empty name"));
+        }
+        if kind == DIR {
+            fs::create_dir_all(&path)?;
+            return Ok(Some(header_len));
+        }
+
+        let mut len = [0; 8];
+        len.copy_from_slice(&buf[3 + name_len..header_len]);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        self.current = Some(Entry {
+            file: StdFile::create(&tmp)?,
+            tmp,
+            path,
+            remaining: u64::from_be_bytes(len),
+        });
+        if u64::from_be_bytes(len) == 0 {
+            self.finish_entry()?;
+        }
+        Ok(Some(header_len))
+    }
+
+    fn finish_entry(&mut self) -> io::Result<()> {
+        let entry = match self.current.take() {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+        let result = entry
+            .file
+            .sync_all()
+            .and_then(|_| fs::rename(&entry.tmp, &entry.path));
+        if result.is_err() {
+            let _ = fs::remove_file(&entry.tmp);
+        }
+        result?;
+        self.files += 1;
+        Ok(())
+    }
+}
+
+impl Drop for Importer {
+    fn drop(&mut self) {
+        // An import that failed or was dropped midway leaves no temporary file.
+        if let Some(entry) = self.current.take() {
+            drop(entry.file);
+            let _ = fs::remove_file(&entry.tmp);
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
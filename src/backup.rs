@@ -0,0 +1,875 @@
+use futures::Future;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File as StdFile, Metadata};
+use std::io::{self, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::SystemTime;
+
+use crate::error;
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+use crate::hash::{self, Algorithm, Digest, Verify};
+use crate::partition::{is_timestamp, timestamp};
+
+/// The suffix of the file next to a snapshot holding the digests of its
+/// files.
+const DIGESTS: &str = ".digests";
+
+/// The outcome of taking a backup.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BackupReport {
+    /// The snapshot directory that was created.
+    pub snapshot: PathBuf,
+    /// The number of files copied because they are new or changed.
+    pub copied: u64,
+    /// The number of bytes copied.
+    pub bytes_copied: u64,
+    /// The number of unchanged files hard-linked to the previous snapshot.
+    pub linked: u64,
+    /// The old snapshot directories that were removed.
+    pub removed: Vec<PathBuf>,
+}
+
+/// Options for taking space-efficient snapshots of a directory, like
+/// `rsnapshot`.
+///
+/// Each backup creates a snapshot directory below the backup root, named
+/// after the UTC time it was taken, such as `20190307T134500`, holding a full
+/// copy of the source tree. Files whose size and modification time match
+/// the newest earlier snapshot are hard-linked to it rather than copied, so
+/// a snapshot only takes space for what changed, yet any snapshot can be
+/// removed or restored on its own.
+///
+/// A snapshot is built in a `.tmp` directory and renamed once complete, so
+/// snapshots are never partial. A temporary directory left behind by an
+/// interrupted backup is removed by the next one. On Unix, symbolic links
+/// are recreated as links; elsewhere, what they point to is copied.
+///
+/// The backup root must be on one filesystem, and should only be written by
+/// these backups, since changing a file in a snapshot in place changes it
+/// in every snapshot it is linked into.
+#[derive(Clone, Debug)]
+pub struct BackupOptions {
+    keep: usize,
+    now: Option<SystemTime>,
+    #[cfg(any(feature = "sha2", feature = "blake3"))]
+    algorithm: Option<Algorithm>,
+    #[cfg(any(feature = "sha2", feature = "blake3"))]
+    verify: Option<Verify>,
+}
+
+impl BackupOptions {
+    /// Creates options that keep the `keep` newest snapshots, including the
+    /// one being taken.
+    pub fn new(keep: usize) -> BackupOptions {
+        BackupOptions {
+            keep,
+            now: None,
+            #[cfg(any(feature = "sha2", feature = "blake3"))]
+            algorithm: None,
+            #[cfg(any(feature = "sha2", feature = "blake3"))]
+            verify: None,
+        }
+    }
+
+    /// Sets the algorithm of the digests recorded for the files of each
+    /// snapshot, which [`restore`] verifies its copies against.
+    ///
+    /// The digests are kept next to the snapshot, in a file named after it
+    /// with a `.digests` extension, such as `20190307T134500.digests`.
+    /// Copied files are hashed while they are copied, and linked files take
+    /// their digest from the previous snapshot if it used the same
+    /// algorithm.
+    ///
+    /// This is only available with the `sha2` or `blake3` feature.
+    ///
+    /// [`restore`]: fn.restore.html
+    #[cfg(any(feature = "sha2", feature = "blake3"))]
+    pub fn hash(&mut self, algorithm: Algorithm) -> &mut BackupOptions {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Reads every file copied into the snapshot back, failing the backup
+    /// with `InvalidData` if it does not match its source, like
+    /// [`hash::copy_verified`].
+    ///
+    /// Copies are checked against digests of the algorithm set with
+    /// [`hash`], or compared byte for byte with the source without one.
+    /// Files linked to the previous snapshot are not copied, so they are
+    /// not read back.
+    ///
+    /// This is only available with the `sha2` or `blake3` feature.
+    ///
+    /// [`hash::copy_verified`]: hash/fn.copy_verified.html
+    /// [`hash`]: #method.hash
+    #[cfg(any(feature = "sha2", feature = "blake3"))]
+    pub fn verify(&mut self, verify: Verify) -> &mut BackupOptions {
+        self.verify = Some(verify);
+        self
+    }
+
+    /// Sets the time the snapshot is named after.
+    ///
+    /// Defaults to the current time when `backup` is called.
+    pub fn now(&mut self, now: SystemTime) -> &mut BackupOptions {
+        self.now = Some(now);
+        self
+    }
+
+    /// Takes a snapshot of `src` below `backup_root`, then removes the
+    /// oldest snapshots beyond the number to keep.
+    pub fn backup<P, Q>(
+        &self,
+        src: P,
+        backup_root: Q,
+    ) -> impl Future<Item = BackupReport, Error = io::Error>
+    where
+        P: Into<PathBuf>,
+        Q: Into<PathBuf>,
+    {
+        let src = src.into();
+        let backup_root = backup_root.into();
+        let keep = self.keep;
+        let now = self.now.unwrap_or_else(SystemTime::now);
+        let digests = Digests::for_backup(self);
+        crate::blocking(move || backup(&src, &backup_root, keep, now, digests))
+    }
+}
+
+/// Takes a snapshot of `src` below `backup_root`, hard-linking unchanged
+/// files to the previous snapshot, and keeps the `keep` newest snapshots.
+///
+/// See [`BackupOptions`] for details and more control.
+///
+/// [`BackupOptions`]: struct.BackupOptions.html
+pub fn backup_rotate<P, Q>(
+    src: P,
+    backup_root: Q,
+    keep: usize,
+) -> impl Future<Item = BackupReport, Error = io::Error>
+where
+    P: Into<PathBuf>,
+    Q: Into<PathBuf>,
+{
+    BackupOptions::new(keep).backup(src, backup_root)
+}
+
+fn backup(
+    src: &Path,
+    backup_root: &Path,
+    keep: usize,
+    now: SystemTime,
+    mut digests: Digests,
+) -> io::Result<BackupReport> {
+    fs::create_dir_all(backup_root)
+        .map_err(|err| error::with_path(err, "create directory", backup_root))?;
+    let mut snapshots = Vec::new();
+    let mut sidecars = Vec::new();
+    for entry in fs::read_dir(backup_root)
+        .map_err(|err| error::with_path(err, "read directory", backup_root))?
+    {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = match name.to_str() {
+            Some(name) => name,
+            None => continue,
+        };
+        if is_timestamp(name) {
+            snapshots.push(entry.path());
+        } else if name.strip_suffix(".tmp").is_some_and(is_timestamp) {
+            fs::remove_dir_all(entry.path())?;
+        } else if let Some(snapshot) = name.strip_suffix(DIGESTS).filter(|s| is_timestamp(s)) {
+            sidecars.push(backup_root.join(snapshot));
+        }
+    }
+    snapshots.sort();
+    // Digests left behind by a backup interrupted before its rename.
+    for snapshot in sidecars {
+        if !snapshots.contains(&snapshot) {
+            remove_sidecar(&snapshot)?;
+        }
+    }
+
+    let name = timestamp(now);
+    let snapshot = backup_root.join(&name);
+    if snapshots.last().is_some_and(|last| *last >= snapshot) {
+        return Err(error::with_path(
+            io::Error::new(
+                ErrorKind::AlreadyExists,
+                "snapshot is not newer than the latest one",
+            ),
+            "backup",
+            &snapshot,
+        ));
+    }
+    let tmp = backup_root.join(format!("{}.tmp", name));
+    let mut report = BackupReport {
+        snapshot: snapshot.clone(),
+        ..BackupReport::default()
+    };
+    let previous = snapshots.last().map(PathBuf::as_path);
+    let copied = copy_tree(src, &tmp, previous, &mut digests, &mut report)
+        .and_then(|()| digests.save(&snapshot));
+    if let Err(err) = copied {
+        let _ = fs::remove_dir_all(&tmp);
+        return Err(err);
+    }
+    if let Err(err) = fs::rename(&tmp, &snapshot) {
+        let _ = remove_sidecar(&snapshot);
+        return Err(err);
+    }
+    snapshots.push(snapshot);
+
+    let excess = snapshots.len().saturating_sub(keep.max(1));
+    for old in snapshots.drain(..excess) {
+        fs::remove_dir_all(&old).map_err(|err| error::with_path(err, "remove", &old))?;
+        remove_sidecar(&old)?;
+        report.removed.push(old);
+    }
+    Ok(report)
+}
+
+/// Returns the path of the file holding the digests of `snapshot`.
+fn sidecar(snapshot: &Path) -> PathBuf {
+    let mut path = snapshot.as_os_str().to_owned();
+    path.push(DIGESTS);
+    PathBuf::from(path)
+}
+
+/// Removes the digests of `snapshot`, if it has any.
+fn remove_sidecar(snapshot: &Path) -> io::Result<()> {
+    let path = sidecar(snapshot);
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(error::with_path(err, "remove", &path)),
+    }
+}
+
+/// Copies the tree at `src` to `dest`, linking files that are unchanged
+/// since `previous`, and records their digests in `digests`.
+fn copy_tree(
+    src: &Path,
+    dest: &Path,
+    previous: Option<&Path>,
+    digests: &mut Digests,
+    report: &mut BackupReport,
+) -> io::Result<()> {
+    let metadata = fs::metadata(src).map_err(|err| error::with_path(err, "stat", src))?;
+    if !metadata.is_dir() {
+        return Err(error::with_path(
+            io::Error::new(ErrorKind::InvalidInput, "not a directory"),
+            "backup",
+            src,
+        ));
+    }
+    fs::create_dir(dest)?;
+    let previous_digests = match previous {
+        Some(previous) if digests.recording() => Digests::load(previous).unwrap_or_default(),
+        _ => Digests::none(),
+    };
+
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let dir = src.join(&relative);
+        let entries =
+            fs::read_dir(&dir).map_err(|err| error::with_path(err, "read directory", &dir))?;
+        for entry in entries {
+            let entry = entry?;
+            let relative = relative.join(entry.file_name());
+            let from = entry.path();
+            let to = dest.join(&relative);
+            let metadata = match fs::symlink_metadata(&from) {
+                Ok(metadata) => metadata,
+                // Removed while the backup runs.
+                Err(ref err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(error::with_path(err, "stat", &from)),
+            };
+            if metadata.is_dir() {
+                fs::create_dir(&to)?;
+                pending.push(relative);
+                continue;
+            }
+            #[cfg(unix)]
+            {
+                if metadata.file_type().is_symlink() {
+                    let target = fs::read_link(&from)?;
+                    std::os::unix::fs::symlink(target, &to)?;
+                    continue;
+                }
+            }
+            let old = previous.map(|previous| previous.join(&relative));
+            if let Some(old) = old.filter(|old| unchanged(old, &metadata)) {
+                fs::hard_link(&old, &to)
+                    .map_err(|err| error::with_paths(err, "link", &old, &to))?;
+                digests
+                    .link(&relative, &to, &previous_digests)
+                    .map_err(|err| error::with_path(err, "hash", &to))?;
+                report.linked += 1;
+                continue;
+            }
+            match digests.copy(&relative, &from, &to, &metadata) {
+                Ok(bytes) => report.bytes_copied += bytes,
+                Err(ref err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(error::with_paths(err, "copy", &from, &to)),
+            }
+            // Later backups compare against the modification time of the
+            // copy.
+            crate::file::set_path_times(&to, None, Some(metadata.modified()?))?;
+            report.copied += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether the file at `old` has the size and modification time of
+/// `metadata`.
+fn unchanged(old: &Path, metadata: &Metadata) -> bool {
+    match fs::symlink_metadata(old) {
+        Ok(old) => {
+            old.is_file()
+                && old.len() == metadata.len()
+                && old.modified().ok() == metadata.modified().ok()
+        }
+        Err(_) => false,
+    }
+}
+
+/// The outcome of restoring a snapshot, with paths relative to the target.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    /// The files that did not exist in the target.
+    pub added: Vec<PathBuf>,
+    /// The files that differed from the snapshot.
+    pub replaced: Vec<PathBuf>,
+    /// The files and directories that are not in the snapshot. Of a removed
+    /// directory, only the directory itself is listed.
+    pub removed: Vec<PathBuf>,
+    /// The number of files that already matched the snapshot.
+    pub unchanged: u64,
+    /// The number of bytes copied from the snapshot.
+    pub bytes: u64,
+}
+
+/// Options for restoring a snapshot taken by [`BackupOptions`].
+///
+/// Restoring makes the target directory match the snapshot: files that are
+/// missing or differ are copied from the snapshot, and files and
+/// directories the snapshot does not have are removed. Like when taking a
+/// backup, a file whose size and modification time match the snapshot is
+/// left alone.
+///
+/// Before anything is changed, the target's filesystem is checked to have
+/// room for every file to copy. Each file is copied to a temporary file and
+/// synced, then verified before it is renamed into place, so no file is
+/// ever left partially restored. If the backup recorded digests, see
+/// [`BackupOptions::hash`], the copy is verified against the digest
+/// recorded for it, which also catches a snapshot damaged since it was
+/// taken. Otherwise it is compared with the snapshot byte for byte. Files
+/// that are not in the snapshot are only removed once everything else is
+/// restored.
+///
+/// [`BackupOptions`]: struct.BackupOptions.html
+/// [`BackupOptions::hash`]: struct.BackupOptions.html#method.hash
+#[derive(Clone, Debug, Default)]
+pub struct RestoreOptions {
+    dry_run: bool,
+}
+
+impl RestoreOptions {
+    /// Creates options that restore the snapshot.
+    pub fn new() -> RestoreOptions {
+        RestoreOptions::default()
+    }
+
+    /// Sets whether to only report what restoring would change, without
+    /// changing anything.
+    ///
+    /// The space check still runs, so a dry run fails like the restore
+    /// would if there is not enough room.
+    pub fn dry_run(&mut self, dry_run: bool) -> &mut RestoreOptions {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Restores the snapshot at `snapshot` over the directory at `target`,
+    /// creating it if it does not exist.
+    pub fn restore<P, Q>(
+        &self,
+        snapshot: P,
+        target: Q,
+    ) -> impl Future<Item = RestoreReport, Error = io::Error>
+    where
+        P: Into<PathBuf>,
+        Q: Into<PathBuf>,
+    {
+        let snapshot = snapshot.into();
+        let target = target.into();
+        let dry_run = self.dry_run;
+        crate::blocking(move || restore_tree(&snapshot, &target, dry_run))
+    }
+}
+
+/// Restores the snapshot at `snapshot` over the directory at `target`.
+///
+/// See [`RestoreOptions`] for details and more control.
+///
+/// [`RestoreOptions`]: struct.RestoreOptions.html
+pub fn restore<P, Q>(snapshot: P, target: Q) -> impl Future<Item = RestoreReport, Error = io::Error>
+where
+    P: Into<PathBuf>,
+    Q: Into<PathBuf>,
+{
+    RestoreOptions::new().restore(snapshot, target)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Entry {
+    File(u64, Option<SystemTime>),
+    Dir,
+    Link(PathBuf),
+}
+
+fn restore_tree(snapshot: &Path, target: &Path, dry_run: bool) -> io::Result<RestoreReport> {
+    let wanted = scan(snapshot)?;
+    let digests = Digests::load(snapshot)?;
+    let present = match scan(target) {
+        Ok(present) => present,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => BTreeMap::new(),
+        Err(err) => return Err(err),
+    };
+
+    let mut report = RestoreReport::default();
+    let mut needed = 0;
+    for (relative, entry) in &wanted {
+        let existing = present.get(relative);
+        if *entry == Entry::Dir {
+            continue;
+        }
+        match existing {
+            Some(existing) if existing == entry => report.unchanged += 1,
+            Some(_) => report.replaced.push(relative.clone()),
+            None => report.added.push(relative.clone()),
+        }
+        if let Entry::File(len, _) = *entry {
+            if existing != Some(entry) {
+                needed += len;
+            }
+        }
+    }
+    for relative in present.keys() {
+        let gone = !wanted.contains_key(relative);
+        let parent_gone = report
+            .removed
+            .last()
+            .is_some_and(|removed| relative.starts_with(removed));
+        if gone && !parent_gone {
+            report.removed.push(relative.clone());
+        }
+    }
+    check_space(target, needed)?;
+    if dry_run {
+        return Ok(report);
+    }
+
+    fs::create_dir_all(target).map_err(|err| error::with_path(err, "create directory", target))?;
+    for (relative, entry) in &wanted {
+        let existing = present.get(relative);
+        if existing == Some(entry) {
+            continue;
+        }
+        let from = snapshot.join(relative);
+        let to = target.join(relative);
+        if *entry != Entry::Dir {
+            if let Some(existing) = existing {
+                if *existing == Entry::Dir {
+                    fs::remove_dir_all(&to).map_err(|err| error::with_path(err, "remove", &to))?;
+                }
+            }
+        }
+        match *entry {
+            Entry::Dir => {
+                if existing.is_some() {
+                    fs::remove_file(&to).map_err(|err| error::with_path(err, "remove", &to))?;
+                }
+                fs::create_dir(&to)
+                    .map_err(|err| error::with_path(err, "create directory", &to))?;
+            }
+            Entry::File(_, modified) => {
+                report.bytes += restore_file(&from, &to, modified, |copy| {
+                    digests.verify(relative, &from, copy)
+                })?;
+            }
+            Entry::Link(ref link) => {
+                if existing.is_some_and(|existing| *existing != Entry::Dir) {
+                    fs::remove_file(&to).map_err(|err| error::with_path(err, "remove", &to))?;
+                }
+                symlink(link, &to).map_err(|err| error::with_path(err, "link", &to))?;
+            }
+        }
+    }
+    for relative in &report.removed {
+        let path = target.join(relative);
+        let removed = match present.get(relative) {
+            Some(Entry::Dir) => fs::remove_dir_all(&path),
+            _ => fs::remove_file(&path),
+        };
+        match removed {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(error::with_path(err, "remove", &path)),
+        }
+    }
+    Ok(report)
+}
+
+/// Lists the tree at `root`, by path relative to it.
+fn scan(root: &Path) -> io::Result<BTreeMap<PathBuf, Entry>> {
+    let metadata = fs::metadata(root).map_err(|err| error::with_path(err, "stat", root))?;
+    if !metadata.is_dir() {
+        return Err(error::with_path(
+            io::Error::new(ErrorKind::InvalidInput, "not a directory"),
+            "restore",
+            root,
+        ));
+    }
+    let mut entries = BTreeMap::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let dir = root.join(&relative);
+        let list =
+            fs::read_dir(&dir).map_err(|err| error::with_path(err, "read directory", &dir))?;
+        for entry in list {
+            let entry = entry?;
+            let relative = relative.join(entry.file_name());
+            let path = entry.path();
+            let metadata =
+                fs::symlink_metadata(&path).map_err(|err| error::with_path(err, "stat", &path))?;
+            let entry = if metadata.is_dir() {
+                pending.push(relative.clone());
+                Entry::Dir
+            } else if metadata.file_type().is_symlink() {
+                Entry::Link(fs::read_link(&path)?)
+            } else {
+                Entry::File(metadata.len(), metadata.modified().ok())
+            };
+            entries.insert(relative, entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Fails with `StorageFull` if the filesystem of `target` has fewer than
+/// `needed` bytes available.
+fn check_space(target: &Path, needed: u64) -> io::Result<()> {
+    let existing = target
+        .ancestors()
+        .find(|dir| dir.is_dir())
+        .unwrap_or(target);
+    let available = match crate::statfs::sys::statfs(existing) {
+        Ok(stats) => stats.available,
+        // The check is best effort where the platform cannot tell.
+        Err(ref err) if err.kind() == ErrorKind::Unsupported => return Ok(()),
+        Err(err) => return Err(error::with_path(err, "statfs", existing)),
+    };
+    if needed > available {
+        return Err(io::Error::new(
+            ErrorKind::StorageFull,
+            format!(
+                "restoring needs {} bytes, but only {} are available",
+                needed, available
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Copies the file at `from` to `to` through a temporary file, which
+/// `verify` checks before it is renamed into place.
+fn restore_file<F>(
+    from: &Path,
+    to: &Path,
+    modified: Option<SystemTime>,
+    verify: F,
+) -> io::Result<u64>
+where
+    F: FnOnce(&Path) -> io::Result<()>,
+{
+    let mut tmp = to.as_os_str().to_owned();
+    tmp.push(format!(".{}.restore", process::id()));
+    let tmp = PathBuf::from(tmp);
+
+    let copied = (|| {
+        let mut source = StdFile::open(from)?;
+        let permissions = source.metadata()?.permissions();
+        let mut copy = StdFile::create(&tmp)?;
+        let len = io::copy(&mut source, &mut copy)?;
+        copy.sync_all()?;
+        drop(copy);
+        verify(&tmp)?;
+        fs::set_permissions(&tmp, permissions)?;
+        crate::file::set_path_times(&tmp, None, modified)?;
+        fs::rename(&tmp, to)?;
+        Ok(len)
+    })();
+    copied.map_err(|err| {
+        let _ = fs::remove_file(&tmp);
+        error::with_paths(err, "restore", from, to)
+    })
+}
+
+/// The digests of the files of a snapshot, by path relative to it.
+///
+/// They are stored next to the snapshot, as the name of the algorithm on
+/// the first line, followed by a line with the hex digest, two spaces and
+/// the path for each file. Files whose path is not UTF-8 or spans lines are
+/// left out, and verified byte for byte.
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+#[derive(Default)]
+struct Digests {
+    algorithm: Option<Algorithm>,
+    files: BTreeMap<PathBuf, Digest>,
+    // How copies into the snapshot are checked, if at all.
+    verify: Option<Verify>,
+}
+
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+impl Digests {
+    fn none() -> Digests {
+        Digests::default()
+    }
+
+    /// Creates digests to record as `opts` asks.
+    fn for_backup(opts: &BackupOptions) -> Digests {
+        Digests {
+            algorithm: opts.algorithm,
+            files: BTreeMap::new(),
+            verify: opts.verify,
+        }
+    }
+
+    /// Loads the digests of `snapshot`, if it has any.
+    fn load(snapshot: &Path) -> io::Result<Digests> {
+        let path = sidecar(snapshot);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(Digests::default()),
+            Err(err) => return Err(error::with_path(err, "read", &path)),
+        };
+        let malformed = || {
+            error::with_path(
+                io::Error::new(ErrorKind::InvalidData, "malformed digests"),
+                "read",
+                &path,
+            )
+        };
+        let mut lines = contents.lines();
+        let algorithm = lines
+            .next()
+            .and_then(Algorithm::from_name)
+            .ok_or_else(malformed)?;
+        let mut files = BTreeMap::new();
+        for line in lines {
+            let (hex, relative) = line.split_once("  ").ok_or_else(malformed)?;
+            let digest = Digest::from_hex(algorithm, hex).ok_or_else(malformed)?;
+            files.insert(PathBuf::from(relative), digest);
+        }
+        Ok(Digests {
+            algorithm: Some(algorithm),
+            files,
+            verify: None,
+        })
+    }
+
+    /// Writes the digests of `snapshot`, if any are recorded.
+    fn save(&self, snapshot: &Path) -> io::Result<()> {
+        let algorithm = match self.algorithm {
+            Some(algorithm) => algorithm,
+            None => return Ok(()),
+        };
+        let mut contents = format!("{}\n", algorithm.name());
+        for (relative, digest) in &self.files {
+            contents.push_str(&format!("{}  {}\n", digest, relative.display()));
+        }
+        let path = sidecar(snapshot);
+        let mut file =
+            StdFile::create(&path).map_err(|err| error::with_path(err, "create", &path))?;
+        std::io::Write::write_all(&mut file, contents.as_bytes())
+            .and_then(|()| file.sync_all())
+            .map_err(|err| error::with_path(err, "write", &path))
+    }
+
+    fn recording(&self) -> bool {
+        self.algorithm.is_some()
+    }
+
+    /// Copies the file at `from` to `to`, recording its digest and checking
+    /// the copy if asked to.
+    fn copy(
+        &mut self,
+        relative: &Path,
+        from: &Path,
+        to: &Path,
+        metadata: &Metadata,
+    ) -> io::Result<u64> {
+        let algorithm = match self.algorithm {
+            Some(algorithm) => algorithm,
+            None => {
+                let len = fs::copy(from, to)?;
+                if self.verify.is_some() {
+                    compare(from, to)?;
+                }
+                return Ok(len);
+            }
+        };
+        let token = crate::CancellationToken::new();
+        let (len, digest) = match self.verify {
+            Some(verify) => hash::copy_file_verified(from, to, algorithm, verify, &token)?,
+            None => hash::copy_file(from, to, algorithm, &token)?,
+        };
+        fs::set_permissions(to, metadata.permissions())?;
+        self.record(relative, digest);
+        Ok(len)
+    }
+
+    /// Records the digest of the file at `path`, linked to the previous
+    /// snapshot, taking it from `previous` if it has it.
+    fn link(&mut self, relative: &Path, path: &Path, previous: &Digests) -> io::Result<()> {
+        let algorithm = match self.algorithm {
+            Some(algorithm) => algorithm,
+            None => return Ok(()),
+        };
+        let known = previous
+            .files
+            .get(relative)
+            .filter(|digest| digest.algorithm() == algorithm);
+        let digest = match known {
+            Some(&digest) => digest,
+            None => {
+                let token = crate::CancellationToken::new();
+                hash::digest_file(&mut StdFile::open(path)?, algorithm, &token)?
+            }
+        };
+        self.record(relative, digest);
+        Ok(())
+    }
+
+    fn record(&mut self, relative: &Path, digest: Digest) {
+        if relative
+            .to_str()
+            .is_some_and(|path| !path.contains(['\n', '\r']))
+        {
+            self.files.insert(relative.to_owned(), digest);
+        }
+    }
+
+    /// Verifies `copy` of the file at `from` against the digest recorded for
+    /// it, or against `from` byte for byte if there is none.
+    fn verify(&self, relative: &Path, from: &Path, copy: &Path) -> io::Result<()> {
+        let (algorithm, expected) = match (self.algorithm, self.files.get(relative)) {
+            (Some(algorithm), Some(expected)) => (algorithm, expected),
+            _ => return compare(from, copy),
+        };
+        let token = crate::CancellationToken::new();
+        let actual = hash::digest_file(&mut StdFile::open(copy)?, algorithm, &token)?;
+        if actual != *expected {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "copy does not match the digest in the snapshot: expected {}, found {}",
+                    expected, actual
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Without a hash feature no digests are recorded, and copies are verified
+/// byte for byte.
+#[cfg(not(any(feature = "sha2", feature = "blake3")))]
+#[derive(Default)]
+struct Digests;
+
+#[cfg(not(any(feature = "sha2", feature = "blake3")))]
+impl Digests {
+    fn none() -> Digests {
+        Digests
+    }
+
+    fn for_backup(_: &BackupOptions) -> Digests {
+        Digests
+    }
+
+    fn load(_: &Path) -> io::Result<Digests> {
+        Ok(Digests)
+    }
+
+    fn save(&self, _: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn recording(&self) -> bool {
+        false
+    }
+
+    fn copy(&mut self, _: &Path, from: &Path, to: &Path, _: &Metadata) -> io::Result<u64> {
+        fs::copy(from, to)
+    }
+
+    fn link(&mut self, _: &Path, _: &Path, _: &Digests) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn verify(&self, _: &Path, from: &Path, copy: &Path) -> io::Result<()> {
+        compare(from, copy)
+    }
+}
+
+/// Fails with `InvalidData` unless the files at `from` and `copy` have the
+/// same contents.
+fn compare(from: &Path, copy: &Path) -> io::Result<()> {
+    let (mut a, mut b) = (StdFile::open(from)?, StdFile::open(copy)?);
+    let (mut buf_a, mut buf_b) = (vec![0; 64 * 1024], vec![0; 64 * 1024]);
+    loop {
+        let n = fill(&mut a, &mut buf_a)?;
+        if fill(&mut b, &mut buf_b)? != n || buf_a[..n] != buf_b[..n] {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "copy does not match its source",
+            ));
+        }
+        if n == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Reads into `buf` until it is full or the end of `file` is reached.
+fn fill(file: &mut StdFile, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match file.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(read) => n += read,
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(n)
+}
+
+#[cfg(unix)]
+fn symlink(link: &Path, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(link, path)
+}
+
+#[cfg(not(unix))]
+fn symlink(_: &Path, _: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "symbolic links can only be restored on Unix",
+    ))
+}
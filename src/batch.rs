@@ -0,0 +1,193 @@
+use futures::Future;
+
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::PathBuf;
+
+use crate::error;
+
+/// Runs `f` on the threadpool, for a sequence of `std::fs` calls that
+/// should not each pay for a trip to the threadpool.
+///
+/// Stat'ing or reading many tiny files one future at a time spends most of
+/// the time handing work to the threadpool and back. Doing all of it in one
+/// closure takes a single hop:
+///
+/// ```no_run
+/// # use futures::Future;
+/// let configs = actix_fs::batch(|| {
+///     let app = std::fs::read_to_string("app.toml")?;
+///     let log = std::fs::read_to_string("log.toml")?;
+///     Ok((app, log))
+/// });
+/// ```
+///
+/// See [`Batch`] to collect the result of every operation, even if some
+/// fail.
+///
+/// [`Batch`]: struct.Batch.html
+pub fn batch<F, T>(f: F) -> impl Future<Item = T, Error = io::Error>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    crate::blocking(f)
+}
+
+/// The result of a successful operation of a [`Batch`].
+///
+/// [`Batch`]: struct.Batch.html
+#[derive(Debug)]
+pub enum BatchOutput {
+    /// The metadata of a file.
+    Metadata(Metadata),
+    /// The contents of a file.
+    Contents(Vec<u8>),
+    /// An operation without a result finished.
+    Done,
+}
+
+impl BatchOutput {
+    /// Returns the metadata, if this is the result of a `metadata`
+    /// operation.
+    pub fn into_metadata(self) -> Option<Metadata> {
+        match self {
+            BatchOutput::Metadata(metadata) => Some(metadata),
+            _ => None,
+        }
+    }
+
+    /// Returns the contents, if this is the result of a `read` operation.
+    pub fn into_contents(self) -> Option<Vec<u8>> {
+        match self {
+            BatchOutput::Contents(contents) => Some(contents),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Metadata(PathBuf),
+    Read(PathBuf),
+    Write(PathBuf, Vec<u8>),
+    CreateDirAll(PathBuf),
+    RemoveFile(PathBuf),
+}
+
+/// A sequence of filesystem operations run in a single trip to the
+/// threadpool.
+///
+/// Operations run in the order they were added, and each has its own
+/// result, so one missing file does not hide the others:
+///
+/// ```no_run
+/// # use actix_fs::Batch;
+/// # use futures::Future;
+/// let sizes = Batch::new()
+///     .metadata("a.json")
+///     .metadata("b.json")
+///     .run()
+///     .map(|results| {
+///         results
+///             .into_iter()
+///             .map(|res| res.ok().and_then(|out| out.into_metadata()).map(|m| m.len()))
+///             .collect::<Vec<_>>()
+///     });
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Batch {
+    ops: Vec<Op>,
+}
+
+impl Batch {
+    /// Creates an empty batch.
+    pub fn new() -> Batch {
+        Batch::default()
+    }
+
+    /// Adds reading the metadata of the file at `path`, following symbolic
+    /// links.
+    pub fn metadata<P>(&mut self, path: P) -> &mut Batch
+    where
+        P: Into<PathBuf>,
+    {
+        self.ops.push(Op::Metadata(path.into()));
+        self
+    }
+
+    /// Adds reading the whole file at `path`.
+    pub fn read<P>(&mut self, path: P) -> &mut Batch
+    where
+        P: Into<PathBuf>,
+    {
+        self.ops.push(Op::Read(path.into()));
+        self
+    }
+
+    /// Adds writing `contents` to the file at `path`, creating or truncating
+    /// it.
+    pub fn write<P>(&mut self, path: P, contents: Vec<u8>) -> &mut Batch
+    where
+        P: Into<PathBuf>,
+    {
+        self.ops.push(Op::Write(path.into(), contents));
+        self
+    }
+
+    /// Adds creating the directory at `path` and all of its missing
+    /// parents.
+    pub fn create_dir_all<P>(&mut self, path: P) -> &mut Batch
+    where
+        P: Into<PathBuf>,
+    {
+        self.ops.push(Op::CreateDirAll(path.into()));
+        self
+    }
+
+    /// Adds removing the file at `path`.
+    pub fn remove_file<P>(&mut self, path: P) -> &mut Batch
+    where
+        P: Into<PathBuf>,
+    {
+        self.ops.push(Op::RemoveFile(path.into()));
+        self
+    }
+
+    /// Returns the number of operations in the batch.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns `true` if the batch has no operations.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Runs the operations, resolving to their results in the order they
+    /// were added.
+    pub fn run(&self) -> impl Future<Item = Vec<io::Result<BatchOutput>>, Error = io::Error> {
+        let ops = self.ops.clone();
+        crate::blocking(move || Ok(ops.into_iter().map(run).collect()))
+    }
+}
+
+fn run(op: Op) -> io::Result<BatchOutput> {
+    match op {
+        Op::Metadata(path) => fs::metadata(&path)
+            .map(BatchOutput::Metadata)
+            .map_err(|err| error::with_path(err, "stat", &path)),
+        Op::Read(path) => fs::read(&path)
+            .map(BatchOutput::Contents)
+            .map_err(|err| error::with_path(err, "read", &path)),
+        Op::Write(path, contents) => fs::write(&path, contents)
+            .map(|()| BatchOutput::Done)
+            .map_err(|err| error::with_path(err, "write", &path)),
+        Op::CreateDirAll(path) => fs::create_dir_all(&path)
+            .map(|()| BatchOutput::Done)
+            .map_err(|err| error::with_path(err, "create directory", &path)),
+        Op::RemoveFile(path) => fs::remove_file(&path)
+            .map(|()| BatchOutput::Done)
+            .map_err(|err| error::with_path(err, "remove", &path)),
+    }
+}
@@ -0,0 +1,386 @@
+use futures::future::{self, Either, Loop};
+use futures::{stream, Future, Stream};
+
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::ops::{Deref, DerefMut};
+
+use crate::File;
+
+const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+/// Adds buffering to reading from a [`File`].
+///
+/// Every read from a `File` is a round-trip to the threadpool, which makes
+/// many small reads very slow. A `BufReader` reads large chunks at a time
+/// and serves small reads from memory.
+///
+/// This is a specialized version of [`std::io::BufReader`][std] for usage
+/// from the Actix runtime.
+///
+/// [`File`]: struct.File.html
+/// [std]: https://doc.rust-lang.org/std/io/struct.BufReader.html
+#[derive(Debug)]
+pub struct BufReader {
+    file: File,
+    buf: Vec<u8>,
+    pos: usize,
+    capacity: usize,
+}
+
+impl BufReader {
+    /// Creates a `BufReader` with a default buffer capacity of 64 KiB.
+    pub fn new(file: File) -> BufReader {
+        BufReader::with_capacity(DEFAULT_CAPACITY, file)
+    }
+
+    /// Creates a `BufReader` that reads `capacity` bytes at a time.
+    pub fn with_capacity(capacity: usize, file: File) -> BufReader {
+        BufReader {
+            file,
+            buf: Vec::new(),
+            pos: 0,
+            capacity,
+        }
+    }
+
+    /// Returns a reference to the underlying file.
+    pub fn get_ref(&self) -> &File {
+        &self.file
+    }
+
+    /// Returns the buffered data that has not been consumed yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    /// Marks `amt` bytes of the buffer as consumed.
+    ///
+    /// `amt` is clamped to the length of [`buffer`].
+    ///
+    /// [`buffer`]: #method.buffer
+    pub fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+
+    /// Returns the underlying file, discarding any buffered data.
+    pub fn into_inner(self) -> File {
+        self.file
+    }
+
+    /// Refills the buffer if it has been fully consumed.
+    ///
+    /// After the returned future resolves, [`buffer`] is empty only at the end
+    /// of the file.
+    ///
+    /// [`buffer`]: #method.buffer
+    pub fn fill_buf(self) -> impl Future<Item = BufReader, Error = io::Error> {
+        if self.pos < self.buf.len() {
+            return Either::A(future::ok(self));
+        }
+
+        let BufReader { file, capacity, .. } = self;
+        Either::B(file.read(capacity).map(move |(file, buf)| BufReader {
+            file,
+            buf,
+            pos: 0,
+            capacity,
+        }))
+    }
+
+    /// Reads up to `len` bytes.
+    ///
+    /// Buffered data is returned first. Once the buffer is empty, reads of at
+    /// least the buffer's capacity go straight to the file. An empty result
+    /// means the end of the file was reached.
+    pub fn read(self, len: usize) -> impl Future<Item = (BufReader, Vec<u8>), Error = io::Error> {
+        if self.pos == self.buf.len() && len >= self.capacity {
+            let BufReader { file, capacity, .. } = self;
+            return Either::A(
+                file.read(len)
+                    .map(move |(file, data)| (BufReader::with_capacity(capacity, file), data)),
+            );
+        }
+
+        Either::B(self.fill_buf().map(move |mut reader| {
+            let n = len.min(reader.buffer().len());
+            let data = reader.buffer()[..n].to_vec();
+            reader.consume(n);
+            (reader, data)
+        }))
+    }
+
+    /// Reads until `delim` or the end of the file, whichever comes first.
+    ///
+    /// The delimiter, if found, is included at the end of the result. An
+    /// empty result means the end of the file was reached.
+    pub fn read_until(
+        self,
+        delim: u8,
+    ) -> impl Future<Item = (BufReader, Vec<u8>), Error = io::Error> {
+        future::loop_fn((self, Vec::new()), move |(reader, mut out)| {
+            reader.fill_buf().map(move |mut reader| {
+                let available = reader.buffer();
+                if available.is_empty() {
+                    return Loop::Break((reader, out));
+                }
+                match available.iter().position(|&b| b == delim) {
+                    Some(i) => {
+                        out.extend_from_slice(&available[..=i]);
+                        reader.consume(i + 1);
+                        Loop::Break((reader, out))
+                    }
+                    None => {
+                        let n = available.len();
+                        out.extend_from_slice(available);
+                        reader.consume(n);
+                        Loop::Continue((reader, out))
+                    }
+                }
+            })
+        })
+    }
+
+    /// Returns a stream over the chunks of the file separated by `delim`.
+    ///
+    /// The delimiter is not included in the chunks. A delimiter at the very
+    /// end of the file does not produce a trailing empty chunk.
+    ///
+    /// The file is read one buffer at a time as the stream is polled, so
+    /// arbitrarily large files can be processed with bounded memory.
+    pub fn split(self, delim: u8) -> impl Stream<Item = Vec<u8>, Error = io::Error> {
+        stream::unfold(Some(self), move |reader| {
+            reader.map(|reader| {
+                reader.read_until(delim).map(move |(reader, mut chunk)| {
+                    if chunk.is_empty() {
+                        return (None, None);
+                    }
+                    if chunk.last() == Some(&delim) {
+                        chunk.pop();
+                    }
+                    (Some(chunk), Some(reader))
+                })
+            })
+        })
+        .filter_map(|chunk| chunk)
+    }
+
+    /// Returns a stream over the lines of the file.
+    ///
+    /// Lines are split on `\n`, and a trailing `\r` is removed as well. A line
+    /// that is not valid UTF-8 fails the stream with an error of kind
+    /// `InvalidData`.
+    pub fn lines(self) -> impl Stream<Item = String, Error = io::Error> {
+        self.split(b'\n').and_then(|mut line| {
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            String::from_utf8(line).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+        })
+    }
+}
+
+/// Adds buffering to writing to a [`File`].
+///
+/// Small writes are collected in memory and handed to the threadpool in
+/// large chunks, either when the buffer is full or when [`flush`] is called.
+///
+/// Buffered data is not written when a `BufWriter` is dropped, as that would
+/// block the dropping thread. Call [`flush`] or [`shutdown`] first.
+///
+/// This is a specialized version of [`std::io::BufWriter`][std] for usage
+/// from the Actix runtime.
+///
+/// [`File`]: struct.File.html
+/// [`flush`]: #method.flush
+/// [`shutdown`]: #method.shutdown
+/// [std]: https://doc.rust-lang.org/std/io/struct.BufWriter.html
+#[derive(Debug)]
+pub struct BufWriter {
+    file: File,
+    buf: Vec<u8>,
+    capacity: usize,
+}
+
+impl BufWriter {
+    /// Creates a `BufWriter` with a default buffer capacity of 64 KiB.
+    pub fn new(file: File) -> BufWriter {
+        BufWriter::with_capacity(DEFAULT_CAPACITY, file)
+    }
+
+    /// Creates a `BufWriter` that writes `capacity` bytes at a time.
+    pub fn with_capacity(capacity: usize, file: File) -> BufWriter {
+        BufWriter {
+            file,
+            buf: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Returns a reference to the underlying file.
+    pub fn get_ref(&self) -> &File {
+        &self.file
+    }
+
+    /// Returns the data that has been written but not flushed yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Writes `data`, flushing the buffer first if `data` does not fit.
+    ///
+    /// Data that is at least as large as the buffer's capacity is written to
+    /// the file directly.
+    pub fn write(self, data: &[u8]) -> impl Future<Item = BufWriter, Error = io::Error> {
+        if self.buf.len() + data.len() <= self.capacity {
+            let mut writer = self;
+            writer.buf.extend_from_slice(data);
+            return Either::A(future::ok(writer));
+        }
+
+        let data = data.to_vec();
+        Either::B(self.flush().and_then(move |mut writer| {
+            if data.len() < writer.capacity {
+                writer.buf.extend_from_slice(&data);
+                return Either::A(future::ok(writer));
+            }
+            let BufWriter {
+                file,
+                buf,
+                capacity,
+            } = writer;
+            Either::B(file.write_all(data).map(move |file| BufWriter {
+                file,
+                buf,
+                capacity,
+            }))
+        }))
+    }
+
+    /// Writes all buffered data to the file.
+    pub fn flush(self) -> impl Future<Item = BufWriter, Error = io::Error> {
+        if self.buf.is_empty() {
+            return Either::A(future::ok(self));
+        }
+
+        let BufWriter {
+            file,
+            buf,
+            capacity,
+        } = self;
+        Either::B(file.write_all(buf).map(move |file| BufWriter {
+            file,
+            buf: Vec::with_capacity(capacity),
+            capacity,
+        }))
+    }
+
+    /// Flushes all buffered data, then syncs the file to disk and closes it.
+    pub fn shutdown(self) -> impl Future<Item = (), Error = io::Error> {
+        self.into_inner()
+            .and_then(|file| file.sync_all())
+            .and_then(File::close)
+    }
+
+    /// Flushes all buffered data and returns the underlying file.
+    pub fn into_inner(self) -> impl Future<Item = File, Error = io::Error> {
+        self.flush().map(|writer| writer.file)
+    }
+}
+
+/// A byte buffer whose memory and capacity are aligned for direct I/O.
+///
+/// Files opened with [`OpenOptions::direct`] bypass the page cache, which
+/// on most platforms requires the memory, length and file offset of every
+/// transfer to be aligned to the logical block size of the device. An
+/// `AlignedBuf` starts at an address aligned to [`ALIGNMENT`] and has a
+/// capacity that is a multiple of it, which covers common block sizes.
+///
+/// Use it with [`File::read_aligned`] and [`File::write_aligned`], which
+/// take care of the parts of a transfer that cannot be aligned.
+///
+/// [`OpenOptions::direct`]: struct.OpenOptions.html#method.direct
+/// [`ALIGNMENT`]: #associatedconstant.ALIGNMENT
+/// [`File::read_aligned`]: struct.File.html#method.read_aligned
+/// [`File::write_aligned`]: struct.File.html#method.write_aligned
+pub struct AlignedBuf {
+    buf: Vec<u8>,
+    offset: usize,
+    len: usize,
+    capacity: usize,
+}
+
+impl AlignedBuf {
+    /// The alignment of the memory and capacity of the buffer in bytes.
+    pub const ALIGNMENT: usize = 4096;
+
+    /// Creates an empty buffer holding at least `capacity` bytes, rounded
+    /// up to a multiple of [`ALIGNMENT`].
+    ///
+    /// [`ALIGNMENT`]: #associatedconstant.ALIGNMENT
+    pub fn with_capacity(capacity: usize) -> AlignedBuf {
+        let capacity = capacity.div_ceil(Self::ALIGNMENT) * Self::ALIGNMENT;
+        // Allocating one extra block leaves room to start at an aligned
+        // address wherever the allocation lands.
+        let buf = vec![0; capacity + Self::ALIGNMENT];
+        let offset = buf.as_ptr().align_offset(Self::ALIGNMENT);
+        AlignedBuf {
+            buf,
+            offset,
+            len: 0,
+            capacity,
+        }
+    }
+
+    /// Returns the number of bytes the buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Empties the buffer, keeping its capacity.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Appends as much of `data` as fits, returning the number of bytes
+    /// appended.
+    pub fn extend_from_slice(&mut self, data: &[u8]) -> usize {
+        let n = data.len().min(self.capacity - self.len);
+        let start = self.offset + self.len;
+        self.buf[start..start + n].copy_from_slice(&data[..n]);
+        self.len += n;
+        n
+    }
+
+    /// Returns the whole capacity of the buffer for reading into.
+    pub(crate) fn spare_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[self.offset..self.offset + self.capacity]
+    }
+
+    pub(crate) fn set_len(&mut self, len: usize) {
+        self.len = len.min(self.capacity);
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf[self.offset..self.offset + self.len]
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[self.offset..self.offset + self.len]
+    }
+}
+
+impl fmt::Debug for AlignedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AlignedBuf")
+            .field("len", &self.len)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
@@ -0,0 +1,101 @@
+use futures::Future;
+use tokio_timer::Timeout;
+
+use std::io::{self, ErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A flag that asks long-running operations to stop.
+///
+/// Operations that accept a token check it between chunks of work, such as
+/// between blobs visited by [`GcOptions::gc`] or blocks of a file being
+/// hashed, and fail with an error of kind
+/// `Other` once it has been cancelled. Work that was never started is
+/// skipped altogether, so cancelling also stops operations still queued
+/// behind a hung one on the threadpool.
+///
+/// A single blocking call, like a read from a hung NFS mount, cannot be
+/// interrupted. The thread running it stays busy until the call returns, but
+/// the operation stops right after.
+///
+/// Cloning a token produces another handle to the same flag.
+///
+/// [`GcOptions::gc`]: struct.GcOptions.html#method.gc
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token that has not been cancelled.
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancels every operation using this token or one of its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns `true` if the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Fails if the token has been cancelled.
+    pub fn check(&self) -> io::Result<()> {
+        if self.is_cancelled() {
+            Err(io::Error::other("operation cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Bounds how long `future` may take, failing with an error of kind
+/// `TimedOut` once `timeout` has elapsed.
+///
+/// The caller stops waiting, but work already running on the threadpool
+/// carries on in the background. Pair this with a [`CancellationToken`],
+/// cancelled when the timeout hits, to stop that work as well:
+///
+/// ```no_run
+/// # use actix_fs::{with_timeout, CancellationToken, GcOptions};
+/// # use futures::{stream, Future};
+/// # use std::time::Duration;
+/// let token = CancellationToken::new();
+/// let on_timeout = token.clone();
+/// let report = with_timeout(
+///     GcOptions::new()
+///         .cancellation(token)
+///         .gc("/mnt/nfs/blobs", stream::empty()),
+///     Duration::from_secs(60),
+/// )
+/// .map_err(move |err| {
+///     on_timeout.cancel();
+///     err
+/// });
+/// ```
+///
+/// [`CancellationToken`]: struct.CancellationToken.html
+pub fn with_timeout<F>(
+    future: F,
+    timeout: Duration,
+) -> impl Future<Item = F::Item, Error = io::Error>
+where
+    F: Future<Error = io::Error>,
+{
+    Timeout::new(future, timeout).map_err(move |err| {
+        if err.is_elapsed() {
+            io::Error::new(
+                ErrorKind::TimedOut,
+                format!("operation timed out after {:?}", timeout),
+            )
+        } else if err.is_inner() {
+            err.into_inner().unwrap()
+        } else {
+            crate::blocking_err(err.into_timer().unwrap())
+        }
+    })
+}
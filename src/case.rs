@@ -0,0 +1,84 @@
+use futures::Future;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// Finds entries below `dir` whose names differ only by case.
+///
+/// Resolves to one group per collision, each listing the colliding paths in
+/// sorted order. A tree without collisions can be copied to, or served from,
+/// a case-insensitive filesystem such as the defaults on macOS and Windows
+/// without one entry silently replacing another.
+///
+/// Names are compared after Unicode lowercasing. Symbolic links to
+/// directories are not followed.
+pub fn detect_case_collisions<P>(dir: P) -> impl Future<Item = Vec<Vec<PathBuf>>, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    crate::blocking(move || {
+        let mut collisions = Vec::new();
+        let mut pending = vec![dir.as_ref().to_owned()];
+        while let Some(current) = pending.pop() {
+            let mut folded: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+            for entry in fs::read_dir(&current)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    pending.push(entry.path());
+                }
+                folded
+                    .entry(fold(Path::new(&entry.file_name())))
+                    .or_default()
+                    .push(entry.path());
+            }
+            for (_, mut paths) in folded {
+                if paths.len() > 1 {
+                    paths.sort();
+                    collisions.push(paths);
+                }
+            }
+        }
+        collisions.sort();
+        Ok(collisions)
+    })
+}
+
+/// Fails with `AlreadyExists` if the directory containing `path` has an
+/// entry whose name differs from that of `path` only by case.
+pub(crate) fn check_case_collision(path: &Path) -> io::Result<()> {
+    let name = match path.file_name() {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+    let parent = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    let folded = fold(Path::new(name));
+    let entries = match fs::read_dir(parent) {
+        Ok(entries) => entries,
+        // Opening fails anyway and reports a better error.
+        Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    for entry in entries {
+        let existing = entry?.file_name();
+        if existing != name && fold(Path::new(&existing)) == folded {
+            return Err(io::Error::new(
+                ErrorKind::AlreadyExists,
+                format!(
+                    "{} differs only by case from existing {}",
+                    path.display(),
+                    parent.join(existing).display()
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn fold(name: &Path) -> String {
+    name.to_string_lossy().to_lowercase()
+}
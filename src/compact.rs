@@ -0,0 +1,381 @@
+use futures::{stream, Future, Stream};
+
+use std::ffi::OsString;
+use std::fs::{self, File as StdFile};
+use std::io::{self, BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::Granularity;
+
+/// A compression format used when compacting partitions or precompressing
+/// static assets.
+///
+/// See [`CompactOptions`] and [`PrecompressOptions`].
+///
+/// [`CompactOptions`]: struct.CompactOptions.html
+/// [`PrecompressOptions`]: struct.PrecompressOptions.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Codec {
+    /// gzip, written with a `.gz` extension.
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// Brotli, written with a `.br` extension.
+    #[cfg(feature = "brotli")]
+    Brotli,
+    /// Zstandard, written with a `.zst` extension.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Codec {
+    /// Returns the file extension, without the leading dot, used for files
+    /// compressed with this codec.
+    pub fn extension(self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Codec::Gzip => "gz",
+            #[cfg(feature = "brotli")]
+            Codec::Brotli => "br",
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => "zst",
+        }
+    }
+
+    fn encoder<W>(self, writer: W) -> io::Result<Encoder<W>>
+    where
+        W: Write,
+    {
+        match self {
+            #[cfg(feature = "gzip")]
+            Codec::Gzip => Ok(Encoder::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            ))),
+            #[cfg(feature = "brotli")]
+            Codec::Brotli => Ok(Encoder::Brotli(Box::new(brotli::CompressorWriter::new(
+                writer, 4096, 11, 22,
+            )))),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => Ok(Encoder::Zstd(zstd::stream::write::Encoder::new(writer, 0)?)),
+        }
+    }
+
+    fn decoder<'a, R>(self, reader: R) -> io::Result<Box<dyn Read + 'a>>
+    where
+        R: Read + 'a,
+    {
+        match self {
+            #[cfg(feature = "gzip")]
+            Codec::Gzip => Ok(Box::new(flate2::read::GzDecoder::new(reader))),
+            #[cfg(feature = "brotli")]
+            Codec::Brotli => Ok(Box::new(brotli::Decompressor::new(reader, 4096))),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => Ok(Box::new(zstd::stream::read::Decoder::new(reader)?)),
+        }
+    }
+}
+
+enum Encoder<W: Write> {
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "brotli")]
+    Brotli(Box<brotli::CompressorWriter<W>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    fn finish(self) -> io::Result<W> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.finish(),
+            #[cfg(feature = "brotli")]
+            Encoder::Brotli(mut encoder) => {
+                encoder.flush()?;
+                Ok(encoder.into_inner())
+            }
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "brotli")]
+            Encoder::Brotli(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "brotli")]
+            Encoder::Brotli(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// The outcome of compacting a single partition.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompactReport {
+    /// The partition directory, relative to the root.
+    pub partition: PathBuf,
+    /// The files that were compressed and then removed.
+    pub inputs: Vec<PathBuf>,
+    /// The compressed files that replaced them.
+    pub outputs: Vec<PathBuf>,
+    /// The total size of the inputs in bytes.
+    pub bytes_in: u64,
+    /// The total size of the outputs in bytes.
+    pub bytes_out: u64,
+}
+
+/// Options for compacting the closed partitions written by a
+/// [`PartitionedWriter`].
+///
+/// A partition is closed once the current time has moved past it. Every
+/// uncompressed file in a closed partition is compressed, the compressed
+/// output is read back and compared against the original, and only then is
+/// the original removed. Running the compactor again is safe: partitions
+/// without uncompressed files are skipped, and as the originals of each
+/// output are removed as soon as it is in place, a rerun after an
+/// interrupted run only compresses what is left. When late records reopen a
+/// partition that was already compacted, the new records are appended to
+/// the existing compressed file rather than replacing it.
+///
+/// [`PartitionedWriter`]: struct.PartitionedWriter.html
+#[derive(Clone, Debug)]
+pub struct CompactOptions {
+    codec: Codec,
+    granularity: Granularity,
+    merge: bool,
+    now: Option<SystemTime>,
+}
+
+impl CompactOptions {
+    /// Creates options that compress each file of hourly partitions
+    /// separately with `codec`.
+    pub fn new(codec: Codec) -> CompactOptions {
+        CompactOptions {
+            codec,
+            granularity: Granularity::Hour,
+            merge: false,
+            now: None,
+        }
+    }
+
+    /// Sets the granularity the partitions were written with.
+    pub fn granularity(&mut self, granularity: Granularity) -> &mut CompactOptions {
+        self.granularity = granularity;
+        self
+    }
+
+    /// Sets whether all files of a partition are concatenated, in name
+    /// order, into a single `merged` output instead of being compressed
+    /// separately.
+    pub fn merge(&mut self, merge: bool) -> &mut CompactOptions {
+        self.merge = merge;
+        self
+    }
+
+    /// Sets the time used to decide which partitions are closed.
+    ///
+    /// Defaults to the current time when `compact` is called.
+    pub fn now(&mut self, now: SystemTime) -> &mut CompactOptions {
+        self.now = Some(now);
+        self
+    }
+
+    /// Compacts the closed partitions below `root`.
+    ///
+    /// Partitions are processed one at a time, oldest first, and the
+    /// returned stream yields a report as each one completes. Collect the
+    /// stream to get a report for the whole run.
+    pub fn compact<P>(&self, root: P) -> impl Stream<Item = CompactReport, Error = io::Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let root = root.into();
+        let opts = self.clone();
+        let now = self.now.unwrap_or_else(SystemTime::now);
+        let open = opts.granularity.partition(now);
+        let depth = opts.granularity.depth();
+
+        let list_root = root.clone();
+        crate::blocking(move || closed_partitions(&list_root, depth, &open))
+            .map(stream::iter_ok)
+            .flatten_stream()
+            .and_then(move |partition| {
+                let root = root.clone();
+                let opts = opts.clone();
+                crate::blocking(move || compact_partition(&root, partition, &opts))
+            })
+            .filter_map(|report| report)
+    }
+}
+
+/// Returns the partitions below `root` that sort before `open`, oldest first.
+fn closed_partitions(root: &Path, depth: usize, open: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut level = vec![PathBuf::new()];
+    for _ in 0..depth {
+        let mut next = Vec::new();
+        for dir in level {
+            let entries = match fs::read_dir(root.join(&dir)) {
+                Ok(entries) => entries,
+                Err(ref err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            for entry in entries {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    next.push(dir.join(entry.file_name()));
+                }
+            }
+        }
+        level = next;
+    }
+    level.retain(|partition| partition.as_path() < open);
+    level.sort();
+    Ok(level)
+}
+
+fn compact_partition(
+    root: &Path,
+    partition: PathBuf,
+    opts: &CompactOptions,
+) -> io::Result<Option<CompactReport>> {
+    let dir = root.join(&partition);
+    let ext = opts.codec.extension();
+
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let compressed = path.extension().is_some_and(|e| e == ext);
+        let temporary = path.extension().is_some_and(|e| e == "tmp");
+        if entry.file_type()?.is_file() && !compressed && !temporary {
+            inputs.push(path);
+        }
+    }
+    if inputs.is_empty() {
+        return Ok(None);
+    }
+    inputs.sort();
+
+    let groups: Vec<(PathBuf, Vec<PathBuf>)> = if opts.merge {
+        vec![(dir.join(format!("merged.{}", ext)), inputs.clone())]
+    } else {
+        inputs
+            .iter()
+            .map(|input| (with_extension(input, ext), vec![input.clone()]))
+            .collect()
+    };
+
+    let mut report = CompactReport {
+        partition,
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+        bytes_in: 0,
+        bytes_out: 0,
+    };
+    for (output, sources) in groups {
+        let tmp = with_extension(&output, "tmp");
+        let previous = match fs::metadata(&output) {
+            Ok(_) => {
+                let previous = with_extension(&output, "old.tmp");
+                decompress(opts.codec, &output, &previous)?;
+                Some(previous)
+            }
+            Err(ref err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+        let contents: Vec<PathBuf> = previous.iter().chain(&sources).cloned().collect();
+        compress(opts.codec, &contents, &tmp)?;
+        verify(opts.codec, &contents, &tmp)?;
+        let bytes_in = sources
+            .iter()
+            .map(|source| Ok(fs::metadata(source)?.len()))
+            .sum::<io::Result<u64>>()?;
+        fs::rename(&tmp, &output)?;
+        // The output now holds the sources, so remove them before anything
+        // else can fail. A rerun would otherwise append them a second time.
+        for source in &sources {
+            fs::remove_file(source)?;
+        }
+        if let Some(previous) = previous {
+            fs::remove_file(previous)?;
+        }
+
+        report.bytes_in += bytes_in;
+        report.bytes_out += fs::metadata(&output)?.len();
+        report.outputs.push(output);
+        report.inputs.extend(sources);
+    }
+    Ok(Some(report))
+}
+
+pub(crate) fn with_extension(path: &Path, ext: &str) -> PathBuf {
+    let mut name = OsString::from(path.file_name().unwrap_or_default());
+    name.push(".");
+    name.push(ext);
+    path.with_file_name(name)
+}
+
+pub(crate) fn compress(codec: Codec, sources: &[PathBuf], output: &Path) -> io::Result<()> {
+    let mut encoder = codec.encoder(StdFile::create(output)?)?;
+    for source in sources {
+        io::copy(&mut StdFile::open(source)?, &mut encoder)?;
+    }
+    encoder.finish()?.sync_all()
+}
+
+/// Decompresses the file at `input` into a new file at `output`.
+fn decompress(codec: Codec, input: &Path, output: &Path) -> io::Result<()> {
+    let mut decoded = codec.decoder(BufReader::new(StdFile::open(input)?))?;
+    io::copy(&mut decoded, &mut StdFile::create(output)?)?;
+    Ok(())
+}
+
+/// Checks that decompressing `output` yields exactly the concatenation of
+/// `sources`.
+fn verify(codec: Codec, sources: &[PathBuf], output: &Path) -> io::Result<()> {
+    let mut decoded = codec.decoder(BufReader::new(StdFile::open(output)?))?;
+    let mut expected = vec![0; 64 * 1024];
+    let mut actual = vec![0; 64 * 1024];
+    for source in sources {
+        let mut source = StdFile::open(source)?;
+        loop {
+            let n = source.read(&mut expected)?;
+            if n == 0 {
+                break;
+            }
+            decoded
+                .read_exact(&mut actual[..n])
+                .map_err(|_| mismatch(output))?;
+            if expected[..n] != actual[..n] {
+                return Err(mismatch(output));
+            }
+        }
+    }
+    if decoded.read(&mut actual)? != 0 {
+        return Err(mismatch(output));
+    }
+    Ok(())
+}
+
+fn mismatch(output: &Path) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("{} does not match its source files", output.display()),
+    )
+}
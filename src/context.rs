@@ -0,0 +1,374 @@
+use actix_web::dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{error, Error, FromRequest, HttpMessage, HttpRequest};
+use futures::future::{self, Either, FutureResult};
+use futures::{Future, Poll};
+
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{Account, Accounting, File, NamedFile, OpenOptions, Root};
+
+/// The filesystem context of a request, attached by the middleware built
+/// with [`FsContextOptions`].
+///
+/// It carries the root the request may access, a deadline, a budget of
+/// bytes it may read or write, labels to attach to metrics, and the
+/// [`Account`] its work is charged to. Handlers
+/// take it as an argument like any other extractor, and the file handlers
+/// and extractors of this crate pick it up on their own.
+///
+/// Cloning an `FsContext` produces another handle to the same budget.
+///
+/// This is only available with the `actix-web` feature.
+///
+/// [`FsContextOptions`]: struct.FsContextOptions.html
+/// [`Account`]: struct.Account.html
+#[derive(Clone, Debug)]
+pub struct FsContext {
+    root: Root,
+    deadline: Option<Instant>,
+    budget: Option<Arc<AtomicU64>>,
+    labels: Arc<Vec<(String, String)>>,
+    account: Option<Account>,
+}
+
+impl FsContext {
+    /// Returns the root the request may access.
+    pub fn root(&self) -> &Root {
+        &self.root
+    }
+
+    /// Returns the instant by which the request should be done.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Returns how many more bytes the request may read or write, if it has
+    /// a budget.
+    pub fn remaining(&self) -> Option<u64> {
+        self.budget
+            .as_ref()
+            .map(|budget| budget.load(Ordering::SeqCst))
+    }
+
+    /// Returns the metrics labels of the request.
+    pub fn labels(&self) -> &[(String, String)] {
+        &self.labels
+    }
+
+    /// Returns the account the work of the request is charged to, if the
+    /// middleware was built with [`FsContextOptions::accounting`].
+    ///
+    /// [`FsContextOptions::accounting`]: struct.FsContextOptions.html#method.accounting
+    pub fn account(&self) -> Option<&Account> {
+        self.account.as_ref()
+    }
+
+    /// Resolves `path` relative to the root like [`Root::resolve`].
+    ///
+    /// [`Root::resolve`]: struct.Root.html#method.resolve
+    pub fn resolve<P>(&self, path: P) -> io::Result<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        self.root.resolve(path)
+    }
+
+    /// Takes `bytes` from the budget.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `StorageFull`, taking nothing, if fewer bytes are left.
+    pub fn charge(&self, bytes: u64) -> io::Result<()> {
+        let budget = match self.budget {
+            Some(ref budget) => budget,
+            None => return Ok(()),
+        };
+        budget
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                left.checked_sub(bytes)
+            })
+            .map(|_| ())
+            .map_err(|_| io::Error::new(ErrorKind::StorageFull, "byte budget of request exceeded"))
+    }
+
+    /// Gives `bytes` taken by [`charge`] back to the budget.
+    ///
+    /// [`charge`]: #method.charge
+    #[cfg(feature = "actix-web")]
+    pub(crate) fn refund(&self, bytes: u64) {
+        if let Some(ref budget) = self.budget {
+            budget.fetch_add(bytes, Ordering::SeqCst);
+        }
+    }
+
+    /// Fails with `TimedOut` if the deadline has passed.
+    pub fn check_deadline(&self) -> io::Result<()> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(io::Error::new(
+                ErrorKind::TimedOut,
+                "deadline of request passed",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Bounds `future` by the deadline like [`with_timeout`].
+    ///
+    /// [`with_timeout`]: fn.with_timeout.html
+    pub fn with_deadline<F>(&self, future: F) -> impl Future<Item = F::Item, Error = io::Error>
+    where
+        F: Future<Error = io::Error>,
+    {
+        match self.deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                Either::A(crate::with_timeout(future, left))
+            }
+            None => Either::B(future),
+        }
+    }
+
+    /// Opens the file at `path`, relative to the root, in read-only mode.
+    pub fn open<P>(&self, path: P) -> impl Future<Item = File, Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut opts = OpenOptions::new();
+        opts.read(true);
+        if let Some(ref account) = self.account {
+            opts.account(account);
+        }
+        let open = self
+            .check_deadline()
+            .map(|()| self.with_deadline(self.root.open_with(path, &opts)));
+        future::result(open).flatten()
+    }
+
+    /// Opens the file at `path`, relative to the root, for serving, taking
+    /// its size from the budget.
+    ///
+    /// The size is charged to the account of the request as read up front,
+    /// after waiting for the account to be within its limit.
+    pub fn named_file<P>(&self, path: P) -> impl Future<Item = NamedFile, Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let ctx = self.clone();
+        let ready = match self.account {
+            Some(ref account) => Either::A(account.ready()),
+            None => Either::B(future::ok(())),
+        };
+        let open = self
+            .check_deadline()
+            .and_then(|()| self.resolve(path))
+            .map(|path| self.with_deadline(ready.and_then(|()| NamedFile::open(path))));
+        future::result(open).flatten().and_then(move |file| {
+            ctx.charge(file.len())?;
+            if let Some(ref account) = ctx.account {
+                account.record_read(file.len());
+            }
+            Ok(file)
+        })
+    }
+}
+
+impl FromRequest for FsContext {
+    type Config = ();
+    type Error = Error;
+    type Future = Result<FsContext, Error>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        req.extensions().get::<FsContext>().cloned().ok_or_else(|| {
+            error::ErrorInternalServerError("no FsContext, wrap the app with FsContextOptions")
+        })
+    }
+}
+
+type RootFn = dyn Fn(&ServiceRequest) -> Option<Root>;
+
+/// Options for the middleware attaching an [`FsContext`] to each request.
+///
+/// ```no_run
+/// # use actix_fs::{FsContext, FsContextOptions, NamedFile, Root};
+/// # use actix_web::{web, App};
+/// # use std::time::Duration;
+/// let app = App::new()
+///     .wrap(
+///         FsContextOptions::new()
+///             .timeout(Duration::from_secs(30))
+///             .budget(64 * 1024 * 1024)
+///             .middleware(Root::new("/srv/files")),
+///     )
+///     .route(
+///         "/{name}",
+///         web::get().to_async(|ctx: FsContext, name: web::Path<String>| {
+///             ctx.named_file(name.into_inner())
+///         }),
+///     );
+/// ```
+///
+/// This is only available with the `actix-web` feature.
+///
+/// [`FsContext`]: struct.FsContext.html
+#[derive(Clone, Debug, Default)]
+pub struct FsContextOptions {
+    timeout: Option<Duration>,
+    budget: Option<u64>,
+    labels: Vec<(String, String)>,
+    accounting: Option<Accounting>,
+}
+
+impl FsContextOptions {
+    /// Creates options without a timeout, budget or labels.
+    pub fn new() -> FsContextOptions {
+        FsContextOptions::default()
+    }
+
+    /// Sets how long after it arrives each request should be done.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut FsContextOptions {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how many bytes each request may read or write.
+    pub fn budget(&mut self, bytes: u64) -> &mut FsContextOptions {
+        self.budget = Some(bytes);
+        self
+    }
+
+    /// Adds a label to attach to metrics recorded for each request.
+    pub fn label<K, V>(&mut self, key: K, value: V) -> &mut FsContextOptions
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.labels.push((key.into(), value.into()));
+        self
+    }
+
+    /// Charges the work of each request to the account of `accounting`
+    /// named after the labels, such as `tenant=acme`, or `k1=v1,k2=v2` for
+    /// several.
+    ///
+    /// Requests wait while the account is over its limit, so give each
+    /// tenant its own middleware and labels to keep one tenant from starving
+    /// the others.
+    pub fn accounting(&mut self, accounting: &Accounting) -> &mut FsContextOptions {
+        self.accounting = Some(accounting.clone());
+        self
+    }
+
+    /// Creates middleware giving every request access to `root`.
+    pub fn middleware(&self, root: Root) -> FsContextMiddleware {
+        self.middleware_with(move |_| Some(root.clone()))
+    }
+
+    /// Creates middleware giving each request access to the root returned
+    /// by `f`, such as the root of a [`Tenant`] picked by a header.
+    ///
+    /// Requests for which `f` returns `None` fail with `404 Not Found`.
+    ///
+    /// [`Tenant`]: struct.Tenant.html
+    pub fn middleware_with<F>(&self, f: F) -> FsContextMiddleware
+    where
+        F: Fn(&ServiceRequest) -> Option<Root> + 'static,
+    {
+        let label = self
+            .labels
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(",");
+        FsContextMiddleware {
+            opts: Rc::new(self.clone()),
+            labels: Arc::new(self.labels.clone()),
+            account: self
+                .accounting
+                .as_ref()
+                .map(|accounting| accounting.account(&label)),
+            root: Rc::new(f),
+        }
+    }
+}
+
+/// Middleware attaching an [`FsContext`] to each request.
+///
+/// See [`FsContextOptions`] for how to create it.
+///
+/// [`FsContext`]: struct.FsContext.html
+/// [`FsContextOptions`]: struct.FsContextOptions.html
+#[derive(Clone)]
+pub struct FsContextMiddleware {
+    opts: Rc<FsContextOptions>,
+    labels: Arc<Vec<(String, String)>>,
+    account: Option<Account>,
+    root: Rc<RootFn>,
+}
+
+impl<S, B> Transform<S> for FsContextMiddleware
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = FsContextService<S>;
+    type Future = FutureResult<FsContextService<S>, ()>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(FsContextService {
+            service,
+            middleware: self.clone(),
+        })
+    }
+}
+
+/// The service created by [`FsContextMiddleware`].
+///
+/// [`FsContextMiddleware`]: struct.FsContextMiddleware.html
+pub struct FsContextService<S> {
+    service: S,
+    middleware: FsContextMiddleware,
+}
+
+impl<S, B> Service for FsContextService<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Either<S::Future, FutureResult<ServiceResponse<B>, Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Error> {
+        self.service.poll_ready()
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let root = match (self.middleware.root)(&req) {
+            Some(root) => root,
+            None => {
+                let res = req.error_response(error::ErrorNotFound("unknown root"));
+                return Either::B(future::ok(res));
+            }
+        };
+        let opts = &self.middleware.opts;
+        let ctx = FsContext {
+            root,
+            deadline: opts.timeout.map(|timeout| Instant::now() + timeout),
+            budget: opts.budget.map(|bytes| Arc::new(AtomicU64::new(bytes))),
+            labels: self.middleware.labels.clone(),
+            account: self.middleware.account.clone(),
+        };
+        req.extensions_mut().insert(ctx);
+        Either::A(self.service.call(req))
+    }
+}
@@ -0,0 +1,125 @@
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use tokio_io::{io as async_io, AsyncRead, AsyncWrite};
+
+use std::io;
+
+use crate::File;
+
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Options for copying between a [`File`] and an asynchronous reader or
+/// writer, such as a socket, a TLS stream or an encoder.
+///
+/// The data is moved one chunk at a time, so only a single chunk is held in
+/// memory. Reading from the file or writing to it happens on the
+/// threadpool, while the reader or writer is driven on the event loop.
+///
+/// [`File`]: struct.File.html
+#[derive(Clone, Debug)]
+pub struct CopyOptions {
+    chunk_size: usize,
+}
+
+impl CopyOptions {
+    /// Creates options that copy 64 KiB at a time.
+    pub fn new() -> CopyOptions {
+        CopyOptions {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// Sets how many bytes are copied at a time.
+    pub fn chunk_size(&mut self, bytes: usize) -> &mut CopyOptions {
+        self.chunk_size = bytes.max(1);
+        self
+    }
+
+    /// Copies the rest of `file`, from its current position, to `writer`.
+    ///
+    /// Resolves to the file, the writer and the number of bytes copied. The
+    /// writer is flushed once the end of the file is reached, but not shut
+    /// down, so it can still be written to.
+    pub fn copy_to<W>(
+        &self,
+        file: File,
+        writer: W,
+    ) -> impl Future<Item = (File, W, u64), Error = io::Error>
+    where
+        W: AsyncWrite,
+    {
+        let chunk_size = self.chunk_size;
+        future::loop_fn((file, writer, 0), move |(file, writer, copied)| {
+            file.read(chunk_size).and_then(move |(file, buf)| {
+                if buf.is_empty() {
+                    return Either::A(
+                        async_io::flush(writer)
+                            .map(move |writer| Loop::Break((file, writer, copied))),
+                    );
+                }
+                let len = buf.len() as u64;
+                Either::B(
+                    async_io::write_all(writer, buf)
+                        .map(move |(writer, _)| Loop::Continue((file, writer, copied + len))),
+                )
+            })
+        })
+    }
+
+    /// Copies everything `reader` yields to `file`, at its current position.
+    ///
+    /// Resolves to the reader, the file and the number of bytes copied. The
+    /// file is not synced.
+    pub fn copy_from<R>(
+        &self,
+        reader: R,
+        file: File,
+    ) -> impl Future<Item = (R, File, u64), Error = io::Error>
+    where
+        R: AsyncRead,
+    {
+        let chunk_size = self.chunk_size;
+        future::loop_fn((reader, file, 0), move |(reader, file, copied)| {
+            async_io::read(reader, vec![0; chunk_size]).and_then(move |(reader, mut buf, n)| {
+                if n == 0 {
+                    return Either::A(future::ok(Loop::Break((reader, file, copied))));
+                }
+                buf.truncate(n);
+                Either::B(
+                    file.write_all(buf)
+                        .map(move |file| Loop::Continue((reader, file, copied + n as u64))),
+                )
+            })
+        })
+    }
+}
+
+impl Default for CopyOptions {
+    fn default() -> CopyOptions {
+        CopyOptions::new()
+    }
+}
+
+/// Copies the rest of `file` to `writer`, 64 KiB at a time.
+///
+/// See [`CopyOptions`] for details and more control.
+///
+/// [`CopyOptions`]: struct.CopyOptions.html
+pub fn copy_to<W>(file: File, writer: W) -> impl Future<Item = (File, W, u64), Error = io::Error>
+where
+    W: AsyncWrite,
+{
+    CopyOptions::new().copy_to(file, writer)
+}
+
+/// Copies everything `reader` yields to `file`, 64 KiB at a time.
+///
+/// See [`CopyOptions`] for details and more control.
+///
+/// [`CopyOptions`]: struct.CopyOptions.html
+pub fn copy_from<R>(reader: R, file: File) -> impl Future<Item = (R, File, u64), Error = io::Error>
+where
+    R: AsyncRead,
+{
+    CopyOptions::new().copy_from(reader, file)
+}
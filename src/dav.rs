@@ -0,0 +1,632 @@
+use actix_web::http::header::{self, HttpDate};
+use actix_web::http::StatusCode;
+use actix_web::web::{self, Payload};
+use actix_web::{error, Error, HttpRequest, HttpResponse, Resource};
+use futures::{future, Future};
+
+use std::fmt::{self, Write};
+use std::fs::{self, Metadata};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::named::entity_tag;
+use crate::{Platform, ReceiveOptions, Root, ServeOptions};
+
+const ALLOW: &str = "OPTIONS, GET, HEAD, PUT, DELETE, MKCOL, COPY, MOVE, PROPFIND";
+const ALLOW_READ_ONLY: &str = "OPTIONS, GET, HEAD, PROPFIND";
+
+type Response = Box<dyn Future<Item = HttpResponse, Error = Error>>;
+
+/// Options for a resource serving a [`Root`] over a subset of WebDAV.
+///
+/// The resource answers `OPTIONS`, `GET`, `HEAD`, `PUT`, `DELETE`, `MKCOL`,
+/// `COPY`, `MOVE` and `PROPFIND` with `Depth` 0 or 1, which is enough for
+/// the file managers of common desktops to browse and edit the tree:
+///
+/// ```no_run
+/// # use actix_fs::{DavOptions, Root};
+/// # use actix_web::App;
+/// let app = App::new().service(
+///     DavOptions::new()
+///         .prefix("/dav")
+///         .resource(Root::new("/srv/files")),
+/// );
+/// ```
+///
+/// Every segment of a request path is checked with [`validate_filename`],
+/// and requests for paths that lead through a symbolic link are refused
+/// with `403 Forbidden`, so requests cannot leave the root. Links are
+/// checked before each request is handled, so a link created by another
+/// process while a request is handled can still be followed. Listings and
+/// copies skip links. Locking and properties other than
+/// the live ones are not supported, and `PROPFIND` reports the same set of
+/// properties whatever the body of the request asks for. Uploads go
+/// through [`receive_file`], so they are atomic and honor the budget and
+/// deadline of an [`FsContext`].
+///
+/// This is only available with the `webdav` feature.
+///
+/// [`Root`]: struct.Root.html
+/// [`validate_filename`]: fn.validate_filename.html
+/// [`receive_file`]: fn.receive_file.html
+/// [`FsContext`]: struct.FsContext.html
+#[derive(Clone, Debug, Default)]
+pub struct DavOptions {
+    prefix: String,
+    read_only: bool,
+}
+
+impl DavOptions {
+    /// Creates options for a writable resource mounted at `/`.
+    pub fn new() -> DavOptions {
+        DavOptions::default()
+    }
+
+    /// Sets the path the resource is mounted at, such as `/dav`.
+    pub fn prefix<S>(&mut self, prefix: S) -> &mut DavOptions
+    where
+        S: Into<String>,
+    {
+        self.prefix = prefix.into().trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Sets whether methods that change the tree are refused with
+    /// `405 Method Not Allowed`.
+    pub fn read_only(&mut self, read_only: bool) -> &mut DavOptions {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Creates the resource serving `root`, to be registered with
+    /// `App::service`.
+    pub fn resource(&self, root: Root) -> Resource {
+        let dav = Rc::new(Dav {
+            root,
+            opts: self.clone(),
+        });
+        web::resource(&format!("{}{{tail:.*}}", self.prefix)).route(
+            web::route()
+                .to_async(move |req: HttpRequest, payload: Payload| dav.handle(req, payload)),
+        )
+    }
+}
+
+/// Creates a writable WebDAV resource serving `root` at `prefix`.
+///
+/// See [`DavOptions`] for details and more control.
+///
+/// This is only available with the `webdav` feature.
+///
+/// [`DavOptions`]: struct.DavOptions.html
+pub fn dav<S>(prefix: S, root: Root) -> Resource
+where
+    S: Into<String>,
+{
+    DavOptions::new().prefix(prefix).resource(root)
+}
+
+struct Dav {
+    root: Root,
+    opts: DavOptions,
+}
+
+impl Dav {
+    fn handle(&self, req: HttpRequest, payload: Payload) -> Response {
+        let (relative, path) = match self.resolve(req.path()) {
+            Some(paths) => paths,
+            None => return Box::new(future::err(error::ErrorNotFound("invalid path"))),
+        };
+        let method = req.method().as_str().to_owned();
+        let reads = ["OPTIONS", "GET", "HEAD", "PROPFIND"].contains(&method.as_str());
+        if self.opts.read_only && !reads {
+            let res = HttpResponse::MethodNotAllowed()
+                .header(header::ALLOW, ALLOW_READ_ONLY)
+                .finish();
+            return Box::new(future::ok(res));
+        }
+        match method.as_str() {
+            "OPTIONS" => {
+                let allow = if self.opts.read_only {
+                    ALLOW_READ_ONLY
+                } else {
+                    ALLOW
+                };
+                let res = HttpResponse::Ok()
+                    .header("DAV", "1")
+                    .header(header::ALLOW, allow)
+                    .finish();
+                Box::new(future::ok(res))
+            }
+            "GET" | "HEAD" => {
+                let mut opts = ServeOptions::new();
+                opts.precompressed(false);
+                let check = self.check_links(&path);
+                Box::new(
+                    check
+                        .map_err(into_error)
+                        .and_then(move |()| crate::serve_file(&req, path, &opts)),
+                )
+            }
+            "PUT" => put(req, payload, self.check_links(&path), path),
+            "DELETE" => delete(relative, self.check_links(&path), path),
+            "MKCOL" => mkcol(&req, self.check_links(&path), path),
+            "COPY" | "MOVE" => self.transfer(&req, relative, path, method == "MOVE"),
+            "PROPFIND" => propfind(
+                &req,
+                self.opts.prefix.clone(),
+                relative,
+                self.check_links(&path),
+                path,
+            ),
+            _ => {
+                let res = HttpResponse::MethodNotAllowed()
+                    .header(header::ALLOW, ALLOW)
+                    .finish();
+                Box::new(future::ok(res))
+            }
+        }
+    }
+
+    /// Returns the relative and the resolved path for the path of a URL, or
+    /// `None` if it is not below the prefix or not valid.
+    fn resolve(&self, url_path: &str) -> Option<(PathBuf, PathBuf)> {
+        let tail = url_path.strip_prefix(self.opts.prefix.as_str())?;
+        if !tail.is_empty() && !tail.starts_with('/') {
+            return None;
+        }
+        let decoded = crate::extract::percent_decode(tail)?;
+        let mut relative = PathBuf::new();
+        for segment in decoded.split('/').filter(|segment| !segment.is_empty()) {
+            crate::validate_filename(segment, Platform::current()).ok()?;
+            relative.push(segment);
+        }
+        let path = self.root.resolve(&relative).ok()?;
+        Some((relative, path))
+    }
+
+    /// Fails with `403 Forbidden` if any component of `path` below the root
+    /// is a symbolic link, which could lead outside of it.
+    fn check_links(&self, path: &Path) -> impl Future<Item = (), Error = io::Error> {
+        let root = self.root.path().to_owned();
+        let path = path.to_owned();
+        crate::blocking(move || check_links(&root, &path))
+    }
+
+    fn transfer(
+        &self,
+        req: &HttpRequest,
+        relative: PathBuf,
+        from: PathBuf,
+        rename: bool,
+    ) -> Response {
+        let destination = match req
+            .headers()
+            .get("Destination")
+            .and_then(|value| value.to_str().ok())
+        {
+            Some(destination) => destination,
+            None => return Box::new(future::err(error::ErrorBadRequest("no Destination"))),
+        };
+        let (to_relative, to) = match self.resolve(url_path(destination)) {
+            Some(paths) => paths,
+            None => {
+                let err = error::ErrorForbidden("destination is outside the resource");
+                return Box::new(future::err(err));
+            }
+        };
+        if relative.as_os_str().is_empty() || to_relative.as_os_str().is_empty() {
+            let err = error::ErrorForbidden("cannot copy or move the root");
+            return Box::new(future::err(err));
+        }
+        // Replacing an ancestor of the source would remove the source with
+        // it, and copying or moving into the source would never end.
+        if to.starts_with(&from) || from.starts_with(&to) {
+            let err = error::ErrorForbidden("cannot copy or move a collection into itself");
+            return Box::new(future::err(err));
+        }
+        let overwrite = !header_is(req, "Overwrite", "F");
+        let recursive = rename || !header_is(req, "Depth", "0");
+
+        let root = self.root.path().to_owned();
+        let transfer = crate::blocking(move || {
+            check_links(&root, &from)?;
+            check_links(&root, &to)?;
+            let metadata = fs::symlink_metadata(&from)?;
+            let existing = match fs::symlink_metadata(&to) {
+                Ok(_) if !overwrite => {
+                    return Err(status(
+                        StatusCode::PRECONDITION_FAILED,
+                        "destination exists",
+                    ));
+                }
+                Ok(existing) => Some(existing),
+                Err(ref err) if err.kind() == ErrorKind::NotFound => None,
+                Err(err) => return Err(err),
+            };
+            check_parent(&to)?;
+            if rename {
+                replace(&from, &to, metadata.is_dir(), existing.as_ref())?;
+            } else {
+                // The copy is made next to the destination, so that the
+                // destination is only replaced once the copy is complete.
+                let staged = sibling(&to, "copy");
+                let copied = copy(&from, &staged, &metadata, recursive)
+                    .and_then(|()| replace(&staged, &to, metadata.is_dir(), existing.as_ref()));
+                if let Err(err) = copied {
+                    if let Ok(staged_metadata) = fs::symlink_metadata(&staged) {
+                        let _ = remove(&staged, &staged_metadata);
+                    }
+                    return Err(err);
+                }
+            }
+            Ok(existing.is_some())
+        });
+        Box::new(transfer.map(created_or_replaced).map_err(into_error))
+    }
+}
+
+fn put<C>(req: HttpRequest, payload: Payload, links: C, path: PathBuf) -> Response
+where
+    C: Future<Item = (), Error = io::Error> + 'static,
+{
+    let check = path.clone();
+    let put = links
+        .and_then(move |()| {
+            crate::blocking(move || {
+                let existed = match fs::metadata(&check) {
+                    Ok(ref metadata) if metadata.is_dir() => {
+                        return Err(status(
+                            StatusCode::METHOD_NOT_ALLOWED,
+                            "resource is a collection",
+                        ));
+                    }
+                    Ok(_) => true,
+                    Err(ref err) if err.kind() == ErrorKind::NotFound => false,
+                    Err(err) => return Err(err),
+                };
+                check_parent(&check)?;
+                Ok(existed)
+            })
+        })
+        .map_err(into_error)
+        .and_then(move |existed| {
+            crate::receive_file(&req, payload, path, &ReceiveOptions::new())
+                .map(move |_| created_or_replaced(existed))
+        });
+    Box::new(put)
+}
+
+fn delete<C>(relative: PathBuf, links: C, path: PathBuf) -> Response
+where
+    C: Future<Item = (), Error = io::Error> + 'static,
+{
+    if relative.as_os_str().is_empty() {
+        return Box::new(future::err(error::ErrorForbidden("cannot delete the root")));
+    }
+    let delete = links.and_then(move |()| {
+        crate::blocking(move || {
+            let metadata = fs::symlink_metadata(&path)?;
+            remove(&path, &metadata)
+        })
+    });
+    Box::new(
+        delete
+            .map(|()| HttpResponse::NoContent().finish())
+            .map_err(into_error),
+    )
+}
+
+fn mkcol<C>(req: &HttpRequest, links: C, path: PathBuf) -> Response
+where
+    C: Future<Item = (), Error = io::Error> + 'static,
+{
+    let has_body = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value != "0");
+    if has_body {
+        let err = error::ErrorUnsupportedMediaType("MKCOL with a body is not supported");
+        return Box::new(future::err(err));
+    }
+    let mkcol = links.and_then(move |()| {
+        crate::blocking(move || match fs::create_dir(&path) {
+            Ok(()) => Ok(()),
+            Err(ref err) if err.kind() == ErrorKind::AlreadyExists => Err(status(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "resource already exists",
+            )),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Err(status(
+                StatusCode::CONFLICT,
+                "parent collection does not exist",
+            )),
+            Err(err) => Err(err),
+        })
+    });
+    Box::new(
+        mkcol
+            .map(|()| HttpResponse::Created().finish())
+            .map_err(into_error),
+    )
+}
+
+fn propfind<C>(
+    req: &HttpRequest,
+    prefix: String,
+    relative: PathBuf,
+    links: C,
+    path: PathBuf,
+) -> Response
+where
+    C: Future<Item = (), Error = io::Error> + 'static,
+{
+    let depth = req
+        .headers()
+        .get("Depth")
+        .and_then(|value| value.to_str().ok());
+    let children = match depth {
+        Some("0") => false,
+        Some("1") => true,
+        _ => {
+            let err = error::ErrorForbidden("only Depth 0 and 1 are supported");
+            return Box::new(future::err(err));
+        }
+    };
+    let list = links.and_then(move |()| {
+        crate::blocking(move || {
+            let metadata = fs::metadata(&path)?;
+            let mut entries = Vec::new();
+            if children && metadata.is_dir() {
+                for entry in fs::read_dir(&path)? {
+                    let entry = entry?;
+                    // Names that cannot be put in a URL are left out, and so
+                    // are links, which cannot be followed.
+                    if entry.file_name().to_str().is_none() {
+                        continue;
+                    }
+                    match fs::symlink_metadata(entry.path()) {
+                        Ok(ref metadata) if metadata.file_type().is_symlink() => {}
+                        Ok(metadata) => entries.push((relative.join(entry.file_name()), metadata)),
+                        Err(ref err) if err.kind() == ErrorKind::NotFound => {}
+                        Err(err) => return Err(err),
+                    }
+                }
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+            entries.insert(0, (relative, metadata));
+            Ok(entries)
+        })
+    });
+    Box::new(list.map_err(into_error).map(move |entries| {
+        let mut body = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        body.push_str("<D:multistatus xmlns:D=\"DAV:\">\n");
+        for (relative, metadata) in entries {
+            write_response(&mut body, &prefix, &relative, &metadata);
+        }
+        body.push_str("</D:multistatus>\n");
+        HttpResponse::build(StatusCode::MULTI_STATUS)
+            .content_type("application/xml; charset=utf-8")
+            .body(body)
+    }))
+}
+
+/// Writes the `response` element of a `multistatus` body for one entry.
+fn write_response(body: &mut String, prefix: &str, relative: &Path, metadata: &Metadata) {
+    let mut href = prefix.to_owned();
+    for component in relative.iter() {
+        href.push('/');
+        href.push_str(&percent_encode(&component.to_string_lossy()));
+    }
+    if metadata.is_dir() || href.is_empty() {
+        href.push('/');
+    }
+    let name = relative
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+
+    // Writing to a `String` cannot fail.
+    let _ = writeln!(body, "<D:response><D:href>{}</D:href>", Xml(&href));
+    body.push_str("<D:propstat><D:prop>");
+    let _ = write!(body, "<D:displayname>{}</D:displayname>", Xml(&name));
+    if metadata.is_dir() {
+        body.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+    } else {
+        body.push_str("<D:resourcetype/>");
+        let content_type = mime_guess::from_path(relative).first_or_octet_stream();
+        let _ = write!(
+            body,
+            "<D:getcontentlength>{}</D:getcontentlength>\
+             <D:getcontenttype>{}</D:getcontenttype>",
+            metadata.len(),
+            Xml(content_type.as_ref())
+        );
+    }
+    if let Ok(modified) = metadata.modified() {
+        let _ = write!(
+            body,
+            "<D:getlastmodified>{}</D:getlastmodified>",
+            HttpDate::from(modified)
+        );
+        if let Some(etag) = entity_tag(metadata.len(), modified).filter(|_| metadata.is_file()) {
+            let _ = write!(body, "<D:getetag>{}</D:getetag>", Xml(&etag.to_string()));
+        }
+    }
+    body.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+}
+
+/// Removes the file or directory tree at `path`.
+fn remove(path: &Path, metadata: &Metadata) -> io::Result<()> {
+    if metadata.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Copies the file or directory at `from` to `to`, with the contents of a
+/// directory only if `recursive` is set.
+fn copy(from: &Path, to: &Path, metadata: &Metadata, recursive: bool) -> io::Result<()> {
+    if !metadata.is_dir() {
+        return fs::copy(from, to).map(|_| ());
+    }
+    fs::create_dir(to)?;
+    if recursive {
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            let metadata = fs::symlink_metadata(entry.path())?;
+            // Copying a link would copy what it points to, which may be
+            // outside the root.
+            if metadata.file_type().is_symlink() {
+                continue;
+            }
+            copy(&entry.path(), &to.join(entry.file_name()), &metadata, true)?;
+        }
+    }
+    Ok(())
+}
+
+/// Renames `from` to `to`, replacing `existing`, what `to` is, only once the
+/// rename has succeeded.
+fn replace(from: &Path, to: &Path, dir: bool, existing: Option<&Metadata>) -> io::Result<()> {
+    let existing = match existing {
+        // A file replaces a file atomically.
+        Some(existing) if dir || existing.is_dir() => existing,
+        _ => return fs::rename(from, to),
+    };
+    let old = sibling(to, "old");
+    fs::rename(to, &old)?;
+    if let Err(err) = fs::rename(from, to) {
+        let _ = fs::rename(&old, to);
+        return Err(err);
+    }
+    remove(&old, existing)
+}
+
+/// Returns a path next to `path` for staging a transfer.
+fn sibling(path: &Path, kind: &str) -> PathBuf {
+    let mut sibling = path.to_owned().into_os_string();
+    sibling.push(format!(".{}.dav-{}", std::process::id(), kind));
+    PathBuf::from(sibling)
+}
+
+/// Fails with `403 Forbidden` if any component of `path` below `root` is a
+/// symbolic link.
+fn check_links(root: &Path, path: &Path) -> io::Result<()> {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let mut current = root.to_owned();
+    for component in relative.components() {
+        current.push(component);
+        match fs::symlink_metadata(&current) {
+            Ok(ref metadata) if metadata.file_type().is_symlink() => {
+                return Err(status(
+                    StatusCode::FORBIDDEN,
+                    "path leads through a symbolic link",
+                ));
+            }
+            Ok(_) => {}
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// Fails with `409 Conflict` if the parent of `path` is not a directory.
+fn check_parent(path: &Path) -> io::Result<()> {
+    let parent = path.parent().is_some_and(Path::is_dir);
+    if !parent {
+        return Err(status(
+            StatusCode::CONFLICT,
+            "parent collection does not exist",
+        ));
+    }
+    Ok(())
+}
+
+fn created_or_replaced(existed: bool) -> HttpResponse {
+    if existed {
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::Created().finish()
+    }
+}
+
+fn header_is(req: &HttpRequest, name: &str, expected: &str) -> bool {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case(expected))
+}
+
+/// Returns the path of a `Destination` header, which may be an absolute URL.
+fn url_path(destination: &str) -> &str {
+    let path = match destination.find("://") {
+        Some(scheme) => {
+            let rest = &destination[scheme + 3..];
+            rest.find('/').map_or("/", |authority| &rest[authority..])
+        }
+        None => destination,
+    };
+    path.split(['?', '#']).next().unwrap_or_default()
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+/// Escapes text for XML.
+struct Xml<'a>(&'a str);
+
+impl fmt::Display for Xml<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&apos;")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An error that maps to a specific status, carried in an `io::Error` out
+/// of the threadpool.
+#[derive(Debug)]
+struct Status(StatusCode, &'static str);
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.1)
+    }
+}
+
+impl std::error::Error for Status {}
+
+fn status(code: StatusCode, reason: &'static str) -> io::Error {
+    io::Error::other(Status(code, reason))
+}
+
+fn into_error(err: io::Error) -> Error {
+    match err.get_ref().and_then(|err| err.downcast_ref::<Status>()) {
+        Some(&Status(code, reason)) => error::InternalError::new(reason, code).into(),
+        None => Error::from(err),
+    }
+}
@@ -1,8 +1,18 @@
-use futures::Future;
+use futures::{stream, Future, Stream};
 
-use std::fs;
-use std::io;
-use std::path::Path;
+use std::cmp::Ordering;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, Metadata};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use crate::{error, Pool};
+
+/// How many entries are stat'ed per trip to the threadpool.
+const STAT_BATCH: usize = 64;
 
 /// Creates a new, empty directory at the provided path
 ///
@@ -13,7 +23,23 @@ pub fn create_dir<P>(path: P) -> impl Future<Item = (), Error = io::Error>
 where
     P: AsRef<Path> + Send + 'static,
 {
-    crate::blocking(move || fs::create_dir(path.as_ref()))
+    crate::blocking(move || {
+        let path = path.as_ref();
+        fs::create_dir(path).map_err(|err| error::with_path(err, "create directory", path))
+    })
+}
+
+/// Like [`create_dir`], but runs on `pool` rather than the global threadpool.
+///
+/// [`create_dir`]: fn.create_dir.html
+pub fn create_dir_on<P>(pool: &Pool, path: P) -> impl Future<Item = (), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    crate::blocking_on(Some(pool), move || {
+        let path = path.as_ref();
+        fs::create_dir(path).map_err(|err| error::with_path(err, "create directory", path))
+    })
 }
 
 /// Recursively create a directory and all of its parent components if they
@@ -26,7 +52,24 @@ pub fn create_dir_all<P>(path: P) -> impl Future<Item = (), Error = io::Error>
 where
     P: AsRef<Path> + Send + 'static,
 {
-    crate::blocking(move || fs::create_dir_all(path.as_ref()))
+    crate::blocking(move || {
+        let path = path.as_ref();
+        fs::create_dir_all(path).map_err(|err| error::with_path(err, "create directory", path))
+    })
+}
+
+/// Like [`create_dir_all`], but runs on `pool` rather than the global
+/// threadpool.
+///
+/// [`create_dir_all`]: fn.create_dir_all.html
+pub fn create_dir_all_on<P>(pool: &Pool, path: P) -> impl Future<Item = (), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    crate::blocking_on(Some(pool), move || {
+        let path = path.as_ref();
+        fs::create_dir_all(path).map_err(|err| error::with_path(err, "create directory", path))
+    })
 }
 
 /// Removes an existing, empty directory.
@@ -38,5 +81,321 @@ pub fn remove_dir<P>(path: P) -> impl Future<Item = (), Error = io::Error>
 where
     P: AsRef<Path> + Send + 'static,
 {
-    crate::blocking(move || fs::remove_dir(path.as_ref()))
+    crate::blocking(move || {
+        let path = path.as_ref();
+        fs::remove_dir(path).map_err(|err| error::with_path(err, "remove directory", path))
+    })
+}
+
+/// Like [`remove_dir`], but runs on `pool` rather than the global threadpool.
+///
+/// [`remove_dir`]: fn.remove_dir.html
+pub fn remove_dir_on<P>(pool: &Pool, path: P) -> impl Future<Item = (), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    crate::blocking_on(Some(pool), move || {
+        let path = path.as_ref();
+        fs::remove_dir(path).map_err(|err| error::with_path(err, "remove directory", path))
+    })
+}
+
+/// An entry yielded by [`read_dir_snapshot`] or [`walk_dir_snapshot`], or
+/// collected by [`read_dir_collected`].
+///
+/// [`read_dir_snapshot`]: fn.read_dir_snapshot.html
+/// [`walk_dir_snapshot`]: fn.walk_dir_snapshot.html
+/// [`read_dir_collected`]: fn.read_dir_collected.html
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    path: PathBuf,
+    metadata: Metadata,
+}
+
+impl DirEntry {
+    /// Returns the full path of the entry.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the file name of the entry.
+    pub fn file_name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or_default()
+    }
+
+    /// Returns the metadata of the entry, read shortly before it was yielded.
+    ///
+    /// Symbolic links are not followed.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+}
+
+/// Returns a stream over the entries of a directory, listed up front.
+///
+/// The names in the directory are captured once, when the stream is first
+/// polled, and yielded in sorted order. Entries are stat'ed in small batches
+/// only as the stream advances, which gives well-defined behavior when the
+/// directory changes while it is being read:
+///
+/// * entries created after the listing are never yielded,
+/// * entries removed before they are stat'ed are skipped,
+/// * entries modified before they are stat'ed report their new metadata.
+///
+/// No entry is ever yielded twice.
+pub fn read_dir_snapshot<P>(path: P) -> impl Stream<Item = DirEntry, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    snapshot(move || {
+        let mut paths = list(path.as_ref())?;
+        paths.sort();
+        Ok(paths)
+    })
+}
+
+/// Returns a stream over every entry below a directory, listed up front.
+///
+/// The whole tree is listed when the stream is first polled, and entries
+/// are yielded in sorted order, so a directory comes right before its
+/// contents. Symbolic links to directories are not followed. A subdirectory
+/// removed while the tree is being listed is skipped. Otherwise this behaves
+/// like [`read_dir_snapshot`].
+///
+/// [`read_dir_snapshot`]: fn.read_dir_snapshot.html
+pub fn walk_dir_snapshot<P>(path: P) -> impl Stream<Item = DirEntry, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    snapshot(move || {
+        let root = path.as_ref();
+        let mut paths = Vec::new();
+        let mut pending = vec![root.to_owned()];
+        while let Some(dir) = pending.pop() {
+            let entries = match list(&dir) {
+                Ok(entries) => entries,
+                Err(ref err) if err.kind() == ErrorKind::NotFound && dir != root => continue,
+                Err(err) => return Err(err),
+            };
+            for entry in entries {
+                if fs::symlink_metadata(&entry).is_ok_and(|metadata| metadata.is_dir()) {
+                    pending.push(entry.clone());
+                }
+                paths.push(entry);
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    })
+}
+
+/// The order of the entries collected by [`read_dir_collected`].
+///
+/// Entries that compare equal are ordered by name.
+///
+/// [`read_dir_collected`]: fn.read_dir_collected.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SortBy {
+    /// By file name.
+    Name,
+    /// By last modification time, oldest first.
+    Modified,
+    /// By size, smallest first.
+    Size,
+}
+
+type Filter = dyn Fn(&DirEntry) -> bool + Send + Sync;
+
+/// Options for [`read_dir_collected`].
+///
+/// [`read_dir_collected`]: fn.read_dir_collected.html
+#[derive(Clone)]
+pub struct ReadDirOptions {
+    extensions: Vec<String>,
+    filter: Option<Arc<Filter>>,
+    sort_by: SortBy,
+    reverse: bool,
+}
+
+impl ReadDirOptions {
+    /// Creates options that collect every entry, sorted by name.
+    pub fn new() -> ReadDirOptions {
+        ReadDirOptions {
+            extensions: Vec::new(),
+            filter: None,
+            sort_by: SortBy::Name,
+            reverse: false,
+        }
+    }
+
+    /// Adds an extension, without the leading dot, that entries must have.
+    ///
+    /// Once any is added, entries without one of the extensions are left
+    /// out. Extensions are compared ignoring ASCII case.
+    pub fn extension<S>(&mut self, extension: S) -> &mut ReadDirOptions
+    where
+        S: Into<String>,
+    {
+        self.extensions.push(extension.into());
+        self
+    }
+
+    /// Sets a function deciding which entries are kept.
+    pub fn filter<F>(&mut self, f: F) -> &mut ReadDirOptions
+    where
+        F: Fn(&DirEntry) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets the order of the entries.
+    pub fn sort_by(&mut self, sort_by: SortBy) -> &mut ReadDirOptions {
+        self.sort_by = sort_by;
+        self
+    }
+
+    /// Sets whether the order is reversed, such as to list the newest
+    /// entries first.
+    pub fn reverse(&mut self, reverse: bool) -> &mut ReadDirOptions {
+        self.reverse = reverse;
+        self
+    }
+
+    fn keeps(&self, entry: &DirEntry) -> bool {
+        let extension = entry.path.extension().and_then(OsStr::to_str);
+        let extension_ok = self.extensions.is_empty()
+            || extension.is_some_and(|extension| {
+                self.extensions
+                    .iter()
+                    .any(|wanted| wanted.eq_ignore_ascii_case(extension))
+            });
+        extension_ok && self.filter.as_ref().is_none_or(|filter| filter(entry))
+    }
+
+    fn compare(&self, a: &DirEntry, b: &DirEntry) -> Ordering {
+        let ordering = match self.sort_by {
+            SortBy::Name => Ordering::Equal,
+            SortBy::Modified => {
+                let modified = |entry: &DirEntry| entry.metadata.modified().unwrap_or(UNIX_EPOCH);
+                modified(a).cmp(&modified(b))
+            }
+            SortBy::Size => a.metadata.len().cmp(&b.metadata.len()),
+        };
+        let ordering = ordering.then_with(|| a.file_name().cmp(b.file_name()));
+        if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+impl Default for ReadDirOptions {
+    fn default() -> ReadDirOptions {
+        ReadDirOptions::new()
+    }
+}
+
+impl fmt::Debug for ReadDirOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReadDirOptions")
+            .field("extensions", &self.extensions)
+            .field("filter", &self.filter.is_some())
+            .field("sort_by", &self.sort_by)
+            .field("reverse", &self.reverse)
+            .finish()
+    }
+}
+
+/// Reads every entry of a directory with its metadata, filtered and sorted
+/// according to `opts`, in a single trip to the threadpool.
+///
+/// This suits rendering a listing of a directory, which needs the metadata
+/// of every entry anyway. Symbolic links are not followed, and entries
+/// removed before they are stat'ed are skipped. Use [`read_dir_snapshot`] for
+/// directories too large to hold in memory at once.
+///
+/// [`read_dir_snapshot`]: fn.read_dir_snapshot.html
+pub fn read_dir_collected<P>(
+    path: P,
+    opts: &ReadDirOptions,
+) -> impl Future<Item = Vec<DirEntry>, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    read_dir_collected_with(None, path, opts)
+}
+
+/// Like [`read_dir_collected`], but runs on `pool` rather than the global
+/// threadpool.
+///
+/// [`read_dir_collected`]: fn.read_dir_collected.html
+pub fn read_dir_collected_on<P>(
+    pool: &Pool,
+    path: P,
+    opts: &ReadDirOptions,
+) -> impl Future<Item = Vec<DirEntry>, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    read_dir_collected_with(Some(pool), path, opts)
+}
+
+fn read_dir_collected_with<P>(
+    pool: Option<&Pool>,
+    path: P,
+    opts: &ReadDirOptions,
+) -> impl Future<Item = Vec<DirEntry>, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let opts = opts.clone();
+    crate::blocking_on(pool, move || {
+        let mut entries = stat(list(path.as_ref())?)?;
+        entries.retain(|entry| opts.keeps(entry));
+        entries.sort_by(|a, b| opts.compare(a, b));
+        Ok(entries)
+    })
+}
+
+fn list(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::read_dir(dir)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect()
+        })
+        .map_err(|err| error::with_path(err, "read directory", dir))
+}
+
+fn snapshot<F>(list: F) -> impl Stream<Item = DirEntry, Error = io::Error>
+where
+    F: FnOnce() -> io::Result<Vec<PathBuf>> + Send + 'static,
+{
+    crate::blocking(list)
+        .map(|paths| {
+            let batches: Vec<Vec<PathBuf>> = paths
+                .chunks(STAT_BATCH)
+                .map(|batch| batch.to_vec())
+                .collect();
+            stream::iter_ok(batches)
+                .and_then(|batch| crate::blocking(move || stat(batch)))
+                .map(stream::iter_ok)
+                .flatten()
+        })
+        .flatten_stream()
+}
+
+/// Stats `paths`, skipping the ones that no longer exist.
+fn stat(paths: Vec<PathBuf>) -> io::Result<Vec<DirEntry>> {
+    let mut entries = Vec::with_capacity(paths.len());
+    for path in paths {
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) => entries.push(DirEntry { path, metadata }),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(entries)
 }
@@ -0,0 +1,154 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An I/O error together with the operation and the paths it failed on.
+///
+/// Functions of this crate that open, read, write, copy, hash, rename or
+/// remove files, or otherwise work on a file or path, attach the operation
+/// and paths to the errors they return, so logs read
+/// `open /srv/data/a.bin: No such file or directory` rather than only the
+/// message of the operating system. To keep every signature compatible
+/// with `std` and with code written against `io::Error`, the context
+/// travels inside the returned `io::Error`, which keeps the `ErrorKind` of
+/// the original error. Use [`Error::from_io`] to get at it.
+///
+/// Converting an `Error` into an `io::Error` preserves its kind and context,
+/// but not the OS error code, which an `io::Error` carrying anything else
+/// cannot hold. Match on OS error codes, such as `ESTALE` or `EXDEV`, with
+/// [`raw_os_error`], which looks through the context.
+///
+/// [`Error::from_io`]: #method.from_io
+/// [`raw_os_error`]: fn.raw_os_error.html
+#[derive(Debug)]
+pub struct Error {
+    op: &'static str,
+    path: Option<PathBuf>,
+    dest: Option<PathBuf>,
+    source: io::Error,
+}
+
+impl Error {
+    /// Creates an error for the operation `op` failing with `source`.
+    pub fn new(op: &'static str, source: io::Error) -> Error {
+        Error {
+            op,
+            path: None,
+            dest: None,
+            source,
+        }
+    }
+
+    /// Sets the path the operation failed on.
+    pub fn with_path<P>(mut self, path: P) -> Error
+    where
+        P: Into<PathBuf>,
+    {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Sets the second path of an operation on two paths, such as the
+    /// destination of a rename.
+    pub fn with_dest<P>(mut self, dest: P) -> Error
+    where
+        P: Into<PathBuf>,
+    {
+        self.dest = Some(dest.into());
+        self
+    }
+
+    /// Returns the context attached to `err` by this crate, if any.
+    pub fn from_io(err: &io::Error) -> Option<&Error> {
+        err.get_ref()?.downcast_ref::<Error>()
+    }
+
+    /// Returns the name of the operation that failed, such as `open`.
+    pub fn op(&self) -> &'static str {
+        self.op
+    }
+
+    /// Returns the path the operation failed on.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the second path of an operation on two paths.
+    pub fn dest(&self) -> Option<&Path> {
+        self.dest.as_deref()
+    }
+
+    /// Returns the kind of the underlying error.
+    pub fn kind(&self) -> io::ErrorKind {
+        self.source.kind()
+    }
+
+    /// Returns the underlying error, which carries the OS error code if there
+    /// is one.
+    pub fn io_error(&self) -> &io::Error {
+        &self.source
+    }
+
+    /// Returns the OS error code of the underlying error, if there is one.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        self.source.raw_os_error()
+    }
+
+    /// Returns the underlying error.
+    pub fn into_io_error(self) -> io::Error {
+        self.source
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.op)?;
+        if let Some(ref path) = self.path {
+            write!(f, " {}", path.display())?;
+        }
+        if let Some(ref dest) = self.dest {
+            write!(f, " -> {}", dest.display())?;
+        }
+        write!(f, ": {}", self.source)
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        io::Error::new(err.kind(), err)
+    }
+}
+
+/// Returns the OS error code of `err`, looking through the context attached
+/// by this crate.
+pub fn raw_os_error(err: &io::Error) -> Option<i32> {
+    match Error::from_io(err) {
+        Some(ctx) => ctx.raw_os_error(),
+        None => err.raw_os_error(),
+    }
+}
+
+/// Attaches `op` and `path` to `err`, unless it already has context.
+#[cfg_attr(not(feature = "runtime"), allow(dead_code))]
+pub(crate) fn with_path(err: io::Error, op: &'static str, path: &Path) -> io::Error {
+    if Error::from_io(&err).is_some() {
+        return err;
+    }
+    Error::new(op, err).with_path(path).into()
+}
+
+/// Attaches `op`, `from` and `to` to `err`, unless it already has context.
+#[cfg_attr(not(feature = "runtime"), allow(dead_code))]
+pub(crate) fn with_paths(err: io::Error, op: &'static str, from: &Path, to: &Path) -> io::Error {
+    if Error::from_io(&err).is_some() {
+        return err;
+    }
+    Error::new(op, err).with_path(from).with_dest(to).into()
+}
@@ -0,0 +1,123 @@
+use actix_web::dev::Payload;
+use actix_web::{error, Error, FromRequest, HttpRequest};
+use futures::{future, Future};
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::{FsContext, Platform};
+
+/// The path of an existing file or directory below the root of the
+/// request's [`FsContext`], taken from the last parameter of the route.
+///
+/// The parameter is percent-decoded, split at `/`, and every segment checked
+/// with [`validate_filename`] before the path is resolved against the root.
+/// Requests whose tail is empty, invalid, escapes the root, or names nothing
+/// that exists fail with `404 Not Found`, so a handler only ever sees a path
+/// that is safe to open:
+///
+/// ```no_run
+/// # use actix_fs::{FsContextOptions, NamedFile, Root, SafeFilePath};
+/// # use actix_web::{web, App};
+/// let app = App::new()
+///     .wrap(FsContextOptions::new().middleware(Root::new("/srv/files")))
+///     .route(
+///         "/files/{tail:.*}",
+///         web::get().to_async(|path: SafeFilePath| NamedFile::open(path)),
+///     );
+/// ```
+///
+/// This is only available with the `actix-web` feature.
+///
+/// [`FsContext`]: struct.FsContext.html
+/// [`validate_filename`]: fn.validate_filename.html
+#[derive(Clone, Debug)]
+pub struct SafeFilePath {
+    path: PathBuf,
+    relative: PathBuf,
+}
+
+impl SafeFilePath {
+    /// Returns the resolved path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path relative to the root.
+    pub fn relative(&self) -> &Path {
+        &self.relative
+    }
+
+    /// Returns the resolved path.
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+}
+
+impl AsRef<Path> for SafeFilePath {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl FromRequest for SafeFilePath {
+    type Config = ();
+    type Error = Error;
+    type Future = Box<dyn Future<Item = SafeFilePath, Error = Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let ctx = match FsContext::from_request(req, payload) {
+            Ok(ctx) => ctx,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        let tail = req.match_info().iter().last().map(|(_, value)| value);
+        let (relative, path) = match tail.and_then(relative).and_then(|relative| {
+            let path = ctx.resolve(&relative).ok()?;
+            Some((relative, path))
+        }) {
+            Some(paths) => paths,
+            None => return Box::new(future::err(error::ErrorNotFound("invalid path"))),
+        };
+        let exists = crate::blocking(move || match fs::metadata(&path) {
+            Ok(_) => Ok(SafeFilePath { path, relative }),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => {
+                Err(io::Error::new(ErrorKind::NotFound, "no such file"))
+            }
+            Err(err) => Err(err),
+        });
+        Box::new(ctx.with_deadline(exists).map_err(Error::from))
+    }
+}
+
+/// Decodes and checks a route tail, returning `None` if it is not a valid
+/// relative path.
+fn relative(tail: &str) -> Option<PathBuf> {
+    let decoded = percent_decode(tail)?;
+    let mut relative = PathBuf::new();
+    for segment in decoded.split('/').filter(|segment| !segment.is_empty()) {
+        crate::validate_filename(segment, Platform::current()).ok()?;
+        relative.push(segment);
+    }
+    if relative.as_os_str().is_empty() {
+        return None;
+    }
+    Some(relative)
+}
+
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
@@ -1,8 +1,21 @@
-use futures::Future;
+use futures::future::{self, Either};
+use futures::{Future, Stream};
 use std::convert::From;
-use std::fs::{self, OpenOptions as StdOpenOptions, File as StdFile};
-use std::io;
-use std::path::Path;
+use std::fs::{self, File as StdFile, FileTimes, OpenOptions as StdOpenOptions, TryLockError};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawHandle, RawHandle};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::case::check_case_collision;
+use crate::error;
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+use crate::hash::{self, Algorithm, Digest};
+use crate::{uring, Account, AlignedBuf, BufReader, LimitedWriter, Pool};
 
 /// A reference to an open file on the filesystem.
 ///
@@ -19,6 +32,248 @@ use std::path::Path;
 #[derive(Debug)]
 pub struct File {
     std: Option<StdFile>,
+    pool: Option<Pool>,
+    reopen: Option<Arc<Reopen>>,
+    account: Option<Account>,
+    // Attached to errors, if known.
+    path: Option<Arc<Path>>,
+    // Opened with `OpenOptions::direct`, so unaligned transfers have to fall
+    // back to the page cache, which io_uring operations cannot do.
+    direct: bool,
+}
+
+/// Copies up to `len` bytes from the current position of `src` to the current
+/// position of `dst`, advancing both.
+///
+/// Resolves to both files and the number of bytes copied, which is less than
+/// `len` only if the end of `src` was reached. On Linux this uses
+/// `copy_file_range`, which avoids copying the data through userspace and
+/// lets filesystems that support it share extents instead of duplicating
+/// them. Where that is not possible, such as on other platforms or between
+/// filesystems on older kernels, the data is read and written in chunks.
+///
+/// The copy runs on the pool of `src`. If it fails, both files are dropped.
+pub fn copy_file_range(
+    src: File,
+    mut dst: File,
+    len: u64,
+) -> impl Future<Item = (File, File, u64), Error = io::Error> {
+    let mut out = dst.take_std();
+    let paths = (src.path.clone(), dst.path.clone());
+    src.blocking_op("copy", Moved::Read(|&(_, copied)| copied), move |std| {
+        let copied = copy_range(std, &mut out, len).map_err(|err| match paths {
+            (Some(ref from), Some(ref to)) => error::with_paths(err, "copy", from, to),
+            (ref from, _) => with_file_path(err, "copy", from),
+        })?;
+        Ok((out, copied))
+    })
+    .map(move |(src, (out, copied))| {
+        if let Some(ref account) = dst.account {
+            account.record_write(copied);
+        }
+        dst.std = Some(out);
+        (src, dst, copied)
+    })
+}
+
+/// Copies up to `len` bytes like [`copy_file_range`], then reads the copied
+/// range of both files back and fails with `InvalidData` if their digests
+/// differ.
+///
+/// Resolves to both files, the number of bytes copied and the verified
+/// digest, with both positions after the copied range. `dst` has to be
+/// open for reading as well as writing. See [`hash::copy_verified`] for
+/// what the check catches.
+///
+/// This is only available with the `sha2` or `blake3` feature.
+///
+/// [`copy_file_range`]: fn.copy_file_range.html
+/// [`hash::copy_verified`]: hash/fn.copy_verified.html
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+pub fn copy_file_range_verified(
+    src: File,
+    dst: File,
+    len: u64,
+    algorithm: Algorithm,
+) -> impl Future<Item = (File, File, u64, Digest), Error = io::Error> {
+    copy_file_range(src, dst, len).and_then(move |(src, mut dst, copied)| {
+        let mut out = dst.take_std();
+        let paths = (src.path.clone(), dst.path.clone());
+        src.blocking_op("verify", Moved::Nothing, move |std| {
+            let digest =
+                verify_range(std, &mut out, copied, algorithm).map_err(|err| match paths {
+                    (Some(ref from), Some(ref to)) => error::with_paths(err, "verify", from, to),
+                    (ref from, _) => with_file_path(err, "verify", from),
+                })?;
+            Ok((out, digest))
+        })
+        .map(move |(src, (out, digest))| {
+            dst.std = Some(out);
+            (src, dst, copied, digest)
+        })
+    })
+}
+
+/// Hashes the `len` bytes before the positions of `src` and `dst`, failing
+/// unless they match, and leaves both positions where they were.
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+fn verify_range(
+    src: &mut StdFile,
+    dst: &mut StdFile,
+    len: u64,
+    algorithm: Algorithm,
+) -> io::Result<Digest> {
+    let token = crate::CancellationToken::new();
+    let digest = |file: &mut StdFile| {
+        let end = file.stream_position()?;
+        file.seek(SeekFrom::Start(end - len))?;
+        let digest = hash::digest_range(file, len, algorithm, &token)?;
+        file.seek(SeekFrom::Start(end))?;
+        Ok::<_, io::Error>(digest)
+    };
+    let expected = digest(src)?;
+    let actual = digest(dst)?;
+    hash::check(expected, actual)?;
+    Ok(actual)
+}
+
+/// Sets the last access and last modification times of the file or
+/// directory at `path`.
+///
+/// A time of `None` leaves that timestamp unchanged. Symbolic links are
+/// followed. See [`File::set_times`] for details.
+///
+/// [`File::set_times`]: struct.File.html#method.set_times
+pub fn set_file_times<P>(
+    path: P,
+    accessed: Option<SystemTime>,
+    modified: Option<SystemTime>,
+) -> impl Future<Item = (), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    crate::blocking(move || set_path_times(path.as_ref(), accessed, modified))
+}
+
+/// Sets the times of the file or directory at `path` on the current thread.
+///
+/// On Unix this uses `utimensat`, so no file is opened: write-only files
+/// work, and FIFOs and devices are left alone.
+#[cfg(unix)]
+pub(crate) fn set_path_times(
+    path: &Path,
+    accessed: Option<SystemTime>,
+    modified: Option<SystemTime>,
+) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let set = (|| {
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "path contains a nul byte"))?;
+        let times = [timespec(accessed)?, timespec(modified)?];
+        if unsafe { libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), times.as_ptr(), 0) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    })();
+    set.map_err(|err| error::with_path(err, "set times of", path))
+}
+
+/// Converts `time` for `utimensat`, where `None` leaves the time unchanged.
+#[cfg(unix)]
+fn timespec(time: Option<SystemTime>) -> io::Result<libc::timespec> {
+    use std::convert::TryFrom;
+
+    let time = match time {
+        Some(time) => time,
+        None => {
+            return Ok(libc::timespec {
+                tv_sec: 0,
+                tv_nsec: libc::UTIME_OMIT,
+            })
+        }
+    };
+    let (secs, nanos) = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => (since.as_secs() as i128, since.subsec_nanos()),
+        Err(err) => {
+            let before = err.duration();
+            match before.subsec_nanos() {
+                0 => (-(before.as_secs() as i128), 0),
+                nanos => (-(before.as_secs() as i128) - 1, 1_000_000_000 - nanos),
+            }
+        }
+    };
+    let tv_sec = libc::time_t::try_from(secs)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "time is out of range"))?;
+    Ok(libc::timespec {
+        tv_sec,
+        tv_nsec: nanos as _,
+    })
+}
+
+/// Sets the times of the file or directory at `path` on the current thread.
+#[cfg(windows)]
+pub(crate) fn set_path_times(
+    path: &Path,
+    accessed: Option<SystemTime>,
+    modified: Option<SystemTime>,
+) -> io::Result<()> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_FLAG_BACKUP_SEMANTICS, FILE_WRITE_ATTRIBUTES,
+    };
+
+    // Only the right to change attributes, which read-only files grant, and
+    // directories need backup semantics to be opened.
+    StdOpenOptions::new()
+        .access_mode(FILE_WRITE_ATTRIBUTES)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)
+        .and_then(|file| file.set_times(file_times(accessed, modified)))
+        .map_err(|err| error::with_path(err, "set times of", path))
+}
+
+fn file_times(accessed: Option<SystemTime>, modified: Option<SystemTime>) -> FileTimes {
+    let mut times = FileTimes::new();
+    if let Some(accessed) = accessed {
+        times = times.set_accessed(accessed);
+    }
+    if let Some(modified) = modified {
+        times = times.set_modified(modified);
+    }
+    times
+}
+
+/// An access pattern hint given to [`File::advise`].
+///
+/// [`File::advise`]: struct.File.html#method.advise
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Advice {
+    /// No particular access pattern, the default.
+    Normal,
+    /// The range will be read sequentially, so reading ahead more
+    /// aggressively pays off.
+    Sequential,
+    /// The range will be read in random order, so reading ahead is wasted.
+    Random,
+    /// The range will be read soon, so it can be read into the page cache
+    /// now.
+    WillNeed,
+    /// The range will not be read again soon, so its pages can be dropped
+    /// from the page cache.
+    DontNeed,
+}
+
+/// How to reopen a file whose handle went stale. See
+/// [`OpenOptions::reopen_on_stale`].
+///
+/// [`OpenOptions::reopen_on_stale`]: struct.OpenOptions.html#method.reopen_on_stale
+#[derive(Debug)]
+struct Reopen {
+    path: PathBuf,
+    opts: StdOpenOptions,
+    retries: usize,
 }
 
 impl File {
@@ -60,11 +315,11 @@ impl File {
     where
         P: AsRef<Path> + Send + 'static,
     {
-        crate::blocking(move || -> io::Result<File> {
-            let std = StdFile::create(path.as_ref())?;
-            let file = File::from_std(std);
-            Ok(file)
-        })
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
     }
 
     /// Convert a [`std::fs::File`][std] to a [`actix_fs::File`][file].
@@ -72,7 +327,986 @@ impl File {
     /// [std]: https://doc.rust-lang.org/std/fs/struct.File.html
     /// [file]: struct.File.html
     pub fn from_std(std: StdFile) -> File {
-        File { std: Some(std) }
+        File {
+            std: Some(std),
+            pool: None,
+            reopen: None,
+            account: None,
+            path: None,
+            direct: false,
+        }
+    }
+
+    /// Reads up to `len` bytes from the current position of the file.
+    ///
+    /// Resolves to the file and the bytes that were read. Like a single
+    /// `read` call this may return fewer than `len` bytes; an empty buffer
+    /// means the end of the file was reached.
+    ///
+    /// If the file was opened with [`OpenOptions::reopen_on_stale`], a read
+    /// failing because the handle went stale is retried on a fresh handle.
+    ///
+    /// [`OpenOptions::reopen_on_stale`]: struct.OpenOptions.html#method.reopen_on_stale
+    pub fn read(mut self, len: usize) -> impl Future<Item = (File, Vec<u8>), Error = io::Error> {
+        if self.pool.is_none() && self.reopen.is_none() && self.account.is_none() && !self.direct {
+            match uring::read(self.take_std(), len) {
+                Ok(read) => {
+                    let err_path = self.path.clone();
+                    return Either::A(
+                        read.map(move |(std, buf)| (self.restore(std), buf))
+                            .map_err(move |err| with_file_path(err, "read", &err_path)),
+                    );
+                }
+                Err(std) => self.std = Some(std),
+            }
+        }
+
+        let reopen = self.reopen.clone();
+        let bytes = Moved::Read(|buf: &Vec<u8>| buf.len() as u64);
+        Either::B(self.blocking_op("read", bytes, move |std| {
+            let mut buf = vec![0; len];
+            let mut retries = reopen.as_ref().map_or(0, |reopen| reopen.retries);
+            let n = loop {
+                let pos = match reopen {
+                    Some(_) => std.stream_position()?,
+                    None => 0,
+                };
+                match direct_io(std, |std| std.read(&mut buf)) {
+                    Err(ref err) if err.kind() == ErrorKind::Interrupted => {}
+                    Err(ref err) if retries > 0 && is_stale(err) => {
+                        retries -= 1;
+                        if let Some(ref reopen) = reopen {
+                            *std = reopen.opts.open(&reopen.path)?;
+                            std.seek(SeekFrom::Start(pos))?;
+                        }
+                    }
+                    res => break res?,
+                }
+            };
+            buf.truncate(n);
+            Ok(buf)
+        }))
+    }
+
+    /// Writes all of `buf` at the current position of the file.
+    pub fn write_all(mut self, buf: Vec<u8>) -> impl Future<Item = File, Error = io::Error> {
+        let buf = if self.pool.is_none() && self.account.is_none() && !self.direct {
+            match uring::write_all(self.take_std(), buf) {
+                Ok(write) => {
+                    let err_path = self.path.clone();
+                    return Either::A(
+                        write
+                            .map(move |std| self.restore(std))
+                            .map_err(move |err| with_file_path(err, "write", &err_path)),
+                    );
+                }
+                Err((std, buf)) => {
+                    self.std = Some(std);
+                    buf
+                }
+            }
+        } else {
+            buf
+        };
+
+        Either::B(
+            self.blocking_op("write", Moved::Written(|&len| len), move |std| {
+                write_all_direct(std, &buf)?;
+                Ok(buf.len() as u64)
+            })
+            .map(|(file, _)| file),
+        )
+    }
+
+    /// Reads into `buf`, replacing its contents with up to its capacity in
+    /// bytes.
+    ///
+    /// Resolves to the file and the buffer, which is empty only at the end of
+    /// the file. For a file opened with [`direct`], the read bypasses the
+    /// page cache if the file position is a multiple of
+    /// [`AlignedBuf::ALIGNMENT`], and goes through it otherwise.
+    ///
+    /// [`direct`]: struct.OpenOptions.html#method.direct
+    /// [`AlignedBuf::ALIGNMENT`]: struct.AlignedBuf.html#associatedconstant.ALIGNMENT
+    pub fn read_aligned(
+        self,
+        mut buf: AlignedBuf,
+    ) -> impl Future<Item = (File, AlignedBuf), Error = io::Error> {
+        let bytes = Moved::Read(|buf: &AlignedBuf| buf.len() as u64);
+        self.blocking_op("read", bytes, move |std| {
+            let n = loop {
+                match direct_io(std, |std| std.read(buf.spare_mut())) {
+                    Err(ref err) if err.kind() == ErrorKind::Interrupted => {}
+                    res => break res?,
+                }
+            };
+            buf.set_len(n);
+            Ok(buf)
+        })
+    }
+
+    /// Writes the contents of `buf` to the file.
+    ///
+    /// Resolves to the file and the buffer, so it can be reused. For a file
+    /// opened with [`direct`], the whole blocks of `buf` bypass the page
+    /// cache if the file position is a multiple of
+    /// [`AlignedBuf::ALIGNMENT`], and the remaining bytes go through it, so
+    /// a transfer can end anywhere.
+    ///
+    /// [`direct`]: struct.OpenOptions.html#method.direct
+    /// [`AlignedBuf::ALIGNMENT`]: struct.AlignedBuf.html#associatedconstant.ALIGNMENT
+    pub fn write_aligned(
+        self,
+        buf: AlignedBuf,
+    ) -> impl Future<Item = (File, AlignedBuf), Error = io::Error> {
+        let bytes = Moved::Written(|buf: &AlignedBuf| buf.len() as u64);
+        self.blocking_op("write", bytes, move |std| {
+            let (blocks, rest) =
+                buf.split_at(buf.len() / AlignedBuf::ALIGNMENT * AlignedBuf::ALIGNMENT);
+            write_all_direct(std, blocks)?;
+            write_all_direct(std, rest)?;
+            Ok(buf)
+        })
+    }
+
+    /// Attempts to sync all OS-internal metadata to disk.
+    ///
+    /// See the underlying [`sync_all`] call for details.
+    ///
+    /// [`sync_all`]: https://doc.rust-lang.org/std/fs/struct.File.html#method.sync_all
+    pub fn sync_all(mut self) -> impl Future<Item = File, Error = io::Error> {
+        if self.pool.is_none() && self.account.is_none() {
+            match uring::fsync(self.take_std()) {
+                Ok(sync) => {
+                    let err_path = self.path.clone();
+                    return Either::A(
+                        sync.map(move |std| self.restore(std))
+                            .map_err(move |err| with_file_path(err, "sync", &err_path)),
+                    );
+                }
+                Err(std) => self.std = Some(std),
+            }
+        }
+
+        Either::B(
+            self.blocking_op("sync", Moved::Nothing, |std| std.sync_all())
+                .map(|(file, _)| file),
+        )
+    }
+
+    /// Reserves disk space for the first `len` bytes of the file.
+    ///
+    /// Once this resolves, writes within that range will not fail for lack of
+    /// space, so a large download can fail up front with an out of space
+    /// error instead of partway through. The file is extended to `len` bytes
+    /// if it is shorter, with the new bytes reading as zeros, but it is never
+    /// shrunk.
+    ///
+    /// This uses `posix_fallocate` on Linux, Android and FreeBSD,
+    /// `F_PREALLOCATE` on macOS and iOS, and `SetFileInformationByHandle` on
+    /// Windows. Where the platform or filesystem cannot reserve space, the
+    /// file is only extended.
+    pub fn allocate(self, len: u64) -> impl Future<Item = File, Error = io::Error> {
+        self.blocking("allocate", move |std| allocate(std, len))
+            .map(|(file, _)| file)
+    }
+
+    /// Tells the kernel how a range of the file is going to be accessed.
+    ///
+    /// A `len` of zero means through the end of the file. For example,
+    /// advising [`DontNeed`] for the range already streamed to a client
+    /// drops its pages from the page cache, so serving a large file does not
+    /// evict the rest of the working set.
+    ///
+    /// This maps to `posix_fadvise` on Linux, Android and FreeBSD, and does
+    /// nothing on other platforms.
+    ///
+    /// [`DontNeed`]: enum.Advice.html#variant.DontNeed
+    pub fn advise(
+        self,
+        offset: u64,
+        len: u64,
+        advice: Advice,
+    ) -> impl Future<Item = File, Error = io::Error> {
+        self.blocking("advise", move |std| advise(std, offset, len, advice))
+            .map(|(file, _)| file)
+    }
+
+    /// Sends `len` bytes of the file, starting at `offset`, to `socket`.
+    ///
+    /// Resolves to the file, the socket and the number of bytes sent, which
+    /// is less than `len` only if the end of the file was reached. The file
+    /// position is not changed. On Linux this uses `sendfile`, so the data
+    /// never passes through userspace; elsewhere it is read and written in
+    /// chunks.
+    ///
+    /// The socket should be in blocking mode, as the copy runs on the
+    /// threadpool. If sending fails, both the file and the socket are
+    /// dropped.
+    ///
+    /// This is only available on Unix.
+    #[cfg(unix)]
+    pub fn sendfile<S>(
+        self,
+        socket: S,
+        offset: u64,
+        len: u64,
+    ) -> impl Future<Item = (File, S, u64), Error = io::Error>
+    where
+        S: Write + AsRawFd + Send + 'static,
+    {
+        self.blocking("sendfile", move |std| {
+            let mut socket = socket;
+            let sent = sendfile(std, &mut socket, offset, len)?;
+            Ok((socket, sent))
+        })
+        .map(|(file, (socket, sent))| (file, socket, sent))
+    }
+
+    /// Sets the last access and last modification times of the file.
+    ///
+    /// A time of `None` leaves that timestamp unchanged. This uses
+    /// `futimens` on Unix and `SetFileTime` on Windows.
+    pub fn set_times(
+        self,
+        accessed: Option<SystemTime>,
+        modified: Option<SystemTime>,
+    ) -> impl Future<Item = File, Error = io::Error> {
+        self.blocking("set times", move |std| {
+            std.set_times(file_times(accessed, modified))
+        })
+        .map(|(file, _)| file)
+    }
+
+    /// Moves the file position to the first byte of data at or after
+    /// `offset`, skipping holes in a sparse file.
+    ///
+    /// Resolves to the file and the new position, or `None` if there is no
+    /// more data after `offset`, in which case the position is unchanged.
+    /// Filesystems that do not track holes report the whole file as data.
+    ///
+    /// This uses `lseek` with `SEEK_DATA`, and is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn seek_data(
+        self,
+        offset: u64,
+    ) -> impl Future<Item = (File, Option<u64>), Error = io::Error> {
+        self.blocking("seek data", move |std| {
+            seek_sparse(std, offset, libc::SEEK_DATA)
+        })
+    }
+
+    /// Moves the file position to the start of the first hole at or after
+    /// `offset` in a sparse file.
+    ///
+    /// The end of the file counts as a hole, so this resolves to the file and
+    /// the new position unless `offset` is at or past the end of the file,
+    /// in which case it resolves to `None` and the position is unchanged.
+    ///
+    /// This uses `lseek` with `SEEK_HOLE`, and is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn seek_hole(
+        self,
+        offset: u64,
+    ) -> impl Future<Item = (File, Option<u64>), Error = io::Error> {
+        self.blocking("seek hole", move |std| {
+            seek_sparse(std, offset, libc::SEEK_HOLE)
+        })
+    }
+
+    /// Deallocates `len` bytes of the file starting at `offset`, turning
+    /// the range into a hole that reads as zeros.
+    ///
+    /// The size of the file does not change, even if the range extends past
+    /// its end, and an empty range does nothing. The filesystem may only
+    /// free whole blocks, zeroing the rest of the range. Fails with an error
+    /// of kind `Unsupported` if the filesystem cannot punch holes.
+    ///
+    /// This uses `fallocate` with `FALLOC_FL_PUNCH_HOLE`, and is only
+    /// available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn punch_hole(self, offset: u64, len: u64) -> impl Future<Item = File, Error = io::Error> {
+        self.blocking("punch hole", move |std| punch_hole(std, offset, len))
+            .map(|(file, _)| file)
+    }
+
+    /// Creates a new handle to the same underlying file.
+    ///
+    /// Resolves to the original file and the clone. Both handles share the
+    /// file position, so concurrent readers should use separate positions, for
+    /// example by opening the file again, if they read different ranges.
+    ///
+    /// See the underlying [`try_clone`] call for details.
+    ///
+    /// [`try_clone`]: https://doc.rust-lang.org/std/fs/struct.File.html#method.try_clone
+    pub fn try_clone(self) -> impl Future<Item = (File, File), Error = io::Error> {
+        self.blocking("clone", |std| std.try_clone())
+            .map(|(file, std)| {
+                let clone = File {
+                    std: Some(std),
+                    pool: file.pool.clone(),
+                    reopen: file.reopen.clone(),
+                    account: file.account.clone(),
+                    path: file.path.clone(),
+                    direct: file.direct,
+                };
+                (file, clone)
+            })
+    }
+
+    /// Converts the file into a [`std::fs::File`][std].
+    ///
+    /// [std]: https://doc.rust-lang.org/std/fs/struct.File.html
+    pub fn into_std(mut self) -> StdFile {
+        self.take_std()
+    }
+
+    /// Returns a stream over the lines of the file.
+    ///
+    /// The file is read in large chunks through a [`BufReader`]; see
+    /// [`BufReader::lines`] for details.
+    ///
+    /// [`BufReader`]: struct.BufReader.html
+    /// [`BufReader::lines`]: struct.BufReader.html#method.lines
+    pub fn lines(self) -> impl Stream<Item = String, Error = io::Error> {
+        BufReader::new(self).lines()
+    }
+
+    /// Returns a stream over the chunks of the file separated by `delim`.
+    ///
+    /// The file is read in large chunks through a [`BufReader`]; see
+    /// [`BufReader::split`] for details.
+    ///
+    /// [`BufReader`]: struct.BufReader.html
+    /// [`BufReader::split`]: struct.BufReader.html#method.split
+    pub fn split(self, delim: u8) -> impl Stream<Item = Vec<u8>, Error = io::Error> {
+        BufReader::new(self).split(delim)
+    }
+
+    /// Writes every chunk of `stream` at the current position of the file,
+    /// failing once more than `limit` bytes would be written.
+    ///
+    /// Resolves to the file and the number of bytes written. See
+    /// [`LimitedWriter`] for details and more control.
+    ///
+    /// [`LimitedWriter`]: struct.LimitedWriter.html
+    pub fn write_stream_limited<S>(
+        self,
+        stream: S,
+        limit: u64,
+    ) -> impl Future<Item = (File, u64), Error = io::Error>
+    where
+        S: Stream<Item = Vec<u8>, Error = io::Error>,
+    {
+        LimitedWriter::new(limit).write(self, stream)
+    }
+
+    /// Closes the file on the threadpool, reporting any error from closing it.
+    ///
+    /// Dropping a `File` closes it on the dropping thread and silently ignores
+    /// errors. Closing can block, notably on network filesystems such as NFS
+    /// that write back cached data when a file is closed, and that is also
+    /// where errors such as running out of space may surface.
+    pub fn close(mut self) -> impl Future<Item = (), Error = io::Error> {
+        let std = self.take_std();
+        crate::blocking_on(self.pool.as_ref(), move || close(std))
+    }
+
+    /// Acquires an exclusive advisory lock on the file, waiting until it
+    /// becomes available.
+    ///
+    /// The lock is held until [`unlock`] is called or the file is closed. It
+    /// is backed by `flock` on Unix and `LockFileEx` on Windows, so it
+    /// coordinates between processes as well as between handles within a
+    /// single process. The wait happens on the threadpool, never on the
+    /// calling thread.
+    ///
+    /// [`unlock`]: #method.unlock
+    pub fn lock_exclusive(self) -> impl Future<Item = File, Error = io::Error> {
+        self.blocking("lock", |std| std.lock())
+            .map(|(file, _)| file)
+    }
+
+    /// Acquires a shared advisory lock on the file, waiting until it becomes
+    /// available.
+    ///
+    /// Any number of handles may hold a shared lock at once, but none may
+    /// while an exclusive lock is held. See [`lock_exclusive`] for details.
+    ///
+    /// [`lock_exclusive`]: #method.lock_exclusive
+    pub fn lock_shared(self) -> impl Future<Item = File, Error = io::Error> {
+        self.blocking("lock", |std| std.lock_shared())
+            .map(|(file, _)| file)
+    }
+
+    /// Attempts to acquire an exclusive advisory lock on the file without
+    /// waiting.
+    ///
+    /// Resolves to the file and `true` if the lock was acquired, or `false` if
+    /// another handle currently holds a conflicting lock.
+    pub fn try_lock_exclusive(self) -> impl Future<Item = (File, bool), Error = io::Error> {
+        self.blocking("lock", |std| try_lock_result(std.try_lock()))
+    }
+
+    /// Attempts to acquire a shared advisory lock on the file without waiting.
+    ///
+    /// Resolves to the file and `true` if the lock was acquired, or `false` if
+    /// another handle currently holds an exclusive lock.
+    pub fn try_lock_shared(self) -> impl Future<Item = (File, bool), Error = io::Error> {
+        self.blocking("lock", |std| try_lock_result(std.try_lock_shared()))
+    }
+
+    /// Releases any advisory lock held by this handle.
+    pub fn unlock(self) -> impl Future<Item = File, Error = io::Error> {
+        self.blocking("unlock", |std| std.unlock())
+            .map(|(file, _)| file)
+    }
+
+    /// Hands back the handle an io_uring operation took, keeping everything
+    /// else the file was opened with.
+    fn restore(mut self, std: StdFile) -> File {
+        self.std = Some(std);
+        self
+    }
+
+    fn take_std(&mut self) -> StdFile {
+        self.std.take().expect("file already closed")
+    }
+
+    /// Runs `f` against the underlying `std::fs::File` on the threadpool as
+    /// the operation `op`, handing the file back together with the result.
+    ///
+    /// If `f` fails the file is dropped, closing it, and the error carries
+    /// `op` and the path of the file, if it is known.
+    pub(crate) fn blocking<F, T>(
+        self,
+        op: &'static str,
+        f: F,
+    ) -> impl Future<Item = (File, T), Error = io::Error>
+    where
+        F: FnOnce(&mut StdFile) -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.blocking_op(op, Moved::Nothing, f)
+    }
+
+    /// Like `blocking`, but also reports the operation to the installed
+    /// metrics as having moved the bytes `moved` counts in its output.
+    ///
+    /// If the file is charged to an account, the operation waits for the
+    /// account to be within its limit first, and is charged to it after.
+    pub(crate) fn blocking_op<F, T>(
+        mut self,
+        op: &'static str,
+        moved: Moved<T>,
+        f: F,
+    ) -> impl Future<Item = (File, T), Error = io::Error>
+    where
+        F: FnOnce(&mut StdFile) -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut std = self.take_std();
+        let pool = self.pool.take();
+        let reopen = self.reopen.take();
+        let account = self.account.take();
+        let path = self.path.take();
+        let direct = self.direct;
+        let ready = match account {
+            Some(ref account) => Either::A(account.ready()),
+            None => Either::B(future::ok(())),
+        };
+        ready.and_then(move |()| {
+            crate::blocking_op(
+                pool.clone().as_ref(),
+                op,
+                None,
+                move |(_, res)| moved.bytes(res),
+                move || -> io::Result<(File, T)> {
+                    let res = f(&mut std).map_err(|err| with_file_path(err, op, &path))?;
+                    if let Some(ref account) = account {
+                        match moved {
+                            Moved::Read(bytes) => account.record_read(bytes(&res)),
+                            Moved::Written(bytes) => account.record_write(bytes(&res)),
+                            Moved::Nothing => account.record_op(),
+                        }
+                    }
+                    Ok((
+                        File {
+                            std: Some(std),
+                            pool,
+                            reopen,
+                            account,
+                            path,
+                            direct,
+                        },
+                        res,
+                    ))
+                },
+            )
+        })
+    }
+}
+
+/// The file contents an operation on a [`File`] moved, counted from its
+/// output, for metrics and accounting.
+pub(crate) enum Moved<T> {
+    Read(fn(&T) -> u64),
+    Written(fn(&T) -> u64),
+    Nothing,
+}
+
+impl<T> Moved<T> {
+    fn bytes(&self, res: &T) -> u64 {
+        match *self {
+            Moved::Read(bytes) | Moved::Written(bytes) => bytes(res),
+            Moved::Nothing => 0,
+        }
+    }
+}
+
+impl<T> Clone for Moved<T> {
+    fn clone(&self) -> Moved<T> {
+        *self
+    }
+}
+
+impl<T> Copy for Moved<T> {}
+
+/// Attaches `op` and the path of a file, if known, to `err`.
+fn with_file_path(err: io::Error, op: &'static str, path: &Option<Arc<Path>>) -> io::Error {
+    match *path {
+        Some(ref path) => error::with_path(err, op, path),
+        None => err,
+    }
+}
+
+#[cfg(unix)]
+fn close(std: StdFile) -> io::Result<()> {
+    use std::os::unix::io::IntoRawFd;
+
+    let fd = std.into_raw_fd();
+    if unsafe { libc::close(fd) } == -1 {
+        let err = io::Error::last_os_error();
+        // The descriptor is released even when close is interrupted.
+        if err.kind() != ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn close(std: StdFile) -> io::Result<()> {
+    drop(std);
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn allocate(std: &StdFile, len: u64) -> io::Result<()> {
+    if len == 0 {
+        return Ok(());
+    }
+    if len > libc::off_t::MAX as u64 {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "length too large"));
+    }
+    match unsafe { libc::posix_fallocate(std.as_raw_fd(), 0, len as libc::off_t) } {
+        0 => Ok(()),
+        libc::EOPNOTSUPP => extend(std, len),
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn allocate(std: &StdFile, len: u64) -> io::Result<()> {
+    let current = std.metadata()?.len();
+    if len <= current {
+        return Ok(());
+    }
+    // Prefer contiguous space, but settle for any.
+    let mut store = libc::fstore_t {
+        fst_flags: libc::F_ALLOCATECONTIG,
+        fst_posmode: libc::F_PEOFPOSMODE,
+        fst_offset: 0,
+        fst_length: (len - current) as libc::off_t,
+        fst_bytesalloc: 0,
+    };
+    let fd = std.as_raw_fd();
+    if unsafe { libc::fcntl(fd, libc::F_PREALLOCATE, &store) } == -1 {
+        store.fst_flags = libc::F_ALLOCATEALL;
+        if unsafe { libc::fcntl(fd, libc::F_PREALLOCATE, &store) } == -1 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::ENOTSUP) {
+                return Err(err);
+            }
+        }
+    }
+    extend(std, len)
+}
+
+#[cfg(windows)]
+fn allocate(std: &StdFile, len: u64) -> io::Result<()> {
+    use std::mem;
+    use windows_sys::Win32::Storage::FileSystem::{
+        FileAllocationInfo, SetFileInformationByHandle, FILE_ALLOCATION_INFO,
+    };
+
+    // A smaller allocation size would truncate the file.
+    if len <= std.metadata()?.len() {
+        return Ok(());
+    }
+    let info = FILE_ALLOCATION_INFO {
+        AllocationSize: len as i64,
+    };
+    let ok = unsafe {
+        SetFileInformationByHandle(
+            std.as_raw_handle(),
+            FileAllocationInfo,
+            &info as *const FILE_ALLOCATION_INFO as *const _,
+            mem::size_of::<FILE_ALLOCATION_INFO>() as u32,
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    extend(std, len)
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "macos",
+    target_os = "ios",
+    windows
+)))]
+fn allocate(std: &StdFile, len: u64) -> io::Result<()> {
+    extend(std, len)
+}
+
+/// Grows the file to `len` bytes if it is shorter.
+fn extend(std: &StdFile, len: u64) -> io::Result<()> {
+    if std.metadata()?.len() < len {
+        std.set_len(len)?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn copy_range(src: &mut StdFile, dst: &mut StdFile, len: u64) -> io::Result<u64> {
+    use std::ptr;
+
+    let mut copied = 0;
+    while copied < len {
+        let chunk = (len - copied).min(1 << 30) as usize;
+        let n = unsafe {
+            libc::copy_file_range(
+                src.as_raw_fd(),
+                ptr::null_mut(),
+                dst.as_raw_fd(),
+                ptr::null_mut(),
+                chunk,
+                0,
+            )
+        };
+        if n == -1 {
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                // Unsupported by the kernel, the filesystems or the file types
+                // involved.
+                Some(libc::EXDEV)
+                | Some(libc::ENOSYS)
+                | Some(libc::EOPNOTSUPP)
+                | Some(libc::EINVAL) => {
+                    return copy_range_fallback(src, dst, len - copied).map(|n| copied + n);
+                }
+                _ => return Err(err),
+            }
+        }
+        if n == 0 {
+            break;
+        }
+        copied += n as u64;
+    }
+    Ok(copied)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copy_range(src: &mut StdFile, dst: &mut StdFile, len: u64) -> io::Result<u64> {
+    copy_range_fallback(src, dst, len)
+}
+
+fn copy_range_fallback(src: &mut StdFile, dst: &mut StdFile, len: u64) -> io::Result<u64> {
+    io::copy(&mut Read::by_ref(src).take(len), dst)
+}
+
+#[cfg(target_os = "linux")]
+fn sendfile<S>(std: &StdFile, socket: &mut S, offset: u64, len: u64) -> io::Result<u64>
+where
+    S: Write + AsRawFd,
+{
+    if offset > libc::off_t::MAX as u64 {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "offset too large"));
+    }
+    let mut off = offset as libc::off_t;
+    let mut sent = 0;
+    while sent < len {
+        let chunk = (len - sent).min(1 << 30) as usize;
+        let n = unsafe { libc::sendfile(socket.as_raw_fd(), std.as_raw_fd(), &mut off, chunk) };
+        if n == -1 {
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                // The file or socket type does not support `sendfile`.
+                Some(libc::EINVAL) | Some(libc::ENOSYS) => {
+                    return sendfile_fallback(std, socket, offset + sent, len - sent)
+                        .map(|n| sent + n);
+                }
+                _ => return Err(err),
+            }
+        }
+        if n == 0 {
+            break;
+        }
+        sent += n as u64;
+    }
+    Ok(sent)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn sendfile<S>(std: &StdFile, socket: &mut S, offset: u64, len: u64) -> io::Result<u64>
+where
+    S: Write,
+{
+    sendfile_fallback(std, socket, offset, len)
+}
+
+#[cfg(unix)]
+fn sendfile_fallback<S>(std: &StdFile, socket: &mut S, offset: u64, len: u64) -> io::Result<u64>
+where
+    S: Write,
+{
+    use std::os::unix::fs::FileExt;
+
+    let mut buf = vec![0; len.min(64 * 1024) as usize];
+    let mut sent = 0;
+    while sent < len {
+        let chunk = (len - sent).min(buf.len() as u64) as usize;
+        let n = match std.read_at(&mut buf[..chunk], offset + sent) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        socket.write_all(&buf[..n])?;
+        sent += n as u64;
+    }
+    Ok(sent)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn advise(std: &StdFile, offset: u64, len: u64, advice: Advice) -> io::Result<()> {
+    use std::convert::TryFrom;
+
+    let advice = match advice {
+        Advice::Normal => libc::POSIX_FADV_NORMAL,
+        Advice::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+        Advice::Random => libc::POSIX_FADV_RANDOM,
+        Advice::WillNeed => libc::POSIX_FADV_WILLNEED,
+        Advice::DontNeed => libc::POSIX_FADV_DONTNEED,
+    };
+    let offset = libc::off_t::try_from(offset)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "offset too large"))?;
+    // Lengths beyond the end of the file are fine, so clamp rather than fail.
+    let len = libc::off_t::try_from(len).unwrap_or(libc::off_t::MAX);
+    match unsafe { libc::posix_fadvise(std.as_raw_fd(), offset, len, advice) } {
+        0 => Ok(()),
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn advise(_: &StdFile, _: u64, _: u64, _: Advice) -> io::Result<()> {
+    Ok(())
+}
+
+/// Switches `std` to direct I/O where the filesystem supports it.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn set_direct(std: &StdFile) {
+    let fd = std.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags != -1 {
+        // Fails with `EINVAL` where the filesystem does not support it, in
+        // which case the file keeps using the page cache.
+        unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_DIRECT) };
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn set_direct(std: &StdFile) {
+    unsafe { libc::fcntl(std.as_raw_fd(), libc::F_NOCACHE, 1) };
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "macos",
+    target_os = "ios"
+)))]
+fn set_direct(_: &StdFile) {}
+
+/// Writes all of `buf` with [`direct_io`], one write at a time, so a write
+/// that has to go through the page cache only repeats what is left.
+fn write_all_direct(std: &mut StdFile, buf: &[u8]) -> io::Result<()> {
+    let mut written = 0;
+    while written < buf.len() {
+        match direct_io(std, |std| std.write(&buf[written..])) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(n) => written += n,
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// Runs `f`, a single transfer, retrying it through the page cache if it
+/// fails because the transfer is not aligned for direct I/O.
+///
+/// Such a transfer fails before moving any bytes, so the retry goes to a
+/// new description of the same file opened without `O_DIRECT`, starting at
+/// the position of `std`, which is then moved past what was transferred.
+/// The flags of `std` are left alone, as clones of the file share them.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn direct_io<F, T>(std: &mut StdFile, mut f: F) -> io::Result<T>
+where
+    F: FnMut(&mut StdFile) -> io::Result<T>,
+{
+    use std::ffi::CString;
+    use std::os::unix::io::FromRawFd;
+
+    let err = match f(std) {
+        Err(err) if err.raw_os_error() == Some(libc::EINVAL) => err,
+        res => return res,
+    };
+    let fd = std.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags == -1 || flags & libc::O_DIRECT == 0 {
+        return Err(err);
+    }
+    let path = CString::new(format!("/proc/self/fd/{}", fd)).unwrap();
+    let cached = unsafe {
+        libc::open(
+            path.as_ptr(),
+            flags & (libc::O_ACCMODE | libc::O_APPEND) | libc::O_CLOEXEC,
+        )
+    };
+    if cached == -1 {
+        // Without `/proc` there is no other description to retry on.
+        return Err(err);
+    }
+    let mut cached = unsafe { StdFile::from_raw_fd(cached) };
+    cached.seek(SeekFrom::Start(std.stream_position()?))?;
+    let res = f(&mut cached);
+    std.seek(SeekFrom::Start(cached.stream_position()?))?;
+    res
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn direct_io<F, T>(std: &mut StdFile, mut f: F) -> io::Result<T>
+where
+    F: FnMut(&mut StdFile) -> io::Result<T>,
+{
+    // `O_DIRECT` on FreeBSD and `F_NOCACHE` on macOS do not require
+    // aligned transfers.
+    f(std)
+}
+
+#[cfg(target_os = "linux")]
+fn seek_sparse(std: &StdFile, offset: u64, whence: libc::c_int) -> io::Result<Option<u64>> {
+    use std::convert::TryFrom;
+
+    let offset = libc::off_t::try_from(offset)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "offset too large"))?;
+    match unsafe { libc::lseek(std.as_raw_fd(), offset, whence) } {
+        -1 => {
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                // Past the last data, or past the end of the file.
+                Some(libc::ENXIO) => Ok(None),
+                _ => Err(err),
+            }
+        }
+        pos => Ok(Some(pos as u64)),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn punch_hole(std: &StdFile, offset: u64, len: u64) -> io::Result<()> {
+    use std::convert::TryFrom;
+
+    let offset = libc::off_t::try_from(offset)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "offset too large"))?;
+    let len = libc::off_t::try_from(len)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "length too large"))?;
+    // `fallocate` rejects an empty range.
+    if len == 0 {
+        return Ok(());
+    }
+    let mode = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+    loop {
+        if unsafe { libc::fallocate(std.as_raw_fd(), mode, offset, len) } == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::EOPNOTSUPP) => {
+                return Err(io::Error::new(
+                    ErrorKind::Unsupported,
+                    "the filesystem does not support punching holes",
+                ));
+            }
+            _ => return Err(err),
+        }
+    }
+}
+
+/// Returns whether `err` means the handle went stale, typically because the
+/// file was replaced on an NFS server.
+#[cfg(unix)]
+fn is_stale(err: &io::Error) -> bool {
+    matches!(err.raw_os_error(), Some(libc::ESTALE) | Some(libc::EIO))
+}
+
+#[cfg(not(unix))]
+fn is_stale(_: &io::Error) -> bool {
+    false
+}
+
+fn try_lock_result(res: Result<(), TryLockError>) -> io::Result<bool> {
+    match res {
+        Ok(()) => Ok(true),
+        Err(TryLockError::WouldBlock) => Ok(false),
+        Err(TryLockError::Error(err)) => Err(err),
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for File {
+    fn as_raw_fd(&self) -> RawFd {
+        self.std.as_ref().expect("file already closed").as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl AsRawHandle for File {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.std
+            .as_ref()
+            .expect("file already closed")
+            .as_raw_handle()
     }
 }
 
@@ -80,7 +1314,7 @@ impl Drop for File {
     fn drop(&mut self) {
         if let Some(_std) = self.std.take() {
             // This is probably fine as closing a file *shouldn't* be a blocking
-            // operation. That said, ideally `shutdown` is called first.
+            // operation. That said, ideally `close` is called first.
         }
     }
 }
@@ -95,21 +1329,54 @@ impl Drop for File {
 ///
 /// [`std::fs::OpenOptions`]: https://doc.rust-lang.org/std/fs/struct.OpenOptions.html
 #[derive(Clone, Debug)]
-pub struct OpenOptions(StdOpenOptions);
+pub struct OpenOptions {
+    std: StdOpenOptions,
+    // Mirrors the options set through the methods below so that backends
+    // other than `std` can open files. `None` when converted from an opaque
+    // `std::fs::OpenOptions`.
+    flags: Option<Flags>,
+    pool: Option<Pool>,
+    stale_retries: usize,
+    case_guard: bool,
+    direct: bool,
+    account: Option<Account>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(not(all(feature = "uring", target_os = "linux")), allow(dead_code))]
+pub(crate) struct Flags {
+    pub(crate) read: bool,
+    pub(crate) write: bool,
+    pub(crate) append: bool,
+    pub(crate) truncate: bool,
+    pub(crate) create: bool,
+    pub(crate) create_new: bool,
+}
 
 impl OpenOptions {
     /// Creates a blank new set of options ready for configuration.
     ///
     /// All options are initially set to `false`.
     pub fn new() -> OpenOptions {
-        OpenOptions(StdOpenOptions::new())
+        OpenOptions {
+            std: StdOpenOptions::new(),
+            flags: Some(Flags::default()),
+            pool: None,
+            stale_retries: 0,
+            case_guard: false,
+            direct: false,
+            account: None,
+        }
     }
 
     /// See the underlying [`read`] call for details.
     ///
     /// [`read`]: https://doc.rust-lang.org/std/fs/struct.OpenOptions.html#method.read
     pub fn read(&mut self, read: bool) -> &mut OpenOptions {
-        self.0.read(read);
+        self.std.read(read);
+        if let Some(ref mut flags) = self.flags {
+            flags.read = read;
+        }
         self
     }
 
@@ -117,7 +1384,10 @@ impl OpenOptions {
     ///
     /// [`write`]: https://doc.rust-lang.org/std/fs/struct.OpenOptions.html#method.write
     pub fn write(&mut self, write: bool) -> &mut OpenOptions {
-        self.0.write(write);
+        self.std.write(write);
+        if let Some(ref mut flags) = self.flags {
+            flags.write = write;
+        }
         self
     }
 
@@ -125,7 +1395,10 @@ impl OpenOptions {
     ///
     /// [`append`]: https://doc.rust-lang.org/std/fs/struct.OpenOptions.html#method.append
     pub fn append(&mut self, append: bool) -> &mut OpenOptions {
-        self.0.append(append);
+        self.std.append(append);
+        if let Some(ref mut flags) = self.flags {
+            flags.append = append;
+        }
         self
     }
 
@@ -133,7 +1406,10 @@ impl OpenOptions {
     ///
     /// [`truncate`]: https://doc.rust-lang.org/std/fs/struct.OpenOptions.html#method.truncate
     pub fn truncate(&mut self, truncate: bool) -> &mut OpenOptions {
-        self.0.truncate(truncate);
+        self.std.truncate(truncate);
+        if let Some(ref mut flags) = self.flags {
+            flags.truncate = truncate;
+        }
         self
     }
 
@@ -141,7 +1417,10 @@ impl OpenOptions {
     ///
     /// [`create`]: https://doc.rust-lang.org/std/fs/struct.OpenOptions.html#method.create
     pub fn create(&mut self, create: bool) -> &mut OpenOptions {
-        self.0.create(create);
+        self.std.create(create);
+        if let Some(ref mut flags) = self.flags {
+            flags.create = create;
+        }
         self
     }
 
@@ -149,7 +1428,90 @@ impl OpenOptions {
     ///
     /// [`create_new`]: https://doc.rust-lang.org/std/fs/struct.OpenOptions.html#method.create_new
     pub fn create_new(&mut self, create_new: bool) -> &mut OpenOptions {
-        self.0.create_new(create_new);
+        self.std.create_new(create_new);
+        if let Some(ref mut flags) = self.flags {
+            flags.create_new = create_new;
+        }
+        self
+    }
+
+    /// Sets the [`Pool`] the file is opened on and that all of its later
+    /// operations run on.
+    ///
+    /// By default files use the global Actix threadpool.
+    ///
+    /// [`Pool`]: struct.Pool.html
+    pub fn pool(&mut self, pool: &Pool) -> &mut OpenOptions {
+        self.pool = Some(pool.clone());
+        self
+    }
+
+    /// Enables reopening the file when a read fails because its handle went
+    /// stale, up to `retries` times per read.
+    ///
+    /// On network filesystems such as NFS, a file replaced or removed on
+    /// another client leaves open handles failing with `ESTALE`, and a
+    /// flaky server can surface as `EIO`. With this option set, a read that
+    /// fails with either error reopens the file by its path, seeks to the
+    /// position the read started at and tries again. Reopening never creates
+    /// or truncates the file, whatever the other options say.
+    ///
+    /// Only reads are retried, since a failed write may have partially
+    /// reached the server. This has no effect on platforms other than Unix.
+    /// By default, stale handles are not reopened.
+    pub fn reopen_on_stale(&mut self, retries: usize) -> &mut OpenOptions {
+        self.stale_retries = retries;
+        self
+    }
+
+    /// Sets whether opening fails with `AlreadyExists` when the directory
+    /// already has an entry whose name differs from the file name only by
+    /// case.
+    ///
+    /// On a case-sensitive filesystem, `Photo.jpg` and `photo.jpg` are
+    /// different files, but copying the tree to a case-insensitive one, as
+    /// is the default on macOS and Windows, makes one replace the other.
+    /// Setting this when creating files keeps such trees portable. The check
+    /// lists the directory before opening, so it is not atomic with respect
+    /// to other processes creating files.
+    ///
+    /// See also [`detect_case_collisions`].
+    ///
+    /// [`detect_case_collisions`]: fn.detect_case_collisions.html
+    pub fn reject_case_collisions(&mut self, reject: bool) -> &mut OpenOptions {
+        self.case_guard = reject;
+        self
+    }
+
+    /// Sets whether reads and writes bypass the page cache.
+    ///
+    /// Large sequential transfers, such as taking or restoring a backup,
+    /// otherwise evict the rest of the working set from memory. Direct I/O
+    /// requires aligned transfers, so read and write the file with
+    /// [`read_aligned`] and [`write_aligned`] to bypass the page cache.
+    /// What cannot be aligned, including every transfer of other methods
+    /// such as `read`, `write_all` or a `BufReader`, falls back to going
+    /// through the page cache.
+    ///
+    /// This uses `O_DIRECT` on Linux, Android and FreeBSD, and `F_NOCACHE`
+    /// on macOS and iOS. Where the platform or filesystem does not support
+    /// it, such as `tmpfs` on older kernels, the file is opened normally.
+    ///
+    /// [`read_aligned`]: struct.File.html#method.read_aligned
+    /// [`write_aligned`]: struct.File.html#method.write_aligned
+    pub fn direct(&mut self, direct: bool) -> &mut OpenOptions {
+        self.direct = direct;
+        self
+    }
+
+    /// Charges opening the file, and every read, write and sync of it, to
+    /// `account`, waiting while the account is over its limit.
+    ///
+    /// See [`Accounting`] for details.
+    ///
+    /// [`Accounting`]: struct.Accounting.html
+    pub fn account(&mut self, account: &Account) -> &mut OpenOptions {
+        self.account = Some(account.clone());
         self
     }
 
@@ -165,18 +1527,102 @@ impl OpenOptions {
     where
         P: AsRef<Path> + Send + 'static,
     {
-        let opt = self.0.clone();
-        crate::blocking(move || -> io::Result<File> {
-            let std = opt.open(path.as_ref())?;
-            let file = File::from_std(std);
-            Ok(file)
-        })
+        let pool = self.pool.clone();
+        let reopen = if self.stale_retries > 0 {
+            let mut opts = self.std.clone();
+            opts.create(false).create_new(false).truncate(false);
+            Some(Arc::new(Reopen {
+                path: path.as_ref().to_owned(),
+                opts,
+                retries: self.stale_retries,
+            }))
+        } else {
+            None
+        };
+        if let (None, Some(flags), false, false, None) = (
+            &self.pool,
+            self.flags,
+            self.case_guard,
+            self.direct,
+            &self.account,
+        ) {
+            if let Some(open) = uring::open(path.as_ref(), flags) {
+                let path: Arc<Path> = Arc::from(path.as_ref());
+                let err_path = path.clone();
+                return Either::A(
+                    open.map(move |std| File {
+                        std: Some(std),
+                        pool: None,
+                        reopen,
+                        account: None,
+                        path: Some(path),
+                        direct: false,
+                    })
+                    .map_err(move |err| error::with_path(err, "open", &err_path)),
+                );
+            }
+        }
+
+        let opt = self.std.clone();
+        let case_guard = self.case_guard;
+        let direct = self.direct;
+        let account = self.account.clone();
+        let ready = match account {
+            Some(ref account) => Either::A(account.ready()),
+            None => Either::B(future::ok(())),
+        };
+        Either::B(ready.and_then(move |()| {
+            let timed = path.as_ref().to_owned();
+            crate::blocking_op(
+                pool.clone().as_ref(),
+                "open",
+                Some(&timed),
+                |_| 0,
+                move || -> io::Result<File> {
+                    let path = path.as_ref();
+                    if case_guard {
+                        check_case_collision(path)?;
+                    }
+                    let std = opt
+                        .open(path)
+                        .map_err(|err| error::with_path(err, "open", path))?;
+                    if direct {
+                        set_direct(&std);
+                    }
+                    if let Some(ref account) = account {
+                        account.record_op();
+                    }
+                    Ok(File {
+                        std: Some(std),
+                        pool,
+                        reopen,
+                        account,
+                        path: Some(Arc::from(path)),
+                        direct,
+                    })
+                },
+            )
+        }))
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> OpenOptions {
+        OpenOptions::new()
     }
 }
 
 impl From<StdOpenOptions> for OpenOptions {
     fn from(options: StdOpenOptions) -> OpenOptions {
-        OpenOptions(options)
+        OpenOptions {
+            std: options,
+            flags: None,
+            pool: None,
+            stale_retries: 0,
+            case_guard: false,
+            direct: false,
+            account: None,
+        }
     }
 }
 
@@ -193,7 +1639,35 @@ pub fn remove_file<P>(path: P) -> impl Future<Item = (), Error = io::Error>
 where
     P: AsRef<Path> + Send + 'static,
 {
-    crate::blocking(move || fs::remove_file(path.as_ref()))
+    remove_file_with(None, path)
+}
+
+/// Like [`remove_file`], but runs on `pool` rather than the global
+/// threadpool.
+///
+/// [`remove_file`]: fn.remove_file.html
+pub fn remove_file_on<P>(pool: &Pool, path: P) -> impl Future<Item = (), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    remove_file_with(Some(pool), path)
+}
+
+fn remove_file_with<P>(pool: Option<&Pool>, path: P) -> impl Future<Item = (), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let timed = path.as_ref().to_owned();
+    crate::blocking_op(
+        pool,
+        "remove",
+        Some(&timed),
+        |_| 0,
+        move || {
+            let path = path.as_ref();
+            fs::remove_file(path).map_err(|err| error::with_path(err, "remove", path))
+        },
+    )
 }
 
 /// Rename a file or directory to a new name, replacing the original file if
@@ -209,5 +1683,38 @@ where
     P: AsRef<Path> + Send + 'static,
     Q: AsRef<Path> + Send + 'static,
 {
-    crate::blocking(move || fs::rename(from.as_ref(), to.as_ref()))
+    rename_with(None, from, to)
+}
+
+/// Like [`rename`], but runs on `pool` rather than the global threadpool.
+///
+/// [`rename`]: fn.rename.html
+pub fn rename_on<P, Q>(pool: &Pool, from: P, to: Q) -> impl Future<Item = (), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+    Q: AsRef<Path> + Send + 'static,
+{
+    rename_with(Some(pool), from, to)
+}
+
+fn rename_with<P, Q>(
+    pool: Option<&Pool>,
+    from: P,
+    to: Q,
+) -> impl Future<Item = (), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+    Q: AsRef<Path> + Send + 'static,
+{
+    let timed = from.as_ref().to_owned();
+    crate::blocking_op(
+        pool,
+        "rename",
+        Some(&timed),
+        |_| 0,
+        move || {
+            let (from, to) = (from.as_ref(), to.as_ref());
+            fs::rename(from, to).map_err(|err| error::with_paths(err, "rename", from, to))
+        },
+    )
 }
@@ -0,0 +1,126 @@
+use std::io::{self, ErrorKind};
+
+/// The platform whose rules [`validate_filename`] checks a name against.
+///
+/// [`validate_filename`]: fn.validate_filename.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Platform {
+    /// Linux, macOS and other Unix systems.
+    Unix,
+    /// Windows, including names on SMB shares served to Windows clients.
+    Windows,
+    /// The intersection of the rules of all platforms, for names that must
+    /// work everywhere.
+    Portable,
+}
+
+impl Platform {
+    /// Returns the platform the program is running on.
+    pub fn current() -> Platform {
+        if cfg!(windows) {
+            Platform::Windows
+        } else {
+            Platform::Unix
+        }
+    }
+}
+
+/// The longest file name most filesystems accept, in bytes on Unix and in
+/// UTF-16 code units on Windows.
+const MAX_LEN: usize = 255;
+
+const WINDOWS_RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Checks whether `name` can be used as a single file name on `platform`.
+///
+/// This is meant for names that come from user input, to reject them with
+/// a clear message before the operating system fails with an opaque error,
+/// or worse, silently creates something else. It checks for
+///
+/// * empty names, `.` and `..`,
+/// * path separators and other characters that are not allowed,
+/// * names reserved for devices on Windows, such as `CON` or `nul.txt`,
+/// * trailing dots and spaces, which Windows silently strips,
+/// * names longer than 255 bytes on Unix, or 255 UTF-16 code units on
+///   Windows.
+///
+/// Invalid names fail with an error of kind `InvalidInput` describing the
+/// problem.
+pub fn validate_filename(name: &str, platform: Platform) -> io::Result<()> {
+    if name.is_empty() {
+        return Err(invalid("file name is empty"));
+    }
+    if name == "." || name == ".." {
+        return Err(invalid(format!("{:?} is not a file name", name)));
+    }
+    if platform != Platform::Windows {
+        validate_unix(name)?;
+    }
+    if platform != Platform::Unix {
+        validate_windows(name)?;
+    }
+    Ok(())
+}
+
+fn validate_unix(name: &str) -> io::Result<()> {
+    if let Some(c) = name.chars().find(|&c| c == '/' || c == '\0') {
+        return Err(invalid(format!(
+            "file name {:?} contains the character {:?}",
+            name, c
+        )));
+    }
+    if name.len() > MAX_LEN {
+        return Err(invalid(format!(
+            "file name is {} bytes long, more than {}",
+            name.len(),
+            MAX_LEN
+        )));
+    }
+    Ok(())
+}
+
+fn validate_windows(name: &str) -> io::Result<()> {
+    let forbidden = |c: char| c < ' ' || "<>:\"/\\|?*".contains(c);
+    if let Some(c) = name.chars().find(|&c| forbidden(c)) {
+        return Err(invalid(format!(
+            "file name {:?} contains the character {:?}",
+            name, c
+        )));
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Err(invalid(format!(
+            "file name {:?} ends with a dot or space",
+            name
+        )));
+    }
+    // Device names are reserved with any extension, and ignoring trailing
+    // spaces before it.
+    let stem = name.split('.').next().unwrap_or(name).trim_end_matches(' ');
+    if WINDOWS_RESERVED
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return Err(invalid(format!(
+            "file name {:?} is reserved on Windows",
+            name
+        )));
+    }
+    let len = name.encode_utf16().count();
+    if len > MAX_LEN {
+        return Err(invalid(format!(
+            "file name is {} UTF-16 code units long, more than {}",
+            len, MAX_LEN
+        )));
+    }
+    Ok(())
+}
+
+fn invalid<M>(msg: M) -> io::Error
+where
+    M: Into<String>,
+{
+    io::Error::new(ErrorKind::InvalidInput, msg.into())
+}
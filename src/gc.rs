@@ -0,0 +1,268 @@
+use futures::{Future, Stream};
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::CancellationToken;
+
+/// The outcome of a garbage collection run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// The number of blobs found.
+    pub scanned: usize,
+    /// The keys of the blobs that were removed.
+    pub removed: Vec<String>,
+    /// The number of unreferenced blobs kept because they were modified
+    /// within the grace period or since being listed, or because the
+    /// recheck found them referenced.
+    pub recent: usize,
+    /// The total size of the removed blobs in bytes.
+    pub bytes_freed: u64,
+}
+
+/// Options for removing unreferenced blobs from a directory.
+///
+/// Every regular file below the directory is a blob, keyed by its path
+/// relative to the directory with `/` as the separator, so content-addressed
+/// layouts that fan out into subdirectories such as `ab/cdef...` work as
+/// is. Files ending in `.tmp` are never touched.
+///
+/// Collection happens in two phases. The directory is listed first, and only
+/// blobs last modified before the grace period are candidates. Once the
+/// reference set has been collected, each unreferenced candidate is checked
+/// again and removed only if it has not been modified since. A blob written,
+/// or rewritten, while the collector runs is therefore never removed, even if
+/// the reference to it is not part of the set yet.
+///
+/// The grace period does not protect a blob that already exists and gains a
+/// new reference, as happens on a deduplication hit in a content-addressed
+/// store: the blob is old and unchanged, so it is removed if the reference
+/// was not yet part of the set. Stores that deduplicate should either set
+/// the blob's modification time, for example with [`set_file_times`], before
+/// storing the reference, or pass a [`recheck`] that looks the key up again
+/// right before removal. Without locking in the store a reference added in
+/// the moment between that last check and the removal can still be lost.
+///
+/// [`set_file_times`]: fn.set_file_times.html
+/// [`recheck`]: #method.recheck
+#[derive(Clone)]
+pub struct GcOptions {
+    grace_period: Duration,
+    dry_run: bool,
+    token: CancellationToken,
+    recheck: Option<Recheck>,
+}
+
+type Recheck = Arc<dyn Fn(&str) -> io::Result<bool> + Send + Sync>;
+
+impl GcOptions {
+    /// Creates options with a grace period of one hour.
+    pub fn new() -> GcOptions {
+        GcOptions {
+            grace_period: Duration::from_secs(60 * 60),
+            dry_run: false,
+            token: CancellationToken::new(),
+            recheck: None,
+        }
+    }
+
+    /// Sets how recently a blob must have been modified to be kept even if
+    /// it is not referenced.
+    ///
+    /// This should comfortably exceed the time between a blob being written
+    /// and a reference to it being stored.
+    pub fn grace_period(&mut self, grace_period: Duration) -> &mut GcOptions {
+        self.grace_period = grace_period;
+        self
+    }
+
+    /// Sets whether the report is produced without removing anything.
+    pub fn dry_run(&mut self, dry_run: bool) -> &mut GcOptions {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Sets a token that stops the collection between blobs once cancelled.
+    ///
+    /// Blobs removed before that stay removed.
+    pub fn cancellation(&mut self, token: CancellationToken) -> &mut GcOptions {
+        self.token = token;
+        self
+    }
+
+    /// Sets a function that is asked, on the threadpool, whether a blob is
+    /// referenced right before it would be removed.
+    ///
+    /// Blobs for which it returns `true` are kept and counted as recent. An
+    /// error stops the collection.
+    pub fn recheck<F>(&mut self, recheck: F) -> &mut GcOptions
+    where
+        F: Fn(&str) -> io::Result<bool> + Send + Sync + 'static,
+    {
+        self.recheck = Some(Arc::new(recheck));
+        self
+    }
+
+    /// Removes the blobs below `dir` whose keys are not yielded by
+    /// `referenced`.
+    pub fn gc<P, S>(&self, dir: P, referenced: S) -> impl Future<Item = GcReport, Error = io::Error>
+    where
+        P: Into<PathBuf>,
+        S: Stream<Item = String, Error = io::Error>,
+    {
+        let dir = dir.into();
+        let opts = self.clone();
+        let cutoff = SystemTime::now()
+            .checked_sub(opts.grace_period)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+
+        let mark_dir = dir.clone();
+        let token = opts.token.clone();
+        crate::blocking(move || mark(&mark_dir, cutoff, &token))
+            .and_then(|marked| {
+                referenced
+                    .collect()
+                    .map(|keys| (marked, keys.into_iter().collect::<HashSet<_>>()))
+            })
+            .and_then(move |((candidates, scanned, recent), referenced)| {
+                crate::blocking(move || {
+                    let mut report = GcReport {
+                        scanned,
+                        recent,
+                        ..GcReport::default()
+                    };
+                    for (key, modified) in candidates {
+                        opts.token.check()?;
+                        if !referenced.contains(&key) {
+                            sweep(&dir, key, modified, &opts, &mut report)?;
+                        }
+                    }
+                    Ok(report)
+                })
+            })
+    }
+}
+
+impl fmt::Debug for GcOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GcOptions")
+            .field("grace_period", &self.grace_period)
+            .field("dry_run", &self.dry_run)
+            .field("token", &self.token)
+            .field("recheck", &self.recheck.is_some())
+            .finish()
+    }
+}
+
+impl Default for GcOptions {
+    fn default() -> GcOptions {
+        GcOptions::new()
+    }
+}
+
+/// Removes the blobs below `dir` whose keys are not yielded by `referenced`,
+/// keeping blobs modified within the last hour.
+///
+/// See [`GcOptions`] for details and more control.
+///
+/// [`GcOptions`]: struct.GcOptions.html
+pub fn gc<P, S>(dir: P, referenced: S) -> impl Future<Item = GcReport, Error = io::Error>
+where
+    P: Into<PathBuf>,
+    S: Stream<Item = String, Error = io::Error>,
+{
+    GcOptions::new().gc(dir, referenced)
+}
+
+type Candidates = Vec<(String, SystemTime)>;
+
+/// Lists the blobs modified before `cutoff`, along with the number of blobs
+/// seen and the number skipped as too recent.
+fn mark(
+    dir: &Path,
+    cutoff: SystemTime,
+    token: &CancellationToken,
+) -> io::Result<(Candidates, usize, usize)> {
+    let mut candidates = Vec::new();
+    let mut scanned = 0;
+    let mut recent = 0;
+    let mut pending = vec![dir.to_owned()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            token.check()?;
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+                continue;
+            }
+            let path = entry.path();
+            if !file_type.is_file() || path.extension().is_some_and(|ext| ext == "tmp") {
+                continue;
+            }
+            let key = match key(dir, &path) {
+                Some(key) => key,
+                None => continue,
+            };
+            scanned += 1;
+            let modified = entry.metadata()?.modified()?;
+            if modified < cutoff {
+                candidates.push((key, modified));
+            } else {
+                recent += 1;
+            }
+        }
+    }
+    candidates.sort();
+    Ok((candidates, scanned, recent))
+}
+
+fn sweep(
+    dir: &Path,
+    key: String,
+    modified: SystemTime,
+    opts: &GcOptions,
+    report: &mut GcReport,
+) -> io::Result<()> {
+    let path = dir.join(&key);
+    let metadata = match fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    // Rewritten since it was marked, so it may be in use again.
+    if metadata.modified()? != modified {
+        report.recent += 1;
+        return Ok(());
+    }
+    // Referenced since the set was collected.
+    if let Some(ref recheck) = opts.recheck {
+        if recheck(&key)? {
+            report.recent += 1;
+            return Ok(());
+        }
+    }
+    if !opts.dry_run {
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        }
+    }
+    report.bytes_freed += metadata.len();
+    report.removed.push(key);
+    Ok(())
+}
+
+/// Returns the key of the blob at `path`, or `None` if it is not valid
+/// UTF-8.
+fn key(dir: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(dir).ok()?;
+    let parts: Option<Vec<&str>> = relative.iter().map(|part| part.to_str()).collect();
+    Some(parts?.join("/"))
+}
@@ -0,0 +1,407 @@
+//! Streaming file digests.
+//!
+//! Files are read in chunks on the threadpool, so digests of large files are
+//! computed without holding them in memory or blocking the event loop.
+//!
+//! This module is only available with the `sha2` or `blake3` feature, and
+//! each algorithm only with its own feature.
+
+use futures::Future;
+
+use std::fmt;
+use std::fs::File as StdFile;
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::Path;
+
+use crate::{error, CancellationToken};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A digest algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    /// SHA-256.
+    #[cfg(feature = "sha2")]
+    Sha256,
+    /// BLAKE3, with its default 32 byte output.
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+impl Algorithm {
+    /// Returns the lowercase name of the algorithm, such as `sha256`.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "sha2")]
+            Algorithm::Sha256 => "sha256",
+            #[cfg(feature = "blake3")]
+            Algorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// Returns the algorithm named `name`, as returned by `name`.
+    pub(crate) fn from_name(name: &str) -> Option<Algorithm> {
+        match name {
+            #[cfg(feature = "sha2")]
+            "sha256" => Some(Algorithm::Sha256),
+            #[cfg(feature = "blake3")]
+            "blake3" => Some(Algorithm::Blake3),
+            _ => None,
+        }
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            #[cfg(feature = "sha2")]
+            Algorithm::Sha256 => Hasher::Sha256(Default::default()),
+            #[cfg(feature = "blake3")]
+            Algorithm::Blake3 => Hasher::Blake3(Box::default()),
+        }
+    }
+}
+
+/// The digest of a file.
+///
+/// Formatting a digest with `{}` or `{:x}` produces lowercase hex.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Digest {
+    algorithm: Algorithm,
+    bytes: [u8; 32],
+}
+
+impl Digest {
+    /// Returns the algorithm the digest was computed with.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Returns the raw bytes of the digest.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.bytes
+    }
+
+    /// Parses a digest formatted as lowercase hex.
+    pub(crate) fn from_hex(algorithm: Algorithm, hex: &str) -> Option<Digest> {
+        if hex.len() != 64 || !hex.is_ascii() {
+            return None;
+        }
+        let mut bytes = [0; 32];
+        for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            let pair = std::str::from_utf8(pair).ok()?;
+            *byte = u8::from_str_radix(pair, 16).ok()?;
+        }
+        Some(Digest { algorithm, bytes })
+    }
+}
+
+impl fmt::LowerHex for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.bytes {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(self, f)
+    }
+}
+
+impl fmt::Debug for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Digest({:?}, {:x})", self.algorithm, self)
+    }
+}
+
+enum Hasher {
+    #[cfg(feature = "sha2")]
+    Sha256(sha2::Sha256),
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            #[cfg(feature = "sha2")]
+            Hasher::Sha256(hasher) => sha2::Digest::update(hasher, data),
+            #[cfg(feature = "blake3")]
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    fn finish(self) -> Digest {
+        match self {
+            #[cfg(feature = "sha2")]
+            Hasher::Sha256(hasher) => Digest {
+                algorithm: Algorithm::Sha256,
+                bytes: sha2::Digest::finalize(hasher).into(),
+            },
+            #[cfg(feature = "blake3")]
+            Hasher::Blake3(hasher) => Digest {
+                algorithm: Algorithm::Blake3,
+                bytes: hasher.finalize().into(),
+            },
+        }
+    }
+}
+
+/// Computes the digest of the file at `path`.
+pub fn digest<P>(path: P, algorithm: Algorithm) -> impl Future<Item = Digest, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    digest_cancellable(path, algorithm, CancellationToken::new())
+}
+
+/// Like [`digest`], but stops between chunks once `token` is cancelled.
+///
+/// [`digest`]: fn.digest.html
+pub fn digest_cancellable<P>(
+    path: P,
+    algorithm: Algorithm,
+    token: CancellationToken,
+) -> impl Future<Item = Digest, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    crate::blocking(move || {
+        let path = path.as_ref();
+        token.check()?;
+        StdFile::open(path)
+            .and_then(|mut file| digest_file(&mut file, algorithm, &token))
+            .map_err(|err| error::with_path(err, "hash", path))
+    })
+}
+
+/// Computes the digest of `file` from its current position to the end.
+pub(crate) fn digest_file(
+    file: &mut StdFile,
+    algorithm: Algorithm,
+    token: &CancellationToken,
+) -> io::Result<Digest> {
+    let mut hasher = algorithm.hasher();
+    each_chunk(file, token, |chunk| {
+        hasher.update(chunk);
+        Ok(())
+    })?;
+    Ok(hasher.finish())
+}
+
+/// Computes the digest of the next `len` bytes of `file`, or of what is
+/// left if it ends sooner.
+pub(crate) fn digest_range(
+    file: &mut StdFile,
+    len: u64,
+    algorithm: Algorithm,
+    token: &CancellationToken,
+) -> io::Result<Digest> {
+    let mut hasher = algorithm.hasher();
+    each_chunk(&mut Read::by_ref(file).take(len), token, |chunk| {
+        hasher.update(chunk);
+        Ok(())
+    })?;
+    Ok(hasher.finish())
+}
+
+/// Computes the SHA-256 digest of the file at `path`.
+#[cfg(feature = "sha2")]
+pub fn sha256<P>(path: P) -> impl Future<Item = Digest, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    digest(path, Algorithm::Sha256)
+}
+
+/// Computes the BLAKE3 digest of the file at `path`.
+#[cfg(feature = "blake3")]
+pub fn blake3<P>(path: P) -> impl Future<Item = Digest, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    digest(path, Algorithm::Blake3)
+}
+
+/// Copies the file at `from` to `to`, computing the digest of the contents
+/// along the way.
+///
+/// The source is read only once, so this costs no more I/O than a plain
+/// copy. `to` is created or truncated, and synced before the future
+/// resolves with the number of bytes copied and the digest.
+pub fn copy<P, Q>(
+    from: P,
+    to: Q,
+    algorithm: Algorithm,
+) -> impl Future<Item = (u64, Digest), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+    Q: AsRef<Path> + Send + 'static,
+{
+    copy_cancellable(from, to, algorithm, CancellationToken::new())
+}
+
+/// Like [`copy`], but stops between chunks once `token` is cancelled.
+///
+/// `to` is left partially written if the copy is cancelled.
+///
+/// [`copy`]: fn.copy.html
+pub fn copy_cancellable<P, Q>(
+    from: P,
+    to: Q,
+    algorithm: Algorithm,
+    token: CancellationToken,
+) -> impl Future<Item = (u64, Digest), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+    Q: AsRef<Path> + Send + 'static,
+{
+    crate::blocking(move || {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        copy_file(from, to, algorithm, &token)
+            .map_err(|err| error::with_paths(err, "copy", from, to))
+    })
+}
+
+/// How [`copy_verified`] and the other verified copies check the copy.
+///
+/// [`copy_verified`]: fn.copy_verified.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Verify {
+    /// Hashes the source while copying, then reads the destination back
+    /// and compares its digest.
+    Readback,
+    /// Copies, then reads both files again and compares their digests.
+    ///
+    /// This reads the source twice, but also catches it changing during
+    /// the copy.
+    Reread,
+}
+
+/// Copies the file at `from` to `to` like [`copy`], then reads `to` back
+/// and fails with `InvalidData` if its digest does not match the source.
+///
+/// Resolves to the number of bytes copied and the verified digest. On a
+/// mismatch, `to` is left in place for inspection.
+///
+/// The readback may be served from the page cache, so it catches data
+/// corrupted on its way to the filesystem, such as by faulty memory or
+/// filesystem bugs, more than corruption on the disk itself.
+///
+/// The same check is available for [`copy_file_range_verified`],
+/// [`BackupOptions::verify`] and [`Tiers::verify`].
+///
+/// [`copy`]: fn.copy.html
+/// [`copy_file_range_verified`]: ../fn.copy_file_range_verified.html
+/// [`BackupOptions::verify`]: ../struct.BackupOptions.html#method.verify
+/// [`Tiers::verify`]: ../struct.Tiers.html#method.verify
+pub fn copy_verified<P, Q>(
+    from: P,
+    to: Q,
+    algorithm: Algorithm,
+    verify: Verify,
+) -> impl Future<Item = (u64, Digest), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+    Q: AsRef<Path> + Send + 'static,
+{
+    copy_verified_cancellable(from, to, algorithm, verify, CancellationToken::new())
+}
+
+/// Like [`copy_verified`], but stops between chunks once `token` is
+/// cancelled, while copying or while reading back.
+///
+/// [`copy_verified`]: fn.copy_verified.html
+pub fn copy_verified_cancellable<P, Q>(
+    from: P,
+    to: Q,
+    algorithm: Algorithm,
+    verify: Verify,
+    token: CancellationToken,
+) -> impl Future<Item = (u64, Digest), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+    Q: AsRef<Path> + Send + 'static,
+{
+    crate::blocking(move || {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        copy_file_verified(from, to, algorithm, verify, &token)
+            .map_err(|err| error::with_paths(err, "copy", from, to))
+    })
+}
+
+/// Copies the file at `from` to `to` like `copy_file`, then checks the copy
+/// as `verify` asks.
+pub(crate) fn copy_file_verified(
+    from: &Path,
+    to: &Path,
+    algorithm: Algorithm,
+    verify: Verify,
+    token: &CancellationToken,
+) -> io::Result<(u64, Digest)> {
+    let (len, mut expected) = copy_file(from, to, algorithm, token)?;
+    if verify == Verify::Reread {
+        expected = digest_file(&mut StdFile::open(from)?, algorithm, token)?;
+    }
+    let actual = digest_file(&mut StdFile::open(to)?, algorithm, token)?;
+    check(expected, actual)?;
+    Ok((len, actual))
+}
+
+/// Fails with `InvalidData` unless the digest of a copy, `actual`, is the
+/// `expected` one.
+pub(crate) fn check(expected: Digest, actual: Digest) -> io::Result<()> {
+    if actual == expected {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        ErrorKind::InvalidData,
+        format!("checksum mismatch: expected {}, found {}", expected, actual),
+    ))
+}
+
+/// Copies the file at `from` to `to` and syncs it, returning the length and
+/// digest of what was read.
+pub(crate) fn copy_file(
+    from: &Path,
+    to: &Path,
+    algorithm: Algorithm,
+    token: &CancellationToken,
+) -> io::Result<(u64, Digest)> {
+    token.check()?;
+    let mut src = StdFile::open(from)?;
+    let mut dst = StdFile::create(to)?;
+    let mut hasher = algorithm.hasher();
+    let len = each_chunk(&mut src, token, |chunk| {
+        hasher.update(chunk);
+        dst.write_all(chunk)
+    })?;
+    dst.sync_all()?;
+    Ok((len, hasher.finish()))
+}
+
+/// Calls `f` with each chunk read from `file`, returning the total length.
+///
+/// Fails once `token` is cancelled.
+fn each_chunk<R, F>(file: &mut R, token: &CancellationToken, mut f: F) -> io::Result<u64>
+where
+    R: Read,
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut len = 0;
+    loop {
+        token.check()?;
+        let n = match file.read(&mut buf) {
+            Ok(0) => return Ok(len),
+            Ok(n) => n,
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        f(&buf[..n])?;
+        len += n as u64;
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+static METRICS: RwLock<Option<Arc<dyn Metrics>>> = RwLock::new(None);
+
+/// A completed filesystem operation, as reported to [`Metrics`].
+///
+/// [`Metrics`]: trait.Metrics.html
+#[derive(Clone, Copy, Debug)]
+pub struct Operation<'a> {
+    /// The name of the operation, such as `"read"` or `"rename"`, or
+    /// `"blocking"` for other work on the threadpool.
+    pub name: &'static str,
+    /// The path the operation acted on, if known.
+    pub path: Option<&'a Path>,
+    /// The number of bytes read or written, if any.
+    pub bytes: u64,
+    /// How long the operation waited for a thread.
+    pub queued: Duration,
+    /// How long the operation ran once it had a thread.
+    pub elapsed: Duration,
+    /// Whether the operation succeeded.
+    pub ok: bool,
+}
+
+/// Receives every filesystem operation the crate runs on a threadpool.
+///
+/// Install an implementation with [`set_metrics`]. The time operations spend
+/// queued shows when the threadpool is saturated, and the time they run
+/// shows slow disks. Operations run through io_uring are not reported.
+///
+/// `record` is called on the thread that ran the operation, right after it
+/// completes, so it should be cheap, such as incrementing counters. Any
+/// closure taking an [`Operation`] implements this trait.
+///
+/// [`set_metrics`]: fn.set_metrics.html
+/// [`Operation`]: struct.Operation.html
+pub trait Metrics: Send + Sync + 'static {
+    /// Records a completed operation.
+    fn record(&self, op: &Operation);
+}
+
+impl<F> Metrics for F
+where
+    F: Fn(&Operation) + Send + Sync + 'static,
+{
+    fn record(&self, op: &Operation) {
+        self(op)
+    }
+}
+
+/// Installs `metrics` to receive every operation from now on, replacing
+/// the previously installed one.
+pub fn set_metrics<M>(metrics: M)
+where
+    M: Metrics,
+{
+    *METRICS.write().unwrap() = Some(Arc::new(metrics));
+}
+
+/// Removes the installed [`Metrics`], if any.
+///
+/// [`Metrics`]: trait.Metrics.html
+pub fn clear_metrics() {
+    *METRICS.write().unwrap() = None;
+}
+
+/// Times an operation from when it is submitted to the threadpool.
+pub(crate) struct Timer {
+    name: &'static str,
+    path: Option<PathBuf>,
+    submitted: Instant,
+    metrics: Arc<dyn Metrics>,
+}
+
+impl Timer {
+    /// Starts timing an operation, if metrics are installed.
+    pub(crate) fn start(name: &'static str, path: Option<&Path>) -> Option<Timer> {
+        let metrics = METRICS.read().unwrap().clone()?;
+        Some(Timer {
+            name,
+            path: path.map(Path::to_owned),
+            submitted: Instant::now(),
+            metrics,
+        })
+    }
+
+    /// Runs the operation on the thread it was given, then records it.
+    pub(crate) fn run<F, B, I>(self, f: F, bytes: B) -> io::Result<I>
+    where
+        F: FnOnce() -> io::Result<I>,
+        B: FnOnce(&I) -> u64,
+    {
+        let started = Instant::now();
+        let res = f();
+        self.metrics.record(&Operation {
+            name: self.name,
+            path: self.path.as_deref(),
+            bytes: res.as_ref().map_or(0, bytes),
+            queued: started - self.submitted,
+            elapsed: started.elapsed(),
+            ok: res.is_ok(),
+        });
+        res
+    }
+}
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Timer")
+            .field("name", &self.name)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// Forwards operations to the [`metrics`] facade, for exporters such as
+/// Prometheus.
+///
+/// Every operation increments the `fs_operations_total` counter, labeled
+/// with `op` and with `result` as `ok` or `error`, and adds its bytes to
+/// `fs_bytes_total`. The `fs_queue_seconds` and `fs_duration_seconds`
+/// histograms, labeled with `op`, track how long operations waited for a
+/// thread and ran. Paths are not used as labels, to keep their number
+/// bounded.
+///
+/// This is only available with the `metrics` feature:
+///
+/// ```no_run
+/// actix_fs::set_metrics(actix_fs::MetricsFacade);
+/// ```
+///
+/// [`metrics`]: https://docs.rs/metrics
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MetricsFacade;
+
+#[cfg(feature = "metrics")]
+impl Metrics for MetricsFacade {
+    fn record(&self, op: &Operation) {
+        let result = if op.ok { "ok" } else { "error" };
+        metrics::counter!("fs_operations_total", "op" => op.name, "result" => result).increment(1);
+        if op.bytes > 0 {
+            metrics::counter!("fs_bytes_total", "op" => op.name).increment(op.bytes);
+        }
+        metrics::histogram!("fs_queue_seconds", "op" => op.name).record(op.queued.as_secs_f64());
+        metrics::histogram!("fs_duration_seconds", "op" => op.name)
+            .record(op.elapsed.as_secs_f64());
+    }
+}
@@ -1,23 +1,274 @@
+#[cfg(feature = "runtime")]
+mod account;
+#[cfg(feature = "actor")]
+mod actor;
+#[cfg(feature = "runtime")]
+mod archive;
+#[cfg(feature = "runtime")]
+mod backup;
+#[cfg(feature = "runtime")]
+mod batch;
+#[cfg(feature = "runtime")]
+mod buf;
+#[cfg(feature = "runtime")]
+mod cancel;
+#[cfg(feature = "runtime")]
+mod case;
+#[cfg(any(feature = "gzip", feature = "brotli", feature = "zstd"))]
+mod compact;
+#[cfg(feature = "actix-web")]
+mod context;
+#[cfg(feature = "runtime")]
+mod copy;
+#[cfg(feature = "webdav")]
+mod dav;
+#[cfg(feature = "runtime")]
 mod dir;
+mod error;
+#[cfg(feature = "actix-web")]
+mod extract;
+#[cfg(feature = "runtime")]
 mod file;
+mod filename;
+#[cfg(feature = "runtime")]
+mod gc;
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+pub mod hash;
+#[cfg(feature = "runtime")]
+mod instrument;
+#[cfg(feature = "runtime")]
+mod limit;
+#[cfg(feature = "actix-web")]
+mod named;
+#[cfg(feature = "unicode")]
+mod normalize;
+#[cfg(feature = "runtime")]
+mod partition;
+#[cfg(feature = "runtime")]
+mod pipeline;
+#[cfg(feature = "runtime")]
+mod pool;
+#[cfg(any(feature = "gzip", feature = "brotli", feature = "zstd"))]
+mod precompress;
+#[cfg(feature = "runtime")]
+mod probe;
+#[cfg(feature = "actix-web")]
+mod receive;
+#[cfg(feature = "runtime")]
+mod registry;
+#[cfg(feature = "runtime")]
+mod retry;
+mod root;
+#[cfg(feature = "runtime")]
+mod rotate;
+#[cfg(feature = "runtime")]
+mod scope;
+#[cfg(feature = "runtime")]
+mod script;
+#[cfg(feature = "runtime")]
+mod sentinel;
+#[cfg(feature = "runtime")]
+mod size;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "runtime")]
+mod statfs;
+#[cfg(feature = "runtime")]
+mod tail;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "runtime")]
+mod tier;
+#[cfg(feature = "runtime")]
+mod uring;
+#[cfg(feature = "watch")]
+mod watch;
+#[cfg(all(unix, feature = "runtime"))]
+mod xattr;
 
-pub use dir::{create_dir, create_dir_all, remove_dir};
-pub use file::{remove_file, rename, File, OpenOptions};
+#[cfg(feature = "runtime")]
+pub use account::{Account, Accounting, RateLimit, Usage};
+#[cfg(feature = "actor")]
+pub use actor::{FsActor, ListDir, ReadFile, RemoveFile, WriteFile};
+#[cfg(feature = "runtime")]
+pub use archive::{export, import};
+#[cfg(feature = "runtime")]
+pub use backup::{
+    backup_rotate, restore, BackupOptions, BackupReport, RestoreOptions, RestoreReport,
+};
+#[cfg(feature = "runtime")]
+pub use batch::{batch, Batch, BatchOutput};
+#[cfg(feature = "runtime")]
+pub use buf::{AlignedBuf, BufReader, BufWriter};
+#[cfg(feature = "runtime")]
+pub use cancel::{with_timeout, CancellationToken};
+#[cfg(feature = "runtime")]
+pub use case::detect_case_collisions;
+#[cfg(any(feature = "gzip", feature = "brotli", feature = "zstd"))]
+pub use compact::{Codec, CompactOptions, CompactReport};
+#[cfg(feature = "actix-web")]
+pub use context::{FsContext, FsContextMiddleware, FsContextOptions, FsContextService};
+#[cfg(feature = "runtime")]
+pub use copy::{copy_from, copy_to, CopyOptions};
+#[cfg(feature = "webdav")]
+pub use dav::{dav, DavOptions};
+#[cfg(feature = "runtime")]
+pub use dir::{
+    create_dir, create_dir_all, create_dir_all_on, create_dir_on, read_dir_collected,
+    read_dir_collected_on, read_dir_snapshot, remove_dir, remove_dir_on, walk_dir_snapshot,
+    DirEntry, ReadDirOptions, SortBy,
+};
+pub use error::{raw_os_error, Error};
+#[cfg(feature = "actix-web")]
+pub use extract::SafeFilePath;
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+pub use file::copy_file_range_verified;
+#[cfg(feature = "runtime")]
+pub use file::{
+    copy_file_range, remove_file, remove_file_on, rename, rename_on, set_file_times, Advice, File,
+    OpenOptions,
+};
+pub use filename::{validate_filename, Platform};
+#[cfg(feature = "runtime")]
+pub use gc::{gc, GcOptions, GcReport};
+#[cfg(feature = "metrics")]
+pub use instrument::MetricsFacade;
+#[cfg(feature = "runtime")]
+pub use instrument::{clear_metrics, set_metrics, Metrics, Operation};
+#[cfg(feature = "runtime")]
+pub use limit::{LimitExceeded, LimitedWriter};
+#[cfg(feature = "actix-web")]
+pub use named::{serve_file, NamedFile, ServeOptions};
+#[cfg(feature = "unicode")]
+pub use normalize::find_entry_normalized;
+#[cfg(feature = "runtime")]
+pub use partition::{Granularity, PartitionOptions, PartitionedWriter};
+#[cfg(feature = "runtime")]
+pub use pipeline::{Derivative, Pipeline, PipelineBuilder};
+#[cfg(feature = "runtime")]
+pub use pool::{Pool, PoolBuilder};
+#[cfg(any(feature = "gzip", feature = "brotli", feature = "zstd"))]
+pub use precompress::{precompress, PrecompressOptions, PrecompressReport};
+#[cfg(feature = "runtime")]
+pub use probe::{probe, MediaFormat, MediaInfo};
+#[cfg(feature = "actix-web")]
+pub use receive::{receive_file, ReceiveOptions, Received};
+#[cfg(feature = "runtime")]
+pub use registry::{Policy, RootRegistry, Tenant, TenantOptions};
+#[cfg(feature = "runtime")]
+pub use retry::RetryOptions;
+pub use root::{Root, WriteOnce};
+#[cfg(feature = "runtime")]
+pub use rotate::{RotateOptions, RotatingFile};
+#[cfg(feature = "runtime")]
+pub use scope::{scope, Scope, ScopeError, ScopeOptions};
+#[cfg(feature = "runtime")]
+pub use script::{run_script, ScriptOptions, Step, StepOutcome};
+#[cfg(feature = "runtime")]
+pub use sentinel::{Alert, Fingerprint, Sentinel};
+#[cfg(feature = "runtime")]
+pub use size::{dir_size, DirSize, DirSizeOptions};
+#[cfg(feature = "sqlite")]
+pub use sqlite::backup_sqlite;
+#[cfg(feature = "runtime")]
+pub use statfs::{statfs, FsStats};
+#[cfg(feature = "runtime")]
+pub use tail::{tail, TailOptions};
+#[cfg(feature = "runtime")]
+pub use tier::{Temperature, TierPolicy, Tiers};
+#[cfg(feature = "watch")]
+pub use watch::{watch, Event, Watch, WatchOptions};
+#[cfg(all(unix, feature = "runtime"))]
+pub use xattr::{get_xattr, list_xattr, remove_xattr, set_xattr};
 
+#[cfg(feature = "runtime")]
+use actix_threadpool::BlockingError;
+#[cfg(feature = "runtime")]
+use futures::future::Either;
+#[cfg(feature = "runtime")]
 use futures::Future;
-use std::io::{self, ErrorKind};
+#[cfg(feature = "runtime")]
+use std::io;
+#[cfg(feature = "runtime")]
+use std::path::Path;
 
+/// Runs `f` on the threadpool that filesystem operations run on.
+///
+/// This is an escape hatch for synchronous filesystem code the crate does
+/// not cover, such as `ioctl` calls or third-party C libraries. The work
+/// is reported to the installed [`Metrics`] like the operations of the
+/// crate, as `"spawn_blocking"`, and errors from `f` are passed through
+/// unchanged, so their kind and any [`Error`] context are kept. To run on
+/// a dedicated [`Pool`] and respect its queue depth, use
+/// [`Pool::spawn_blocking`].
+///
+/// `f` should not block for long on anything but the filesystem, as it
+/// holds a thread shared with all other filesystem work.
+///
+/// [`Metrics`]: trait.Metrics.html
+/// [`Error`]: struct.Error.html
+/// [`Pool`]: struct.Pool.html
+/// [`Pool::spawn_blocking`]: struct.Pool.html#method.spawn_blocking
+#[cfg(feature = "runtime")]
+pub fn spawn_blocking_fs<F, I>(f: F) -> impl Future<Item = I, Error = io::Error>
+where
+    F: FnOnce() -> io::Result<I> + Send + 'static,
+    I: Send + 'static,
+{
+    blocking_op(None, "spawn_blocking", None, |_| 0, f)
+}
+
+#[cfg(feature = "runtime")]
 fn blocking<F, I>(f: F) -> impl Future<Item = I, Error = io::Error>
 where
     F: FnOnce() -> Result<I, io::Error> + Send + 'static,
     I: Send + 'static,
 {
-    actix_threadpool::run(f).map_err(|err| blocking_err(err))
+    blocking_on(None, f)
+}
+
+#[cfg(feature = "runtime")]
+fn blocking_on<F, I>(pool: Option<&Pool>, f: F) -> impl Future<Item = I, Error = io::Error>
+where
+    F: FnOnce() -> Result<I, io::Error> + Send + 'static,
+    I: Send + 'static,
+{
+    blocking_op(pool, "blocking", None, |_| 0, f)
+}
+
+/// Runs `f` on `pool` or the global threadpool, reporting it to the
+/// installed metrics as `op` on `path`, having moved `bytes` of its output.
+#[cfg(feature = "runtime")]
+fn blocking_op<F, B, I>(
+    pool: Option<&Pool>,
+    op: &'static str,
+    path: Option<&Path>,
+    bytes: B,
+    f: F,
+) -> impl Future<Item = I, Error = io::Error>
+where
+    F: FnOnce() -> Result<I, io::Error> + Send + 'static,
+    B: FnOnce(&I) -> u64 + Send + 'static,
+    I: Send + 'static,
+{
+    let timer = instrument::Timer::start(op, path);
+    let f = move || match timer {
+        Some(timer) => timer.run(f, bytes),
+        None => f(),
+    };
+    match pool {
+        Some(pool) => Either::A(pool.run(f)),
+        None => Either::B(actix_threadpool::run(f).map_err(|err| match err {
+            BlockingError::Error(err) => err,
+            BlockingError::Canceled => blocking_err(err),
+        })),
+    }
 }
 
+#[cfg(feature = "runtime")]
 fn blocking_err<E>(err: E) -> io::Error
 where
     E: Send + std::fmt::Display + 'static,
 {
-    io::Error::new(ErrorKind::Other, format!("{}", err))
+    io::Error::other(format!("{}", err))
 }
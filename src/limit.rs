@@ -0,0 +1,144 @@
+use futures::future::{self, Either, Loop};
+use futures::{Future, Stream};
+
+use std::error;
+use std::fmt;
+use std::fs::File as StdFile;
+use std::io::{self, ErrorKind};
+
+use crate::File;
+
+/// The error of a write that would have exceeded the limit of a
+/// [`LimitedWriter`].
+///
+/// It travels inside the returned `io::Error`, which has the kind
+/// `FileTooLarge`. Use [`LimitExceeded::from_io`] to get at it.
+///
+/// [`LimitedWriter`]: struct.LimitedWriter.html
+/// [`LimitExceeded::from_io`]: #method.from_io
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LimitExceeded {
+    limit: u64,
+}
+
+impl LimitExceeded {
+    /// Returns the limit error carried by `err`, if any.
+    pub fn from_io(err: &io::Error) -> Option<&LimitExceeded> {
+        err.get_ref()?.downcast_ref::<LimitExceeded>()
+    }
+
+    /// Returns the limit in bytes.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "write exceeds the limit of {} bytes", self.limit)
+    }
+}
+
+impl error::Error for LimitExceeded {}
+
+impl From<LimitExceeded> for io::Error {
+    fn from(err: LimitExceeded) -> io::Error {
+        io::Error::new(ErrorKind::FileTooLarge, err)
+    }
+}
+
+/// Writes a stream of chunks to a file, up to a limit.
+///
+/// Meant for uploads and other data of a size not known up front, so a
+/// client cannot fill the disk. Once a chunk would take the total past the
+/// limit, the write fails with a [`LimitExceeded`] error without writing
+/// that chunk. The data written before stays in the file, so write to a
+/// temporary file and remove it on error.
+///
+/// [`LimitExceeded`]: struct.LimitExceeded.html
+#[derive(Clone, Debug)]
+pub struct LimitedWriter {
+    limit: u64,
+    check_space: bool,
+}
+
+impl LimitedWriter {
+    /// Creates a writer that writes at most `limit` bytes.
+    pub fn new(limit: u64) -> LimitedWriter {
+        LimitedWriter {
+            limit,
+            check_space: false,
+        }
+    }
+
+    /// Sets whether the filesystem of the file is checked for room for
+    /// `limit` bytes before anything is written, failing with `StorageFull`
+    /// if there is not.
+    ///
+    /// The check uses `fstatvfs` and is skipped on platforms other than
+    /// Unix.
+    pub fn check_space(&mut self, check_space: bool) -> &mut LimitedWriter {
+        self.check_space = check_space;
+        self
+    }
+
+    /// Writes every chunk of `stream` at the current position of `file`.
+    ///
+    /// Resolves to the file and the number of bytes written.
+    pub fn write<S>(
+        &self,
+        file: File,
+        stream: S,
+    ) -> impl Future<Item = (File, u64), Error = io::Error>
+    where
+        S: Stream<Item = Vec<u8>, Error = io::Error>,
+    {
+        let limit = self.limit;
+        let checked = if self.check_space {
+            Either::A(
+                file.blocking("check space", move |std| check_space(std, limit))
+                    .map(|(file, ())| file),
+            )
+        } else {
+            Either::B(future::ok(file))
+        };
+        checked.and_then(move |file| {
+            future::loop_fn((file, stream, 0), move |(file, stream, written)| {
+                stream
+                    .into_future()
+                    .map_err(|(err, _)| err)
+                    .and_then(move |(chunk, stream)| {
+                        let chunk = match chunk {
+                            Some(chunk) => chunk,
+                            None => return Either::A(future::ok(Loop::Break((file, written)))),
+                        };
+                        let total = written + chunk.len() as u64;
+                        if total > limit {
+                            return Either::A(future::err(LimitExceeded { limit }.into()));
+                        }
+                        Either::B(
+                            file.write_all(chunk)
+                                .map(move |file| Loop::Continue((file, stream, total))),
+                        )
+                    })
+            })
+        })
+    }
+}
+
+#[cfg(unix)]
+fn check_space(std: &mut StdFile, needed: u64) -> io::Result<()> {
+    let available = crate::statfs::sys::fstatfs(std)?.available;
+    if available < needed {
+        return Err(io::Error::new(
+            ErrorKind::StorageFull,
+            format!("{} bytes needed, but only {} available", needed, available),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_space(_: &mut StdFile, _: u64) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,454 @@
+use actix_web::dev::{Body, SizedStream};
+use actix_web::http::header::{self, EntityTag, HttpDate, IfModifiedSince, IfNoneMatch, IfRange};
+use actix_web::http::{Method, StatusCode};
+use actix_web::web::Bytes;
+use actix_web::{Error, HttpMessage, HttpRequest, HttpResponse, Responder};
+use futures::future::Either;
+use futures::{stream, Future, Stream};
+use mime_guess::Mime;
+
+use std::fs::{self, File as StdFile};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::FsContext;
+
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// A file that can be returned from an actix-web handler.
+///
+/// The response gets a `Content-Type` guessed from the file extension, an
+/// `ETag` and `Last-Modified` header, answers conditional requests with
+/// `304 Not Modified`, and serves single byte ranges with
+/// `206 Partial Content`. The body is read in chunks on the threadpool as
+/// the client consumes it.
+///
+/// This is only available with the `actix-web` feature.
+#[derive(Debug)]
+pub struct NamedFile {
+    path: PathBuf,
+    file: StdFile,
+    content_type: Mime,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl NamedFile {
+    /// Opens the file at `path` for serving.
+    pub fn open<P>(path: P) -> impl Future<Item = NamedFile, Error = io::Error>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        crate::blocking(move || NamedFile::open_sync(path.as_ref()))
+    }
+
+    fn open_sync(path: &Path) -> io::Result<NamedFile> {
+        let file = StdFile::open(path)?;
+        let metadata = file.metadata()?;
+        if !metadata.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "not a file"));
+        }
+        Ok(NamedFile {
+            path: path.to_owned(),
+            file,
+            content_type: mime_guess::from_path(path).first_or_octet_stream(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    /// Returns the path the file was opened from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the size of the file in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the content type the file is served with.
+    pub fn content_type(&self) -> &Mime {
+        &self.content_type
+    }
+
+    /// Overrides the content type guessed from the file extension.
+    pub fn set_content_type(mut self, content_type: Mime) -> NamedFile {
+        self.content_type = content_type;
+        self
+    }
+
+    fn etag(&self) -> Option<EntityTag> {
+        entity_tag(self.len, self.modified?)
+    }
+
+    fn not_modified(&self, req: &HttpRequest, etag: Option<&EntityTag>) -> bool {
+        match req.get_header::<IfNoneMatch>() {
+            Some(IfNoneMatch::Any) => return true,
+            Some(IfNoneMatch::Items(ref tags)) => {
+                return etag.is_some_and(|etag| tags.iter().any(|tag| tag.weak_eq(etag)));
+            }
+            None => {}
+        }
+        match (req.get_header::<IfModifiedSince>(), self.modified) {
+            (Some(IfModifiedSince(since)), Some(modified)) => {
+                modified_secs(modified) <= modified_secs(since.into())
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether a `Range` header may be honored according to
+    /// `If-Range`.
+    fn range_applies(&self, req: &HttpRequest, etag: Option<&EntityTag>) -> bool {
+        match req.get_header::<IfRange>() {
+            None => true,
+            Some(IfRange::EntityTag(ref tag)) => etag.is_some_and(|etag| tag.strong_eq(etag)),
+            Some(IfRange::Date(date)) => self
+                .modified
+                .is_some_and(|modified| modified_secs(modified) <= modified_secs(date.into())),
+        }
+    }
+}
+
+impl Responder for NamedFile {
+    type Error = Error;
+    type Future = Result<HttpResponse, Error>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        Ok(self.respond(req).0)
+    }
+}
+
+impl NamedFile {
+    /// Answers `req` with the file, returning the response and how many
+    /// bytes of the file its body carries.
+    fn respond(self, req: &HttpRequest) -> (HttpResponse, u64) {
+        let etag = self.etag();
+        let mut res = HttpResponse::Ok();
+        res.set_header(header::CONTENT_TYPE, self.content_type.to_string())
+            .set_header(header::ACCEPT_RANGES, "bytes");
+        if let Some(ref etag) = etag {
+            res.set(header::ETag(etag.clone()));
+        }
+        if let Some(modified) = self.modified {
+            res.set(header::LastModified(HttpDate::from(modified)));
+        }
+
+        if self.not_modified(req, etag.as_ref()) {
+            return (res.status(StatusCode::NOT_MODIFIED).finish(), 0);
+        }
+
+        let mut offset = 0;
+        let mut len = self.len;
+        let range = req
+            .headers()
+            .get(header::RANGE)
+            .and_then(|range| range.to_str().ok());
+        if let Some(range) = range {
+            if self.range_applies(req, etag.as_ref()) {
+                match parse_range(range, self.len) {
+                    Range::Satisfiable(start, end) => {
+                        offset = start;
+                        len = end - start + 1;
+                        res.status(StatusCode::PARTIAL_CONTENT).set_header(
+                            header::CONTENT_RANGE,
+                            format!("bytes {}-{}/{}", start, end, self.len),
+                        );
+                    }
+                    Range::Unsatisfiable => {
+                        let res = res
+                            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                            .set_header(header::CONTENT_RANGE, format!("bytes */{}", self.len))
+                            .finish();
+                        return (res, 0);
+                    }
+                    Range::Ignored => {}
+                }
+            }
+        }
+
+        if *req.method() == Method::HEAD {
+            return (res.content_length(len).finish(), 0);
+        }
+        let body = SizedStream::new(len, chunks(self.file, offset, len));
+        (res.body(Body::from_message(body)), len)
+    }
+}
+
+/// Options for [`serve_file`].
+///
+/// [`serve_file`]: fn.serve_file.html
+#[derive(Clone, Debug)]
+pub struct ServeOptions {
+    precompressed: bool,
+    content_type: Option<Mime>,
+}
+
+impl ServeOptions {
+    /// Creates options that serve precompressed variants and guess the
+    /// content type from the file extension.
+    pub fn new() -> ServeOptions {
+        ServeOptions {
+            precompressed: true,
+            content_type: None,
+        }
+    }
+
+    /// Sets whether `.br` and `.gz` variants next to the file are served to
+    /// clients that accept them.
+    pub fn precompressed(&mut self, precompressed: bool) -> &mut ServeOptions {
+        self.precompressed = precompressed;
+        self
+    }
+
+    /// Overrides the content type guessed from the file extension.
+    pub fn content_type(&mut self, content_type: Mime) -> &mut ServeOptions {
+        self.content_type = Some(content_type);
+        self
+    }
+}
+
+impl Default for ServeOptions {
+    fn default() -> ServeOptions {
+        ServeOptions::new()
+    }
+}
+
+/// Encodings of precompressed variants, in order of preference, with the
+/// extension of their files.
+const ENCODINGS: &[(&str, &str)] = &[("br", "br"), ("gzip", "gz")];
+
+/// Answers `req` with the file at `path`.
+///
+/// On top of what [`NamedFile`] does for `HEAD`, `Range` and conditional
+/// requests, this negotiates `Accept-Encoding`: if the client accepts
+/// Brotli or gzip and a `.br` or `.gz` file exists next to `path`, that file
+/// is served with a `Content-Encoding` header and the content type of
+/// `path`. Responses then also carry `Vary: Accept-Encoding`. A variant
+/// last modified before `path` is stale and skipped.
+///
+/// If the request has an [`FsContext`], the file is opened within its
+/// deadline, and the bytes of the response body are taken from its budget,
+/// so `HEAD`, `304 Not Modified` and range responses only pay for what they
+/// send.
+///
+/// Missing files are answered with `404 Not Found`.
+///
+/// This is only available with the `actix-web` feature.
+///
+/// [`NamedFile`]: struct.NamedFile.html
+/// [`FsContext`]: struct.FsContext.html
+pub fn serve_file<P>(
+    req: &HttpRequest,
+    path: P,
+    opts: &ServeOptions,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    P: AsRef<Path>,
+{
+    let req = req.clone();
+    let path = path.as_ref().to_owned();
+    let precompressed = opts.precompressed;
+    let content_type = opts
+        .content_type
+        .clone()
+        .unwrap_or_else(|| mime_guess::from_path(&path).first_or_octet_stream());
+    let encodings = if precompressed {
+        accepted_encodings(&req)
+    } else {
+        Vec::new()
+    };
+    let ctx = req.extensions().get::<FsContext>().cloned();
+
+    let open = crate::blocking(move || {
+        if encodings.is_empty() {
+            return Ok((NamedFile::open_sync(&path)?, None));
+        }
+        let source = match fs::metadata(&path) {
+            Ok(metadata) => metadata.modified().ok(),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+        for encoding in encodings {
+            let mut variant = path.clone().into_os_string();
+            variant.push(".");
+            variant.push(encoding.1);
+            match NamedFile::open_sync(Path::new(&variant)) {
+                Ok(file) if is_stale(&file, source) => {}
+                Ok(file) => return Ok((file, Some(encoding.0))),
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok((NamedFile::open_sync(&path)?, None))
+    });
+    let open = match ctx {
+        Some(ref ctx) => Either::A(ctx.with_deadline(open)),
+        None => Either::B(open),
+    };
+    open.map_err(Error::from).and_then(move |(file, encoding)| {
+        let (mut res, sent) = file.set_content_type(content_type).respond(&req);
+        if let Some(ref ctx) = ctx {
+            ctx.charge(sent)?;
+        }
+        if precompressed {
+            res.headers_mut().insert(
+                header::VARY,
+                header::HeaderValue::from_static("Accept-Encoding"),
+            );
+        }
+        if let Some(encoding) = encoding {
+            res.headers_mut().insert(
+                header::CONTENT_ENCODING,
+                header::HeaderValue::from_static(encoding),
+            );
+        }
+        Ok(res)
+    })
+}
+
+/// Returns whether the variant `file` was modified before its source was
+/// last modified at `source`.
+fn is_stale(file: &NamedFile, source: Option<SystemTime>) -> bool {
+    match (file.modified, source) {
+        (Some(modified), Some(source)) => modified < source,
+        _ => false,
+    }
+}
+
+/// Returns the encodings of precompressed variants `req` accepts, the most
+/// preferred first.
+fn accepted_encodings(req: &HttpRequest) -> Vec<(&'static str, &'static str)> {
+    let accept = match req
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|accept| accept.to_str().ok())
+    {
+        Some(accept) => accept,
+        None => return Vec::new(),
+    };
+    let mut accepted: Vec<_> = ENCODINGS
+        .iter()
+        .filter_map(|&encoding| {
+            let quality = quality(accept, encoding.0);
+            if quality > 0.0 {
+                Some((encoding, quality))
+            } else {
+                None
+            }
+        })
+        .collect();
+    // A stable sort keeps the order of `ENCODINGS` between equal qualities.
+    accepted.sort_by(|a, b| b.1.total_cmp(&a.1));
+    accepted.into_iter().map(|(encoding, _)| encoding).collect()
+}
+
+/// Returns the quality `accept` gives `encoding`, falling back to that of
+/// `*`.
+fn quality(accept: &str, encoding: &str) -> f32 {
+    let mut wildcard = 0.0;
+    for item in accept.split(',') {
+        let mut parts = item.split(';');
+        let name = parts.next().unwrap_or("").trim();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .next()
+            .map_or(Some(1.0), |q| q.trim().parse().ok())
+            .unwrap_or(0.0);
+        if name.eq_ignore_ascii_case(encoding) {
+            return quality;
+        }
+        if name == "*" {
+            wildcard = quality;
+        }
+    }
+    wildcard
+}
+
+/// Reads `len` bytes starting at `offset`, one chunk at a time on the
+/// threadpool.
+fn chunks(file: StdFile, offset: u64, len: u64) -> impl Stream<Item = Bytes, Error = Error> {
+    stream::unfold((file, offset, len), |(mut file, offset, remaining)| {
+        if remaining == 0 {
+            return None;
+        }
+        Some(crate::blocking(move || {
+            let size = remaining.min(CHUNK_SIZE);
+            let mut buf = vec![0; size as usize];
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut buf)?;
+            Ok((Bytes::from(buf), (file, offset + size, remaining - size)))
+        }))
+    })
+    .map_err(Error::from)
+}
+
+enum Range {
+    /// An inclusive byte range.
+    Satisfiable(u64, u64),
+    Unsatisfiable,
+    /// Malformed or multiple ranges, which are answered with the whole file.
+    Ignored,
+}
+
+fn parse_range(header: &str, len: u64) -> Range {
+    let spec = match header.trim().strip_prefix("bytes=") {
+        Some(spec) if !spec.contains(',') => spec.trim(),
+        _ => return Range::Ignored,
+    };
+    let (start, end) = match spec.find('-') {
+        Some(i) => (spec[..i].trim(), spec[i + 1..].trim()),
+        None => return Range::Ignored,
+    };
+    let range = if start.is_empty() {
+        // A suffix range, the last `end` bytes.
+        match end.parse::<u64>() {
+            Ok(0) => return Range::Unsatisfiable,
+            Ok(suffix) => (len.saturating_sub(suffix), len.wrapping_sub(1)),
+            Err(_) => return Range::Ignored,
+        }
+    } else {
+        let start = match start.parse::<u64>() {
+            Ok(start) => start,
+            Err(_) => return Range::Ignored,
+        };
+        let end = if end.is_empty() {
+            len.wrapping_sub(1)
+        } else {
+            match end.parse::<u64>() {
+                Ok(end) if end >= start => end.min(len.wrapping_sub(1)),
+                _ => return Range::Ignored,
+            }
+        };
+        (start, end)
+    };
+    if len == 0 || range.0 >= len {
+        return Range::Unsatisfiable;
+    }
+    Range::Satisfiable(range.0, range.1)
+}
+
+/// Returns the `ETag` of a file with the given size and modification time.
+pub(crate) fn entity_tag(len: u64, modified: SystemTime) -> Option<EntityTag> {
+    let since = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some(EntityTag::strong(format!(
+        "{:x}-{:x}.{:x}",
+        len,
+        since.as_secs(),
+        since.subsec_nanos()
+    )))
+}
+
+/// HTTP dates have a resolution of one second.
+fn modified_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,66 @@
+use futures::Future;
+use unicode_normalization::UnicodeNormalization;
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Looks up the entry of `dir` named `name`, treating names that are
+/// canonically equivalent in Unicode as equal.
+///
+/// The same user-visible name can be encoded in more than one way, such as
+/// `é` as a single code point (NFC) or as `e` followed by a combining accent
+/// (NFD). macOS tends to hand out decomposed names while most other systems
+/// and browsers produce composed ones, so a name typed by a user often fails
+/// to match the bytes on disk even though the file is there.
+///
+/// Resolves to the path of the entry as it is actually named on disk, or
+/// `None` if there is no such entry. An exact match is preferred; otherwise
+/// the first equivalent name in sorted order is used.
+///
+/// # Errors
+///
+/// Fails with `InvalidInput` if `name` is not a single plain file name, such
+/// as one that is empty, `..`, absolute or contains a separator.
+///
+/// This is only available with the `unicode` feature.
+pub fn find_entry_normalized<P, N>(
+    dir: P,
+    name: N,
+) -> impl Future<Item = Option<PathBuf>, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+    N: AsRef<str> + Send + 'static,
+{
+    crate::blocking(move || {
+        let dir = dir.as_ref();
+        let name = name.as_ref();
+        let mut components = Path::new(name).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(component)), None) if component == name => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{:?} is not a file name", name),
+                ))
+            }
+        }
+        let exact = dir.join(name);
+        if fs::symlink_metadata(&exact).is_ok() {
+            return Ok(Some(exact));
+        }
+
+        let wanted: String = name.nfc().collect();
+        let mut matches = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let file_name = entry?.file_name();
+            if let Some(file_name) = file_name.to_str() {
+                if file_name.nfc().eq(wanted.chars()) {
+                    matches.push(dir.join(file_name));
+                }
+            }
+        }
+        matches.sort();
+        Ok(matches.into_iter().next())
+    })
+}
@@ -0,0 +1,271 @@
+use futures::Future;
+
+use std::fs::{self, File as StdFile, OpenOptions as StdOpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How finely a [`PartitionedWriter`] splits records into directories.
+///
+/// Partitions are named after the UTC time records are written at.
+///
+/// [`PartitionedWriter`]: struct.PartitionedWriter.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Granularity {
+    /// One partition per day, laid out as `YYYY/MM/DD`.
+    Day,
+    /// One partition per hour, laid out as `YYYY/MM/DD/HH`.
+    Hour,
+}
+
+impl Granularity {
+    /// Returns the directory, relative to the writer's root, that records
+    /// written at `time` belong to.
+    pub fn partition(self, time: SystemTime) -> PathBuf {
+        let (year, month, day, hour) = civil(time);
+        let mut path = PathBuf::from(format!("{:04}", year));
+        path.push(format!("{:02}", month));
+        path.push(format!("{:02}", day));
+        if self == Granularity::Hour {
+            path.push(format!("{:02}", hour));
+        }
+        path
+    }
+
+    /// Returns the number of directory levels below the root that make up a
+    /// partition.
+    pub fn depth(self) -> usize {
+        match self {
+            Granularity::Day => 3,
+            Granularity::Hour => 4,
+        }
+    }
+}
+
+/// Options which can be used to configure a [`PartitionedWriter`].
+///
+/// [`PartitionedWriter`]: struct.PartitionedWriter.html
+#[derive(Clone, Debug)]
+pub struct PartitionOptions {
+    granularity: Granularity,
+    file_name: String,
+}
+
+impl PartitionOptions {
+    /// Creates the default set of options: hourly partitions, each holding a
+    /// single file named `data.log`.
+    pub fn new() -> PartitionOptions {
+        PartitionOptions {
+            granularity: Granularity::Hour,
+            file_name: "data.log".to_owned(),
+        }
+    }
+
+    /// Sets how finely records are split into directories.
+    pub fn granularity(&mut self, granularity: Granularity) -> &mut PartitionOptions {
+        self.granularity = granularity;
+        self
+    }
+
+    /// Sets the name of the file records are appended to inside each
+    /// partition.
+    pub fn file_name<S>(&mut self, file_name: S) -> &mut PartitionOptions
+    where
+        S: Into<String>,
+    {
+        self.file_name = file_name.into();
+        self
+    }
+
+    /// Creates a writer for partitions below `root` with the options
+    /// specified by `self`.
+    pub fn open<P>(&self, root: P) -> PartitionedWriter
+    where
+        P: Into<PathBuf>,
+    {
+        PartitionedWriter {
+            inner: Arc::new(Inner {
+                root: root.into(),
+                opts: self.clone(),
+                current: Mutex::new(None),
+            }),
+        }
+    }
+}
+
+impl Default for PartitionOptions {
+    fn default() -> PartitionOptions {
+        PartitionOptions::new()
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    root: PathBuf,
+    opts: PartitionOptions,
+    current: Mutex<Option<Current>>,
+}
+
+#[derive(Debug)]
+struct Current {
+    partition: PathBuf,
+    file: StdFile,
+}
+
+/// Appends records into a time-partitioned directory layout.
+///
+/// Records are routed to `YYYY/MM/DD/HH` (or `YYYY/MM/DD`) directories below
+/// the writer's root according to the time they are written at. Directories
+/// are created as needed and the writer rolls over to a new file whenever a
+/// record falls into a different partition than the previous one.
+///
+/// Records are appended verbatim, so any delimiter such as a trailing newline
+/// must be part of the record. Files are opened in append mode, so a writer
+/// restarted within the same partition continues the existing file.
+///
+/// Cloning a `PartitionedWriter` produces another handle to the same writer;
+/// appends from all handles are serialized.
+#[derive(Clone, Debug)]
+pub struct PartitionedWriter {
+    inner: Arc<Inner>,
+}
+
+impl PartitionedWriter {
+    /// Creates a writer for hourly partitions below `root`.
+    ///
+    /// See [`PartitionOptions`] for more control over the layout.
+    ///
+    /// [`PartitionOptions`]: struct.PartitionOptions.html
+    pub fn new<P>(root: P) -> PartitionedWriter
+    where
+        P: Into<PathBuf>,
+    {
+        PartitionOptions::new().open(root)
+    }
+
+    /// Returns the directory partitions are created in.
+    pub fn root(&self) -> &Path {
+        &self.inner.root
+    }
+
+    /// Returns the granularity of the partitions.
+    pub fn granularity(&self) -> Granularity {
+        self.inner.opts.granularity
+    }
+
+    /// Appends `record` to the partition for the current time, resolving to
+    /// the path of the file it was written to.
+    pub fn append(&self, record: Vec<u8>) -> impl Future<Item = PathBuf, Error = io::Error> {
+        self.append_at(SystemTime::now(), record)
+    }
+
+    /// Appends `record` to the partition for `time`, resolving to the path
+    /// of the file it was written to.
+    ///
+    /// This is useful for routing events by the time they occurred rather
+    /// than the time they were received.
+    pub fn append_at(
+        &self,
+        time: SystemTime,
+        record: Vec<u8>,
+    ) -> impl Future<Item = PathBuf, Error = io::Error> {
+        let inner = self.inner.clone();
+        crate::blocking(move || -> io::Result<PathBuf> {
+            let partition = inner.opts.granularity.partition(time);
+            let mut current = inner.current.lock().unwrap();
+            let rollover = match *current {
+                Some(ref current) => current.partition != partition,
+                None => true,
+            };
+            if rollover {
+                if let Some(previous) = current.take() {
+                    previous.file.sync_all()?;
+                }
+                let dir = inner.root.join(&partition);
+                fs::create_dir_all(&dir)?;
+                let file = StdOpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(dir.join(&inner.opts.file_name))?;
+                *current = Some(Current { partition, file });
+            }
+
+            let current = current.as_mut().unwrap();
+            current.file.write_all(&record)?;
+            Ok(inner
+                .root
+                .join(&current.partition)
+                .join(&inner.opts.file_name))
+        })
+    }
+
+    /// Flushes the current file to disk.
+    pub fn flush(&self) -> impl Future<Item = (), Error = io::Error> {
+        let inner = self.inner.clone();
+        crate::blocking(move || match *inner.current.lock().unwrap() {
+            Some(ref current) => current.file.sync_all(),
+            None => Ok(()),
+        })
+    }
+}
+
+/// Returns the whole seconds since the epoch, rounding pre-epoch times down.
+fn unix_secs(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(err) => {
+            let before = err.duration();
+            match before.subsec_nanos() {
+                0 => -(before.as_secs() as i64),
+                _ => -(before.as_secs() as i64) - 1,
+            }
+        }
+    }
+}
+
+/// Converts `time` to a UTC `(year, month, day, hour)`.
+fn civil(time: SystemTime) -> (i64, u32, u32, u32) {
+    let secs = unix_secs(time);
+    let days = secs.div_euclid(86_400);
+    let hour = (secs.rem_euclid(86_400) / 3_600) as u32;
+
+    // Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day, hour)
+}
+
+/// Formats `time` as a compact UTC timestamp, `YYYYMMDDTHHMMSS`.
+pub(crate) fn timestamp(time: SystemTime) -> String {
+    let (year, month, day, hour) = civil(time);
+    let secs = unix_secs(time).rem_euclid(3_600);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        hour,
+        secs / 60,
+        secs % 60
+    )
+}
+
+/// Returns whether `s` is a timestamp formatted by `timestamp`.
+pub(crate) fn is_timestamp(s: &str) -> bool {
+    s.len() == 15
+        && s.bytes().enumerate().all(|(i, b)| {
+            if i == 8 {
+                b == b'T'
+            } else {
+                b.is_ascii_digit()
+            }
+        })
+}
@@ -0,0 +1,258 @@
+use futures::future::{self, Either, Loop};
+use futures::{Future, IntoFuture};
+use tokio_timer::Delay;
+
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File as StdFile};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+type GenerateFuture = Box<dyn Future<Item = (), Error = io::Error> + Send>;
+
+type Generate = Arc<dyn Fn(&Path, &Path) -> GenerateFuture + Send + Sync>;
+
+#[derive(Clone)]
+struct Generator {
+    suffix: String,
+    generate: Generate,
+}
+
+/// The outcome of generating a single derivative.
+#[derive(Debug)]
+pub struct Derivative {
+    /// The suffix the derivative was registered with.
+    pub suffix: String,
+    /// Where the derivative is, or would have been, stored.
+    pub path: PathBuf,
+    /// How many times generation was attempted.
+    pub attempts: usize,
+    /// The error of the last attempt, if every attempt failed.
+    pub error: Option<io::Error>,
+}
+
+/// Configures and creates a [`Pipeline`].
+///
+/// [`Pipeline`]: struct.Pipeline.html
+#[derive(Clone)]
+pub struct PipelineBuilder {
+    generators: Vec<Generator>,
+    retries: usize,
+    retry_delay: Duration,
+    max_retry_delay: Duration,
+}
+
+impl PipelineBuilder {
+    /// Creates a builder with no derivatives that retries failed generators
+    /// twice, waiting one second before the first retry and at most a
+    /// minute before any later one.
+    pub fn new() -> PipelineBuilder {
+        PipelineBuilder {
+            generators: Vec::new(),
+            retries: 2,
+            retry_delay: Duration::from_secs(1),
+            max_retry_delay: Duration::from_secs(60),
+        }
+    }
+
+    /// Registers a derivative generator.
+    ///
+    /// For a source file `photo.jpg` and a suffix of `thumb.webp`, the
+    /// derivative is stored as `photo.jpg.thumb.webp` in the same directory.
+    ///
+    /// `generate` is called on the threadpool with the path of the source file
+    /// and a temporary path to write the derivative to. It may do its work
+    /// right away and return an `io::Result`, or return a future, for example
+    /// one waiting for an external encoder. Once it succeeds the temporary
+    /// file is synced and renamed into place, so a derivative is either
+    /// complete or absent.
+    pub fn derivative<S, F, R>(&mut self, suffix: S, generate: F) -> &mut PipelineBuilder
+    where
+        S: Into<String>,
+        F: Fn(&Path, &Path) -> R + Send + Sync + 'static,
+        R: IntoFuture<Item = (), Error = io::Error>,
+        R::Future: Send + 'static,
+    {
+        self.generators.push(Generator {
+            suffix: suffix.into(),
+            generate: Arc::new(move |source: &Path, tmp: &Path| -> GenerateFuture {
+                Box::new(generate(source, tmp).into_future())
+            }),
+        });
+        self
+    }
+
+    /// Sets how many times a failed generator is retried.
+    pub fn retries(&mut self, retries: usize) -> &mut PipelineBuilder {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets how long to wait before the first retry. The delay doubles with
+    /// every further retry, up to the [`max_retry_delay`].
+    ///
+    /// [`max_retry_delay`]: #method.max_retry_delay
+    pub fn retry_delay(&mut self, delay: Duration) -> &mut PipelineBuilder {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Sets the longest wait before any retry, however many retries came
+    /// before it.
+    pub fn max_retry_delay(&mut self, delay: Duration) -> &mut PipelineBuilder {
+        self.max_retry_delay = delay;
+        self
+    }
+
+    /// Creates the pipeline.
+    pub fn build(&self) -> Pipeline {
+        Pipeline {
+            inner: Arc::new(self.clone()),
+        }
+    }
+}
+
+impl Default for PipelineBuilder {
+    fn default() -> PipelineBuilder {
+        PipelineBuilder::new()
+    }
+}
+
+impl fmt::Debug for PipelineBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let suffixes: Vec<&str> = self.generators.iter().map(|g| &*g.suffix).collect();
+        f.debug_struct("PipelineBuilder")
+            .field("derivatives", &suffixes)
+            .field("retries", &self.retries)
+            .field("retry_delay", &self.retry_delay)
+            .field("max_retry_delay", &self.max_retry_delay)
+            .finish()
+    }
+}
+
+/// Generates derivatives, such as thumbnails or transcoded variants, of
+/// files once they have been finalized.
+///
+/// Register generators with a [`PipelineBuilder`], then call [`process`]
+/// after each file is written. All derivatives of a file are generated
+/// concurrently, and a failing generator is retried with a growing delay.
+///
+/// Cloning a `Pipeline` produces another handle to the same generators.
+///
+/// [`PipelineBuilder`]: struct.PipelineBuilder.html
+/// [`process`]: #method.process
+#[derive(Clone, Debug)]
+pub struct Pipeline {
+    inner: Arc<PipelineBuilder>,
+}
+
+impl Pipeline {
+    /// Returns a builder for configuring a pipeline.
+    pub fn builder() -> PipelineBuilder {
+        PipelineBuilder::new()
+    }
+
+    /// Generates every registered derivative of the file at `source`.
+    ///
+    /// Resolves once all generators have succeeded or run out of retries,
+    /// with one [`Derivative`] per generator in registration order. A
+    /// failing generator does not affect the others, so the returned future
+    /// itself never fails.
+    ///
+    /// [`Derivative`]: struct.Derivative.html
+    pub fn process<P>(&self, source: P) -> impl Future<Item = Vec<Derivative>, Error = io::Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let source = source.into();
+        let inner = self.inner.clone();
+        let derivatives: Vec<_> = inner
+            .generators
+            .iter()
+            .map(|generator| generate(&inner, generator.clone(), source.clone()))
+            .collect();
+        future::join_all(derivatives)
+    }
+}
+
+fn generate(
+    opts: &PipelineBuilder,
+    generator: Generator,
+    source: PathBuf,
+) -> impl Future<Item = Derivative, Error = io::Error> {
+    let path = with_suffix(&source, &generator.suffix);
+    let tmp = with_suffix(&path, "tmp");
+    let retries = opts.retries;
+    let retry_delay = opts.retry_delay;
+    let max_retry_delay = opts.max_retry_delay;
+
+    future::loop_fn(0, move |attempt| {
+        let tmp = tmp.clone();
+        let path = path.clone();
+        let suffix = generator.suffix.clone();
+        let generate = generator.generate.clone();
+        let start = (source.clone(), tmp.clone());
+        // Generators that do their work synchronously run entirely on the
+        // threadpool; asynchronous ones only create their future there.
+        crate::blocking(move || Ok(generate(&start.0, &start.1)))
+            .flatten()
+            .and_then({
+                let tmp = tmp.clone();
+                let path = path.clone();
+                move |_| crate::blocking(move || finalize(&tmp, &path))
+            })
+            .then(move |res| {
+                let err = res.err();
+                let cleanup = if err.is_some() {
+                    Either::A(crate::blocking(move || {
+                        let _ = fs::remove_file(&tmp);
+                        Ok(())
+                    }))
+                } else {
+                    Either::B(future::ok(()))
+                };
+                let derivative = Derivative {
+                    suffix,
+                    path,
+                    attempts: attempt + 1,
+                    error: err,
+                };
+                cleanup.and_then(move |_| {
+                    if derivative.error.is_none() || attempt >= retries {
+                        return Either::A(future::ok(Loop::Break(derivative)));
+                    }
+                    let delay = backoff(retry_delay, max_retry_delay, attempt);
+                    Either::B(
+                        Delay::new(Instant::now() + delay)
+                            .map_err(crate::blocking_err)
+                            .map(move |_| Loop::Continue(attempt + 1)),
+                    )
+                })
+            })
+    })
+}
+
+/// Returns the delay before retrying after `attempt`, doubling `first` for
+/// every earlier retry but never exceeding `max`.
+fn backoff(first: Duration, max: Duration, attempt: usize) -> Duration {
+    u32::try_from(attempt)
+        .ok()
+        .and_then(|attempt| 2u32.checked_pow(attempt))
+        .and_then(|factor| first.checked_mul(factor))
+        .map_or(max, |delay| delay.min(max))
+}
+
+fn finalize(tmp: &Path, path: &Path) -> io::Result<()> {
+    StdFile::open(tmp)?.sync_all()?;
+    fs::rename(tmp, path)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.file_name().unwrap_or_default());
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
@@ -0,0 +1,205 @@
+use futures::future::{self, Either};
+use futures::sync::oneshot;
+use futures::Future;
+use threadpool::ThreadPool;
+
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A dedicated threadpool for filesystem work.
+///
+/// By default every operation runs on the global Actix threadpool, which is
+/// shared with all other blocking work in the process. A `Pool` isolates
+/// filesystem work so that heavy I/O cannot starve other blocking tasks, and
+/// vice versa.
+///
+/// Files opened with [`OpenOptions::pool`] run all of their operations on the
+/// given pool. Operations on paths run on a pool through their `_on`
+/// variants: [`remove_file_on`], [`rename_on`], [`create_dir_on`],
+/// [`create_dir_all_on`], [`remove_dir_on`] and [`read_dir_collected_on`].
+/// Everything else, such as reading metadata, walking directories, hashing
+/// or backups, runs on the global threadpool; wrap the synchronous `std::fs`
+/// calls in [`spawn_blocking`] to run them on a pool instead.
+///
+/// Cloning a `Pool` produces another handle to the same threads.
+///
+/// [`OpenOptions::pool`]: struct.OpenOptions.html#method.pool
+/// [`remove_file_on`]: fn.remove_file_on.html
+/// [`rename_on`]: fn.rename_on.html
+/// [`create_dir_on`]: fn.create_dir_on.html
+/// [`create_dir_all_on`]: fn.create_dir_all_on.html
+/// [`remove_dir_on`]: fn.remove_dir_on.html
+/// [`read_dir_collected_on`]: fn.read_dir_collected_on.html
+/// [`spawn_blocking`]: #method.spawn_blocking
+#[derive(Clone)]
+pub struct Pool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    pool: Mutex<ThreadPool>,
+    pending: AtomicUsize,
+    queue_depth: Option<usize>,
+}
+
+impl Pool {
+    /// Creates a pool with `threads` worker threads and an unbounded queue.
+    pub fn new(threads: usize) -> Pool {
+        PoolBuilder::new().threads(threads).build()
+    }
+
+    /// Returns a builder for configuring a pool.
+    pub fn builder() -> PoolBuilder {
+        PoolBuilder::new()
+    }
+
+    /// Returns the number of operations that are queued or running.
+    pub fn pending(&self) -> usize {
+        self.inner.pending.load(Ordering::SeqCst)
+    }
+
+    /// Runs `f` on one of the pool's threads.
+    ///
+    /// This is the counterpart of [`spawn_blocking_fs`] for running custom
+    /// synchronous filesystem code on this pool. Fails with `WouldBlock`
+    /// without running `f` if the queue is full.
+    ///
+    /// [`spawn_blocking_fs`]: fn.spawn_blocking_fs.html
+    pub fn spawn_blocking<F, I>(&self, f: F) -> impl Future<Item = I, Error = io::Error>
+    where
+        F: FnOnce() -> io::Result<I> + Send + 'static,
+        I: Send + 'static,
+    {
+        crate::blocking_op(Some(self), "spawn_blocking", None, |_| 0, f)
+    }
+
+    /// Runs `f` on one of the pool's threads.
+    ///
+    /// Fails with `WouldBlock` without running `f` if the queue is full.
+    pub(crate) fn run<F, I>(&self, f: F) -> impl Future<Item = I, Error = io::Error>
+    where
+        F: FnOnce() -> io::Result<I> + Send + 'static,
+        I: Send + 'static,
+    {
+        let pending = self.inner.pending.fetch_add(1, Ordering::SeqCst);
+        let guard = PendingGuard(self.inner.clone());
+        if let Some(depth) = self.inner.queue_depth {
+            if pending >= depth {
+                drop(guard);
+                return Either::A(future::err(io::Error::new(
+                    ErrorKind::WouldBlock,
+                    "filesystem pool queue is full",
+                )));
+            }
+        }
+
+        let (tx, rx) = oneshot::channel();
+        self.inner.pool.lock().unwrap().execute(move || {
+            if tx.is_canceled() {
+                return;
+            }
+            let res = f();
+            // Release the slot before the caller can observe completion.
+            drop(guard);
+            let _ = tx.send(res);
+        });
+        Either::B(rx.then(|res| match res {
+            Ok(res) => res,
+            Err(_) => Err(io::Error::other("Thread pool is gone")),
+        }))
+    }
+}
+
+impl fmt::Debug for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("pending", &self.pending())
+            .field("queue_depth", &self.inner.queue_depth)
+            .finish()
+    }
+}
+
+// Keeps `pending` accurate even if the operation panics.
+struct PendingGuard(Arc<Inner>);
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Configures and creates a [`Pool`].
+///
+/// [`Pool`]: struct.Pool.html
+#[derive(Clone, Debug)]
+pub struct PoolBuilder {
+    threads: usize,
+    queue_depth: Option<usize>,
+    name: String,
+}
+
+impl PoolBuilder {
+    /// Creates a builder with one thread per available CPU, an unbounded
+    /// queue and threads named `actix-fs`.
+    pub fn new() -> PoolBuilder {
+        PoolBuilder {
+            threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            queue_depth: None,
+            name: "actix-fs".to_owned(),
+        }
+    }
+
+    /// Sets the number of worker threads.
+    ///
+    /// # Panics
+    ///
+    /// `build` panics if this is zero.
+    pub fn threads(&mut self, threads: usize) -> &mut PoolBuilder {
+        self.threads = threads;
+        self
+    }
+
+    /// Limits how many operations may be queued or running at once.
+    ///
+    /// Operations submitted while the limit is reached fail immediately with
+    /// an error of kind `WouldBlock` instead of waiting in the queue.
+    pub fn queue_depth(&mut self, depth: usize) -> &mut PoolBuilder {
+        self.queue_depth = Some(depth);
+        self
+    }
+
+    /// Sets the name given to the worker threads.
+    pub fn name<S>(&mut self, name: S) -> &mut PoolBuilder
+    where
+        S: Into<String>,
+    {
+        self.name = name.into();
+        self
+    }
+
+    /// Creates the pool and starts its threads.
+    pub fn build(&self) -> Pool {
+        let pool = threadpool::Builder::new()
+            .num_threads(self.threads)
+            .thread_name(self.name.clone())
+            .build();
+        Pool {
+            inner: Arc::new(Inner {
+                pool: Mutex::new(pool),
+                pending: AtomicUsize::new(0),
+                queue_depth: self.queue_depth,
+            }),
+        }
+    }
+}
+
+impl Default for PoolBuilder {
+    fn default() -> PoolBuilder {
+        PoolBuilder::new()
+    }
+}
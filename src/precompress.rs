@@ -0,0 +1,216 @@
+use futures::Future;
+
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::compact::{compress, with_extension};
+use crate::Codec;
+
+/// Extensions of compressed files, which are never compressed again.
+const COMPRESSED: &[&str] = &["gz", "br", "zst"];
+
+/// The file listing the variants written to a directory, one name a line.
+const MANIFEST: &str = ".precompressed";
+
+/// The outcome of precompressing a directory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrecompressReport {
+    /// The variants that were written, relative to the directory.
+    pub written: Vec<PathBuf>,
+    /// The number of variants that were already up to date.
+    pub fresh: usize,
+    /// The variants written earlier that were removed because their asset
+    /// is gone or below the minimum size, or their codec is no longer
+    /// used, relative to the directory.
+    pub removed: Vec<PathBuf>,
+}
+
+/// Options for keeping precompressed variants of static assets, such as
+/// `app.js.gz` and `app.js.br` next to `app.js`, up to date for
+/// [`serve_file`].
+///
+/// Every regular file below the directory is an asset, except files ending
+/// in `.gz`, `.br`, `.zst` or `.tmp`, and the `.precompressed` files listing
+/// the variants written to each directory. A variant gets the modification time of
+/// its asset, and is only written again once the asset's modification time
+/// changes. Variants are written to a temporary file first and renamed into
+/// place, so a variant being served is never partially written.
+///
+/// Variants of assets that no longer exist, or that have become smaller than
+/// the minimum size, are removed. Only variants listed in `.precompressed`
+/// are ever removed, so other compressed files, such as a `.tar.gz`
+/// download, are left alone.
+///
+/// [`serve_file`]: fn.serve_file.html
+#[derive(Clone, Debug)]
+pub struct PrecompressOptions {
+    codecs: Vec<Codec>,
+    min_size: u64,
+}
+
+impl PrecompressOptions {
+    /// Creates options that write a variant for each of `codecs`.
+    pub fn new(codecs: &[Codec]) -> PrecompressOptions {
+        PrecompressOptions {
+            codecs: codecs.to_vec(),
+            min_size: 0,
+        }
+    }
+
+    /// Sets the size in bytes below which assets are not worth
+    /// compressing.
+    pub fn min_size(&mut self, bytes: u64) -> &mut PrecompressOptions {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Brings the variants of the assets below `dir` up to date.
+    pub fn precompress<P>(&self, dir: P) -> impl Future<Item = PrecompressReport, Error = io::Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        let opts = self.clone();
+        crate::blocking(move || {
+            let mut report = PrecompressReport::default();
+            let mut pending = vec![PathBuf::new()];
+            while let Some(relative) = pending.pop() {
+                precompress_dir(&dir, relative, &opts, &mut pending, &mut report)?;
+            }
+            report.written.sort();
+            report.removed.sort();
+            Ok(report)
+        })
+    }
+}
+
+/// Brings the variants of the assets below `dir` up to date, writing one
+/// for each of `codecs`.
+///
+/// See [`PrecompressOptions`] for details and more control.
+///
+/// [`PrecompressOptions`]: struct.PrecompressOptions.html
+pub fn precompress<P>(
+    dir: P,
+    codecs: &[Codec],
+) -> impl Future<Item = PrecompressReport, Error = io::Error>
+where
+    P: Into<PathBuf>,
+{
+    PrecompressOptions::new(codecs).precompress(dir)
+}
+
+fn precompress_dir(
+    root: &Path,
+    relative: PathBuf,
+    opts: &PrecompressOptions,
+    pending: &mut Vec<PathBuf>,
+    report: &mut PrecompressReport,
+) -> io::Result<()> {
+    let dir = root.join(&relative);
+    let mut assets = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            pending.push(relative.join(entry.file_name()));
+            continue;
+        }
+        if !file_type.is_file() || entry.file_name() == MANIFEST {
+            continue;
+        }
+        let path = entry.path();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("tmp") => {}
+            Some(ext) if COMPRESSED.contains(&ext) => {}
+            _ => assets.push(path),
+        }
+    }
+    assets.sort();
+
+    let mut kept = Vec::new();
+    for asset in assets {
+        let metadata = match fs::metadata(&asset) {
+            Ok(metadata) => metadata,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        if metadata.len() < opts.min_size {
+            continue;
+        }
+        let modified = metadata.modified()?;
+        for &codec in &opts.codecs {
+            let variant = with_extension(&asset, codec.extension());
+            let fresh = match fs::metadata(&variant) {
+                Ok(variant) => variant.modified()? == modified,
+                Err(ref err) if err.kind() == ErrorKind::NotFound => false,
+                Err(err) => return Err(err),
+            };
+            if fresh {
+                report.fresh += 1;
+            } else {
+                let tmp = with_extension(&variant, "tmp");
+                compress(codec, std::slice::from_ref(&asset), &tmp)?;
+                OpenOptions::new()
+                    .write(true)
+                    .open(&tmp)?
+                    .set_modified(modified)?;
+                fs::rename(&tmp, &variant)?;
+                report.written.push(relative.join(file_name(&variant)));
+            }
+            // A name that isn't UTF-8 is left out of the manifest, so the
+            // variant is never removed.
+            if let Some(name) = variant.file_name().and_then(|name| name.to_str()) {
+                kept.push(name.to_owned());
+            }
+        }
+    }
+    kept.sort();
+
+    let manifest = dir.join(MANIFEST);
+    let listed = match fs::read_to_string(&manifest) {
+        Ok(listed) => listed,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let listed: Vec<_> = listed.lines().filter(|name| !name.is_empty()).collect();
+    for &name in &listed {
+        if kept.iter().any(|kept| kept == name) || !is_variant_name(name) {
+            continue;
+        }
+        match fs::remove_file(dir.join(name)) {
+            Ok(()) => report.removed.push(relative.join(name)),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+    }
+    if listed != kept {
+        if kept.is_empty() {
+            fs::remove_file(&manifest)?;
+        } else {
+            let tmp = with_extension(&manifest, "tmp");
+            let mut contents = kept.join("\n");
+            contents.push('\n');
+            fs::write(&tmp, contents)?;
+            fs::rename(&tmp, &manifest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether `name` is a plain file name with a codec extension, so a
+/// tampered manifest cannot remove anything else.
+fn is_variant_name(name: &str) -> bool {
+    let path = Path::new(name);
+    path.file_name() == Some(path.as_os_str())
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| COMPRESSED.contains(&ext))
+}
+
+fn file_name(path: &Path) -> OsString {
+    path.file_name().unwrap_or_default().to_owned()
+}
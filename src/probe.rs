@@ -0,0 +1,322 @@
+use futures::Future;
+
+use std::fs::File as StdFile;
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+/// A media format recognized by [`probe`].
+///
+/// [`probe`]: fn.probe.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MediaFormat {
+    /// A PNG image.
+    Png,
+    /// A JPEG image.
+    Jpeg,
+    /// A GIF image.
+    Gif,
+    /// A Windows bitmap.
+    Bmp,
+    /// A WebP image.
+    WebP,
+    /// A WAV audio file.
+    Wav,
+    /// An MP4 or QuickTime container.
+    Mp4,
+}
+
+/// Basic facts about a media file, read from its headers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MediaInfo {
+    /// The format of the file.
+    pub format: MediaFormat,
+    /// The width and height in pixels, for images.
+    pub dimensions: Option<(u32, u32)>,
+    /// The playback duration, for audio and video containers.
+    pub duration: Option<Duration>,
+}
+
+/// Reads the headers of the media file at `path` and reports its format and
+/// dimensions or duration.
+///
+/// Only the headers are read, never the encoded data, so this is cheap even
+/// for large files. Files in an unrecognized format, or with malformed
+/// headers, fail with an error of kind `InvalidData`.
+pub fn probe<P>(path: P) -> impl Future<Item = MediaInfo, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    crate::blocking(move || {
+        let mut file = BufReader::new(StdFile::open(path)?);
+        let mut magic = [0; 12];
+        let n = read_up_to(&mut file, &mut magic)?;
+        file.seek(SeekFrom::Start(0))?;
+        let magic = &magic[..n];
+
+        if magic.starts_with(b"\x89PNG\r\n\x1a\n") {
+            png(&mut file)
+        } else if magic.starts_with(b"\xff\xd8") {
+            jpeg(&mut file)
+        } else if magic.starts_with(b"GIF87a") || magic.starts_with(b"GIF89a") {
+            gif(&mut file)
+        } else if magic.starts_with(b"BM") {
+            bmp(&mut file)
+        } else if magic.len() == 12 && &magic[..4] == b"RIFF" && &magic[8..] == b"WEBP" {
+            webp(&mut file)
+        } else if magic.len() == 12 && &magic[..4] == b"RIFF" && &magic[8..] == b"WAVE" {
+            wav(&mut file)
+        } else if magic.len() >= 8 && &magic[4..8] == b"ftyp" {
+            mp4(&mut file)
+        } else {
+            Err(invalid("unrecognized media format"))
+        }
+    })
+}
+
+type Reader = BufReader<StdFile>;
+
+fn image(format: MediaFormat, width: u32, height: u32) -> io::Result<MediaInfo> {
+    Ok(MediaInfo {
+        format,
+        dimensions: Some((width, height)),
+        duration: None,
+    })
+}
+
+fn media(format: MediaFormat, duration: Duration) -> io::Result<MediaInfo> {
+    Ok(MediaInfo {
+        format,
+        dimensions: None,
+        duration: Some(duration),
+    })
+}
+
+fn png(file: &mut Reader) -> io::Result<MediaInfo> {
+    let header = read_array::<24>(file)?;
+    if &header[12..16] != b"IHDR" {
+        return Err(invalid("PNG does not start with an IHDR chunk"));
+    }
+    image(MediaFormat::Png, be32(&header[16..]), be32(&header[20..]))
+}
+
+fn gif(file: &mut Reader) -> io::Result<MediaInfo> {
+    let header = read_array::<10>(file)?;
+    image(
+        MediaFormat::Gif,
+        le16(&header[6..]) as u32,
+        le16(&header[8..]) as u32,
+    )
+}
+
+fn bmp(file: &mut Reader) -> io::Result<MediaInfo> {
+    let header = read_array::<26>(file)?;
+    // OS/2 bitmaps have a 12 byte header with 16-bit dimensions.
+    if le32(&header[14..]) == 12 {
+        return image(
+            MediaFormat::Bmp,
+            le16(&header[18..]) as u32,
+            le16(&header[20..]) as u32,
+        );
+    }
+    // The height is negative for images stored top-down.
+    let width = le32(&header[18..]) as i32;
+    let height = le32(&header[22..]) as i32;
+    image(
+        MediaFormat::Bmp,
+        width.unsigned_abs(),
+        height.unsigned_abs(),
+    )
+}
+
+fn webp(file: &mut Reader) -> io::Result<MediaInfo> {
+    let header = read_array::<30>(file)?;
+    let data = &header[20..];
+    match &header[12..16] {
+        b"VP8 " => image(
+            MediaFormat::WebP,
+            (le16(&data[6..]) & 0x3fff) as u32,
+            (le16(&data[8..]) & 0x3fff) as u32,
+        ),
+        b"VP8L" => {
+            let bits = le32(&data[1..]);
+            image(
+                MediaFormat::WebP,
+                (bits & 0x3fff) + 1,
+                ((bits >> 14) & 0x3fff) + 1,
+            )
+        }
+        b"VP8X" => image(
+            MediaFormat::WebP,
+            le24(&data[4..]) + 1,
+            le24(&data[7..]) + 1,
+        ),
+        _ => Err(invalid("unknown WebP chunk")),
+    }
+}
+
+fn jpeg(file: &mut Reader) -> io::Result<MediaInfo> {
+    file.seek_relative(2)?;
+    loop {
+        let mut marker = read_array::<1>(file)?[0];
+        if marker != 0xff {
+            return Err(invalid("malformed JPEG segment"));
+        }
+        // Any number of fill bytes may precede a marker.
+        while marker == 0xff {
+            marker = read_array::<1>(file)?[0];
+        }
+        match marker {
+            // Standalone markers carry no length.
+            0x01 | 0xd0..=0xd7 => continue,
+            0xd9 | 0xda => return Err(invalid("JPEG has no frame header")),
+            _ => {}
+        }
+        let len = be16(&read_array::<2>(file)?) as i64;
+        if len < 2 {
+            return Err(invalid("malformed JPEG segment"));
+        }
+        let is_frame = (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker);
+        if is_frame {
+            let frame = read_array::<5>(file)?;
+            return image(
+                MediaFormat::Jpeg,
+                be16(&frame[3..]) as u32,
+                be16(&frame[1..]) as u32,
+            );
+        }
+        file.seek_relative(len - 2)?;
+    }
+}
+
+fn wav(file: &mut Reader) -> io::Result<MediaInfo> {
+    file.seek_relative(12)?;
+    let mut byte_rate = None;
+    loop {
+        let chunk = read_array::<8>(file)?;
+        let size = le32(&chunk[4..]) as u64;
+        match &chunk[..4] {
+            b"fmt " => {
+                if size < 16 {
+                    return Err(invalid("malformed WAV format chunk"));
+                }
+                let fmt = read_array::<16>(file)?;
+                byte_rate = Some(le32(&fmt[8..]) as u64);
+                file.seek_relative((size - 16 + size % 2) as i64)?;
+            }
+            b"data" => {
+                return match byte_rate {
+                    Some(rate) if rate > 0 => media(MediaFormat::Wav, ticks(size, rate)),
+                    _ => Err(invalid("WAV data precedes its format chunk")),
+                };
+            }
+            // Chunks are padded to an even size. The size is 32-bit, so
+            // this fits in an i64.
+            _ => file.seek_relative((size + size % 2) as i64)?,
+        }
+    }
+}
+
+fn mp4(file: &mut Reader) -> io::Result<MediaInfo> {
+    let end = file.get_ref().metadata()?.len();
+    let moov = find_box(file, b"moov", end)?;
+    find_box(file, b"mvhd", moov)?;
+
+    let version = read_array::<4>(file)?[0];
+    let (timescale, duration) = if version == 1 {
+        let fields = read_array::<28>(file)?;
+        (be32(&fields[16..]), be64(&fields[20..]))
+    } else {
+        let fields = read_array::<16>(file)?;
+        (be32(&fields[8..]), be32(&fields[12..]) as u64)
+    };
+    if timescale == 0 {
+        return Err(invalid("MP4 movie header has no timescale"));
+    }
+    media(MediaFormat::Mp4, ticks(duration, timescale as u64))
+}
+
+/// Returns the duration of `count` ticks of a clock running at `per_second`
+/// ticks per second, which must not be zero.
+fn ticks(count: u64, per_second: u64) -> Duration {
+    // The remainder is below `per_second`, so its nanoseconds fit in a u64.
+    let nanos = (count % per_second) as u128 * 1_000_000_000 / per_second as u128;
+    Duration::from_secs(count / per_second) + Duration::from_nanos(nanos as u64)
+}
+
+/// Skips boxes until one of type `kind`, leaving the reader at the start of
+/// its contents and returning the offset of its end.
+fn find_box(file: &mut Reader, kind: &[u8; 4], end: u64) -> io::Result<u64> {
+    loop {
+        let start = file.stream_position()?;
+        if end.saturating_sub(start) < 8 {
+            return Err(invalid("MP4 has no movie header"));
+        }
+        let header = read_array::<8>(file)?;
+        let (size, header_len) = match be32(&header) {
+            0 => (end - start, 8),
+            1 => (be64(&read_array::<8>(file)?), 16),
+            size => (size as u64, 8),
+        };
+        let box_end = match start.checked_add(size) {
+            Some(box_end) if size >= header_len && box_end <= end => box_end,
+            _ => return Err(invalid("malformed MP4 box")),
+        };
+        if &header[4..] == kind {
+            return Ok(box_end);
+        }
+        file.seek(SeekFrom::Start(box_end))?;
+    }
+}
+
+fn read_up_to(file: &mut Reader, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match file.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(read) => n += read,
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(n)
+}
+
+fn read_array<const N: usize>(file: &mut Reader) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    file.read_exact(&mut buf).map_err(|err| match err.kind() {
+        ErrorKind::UnexpectedEof => invalid("truncated media header"),
+        _ => err,
+    })?;
+    Ok(buf)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn be16(b: &[u8]) -> u16 {
+    u16::from_be_bytes([b[0], b[1]])
+}
+
+fn be32(b: &[u8]) -> u32 {
+    u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+}
+
+fn be64(b: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&b[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+fn le16(b: &[u8]) -> u16 {
+    u16::from_le_bytes([b[0], b[1]])
+}
+
+fn le24(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], 0])
+}
+
+fn le32(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
@@ -0,0 +1,310 @@
+use actix_web::http::header;
+use actix_web::web::Payload;
+use actix_web::{error, Error, HttpRequest};
+use futures::future::{self, Either};
+use futures::{Future, Stream};
+use mime_guess::Mime;
+
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+use crate::hash::{Algorithm, Digest};
+use crate::{FsContext, LimitExceeded, LimitedWriter, OpenOptions};
+
+/// A file stored by [`receive_file`].
+///
+/// [`receive_file`]: fn.receive_file.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Received {
+    /// The path the file was stored at.
+    pub path: PathBuf,
+    /// The size of the file in bytes.
+    pub len: u64,
+    /// The content type the client declared, if any.
+    pub content_type: Option<Mime>,
+    /// The digest of the file, if one was requested.
+    #[cfg(any(feature = "sha2", feature = "blake3"))]
+    pub digest: Option<Digest>,
+}
+
+type Scan = dyn Fn(&Path) -> io::Result<()> + Send + Sync;
+
+/// Options for [`receive_file`].
+///
+/// [`receive_file`]: fn.receive_file.html
+#[derive(Clone)]
+pub struct ReceiveOptions {
+    max_size: Option<u64>,
+    content_types: Vec<Mime>,
+    #[cfg(any(feature = "sha2", feature = "blake3"))]
+    algorithm: Option<Algorithm>,
+    scan: Option<Arc<Scan>>,
+}
+
+impl ReceiveOptions {
+    /// Creates options that accept bodies of any size and content type.
+    pub fn new() -> ReceiveOptions {
+        ReceiveOptions {
+            max_size: None,
+            content_types: Vec::new(),
+            #[cfg(any(feature = "sha2", feature = "blake3"))]
+            algorithm: None,
+            scan: None,
+        }
+    }
+
+    /// Sets the largest body accepted, in bytes.
+    pub fn max_size(&mut self, bytes: u64) -> &mut ReceiveOptions {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Adds a content type to accept, such as `image/png` or `image/*`.
+    ///
+    /// Once any is added, requests with other content types, or without
+    /// one, are rejected.
+    pub fn allow_content_type(&mut self, content_type: Mime) -> &mut ReceiveOptions {
+        self.content_types.push(content_type);
+        self
+    }
+
+    /// Sets the algorithm of the digest computed for the stored file.
+    ///
+    /// This is only available with the `sha2` or `blake3` feature.
+    #[cfg(any(feature = "sha2", feature = "blake3"))]
+    pub fn hash(&mut self, algorithm: Algorithm) -> &mut ReceiveOptions {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Sets a function that checks the received file, such as a virus
+    /// scanner, before it is moved into place.
+    ///
+    /// It is called on the threadpool with the path of the temporary file.
+    /// Failing with an error of kind `InvalidData` rejects the file with
+    /// `422 Unprocessable Entity`. Other errors are passed on.
+    pub fn scan<F>(&mut self, f: F) -> &mut ReceiveOptions
+    where
+        F: Fn(&Path) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.scan = Some(Arc::new(f));
+        self
+    }
+
+    fn accepts(&self, content_type: Option<&Mime>) -> bool {
+        if self.content_types.is_empty() {
+            return true;
+        }
+        let content_type = match content_type {
+            Some(content_type) => content_type,
+            None => return false,
+        };
+        self.content_types.iter().any(|allowed| {
+            allowed.type_() == content_type.type_()
+                && (allowed.subtype() == "*" || allowed.subtype() == content_type.subtype())
+        })
+    }
+}
+
+impl Default for ReceiveOptions {
+    fn default() -> ReceiveOptions {
+        ReceiveOptions::new()
+    }
+}
+
+impl fmt::Debug for ReceiveOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = f.debug_struct("ReceiveOptions");
+        s.field("max_size", &self.max_size)
+            .field("content_types", &self.content_types);
+        #[cfg(any(feature = "sha2", feature = "blake3"))]
+        s.field("algorithm", &self.algorithm);
+        s.field("scan", &self.scan.is_some()).finish()
+    }
+}
+
+/// Stores the body of `req` at `dest`.
+///
+/// This is the counterpart of [`serve_file`] for uploads. The request is
+/// rejected with
+///
+/// * `415 Unsupported Media Type` if its content type is not allowed,
+/// * `413 Payload Too Large` once the body exceeds the maximum size,
+/// * `422 Unprocessable Entity` if the scan function rejects the file.
+///
+/// The body is written to a temporary file next to `dest`, synced, hashed
+/// and scanned, and only then renamed to `dest`, replacing any file there.
+/// If anything fails, the temporary file is removed and `dest` is left as
+/// it was.
+///
+/// If the request has an [`FsContext`], the upload is bound by its deadline,
+/// the maximum size is capped by its remaining budget, and the size of the
+/// stored file is charged to its account. Right before the rename, the
+/// deadline is checked again and the size is taken from the budget, so an
+/// upload that ran out of either never replaces `dest`.
+///
+/// This is only available with the `actix-web` feature.
+///
+/// [`serve_file`]: fn.serve_file.html
+/// [`FsContext`]: struct.FsContext.html
+pub fn receive_file<P>(
+    req: &HttpRequest,
+    payload: Payload,
+    dest: P,
+    opts: &ReceiveOptions,
+) -> impl Future<Item = Received, Error = Error>
+where
+    P: AsRef<Path>,
+{
+    let dest = dest.as_ref().to_owned();
+    let ctx = req.extensions().get::<FsContext>().cloned();
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Mime>().ok());
+    if !opts.accepts(content_type.as_ref()) {
+        return Either::A(future::err(error::ErrorUnsupportedMediaType(
+            "content type not allowed",
+        )));
+    }
+
+    let remaining = ctx.as_ref().and_then(FsContext::remaining);
+    let limit = match (opts.max_size, remaining) {
+        (Some(max), Some(remaining)) => Some(max.min(remaining)),
+        (max, remaining) => max.or(remaining),
+    };
+    let declared = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if let (Some(limit), Some(declared)) = (limit, declared) {
+        if declared > limit {
+            return Either::A(future::err(too_large(limit)));
+        }
+    }
+
+    let tmp = tmp_path(&dest);
+    let body = payload
+        .map(|chunk| chunk.to_vec())
+        .map_err(|err| io::Error::other(err.to_string()));
+    let opts = opts.clone();
+    let cleanup = tmp.clone();
+    let mut open = OpenOptions::new();
+    open.read(true).write(true).create(true).truncate(true);
+    if let Some(account) = ctx.as_ref().and_then(FsContext::account) {
+        open.account(account);
+    }
+    let context = ctx.clone();
+    let received = open
+        .open(tmp.clone())
+        .and_then(move |file| LimitedWriter::new(limit.unwrap_or(u64::MAX)).write(file, body))
+        .and_then(move |(file, len)| {
+            file.blocking("store", move |std| {
+                let received = Received {
+                    path: dest,
+                    len,
+                    content_type,
+                    #[cfg(any(feature = "sha2", feature = "blake3"))]
+                    digest: None,
+                };
+                let stored = store(std, &tmp, received, &opts, context.as_ref());
+                if stored.is_err() {
+                    let _ = fs::remove_file(&tmp);
+                }
+                stored
+            })
+        })
+        .map(|(_, received)| received);
+    // The deadline bounds the whole upload, and the temporary file is
+    // removed whether it passes or anything else fails.
+    let received = match ctx {
+        Some(ref ctx) => Either::A(ctx.with_deadline(received)),
+        None => Either::B(received),
+    };
+    Either::B(
+        received
+            .or_else(move |err| {
+                crate::blocking(move || {
+                    let _ = fs::remove_file(&cleanup);
+                    Ok(())
+                })
+                .then(move |_| Err(err))
+            })
+            .map_err(|err| match LimitExceeded::from_io(&err) {
+                Some(exceeded) => too_large(exceeded.limit()),
+                None if err.kind() == ErrorKind::InvalidData => {
+                    error::ErrorUnprocessableEntity(err)
+                }
+                None => Error::from(err),
+            }),
+    )
+}
+
+/// Syncs, hashes and scans the temporary file of an upload, then renames it
+/// to the path of `received`.
+///
+/// The deadline is checked and the size is charged to the budget of `ctx`
+/// right before the rename, as the upload may have timed out while this
+/// ran, and nothing may replace the destination after that.
+fn store(
+    std: &mut fs::File,
+    tmp: &Path,
+    received: Received,
+    opts: &ReceiveOptions,
+    ctx: Option<&FsContext>,
+) -> io::Result<Received> {
+    std.sync_all()?;
+    #[cfg(any(feature = "sha2", feature = "blake3"))]
+    let received = {
+        let mut received = received;
+        if let Some(algorithm) = opts.algorithm {
+            use std::io::{Seek, SeekFrom};
+
+            std.seek(SeekFrom::Start(0))?;
+            let token = crate::CancellationToken::new();
+            received.digest = Some(crate::hash::digest_file(std, algorithm, &token)?);
+        }
+        received
+    };
+    if let Some(ref scan) = opts.scan {
+        scan(tmp)?;
+    }
+    if let Some(ctx) = ctx {
+        ctx.check_deadline()?;
+        ctx.charge(received.len)?;
+    }
+    if let Err(err) = fs::rename(tmp, &received.path) {
+        if let Some(ctx) = ctx {
+            ctx.refund(received.len);
+        }
+        return Err(err);
+    }
+    Ok(received)
+}
+
+fn too_large(limit: u64) -> Error {
+    error::ErrorPayloadTooLarge(format!("body exceeds the limit of {} bytes", limit))
+}
+
+/// Returns a path for the temporary file of an upload to `dest`, unique
+/// within the process.
+fn tmp_path(dest: &Path) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let mut name = OsString::from(dest.file_name().unwrap_or_default());
+    name.push(format!(
+        ".{}-{}.tmp",
+        process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    dest.with_file_name(name)
+}
@@ -0,0 +1,342 @@
+use futures::{future, Future};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::{File, Root};
+
+/// What a [`Tenant`] is allowed to do with its files.
+///
+/// [`Tenant`]: struct.Tenant.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Policy {
+    /// Files may be read, written and removed.
+    ReadWrite,
+    /// Files may be read only.
+    ReadOnly,
+    /// Files may be created and read, but never overwritten or removed, like
+    /// with [`WriteOnce`].
+    ///
+    /// [`WriteOnce`]: struct.WriteOnce.html
+    WriteOnce,
+}
+
+/// Options for a tenant added to a [`RootRegistry`].
+///
+/// [`RootRegistry`]: struct.RootRegistry.html
+#[derive(Clone, Debug)]
+pub struct TenantOptions {
+    quota: Option<u64>,
+    policy: Policy,
+    labels: Vec<(String, String)>,
+}
+
+impl TenantOptions {
+    /// Creates options for a read-write tenant without a quota or labels.
+    pub fn new() -> TenantOptions {
+        TenantOptions {
+            quota: None,
+            policy: Policy::ReadWrite,
+            labels: Vec::new(),
+        }
+    }
+
+    /// Sets how many bytes the files written through the tenant may take up
+    /// in total.
+    pub fn quota(&mut self, bytes: u64) -> &mut TenantOptions {
+        self.quota = Some(bytes);
+        self
+    }
+
+    /// Sets what the tenant is allowed to do with its files.
+    pub fn policy(&mut self, policy: Policy) -> &mut TenantOptions {
+        self.policy = policy;
+        self
+    }
+
+    /// Adds a label, such as a plan or region, describing the tenant.
+    ///
+    /// The crate does not attach labels to anything by itself, since
+    /// [`Metrics`] only see paths. Read them back with [`Tenant::labels`] to
+    /// tag your own metrics, or pass them to
+    /// [`FsContextOptions::label`] to account for the tenant's requests.
+    ///
+    /// [`Metrics`]: trait.Metrics.html
+    /// [`Tenant::labels`]: struct.Tenant.html#method.labels
+    /// [`FsContextOptions::label`]: struct.FsContextOptions.html#method.label
+    pub fn label<K, V>(&mut self, key: K, value: V) -> &mut TenantOptions
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.labels.push((key.into(), value.into()));
+        self
+    }
+}
+
+impl Default for TenantOptions {
+    fn default() -> TenantOptions {
+        TenantOptions::new()
+    }
+}
+
+/// Maps tenant or bucket identifiers to sandboxed [`Root`]s.
+///
+/// Each tenant gets its own root directory, a [`Policy`], an optional quota
+/// and a set of labels, so a multi-tenant storage service can
+/// resolve a request to a [`Tenant`] once and rely on it for isolation and
+/// accounting.
+///
+/// Cloning a `RootRegistry` produces another handle to the same tenants.
+///
+/// [`Root`]: struct.Root.html
+/// [`Policy`]: enum.Policy.html
+/// [`Tenant`]: struct.Tenant.html
+#[derive(Clone, Debug, Default)]
+pub struct RootRegistry {
+    tenants: Arc<Mutex<HashMap<String, Tenant>>>,
+}
+
+impl RootRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> RootRegistry {
+        RootRegistry::default()
+    }
+
+    /// Adds a tenant whose files live in the directory at `path`, replacing
+    /// any tenant with the same `id`.
+    ///
+    /// The tenant starts out with no usage recorded. Call
+    /// [`Tenant::refresh_usage`] to account for files that already exist.
+    ///
+    /// [`Tenant::refresh_usage`]: struct.Tenant.html#method.refresh_usage
+    pub fn insert<I, P>(&self, id: I, path: P, opts: &TenantOptions) -> Tenant
+    where
+        I: Into<String>,
+        P: Into<PathBuf>,
+    {
+        let id = id.into();
+        let tenant = Tenant {
+            inner: Arc::new(TenantInner {
+                id: id.clone(),
+                root: Root::new(path),
+                opts: opts.clone(),
+                used: Mutex::new(0),
+            }),
+        };
+        self.tenants.lock().unwrap().insert(id, tenant.clone());
+        tenant
+    }
+
+    /// Returns the tenant with the given `id`.
+    pub fn get(&self, id: &str) -> Option<Tenant> {
+        self.tenants.lock().unwrap().get(id).cloned()
+    }
+
+    /// Removes the tenant with the given `id` from the registry, leaving its
+    /// files in place.
+    pub fn remove(&self, id: &str) -> Option<Tenant> {
+        self.tenants.lock().unwrap().remove(id)
+    }
+
+    /// Returns the identifiers of all tenants, sorted.
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<_> = self.tenants.lock().unwrap().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+}
+
+/// A tenant of a [`RootRegistry`].
+///
+/// All paths are resolved relative to the tenant's root like with
+/// [`Root::resolve`], every operation is checked against the tenant's
+/// [`Policy`], and writes are counted towards its quota.
+///
+/// Writes and removals through the same tenant run one at a time, so
+/// concurrent writes cannot together exceed the quota.
+///
+/// Cloning a `Tenant` is cheap, and clones share the recorded usage.
+///
+/// [`RootRegistry`]: struct.RootRegistry.html
+/// [`Root::resolve`]: struct.Root.html#method.resolve
+/// [`Policy`]: enum.Policy.html
+#[derive(Clone, Debug)]
+pub struct Tenant {
+    inner: Arc<TenantInner>,
+}
+
+#[derive(Debug)]
+struct TenantInner {
+    id: String,
+    root: Root,
+    opts: TenantOptions,
+    used: Mutex<u64>,
+}
+
+impl Tenant {
+    /// Returns the identifier of the tenant.
+    pub fn id(&self) -> &str {
+        &self.inner.id
+    }
+
+    /// Returns the root the tenant's files live in.
+    pub fn root(&self) -> &Root {
+        &self.inner.root
+    }
+
+    /// Returns the policy of the tenant.
+    pub fn policy(&self) -> Policy {
+        self.inner.opts.policy
+    }
+
+    /// Returns the quota of the tenant in bytes, if it has one.
+    pub fn quota(&self) -> Option<u64> {
+        self.inner.opts.quota
+    }
+
+    /// Returns the labels of the tenant.
+    pub fn labels(&self) -> &[(String, String)] {
+        &self.inner.opts.labels
+    }
+
+    /// Returns how many bytes the tenant's files are recorded to take up.
+    pub fn used(&self) -> u64 {
+        *self.inner.used.lock().unwrap()
+    }
+
+    /// Opens the file at `path` in read-only mode.
+    pub fn open<P>(&self, path: P) -> impl Future<Item = File, Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        self.inner.root.open(path)
+    }
+
+    /// Writes `contents` to the file at `path`, creating it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `PermissionDenied` if the policy forbids the write, with
+    /// `AlreadyExists` if the policy is [`WriteOnce`] and the file exists,
+    /// and with `StorageFull` if the write would exceed the quota.
+    ///
+    /// [`WriteOnce`]: enum.Policy.html#variant.WriteOnce
+    pub fn write<P>(&self, path: P, contents: Vec<u8>) -> impl Future<Item = (), Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let inner = self.inner.clone();
+        let policy = inner.opts.policy;
+        let path = match policy {
+            Policy::ReadOnly => Err(denied(&inner.id, "write")),
+            _ => inner.root.resolve(path),
+        };
+        future::result(path).and_then(move |path| {
+            crate::blocking(move || {
+                // The lock is held until the file is written, so no other
+                // write can pass the quota check on the same usage, or
+                // replace the file after its size was read.
+                let mut used = inner.used.lock().unwrap();
+                let existing = match fs::metadata(&path) {
+                    Ok(metadata) => metadata.len(),
+                    Err(ref err) if err.kind() == ErrorKind::NotFound => 0,
+                    Err(err) => return Err(err),
+                };
+                let taken = contents.len() as u64;
+                let new = used.saturating_sub(existing) + taken;
+                if taken > existing && inner.opts.quota.is_some_and(|quota| new > quota) {
+                    return Err(io::Error::new(
+                        ErrorKind::StorageFull,
+                        format!("quota of tenant {} exceeded", inner.id),
+                    ));
+                }
+                write(&path, &contents, policy)?;
+                *used = new;
+                Ok(())
+            })
+        })
+    }
+
+    /// Removes the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `PermissionDenied` unless the policy is [`ReadWrite`].
+    ///
+    /// [`ReadWrite`]: enum.Policy.html#variant.ReadWrite
+    pub fn remove_file<P>(&self, path: P) -> impl Future<Item = (), Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let inner = self.inner.clone();
+        let path = match inner.opts.policy {
+            Policy::ReadWrite => inner.root.resolve(path),
+            _ => Err(denied(&inner.id, "remove")),
+        };
+        future::result(path).and_then(move |path| {
+            crate::blocking(move || {
+                let mut used = inner.used.lock().unwrap();
+                let len = fs::metadata(&path)?.len();
+                fs::remove_file(&path)?;
+                *used = used.saturating_sub(len);
+                Ok(())
+            })
+        })
+    }
+
+    /// Recomputes the recorded usage from the files below the tenant's root,
+    /// resolving to the new usage in bytes.
+    pub fn refresh_usage(&self) -> impl Future<Item = u64, Error = io::Error> {
+        let inner = self.inner.clone();
+        crate::blocking(move || {
+            let mut recorded = inner.used.lock().unwrap();
+            let mut used = 0;
+            let mut pending = vec![inner.root.path().to_owned()];
+            while let Some(dir) = pending.pop() {
+                let entries = match fs::read_dir(&dir) {
+                    Ok(entries) => entries,
+                    Err(ref err) if err.kind() == ErrorKind::NotFound => continue,
+                    Err(err) => return Err(err),
+                };
+                for entry in entries {
+                    let entry = entry?;
+                    let metadata = entry.metadata()?;
+                    if metadata.is_dir() {
+                        pending.push(entry.path());
+                    } else {
+                        used += metadata.len();
+                    }
+                }
+            }
+            *recorded = used;
+            Ok(used)
+        })
+    }
+}
+
+fn write(path: &Path, contents: &[u8], policy: Policy) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut opts = fs::OpenOptions::new();
+    opts.write(true);
+    if policy == Policy::WriteOnce {
+        opts.create_new(true);
+    } else {
+        opts.create(true).truncate(true);
+    }
+    let mut file = opts.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+fn denied(id: &str, op: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::PermissionDenied,
+        format!("tenant {} may not {} files", id, op),
+    )
+}
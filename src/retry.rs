@@ -0,0 +1,152 @@
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use tokio_timer::Delay;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error;
+
+/// Options for renaming and removing files that another process may briefly
+/// hold open.
+///
+/// On Windows, and on SMB shares in particular, antivirus scanners, search
+/// indexers and backup agents open files all the time, which makes renames
+/// and removals fail with `ERROR_SHARING_VIOLATION`, `ERROR_LOCK_VIOLATION`
+/// or `ERROR_ACCESS_DENIED` for a moment. Operations run through these
+/// options are retried with a growing delay until they succeed, fail with a
+/// different error, or the timeout runs out, much like robocopy does.
+///
+/// On other platforms these errors do not occur transiently, so operations
+/// are attempted only once.
+#[derive(Clone, Debug)]
+pub struct RetryOptions {
+    timeout: Duration,
+    delay: Duration,
+    max_delay: Duration,
+}
+
+impl RetryOptions {
+    /// Creates options that keep retrying for up to ten seconds, starting
+    /// with a delay of 50 milliseconds that doubles up to one second.
+    pub fn new() -> RetryOptions {
+        RetryOptions {
+            timeout: Duration::from_secs(10),
+            delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Sets how long after the first attempt no further attempts are made.
+    ///
+    /// A timeout of `Duration::MAX` keeps retrying for as long as the error
+    /// persists.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut RetryOptions {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how long to wait before the first retry. The delay doubles with
+    /// every further retry, up to the [`max_delay`].
+    ///
+    /// [`max_delay`]: #method.max_delay
+    pub fn delay(&mut self, delay: Duration) -> &mut RetryOptions {
+        self.delay = delay;
+        self
+    }
+
+    /// Sets the longest delay between two attempts.
+    pub fn max_delay(&mut self, max_delay: Duration) -> &mut RetryOptions {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Renames a file or directory, replacing `to` if it exists.
+    ///
+    /// See [`rename`](fn.rename.html).
+    pub fn rename<P, Q>(&self, from: P, to: Q) -> impl Future<Item = (), Error = io::Error>
+    where
+        P: AsRef<Path> + Send + Sync + 'static,
+        Q: AsRef<Path> + Send + Sync + 'static,
+    {
+        self.run(move || {
+            let (from, to) = (from.as_ref(), to.as_ref());
+            fs::rename(from, to).map_err(|err| error::with_paths(err, "rename", from, to))
+        })
+    }
+
+    /// Removes a file.
+    ///
+    /// See [`remove_file`](fn.remove_file.html).
+    pub fn remove_file<P>(&self, path: P) -> impl Future<Item = (), Error = io::Error>
+    where
+        P: AsRef<Path> + Send + Sync + 'static,
+    {
+        self.run(move || {
+            let path = path.as_ref();
+            fs::remove_file(path).map_err(|err| error::with_path(err, "remove", path))
+        })
+    }
+
+    fn run<F>(&self, op: F) -> impl Future<Item = (), Error = io::Error>
+    where
+        F: Fn() -> io::Result<()> + Send + Sync + 'static,
+    {
+        let op = Arc::new(op);
+        // A timeout too long to represent never runs out.
+        let deadline = Instant::now().checked_add(self.timeout);
+        let max_delay = self.max_delay;
+
+        future::loop_fn(self.delay.min(max_delay), move |delay| {
+            let op = op.clone();
+            crate::blocking(move || op()).then(move |res| {
+                let retry_at = Instant::now()
+                    .checked_add(delay)
+                    .filter(|&at| deadline.is_none_or(|deadline| at <= deadline));
+                match (res, retry_at) {
+                    (Err(ref err), Some(at)) if is_transient(err) => {
+                        let next = delay.checked_mul(2).unwrap_or(max_delay).min(max_delay);
+                        Either::A(
+                            Delay::new(at)
+                                .map_err(crate::blocking_err)
+                                .map(move |_| Loop::Continue(next)),
+                        )
+                    }
+                    (res, _) => Either::B(future::result(res.map(Loop::Break))),
+                }
+            })
+        })
+    }
+}
+
+impl Default for RetryOptions {
+    fn default() -> RetryOptions {
+        RetryOptions::new()
+    }
+}
+
+/// Returns whether `err` is caused by another process holding the file open.
+#[cfg(windows)]
+fn is_transient(err: &io::Error) -> bool {
+    use windows_sys::Win32::Foundation::{
+        ERROR_ACCESS_DENIED, ERROR_LOCK_VIOLATION, ERROR_SHARING_VIOLATION,
+    };
+
+    match error::raw_os_error(err) {
+        Some(code) => [
+            ERROR_ACCESS_DENIED,
+            ERROR_LOCK_VIOLATION,
+            ERROR_SHARING_VIOLATION,
+        ]
+        .contains(&(code as u32)),
+        None => false,
+    }
+}
+
+#[cfg(not(windows))]
+fn is_transient(_: &io::Error) -> bool {
+    false
+}
@@ -0,0 +1,260 @@
+#[cfg(feature = "runtime")]
+use futures::{future, Future};
+
+#[cfg(feature = "runtime")]
+use std::fs;
+#[cfg(feature = "runtime")]
+use std::io::Write;
+use std::io::{self, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(feature = "runtime")]
+use crate::{File, OpenOptions};
+
+/// A directory that confines every operation to the tree below it.
+///
+/// Paths given to a `Root` are relative to its directory. Absolute paths and
+/// paths that would climb above the root with `..` are rejected with an error
+/// of kind `PermissionDenied`, which makes a `Root` suitable for resolving
+/// names that come from untrusted input.
+///
+/// Resolution is lexical: symbolic links inside the root are followed by the
+/// operating system as usual.
+///
+/// Without the default `runtime` feature, only resolving paths is
+/// available, so libraries can validate paths without the threadpool.
+///
+/// Cloning a `Root` is cheap.
+#[derive(Clone, Debug)]
+pub struct Root {
+    path: Arc<PathBuf>,
+}
+
+impl Root {
+    /// Creates a root for the directory at `path`.
+    ///
+    /// The directory is not required to exist until it is used.
+    pub fn new<P>(path: P) -> Root
+    where
+        P: Into<PathBuf>,
+    {
+        Root {
+            path: Arc::new(path.into()),
+        }
+    }
+
+    /// Returns the directory this root confines operations to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Resolves `path` relative to the root.
+    ///
+    /// `.` components are dropped and `..` components remove the preceding
+    /// component, as long as that never leaves the root.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `PermissionDenied` if `path` is absolute or escapes the
+    /// root.
+    pub fn resolve<P>(&self, path: P) -> io::Result<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        let mut resolved = PathBuf::new();
+        for component in path.as_ref().components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !resolved.pop() {
+                        return Err(escapes(path.as_ref()));
+                    }
+                }
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(escapes(path.as_ref()));
+                }
+            }
+        }
+        Ok(self.path.join(resolved))
+    }
+
+    /// Opens the file at `path` in read-only mode.
+    #[cfg(feature = "runtime")]
+    pub fn open<P>(&self, path: P) -> impl Future<Item = File, Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut opts = OpenOptions::new();
+        opts.read(true);
+        self.open_with(path, &opts)
+    }
+
+    /// Opens the file at `path` in write-only mode, creating it if it does not
+    /// exist and truncating it if it does.
+    #[cfg(feature = "runtime")]
+    pub fn create<P>(&self, path: P) -> impl Future<Item = File, Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut opts = OpenOptions::new();
+        opts.write(true).create(true).truncate(true);
+        self.open_with(path, &opts)
+    }
+
+    /// Opens the file at `path` with the given options.
+    #[cfg(feature = "runtime")]
+    pub fn open_with<P>(
+        &self,
+        path: P,
+        opts: &OpenOptions,
+    ) -> impl Future<Item = File, Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let opts = opts.clone();
+        future::result(self.resolve(path)).and_then(move |path| opts.open(path))
+    }
+
+    /// Recursively creates the directory at `path` and any missing parents.
+    #[cfg(feature = "runtime")]
+    pub fn create_dir_all<P>(&self, path: P) -> impl Future<Item = (), Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        future::result(self.resolve(path)).and_then(crate::create_dir_all)
+    }
+
+    /// Removes the file at `path`.
+    #[cfg(feature = "runtime")]
+    pub fn remove_file<P>(&self, path: P) -> impl Future<Item = (), Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        future::result(self.resolve(path)).and_then(crate::remove_file)
+    }
+
+    /// Renames the file or directory at `from` to `to`, replacing `to` if it
+    /// already exists.
+    #[cfg(feature = "runtime")]
+    pub fn rename<P, Q>(&self, from: P, to: Q) -> impl Future<Item = (), Error = io::Error>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let paths = self.resolve(from).and_then(|from| Ok((from, self.resolve(to)?)));
+        future::result(paths).and_then(|(from, to)| crate::rename(from, to))
+    }
+
+    /// Restricts this root to the [`WriteOnce`] policy.
+    ///
+    /// [`WriteOnce`]: struct.WriteOnce.html
+    pub fn write_once(self) -> WriteOnce {
+        WriteOnce { root: self }
+    }
+}
+
+/// A [`Root`] where files may be created but never overwritten, removed or
+/// renamed.
+///
+/// `WriteOnce` has no operation that could change or discard existing data:
+/// files are always created with `create_new`, so creating a name that is
+/// already taken fails with `AlreadyExists`, and there is no way to remove or
+/// rename entries. This gives append-only storage, such as audit records, its
+/// guarantee at the type level.
+///
+/// [`Root`]: struct.Root.html
+#[derive(Clone, Debug)]
+pub struct WriteOnce {
+    root: Root,
+}
+
+impl WriteOnce {
+    /// Creates a write-once root for the directory at `path`.
+    pub fn new<P>(path: P) -> WriteOnce
+    where
+        P: Into<PathBuf>,
+    {
+        Root::new(path).write_once()
+    }
+
+    /// Returns the directory this root confines operations to.
+    pub fn path(&self) -> &Path {
+        self.root.path()
+    }
+
+    /// Resolves `path` relative to the root.
+    ///
+    /// See [`Root::resolve`] for details.
+    ///
+    /// [`Root::resolve`]: struct.Root.html#method.resolve
+    pub fn resolve<P>(&self, path: P) -> io::Result<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        self.root.resolve(path)
+    }
+
+    /// Opens the file at `path` in read-only mode.
+    #[cfg(feature = "runtime")]
+    pub fn open<P>(&self, path: P) -> impl Future<Item = File, Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        self.root.open(path)
+    }
+
+    /// Creates a new file at `path` in write-only mode.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `AlreadyExists` if there is already an entry at `path`.
+    #[cfg(feature = "runtime")]
+    pub fn create<P>(&self, path: P) -> impl Future<Item = File, Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut opts = OpenOptions::new();
+        opts.write(true).create_new(true);
+        self.root.open_with(path, &opts)
+    }
+
+    /// Creates a new file at `path` holding `contents`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `AlreadyExists` if there is already an entry at `path`.
+    #[cfg(feature = "runtime")]
+    pub fn write<P>(&self, path: P, contents: Vec<u8>) -> impl Future<Item = (), Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        future::result(self.resolve(path)).and_then(move |path| {
+            crate::blocking(move || {
+                let mut file = fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(path)?;
+                file.write_all(&contents)?;
+                file.sync_all()
+            })
+        })
+    }
+
+    /// Recursively creates the directory at `path` and any missing parents.
+    #[cfg(feature = "runtime")]
+    pub fn create_dir_all<P>(&self, path: P) -> impl Future<Item = (), Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        self.root.create_dir_all(path)
+    }
+}
+
+fn escapes(path: &Path) -> io::Error {
+    io::Error::new(
+        ErrorKind::PermissionDenied,
+        format!("{} escapes the root directory", path.display()),
+    )
+}
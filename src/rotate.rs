@@ -0,0 +1,275 @@
+use futures::Future;
+
+use std::ffi::OsString;
+use std::fs::{self, File as StdFile, OpenOptions as StdOpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::partition::{is_timestamp, timestamp};
+use crate::Granularity;
+
+/// Options which can be used to configure a [`RotatingFile`].
+///
+/// [`RotatingFile`]: struct.RotatingFile.html
+#[derive(Clone, Debug)]
+pub struct RotateOptions {
+    max_size: Option<u64>,
+    every: Option<Granularity>,
+    keep: Option<usize>,
+}
+
+impl RotateOptions {
+    /// Creates the default set of options: rotate daily, without a size
+    /// limit, and keep every rotated file.
+    pub fn new() -> RotateOptions {
+        RotateOptions {
+            max_size: None,
+            every: Some(Granularity::Day),
+            keep: None,
+        }
+    }
+
+    /// Sets the size in bytes a file may grow to before it is rotated.
+    ///
+    /// A write that would take the file past this size goes to a new file
+    /// instead, unless the file is empty, so writes are never split.
+    pub fn max_size(&mut self, bytes: u64) -> &mut RotateOptions {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Sets the time boundaries, in UTC, at which the file is rotated, or
+    /// `None` to only rotate by size.
+    pub fn every(&mut self, every: Option<Granularity>) -> &mut RotateOptions {
+        self.every = every;
+        self
+    }
+
+    /// Sets how many rotated files are kept, removing the oldest ones
+    /// beyond that.
+    pub fn keep(&mut self, keep: usize) -> &mut RotateOptions {
+        self.keep = Some(keep);
+        self
+    }
+
+    /// Creates a writer appending to the file at `path` with the options
+    /// specified by `self`.
+    pub fn open<P>(&self, path: P) -> RotatingFile
+    where
+        P: Into<PathBuf>,
+    {
+        RotatingFile {
+            inner: Arc::new(Inner {
+                path: path.into(),
+                opts: self.clone(),
+                current: Mutex::new(None),
+            }),
+        }
+    }
+}
+
+impl Default for RotateOptions {
+    fn default() -> RotateOptions {
+        RotateOptions::new()
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    path: PathBuf,
+    opts: RotateOptions,
+    current: Mutex<Option<Current>>,
+}
+
+#[derive(Debug)]
+struct Current {
+    file: StdFile,
+    len: u64,
+    opened: SystemTime,
+}
+
+/// Appends to a log file, rotating it by size or at time boundaries.
+///
+/// To rotate, the file is synced, renamed by appending the UTC time of the
+/// rotation to its name, such as `app.log.20190307T134500`, and a new file
+/// is created in its place. Rotated files therefore sort by age.
+///
+/// The file is opened in append mode when first written to, so a writer
+/// restarted before the file is due continues it. A file last modified
+/// before the current time boundary is rotated on the first write.
+///
+/// Cloning a `RotatingFile` produces another handle to the same writer;
+/// writes from all handles are serialized.
+#[derive(Clone, Debug)]
+pub struct RotatingFile {
+    inner: Arc<Inner>,
+}
+
+impl RotatingFile {
+    /// Creates a writer appending to the file at `path`, rotating it daily.
+    ///
+    /// See [`RotateOptions`] for more control over rotation.
+    ///
+    /// [`RotateOptions`]: struct.RotateOptions.html
+    pub fn new<P>(path: P) -> RotatingFile
+    where
+        P: Into<PathBuf>,
+    {
+        RotateOptions::new().open(path)
+    }
+
+    /// Returns the path of the file being written.
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    /// Appends `data` to the file, rotating it first if it is due.
+    pub fn write<B>(&self, data: B) -> impl Future<Item = (), Error = io::Error>
+    where
+        B: AsRef<[u8]> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        crate::blocking(move || {
+            let data = data.as_ref();
+            let now = SystemTime::now();
+            let mut current = inner.current.lock().unwrap();
+            if current.is_none() {
+                *current = Some(inner.open()?);
+            }
+            if inner.due(current.as_ref().unwrap(), now, data.len() as u64) {
+                current.take().unwrap().file.sync_all()?;
+                inner.rotate(now)?;
+                *current = Some(inner.open()?);
+            }
+
+            let current = current.as_mut().unwrap();
+            current.file.write_all(data)?;
+            current.len += data.len() as u64;
+            Ok(())
+        })
+    }
+
+    /// Flushes the current file to disk.
+    pub fn flush(&self) -> impl Future<Item = (), Error = io::Error> {
+        let inner = self.inner.clone();
+        crate::blocking(move || match *inner.current.lock().unwrap() {
+            Some(ref current) => current.file.sync_all(),
+            None => Ok(()),
+        })
+    }
+}
+
+impl Inner {
+    fn open(&self) -> io::Result<Current> {
+        let file = StdOpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let metadata = file.metadata()?;
+        let opened = if metadata.len() > 0 {
+            metadata.modified()?
+        } else {
+            SystemTime::now()
+        };
+        Ok(Current {
+            file,
+            len: metadata.len(),
+            opened,
+        })
+    }
+
+    /// Returns whether `current` has to be rotated before `len` more bytes
+    /// are written to it at `now`.
+    fn due(&self, current: &Current, now: SystemTime, len: u64) -> bool {
+        if current.len == 0 {
+            return false;
+        }
+        let full = self
+            .opts
+            .max_size
+            .is_some_and(|max| current.len + len > max);
+        let expired = self
+            .opts
+            .every
+            .is_some_and(|every| every.partition(current.opened) != every.partition(now));
+        full || expired
+    }
+
+    /// Renames the file out of the way and removes rotated files beyond the
+    /// number to keep.
+    fn rotate(&self, now: SystemTime) -> io::Result<()> {
+        let mut rotated = self.rotated()?;
+        let stamp = timestamp(now);
+        // Rotations within the same second are numbered, so that the newest
+        // always sorts last, even after older ones have been removed.
+        let n = rotated
+            .iter()
+            .filter(|rotated| rotated.0 == stamp)
+            .map(|rotated| rotated.1 + 1)
+            .max()
+            .unwrap_or(0);
+        let mut name = OsString::from(self.path.file_name().unwrap_or_default());
+        name.push(format!(".{}", stamp));
+        if n > 0 {
+            name.push(format!(".{}", n));
+        }
+        let target = self.path.with_file_name(name);
+        fs::rename(&self.path, &target)?;
+        rotated.push((stamp, n, target));
+
+        let keep = match self.opts.keep {
+            Some(keep) => keep,
+            None => return Ok(()),
+        };
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(keep);
+        for (_, _, path) in &rotated[..excess] {
+            match fs::remove_file(path) {
+                Ok(()) => {}
+                Err(ref err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists the rotated files next to the file, as their timestamp, number
+    /// and path.
+    fn rotated(&self) -> io::Result<Vec<(String, u64, PathBuf)>> {
+        let dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut prefix = self
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        prefix.push('.');
+
+        let mut rotated = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let suffix = match file_name.to_str().and_then(|n| n.strip_prefix(&prefix)) {
+                Some(suffix) => suffix,
+                None => continue,
+            };
+            // Only names this writer produces, `YYYYMMDDTHHMMSS[.N]`.
+            let mut parts = suffix.splitn(2, '.');
+            let stamp = parts.next().unwrap_or_default();
+            let n = match parts.next().map(str::parse) {
+                Some(Ok(n)) => n,
+                Some(Err(_)) => continue,
+                None => 0,
+            };
+            if is_timestamp(stamp) {
+                rotated.push((stamp.to_owned(), n, entry.path()));
+            }
+        }
+        Ok(rotated)
+    }
+}
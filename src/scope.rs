@@ -0,0 +1,237 @@
+use futures::{Async, Future, Poll};
+
+use std::error;
+use std::fmt;
+use std::io;
+
+use crate::CancellationToken;
+
+type Task = Box<dyn Future<Item = (), Error = io::Error> + Send>;
+
+/// A group of futures spawned by the closure given to [`scope`].
+///
+/// [`scope`]: fn.scope.html
+pub struct Scope {
+    tasks: Vec<Task>,
+    token: CancellationToken,
+}
+
+impl Scope {
+    /// Adds `future` to the scope, discarding its output.
+    ///
+    /// To use the output, such as a digest, move it somewhere from a
+    /// combinator like `map` before spawning.
+    pub fn spawn<F>(&mut self, future: F) -> &mut Scope
+    where
+        F: Future<Error = io::Error> + Send + 'static,
+    {
+        self.tasks.push(Box::new(future.map(|_| ())));
+        self
+    }
+
+    /// Returns the token cancelled when the scope fails.
+    ///
+    /// Pass it to operations that take a [`CancellationToken`], so that work
+    /// already handed to the threadpool stops as well.
+    ///
+    /// [`CancellationToken`]: struct.CancellationToken.html
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Scope")
+            .field("tasks", &self.tasks.len())
+            .field("token", &self.token)
+            .finish()
+    }
+}
+
+/// The errors of the futures of a failed [`scope`].
+///
+/// A failed scope resolves to an `io::Error` with the kind of the first
+/// error, which carries a `ScopeError`. Use [`ScopeError::from_io`] to get
+/// at every error.
+///
+/// [`scope`]: fn.scope.html
+/// [`ScopeError::from_io`]: #method.from_io
+#[derive(Debug)]
+pub struct ScopeError {
+    errors: Vec<io::Error>,
+}
+
+impl ScopeError {
+    /// Returns the errors of a failed scope carried by `err`, if any.
+    pub fn from_io(err: &io::Error) -> Option<&ScopeError> {
+        err.get_ref()?.downcast_ref::<ScopeError>()
+    }
+
+    /// Returns the errors in the order they happened.
+    pub fn errors(&self) -> &[io::Error] {
+        &self.errors
+    }
+
+    /// Returns the errors in the order they happened.
+    pub fn into_errors(self) -> Vec<io::Error> {
+        self.errors
+    }
+}
+
+impl fmt::Display for ScopeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.errors.len() {
+            1 => write!(f, "{}", self.errors[0]),
+            n => {
+                write!(f, "{} operations failed", n)?;
+                for err in &self.errors {
+                    write!(f, "; {}", err)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for ScopeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.errors.first().map(|err| err as _)
+    }
+}
+
+/// Options for running a group of futures as a [`scope`].
+///
+/// [`scope`]: fn.scope.html
+#[derive(Clone, Debug, Default)]
+pub struct ScopeOptions {
+    keep_going: bool,
+    token: CancellationToken,
+}
+
+impl ScopeOptions {
+    /// Creates options that cancel the scope on the first error.
+    pub fn new() -> ScopeOptions {
+        ScopeOptions::default()
+    }
+
+    /// Sets whether the other futures keep running after one fails, so
+    /// that the scope fails with every error rather than the first, and
+    /// only resolves once no operation of it is running any more.
+    pub fn keep_going(&mut self, keep_going: bool) -> &mut ScopeOptions {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Sets the token cancelled when the scope fails, and handed to the
+    /// futures through [`Scope::token`].
+    ///
+    /// Cancelling it from outside does not stop the scope itself, only the
+    /// operations using it.
+    ///
+    /// [`Scope::token`]: struct.Scope.html#method.token
+    pub fn cancellation(&mut self, token: CancellationToken) -> &mut ScopeOptions {
+        self.token = token;
+        self
+    }
+
+    /// Calls `f` to spawn futures into a scope, and resolves once all of
+    /// them have.
+    pub fn run<F>(&self, f: F) -> impl Future<Item = (), Error = io::Error>
+    where
+        F: FnOnce(&mut Scope),
+    {
+        let mut scope = Scope {
+            tasks: Vec::new(),
+            token: self.token.clone(),
+        };
+        f(&mut scope);
+        Run {
+            tasks: scope.tasks,
+            token: scope.token,
+            errors: Vec::new(),
+            keep_going: self.keep_going,
+        }
+    }
+}
+
+/// Runs the futures spawned by `f` concurrently, resolving once all of
+/// them have.
+///
+/// This keeps work that belongs together, like copying and hashing the
+/// files of a request, from outliving the request or failing silently:
+///
+/// ```no_run
+/// # use actix_fs::{copy_file_range, File};
+/// # use futures::Future;
+/// let done = actix_fs::scope(|s| {
+///     s.spawn(File::open("a.bin").join(File::create("b.bin")).and_then(
+///         |(src, dst)| copy_file_range(src, dst, u64::MAX),
+///     ));
+///     s.spawn(actix_fs::remove_file("stale.bin"));
+/// });
+/// ```
+///
+/// On the first error, the scope's [`token`] is cancelled, the other
+/// futures are dropped, which cancels work still queued for the
+/// threadpool, and the scope fails with the error. See [`ScopeOptions`] to
+/// let the other futures finish instead.
+///
+/// Only queued work is cancelled. An operation already running on a thread
+/// when the scope fails keeps running in the background after the scope
+/// has resolved, and may still change files, unless it takes the token and
+/// stops at its next check. Use [`ScopeOptions::keep_going`] when the
+/// caller needs every operation to be finished once the scope resolves.
+///
+/// [`token`]: struct.Scope.html#method.token
+/// [`ScopeOptions`]: struct.ScopeOptions.html
+/// [`ScopeOptions::keep_going`]: struct.ScopeOptions.html#method.keep_going
+pub fn scope<F>(f: F) -> impl Future<Item = (), Error = io::Error>
+where
+    F: FnOnce(&mut Scope),
+{
+    ScopeOptions::new().run(f)
+}
+
+struct Run {
+    tasks: Vec<Task>,
+    token: CancellationToken,
+    errors: Vec<io::Error>,
+    keep_going: bool,
+}
+
+impl Future for Run {
+    type Item = ();
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(), io::Error> {
+        let mut i = 0;
+        while i < self.tasks.len() {
+            match self.tasks[i].poll() {
+                Ok(Async::NotReady) => {
+                    i += 1;
+                    continue;
+                }
+                Ok(Async::Ready(())) => {}
+                Err(err) => {
+                    self.errors.push(err);
+                    if !self.keep_going {
+                        self.token.cancel();
+                        self.tasks.clear();
+                        break;
+                    }
+                }
+            }
+            drop(self.tasks.swap_remove(i));
+        }
+        if !self.tasks.is_empty() {
+            return Ok(Async::NotReady);
+        }
+        if self.errors.is_empty() {
+            return Ok(Async::Ready(()));
+        }
+        let errors = std::mem::take(&mut self.errors);
+        let kind = errors[0].kind();
+        Err(io::Error::new(kind, ScopeError { errors }))
+    }
+}
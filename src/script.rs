@@ -0,0 +1,264 @@
+use futures::future::{self, Either, Loop};
+use futures::{Future, Stream};
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::vec;
+
+use crate::{error, File, Root};
+
+type Body = Box<dyn Stream<Item = Vec<u8>, Error = io::Error> + Send>;
+
+/// An operation of a script run by [`ScriptOptions::run`].
+///
+/// Paths are relative to the root the script runs in, and a step whose path
+/// resolves to the root itself, such as `""` or `"a/.."`, fails with
+/// `PermissionDenied`.
+///
+/// [`ScriptOptions::run`]: struct.ScriptOptions.html#method.run
+pub enum Step {
+    /// Creates a directory and all of its missing parents.
+    Mkdir(PathBuf),
+    /// Writes the contents of a stream to a file, creating or replacing it.
+    ///
+    /// The contents are written to a temporary file next to it, synced, and
+    /// renamed into place, so the file is never partially written.
+    Put(PathBuf, Body),
+    /// Renames a file or directory, replacing the target if it exists.
+    Rename(PathBuf, PathBuf),
+    /// Sets the permission bits of a file or directory.
+    ///
+    /// On platforms other than Unix, only whether the file is read-only is
+    /// set, which is the case if no write bit is set in the mode.
+    Chmod(PathBuf, u32),
+    /// Removes a file, or a directory with everything in it.
+    Remove(PathBuf),
+}
+
+impl Step {
+    /// Creates a step writing the contents of `stream` to the file at
+    /// `path`.
+    pub fn put<P, S>(path: P, stream: S) -> Step
+    where
+        P: Into<PathBuf>,
+        S: Stream<Item = Vec<u8>, Error = io::Error> + Send + 'static,
+    {
+        Step::Put(path.into(), Box::new(stream))
+    }
+}
+
+impl fmt::Debug for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Step::Mkdir(ref path) => f.debug_tuple("Mkdir").field(path).finish(),
+            Step::Put(ref path, _) => f.debug_tuple("Put").field(path).finish(),
+            Step::Rename(ref from, ref to) => {
+                f.debug_tuple("Rename").field(from).field(to).finish()
+            }
+            Step::Chmod(ref path, mode) => f
+                .debug_tuple("Chmod")
+                .field(path)
+                .field(&format_args!("{:o}", mode))
+                .finish(),
+            Step::Remove(ref path) => f.debug_tuple("Remove").field(path).finish(),
+        }
+    }
+}
+
+/// What happened to a [`Step`] of a script.
+///
+/// [`Step`]: enum.Step.html
+#[derive(Debug)]
+pub enum StepOutcome {
+    /// The step succeeded.
+    Done,
+    /// The step failed.
+    Failed(io::Error),
+    /// The step was not run because an earlier one failed.
+    Skipped,
+}
+
+impl StepOutcome {
+    /// Returns `true` if the step succeeded.
+    pub fn is_done(&self) -> bool {
+        matches!(*self, StepOutcome::Done)
+    }
+
+    /// Returns the error, if the step failed.
+    pub fn err(&self) -> Option<&io::Error> {
+        match *self {
+            StepOutcome::Failed(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Options for running a script, a list of [`Step`]s described as data,
+/// such as the steps of a deployment received over HTTP.
+///
+/// Steps run one after another, each confined to a [`Root`], and each gets
+/// its own outcome:
+///
+/// ```no_run
+/// # use actix_fs::{Root, ScriptOptions, Step};
+/// # use futures::{stream, Future};
+/// let outcomes = ScriptOptions::new().run(
+///     &Root::new("/srv/app"),
+///     vec![
+///         Step::Mkdir("releases/42".into()),
+///         Step::put("releases/42/app", stream::once(Ok(b"...".to_vec()))),
+///         Step::Chmod("releases/42/app".into(), 0o755),
+///         Step::Rename("releases/42".into(), "current".into()),
+///     ],
+/// );
+/// ```
+///
+/// [`Step`]: enum.Step.html
+/// [`Root`]: struct.Root.html
+#[derive(Clone, Debug, Default)]
+pub struct ScriptOptions {
+    keep_going: bool,
+}
+
+impl ScriptOptions {
+    /// Creates options that stop at the first failing step.
+    pub fn new() -> ScriptOptions {
+        ScriptOptions::default()
+    }
+
+    /// Sets whether the steps after a failing one still run, rather than
+    /// being skipped.
+    pub fn keep_going(&mut self, keep_going: bool) -> &mut ScriptOptions {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Runs `steps` in `root`, resolving to the outcome of each step in
+    /// order.
+    pub fn run(
+        &self,
+        root: &Root,
+        steps: Vec<Step>,
+    ) -> impl Future<Item = Vec<StepOutcome>, Error = io::Error> {
+        let root = root.clone();
+        let keep_going = self.keep_going;
+        let outcomes = Vec::with_capacity(steps.len());
+        future::loop_fn(
+            (steps.into_iter(), outcomes, false),
+            move |(mut steps, mut outcomes, failed): (vec::IntoIter<Step>, Vec<_>, bool)| {
+                let step = match steps.next() {
+                    Some(step) => step,
+                    None => return Either::A(future::ok(Loop::Break(outcomes))),
+                };
+                if failed && !keep_going {
+                    outcomes.push(StepOutcome::Skipped);
+                    return Either::A(future::ok(Loop::Continue((steps, outcomes, failed))));
+                }
+                Either::B(run(&root, step).then(move |res| {
+                    let failed = failed || res.is_err();
+                    outcomes.push(match res {
+                        Ok(()) => StepOutcome::Done,
+                        Err(err) => StepOutcome::Failed(err),
+                    });
+                    Ok(Loop::Continue((steps, outcomes, failed)))
+                }))
+            },
+        )
+    }
+}
+
+/// Runs `steps` in `root`, stopping at the first failing step.
+///
+/// See [`ScriptOptions`] for details and more control.
+///
+/// [`ScriptOptions`]: struct.ScriptOptions.html
+pub fn run_script(
+    root: &Root,
+    steps: Vec<Step>,
+) -> impl Future<Item = Vec<StepOutcome>, Error = io::Error> {
+    ScriptOptions::new().run(root, steps)
+}
+
+fn run(root: &Root, step: Step) -> Box<dyn Future<Item = (), Error = io::Error> + Send> {
+    let resolved = match step {
+        Step::Rename(ref from, ref to) => root
+            .resolve(from)
+            .and_then(|from| Ok((from, root.resolve(to)?))),
+        Step::Mkdir(ref path)
+        | Step::Put(ref path, _)
+        | Step::Chmod(ref path, _)
+        | Step::Remove(ref path) => root.resolve(path).map(|path| (path, PathBuf::new())),
+    };
+    let (path, to) = match resolved {
+        Ok(paths) => paths,
+        Err(err) => return Box::new(future::err(err)),
+    };
+    // Steps act within the root, never on the root itself, which `Remove`
+    // would delete and `Rename` would move away.
+    let renamed = matches!(step, Step::Rename(..));
+    if path == root.path() || (renamed && to == root.path()) {
+        return Box::new(future::err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "a step cannot act on the root directory",
+        )));
+    }
+    match step {
+        Step::Mkdir(_) => Box::new(crate::blocking(move || {
+            fs::create_dir_all(&path)
+                .map_err(|err| error::with_path(err, "create directory", &path))
+        })),
+        Step::Put(_, body) => Box::new(put(path, body)),
+        Step::Rename(..) => Box::new(crate::blocking(move || {
+            fs::rename(&path, &to).map_err(|err| error::with_paths(err, "rename", &path, &to))
+        })),
+        Step::Chmod(_, mode) => Box::new(crate::blocking(move || {
+            chmod(&path, mode).map_err(|err| error::with_path(err, "chmod", &path))
+        })),
+        Step::Remove(_) => Box::new(crate::blocking(move || {
+            let metadata = fs::symlink_metadata(&path);
+            let removed = metadata.and_then(|metadata| {
+                if metadata.is_dir() {
+                    fs::remove_dir_all(&path)
+                } else {
+                    fs::remove_file(&path)
+                }
+            });
+            removed.map_err(|err| error::with_path(err, "remove", &path))
+        })),
+    }
+}
+
+fn put(path: PathBuf, body: Body) -> impl Future<Item = (), Error = io::Error> {
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let cleanup = tmp.clone();
+    File::create(tmp.clone())
+        .and_then(|file| body.fold(file, |file, chunk| file.write_all(chunk)))
+        .and_then(|file| file.sync_all())
+        .and_then(|file| file.close())
+        .and_then(move |()| crate::rename(tmp, path))
+        .or_else(move |err| {
+            crate::blocking(move || {
+                let _ = fs::remove_file(&cleanup);
+                Ok(())
+            })
+            .then(move |_| Err(err))
+        })
+}
+
+#[cfg(unix)]
+fn chmod(path: &std::path::Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn chmod(path: &std::path::Path, mode: u32) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o222 == 0);
+    fs::set_permissions(path, permissions)
+}
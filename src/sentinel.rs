@@ -0,0 +1,295 @@
+use futures::{stream, Future, Stream};
+use tokio_timer::Interval;
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fs::{self, File as StdFile};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// The recorded state of a file watched by a [`Sentinel`].
+///
+/// [`Sentinel`]: struct.Sentinel.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+    len: u64,
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+impl Fingerprint {
+    /// Returns the size of the file in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the file was empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the last modification time of the file, if the platform
+    /// reports one.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// Returns the keyed content hash of the file.
+    ///
+    /// The key is chosen at random for each `Sentinel`, so hashes are only
+    /// comparable between fingerprints taken by the same sentinel.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// An unexpected change detected by a [`Sentinel`].
+///
+/// [`Sentinel`]: struct.Sentinel.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Alert {
+    /// The file's size, modification time or contents no longer match the
+    /// registered fingerprint.
+    Changed {
+        path: PathBuf,
+        expected: Fingerprint,
+        actual: Fingerprint,
+    },
+    /// The file no longer exists.
+    Missing { path: PathBuf },
+    /// The file could not be read, for example because its permissions
+    /// changed.
+    ///
+    /// Only [`Sentinel::verify_all`] and [`Sentinel::watch`] report this;
+    /// [`Sentinel::verify`] fails with the error instead.
+    ///
+    /// [`Sentinel::verify_all`]: struct.Sentinel.html#method.verify_all
+    /// [`Sentinel::watch`]: struct.Sentinel.html#method.watch
+    /// [`Sentinel::verify`]: struct.Sentinel.html#method.verify
+    Unreadable { path: PathBuf, kind: ErrorKind },
+}
+
+impl Alert {
+    /// Returns the path of the file the alert is about.
+    pub fn path(&self) -> &Path {
+        match self {
+            Alert::Changed { path, .. } => path,
+            Alert::Missing { path } => path,
+            Alert::Unreadable { path, .. } => path,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    expected: Fingerprint,
+    // The last alert raised, so a single change is only reported once by
+    // `watch`.
+    alerted: Option<Alert>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    key: RandomState,
+    entries: Mutex<HashMap<PathBuf, Entry>>,
+}
+
+/// Detects external modification of registered files.
+///
+/// A `Sentinel` records the size, modification time and a keyed hash of the
+/// contents of each registered file. Files can then be checked individually
+/// before they are used with [`verify`], or periodically with [`watch`],
+/// which yields an [`Alert`] whenever a file changes unexpectedly.
+///
+/// Cloning a `Sentinel` produces another handle to the same set of files.
+///
+/// [`verify`]: #method.verify
+/// [`watch`]: #method.watch
+/// [`Alert`]: enum.Alert.html
+#[derive(Clone, Debug)]
+pub struct Sentinel {
+    inner: Arc<Inner>,
+}
+
+impl Sentinel {
+    /// Creates a sentinel with no registered files.
+    pub fn new() -> Sentinel {
+        Sentinel {
+            inner: Arc::new(Inner {
+                key: RandomState::new(),
+                entries: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Records the current state of the file at `path` as the expected one.
+    ///
+    /// Registering a file that is already registered accepts its current
+    /// state, which is how intended changes are acknowledged.
+    pub fn register<P>(&self, path: P) -> impl Future<Item = Fingerprint, Error = io::Error>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        crate::blocking(move || -> io::Result<Fingerprint> {
+            let path = path.as_ref().to_path_buf();
+            let fingerprint = fingerprint(&inner.key, &path)?;
+            let entry = Entry {
+                expected: fingerprint.clone(),
+                alerted: None,
+            };
+            inner.entries.lock().unwrap().insert(path, entry);
+            Ok(fingerprint)
+        })
+    }
+
+    /// Stops tracking the file at `path`, returning whether it was
+    /// registered.
+    pub fn unregister<P>(&self, path: P) -> bool
+    where
+        P: AsRef<Path>,
+    {
+        self.inner.entries.lock().unwrap().remove(path.as_ref()).is_some()
+    }
+
+    /// Checks the file at `path` against its registered fingerprint.
+    ///
+    /// Resolves to `None` if the file is unchanged. Checking a file that was
+    /// never registered results in an error of kind `NotFound`.
+    pub fn verify<P>(&self, path: P) -> impl Future<Item = Option<Alert>, Error = io::Error>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        crate::blocking(move || -> io::Result<Option<Alert>> {
+            let path = path.as_ref();
+            let expected = match inner.entries.lock().unwrap().get(path) {
+                Some(entry) => entry.expected.clone(),
+                None => {
+                    return Err(io::Error::new(
+                        ErrorKind::NotFound,
+                        format!("{} is not registered", path.display()),
+                    ))
+                }
+            };
+            let actual = observe(&inner.key, path)?;
+            Ok(check(path, &expected, actual.as_ref()))
+        })
+    }
+
+    /// Checks every registered file, resolving to the alerts for those that
+    /// changed or could not be read.
+    pub fn verify_all(&self) -> impl Future<Item = Vec<Alert>, Error = io::Error> {
+        let inner = self.inner.clone();
+        crate::blocking(move || verify_all(&inner, false))
+    }
+
+    /// Checks every registered file each `period`, yielding an alert the
+    /// first time each unexpected change is seen.
+    ///
+    /// A file that keeps its changed state is not reported again until it
+    /// changes once more or is re-registered. A file that cannot be read is
+    /// reported the same way, and the others are still checked.
+    pub fn watch(&self, period: Duration) -> impl Stream<Item = Alert, Error = io::Error> {
+        let inner = self.inner.clone();
+        Interval::new_interval(period)
+            .map_err(crate::blocking_err)
+            .and_then(move |_| {
+                let inner = inner.clone();
+                crate::blocking(move || verify_all(&inner, true))
+            })
+            .map(stream::iter_ok)
+            .flatten()
+    }
+}
+
+impl Default for Sentinel {
+    fn default() -> Sentinel {
+        Sentinel::new()
+    }
+}
+
+fn verify_all(inner: &Inner, dedup: bool) -> io::Result<Vec<Alert>> {
+    let paths: Vec<(PathBuf, Fingerprint)> = {
+        let entries = inner.entries.lock().unwrap();
+        entries
+            .iter()
+            .map(|(path, entry)| (path.clone(), entry.expected.clone()))
+            .collect()
+    };
+
+    let mut alerts = Vec::new();
+    for (path, expected) in paths {
+        let alert = match observe(&inner.key, &path) {
+            Ok(actual) => match check(&path, &expected, actual.as_ref()) {
+                Some(alert) => alert,
+                None => continue,
+            },
+            Err(err) => Alert::Unreadable {
+                path: path.clone(),
+                kind: err.kind(),
+            },
+        };
+        if dedup {
+            let mut entries = inner.entries.lock().unwrap();
+            match entries.get_mut(&path) {
+                // Re-registered or removed while we were hashing.
+                Some(entry) if entry.expected != expected => continue,
+                None => continue,
+                Some(entry) => {
+                    if entry.alerted.as_ref() == Some(&alert) {
+                        continue;
+                    }
+                    entry.alerted = Some(alert.clone());
+                }
+            }
+        }
+        alerts.push(alert);
+    }
+    Ok(alerts)
+}
+
+fn check(path: &Path, expected: &Fingerprint, actual: Option<&Fingerprint>) -> Option<Alert> {
+    match actual {
+        Some(actual) if actual == expected => None,
+        Some(actual) => Some(Alert::Changed {
+            path: path.to_path_buf(),
+            expected: expected.clone(),
+            actual: actual.clone(),
+        }),
+        None => Some(Alert::Missing {
+            path: path.to_path_buf(),
+        }),
+    }
+}
+
+fn observe(key: &RandomState, path: &Path) -> io::Result<Option<Fingerprint>> {
+    match fingerprint(key, path) {
+        Ok(fingerprint) => Ok(Some(fingerprint)),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn fingerprint(key: &RandomState, path: &Path) -> io::Result<Fingerprint> {
+    let mut file = StdFile::open(path)?;
+    let meta = fs::metadata(path)?;
+    let mut hasher = key.build_hasher();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => hasher.write(&buf[..n]),
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(Fingerprint {
+        len: meta.len(),
+        modified: meta.modified().ok(),
+        hash: hasher.finish(),
+    })
+}
@@ -0,0 +1,245 @@
+use futures::{stream, Future, Stream};
+
+use std::collections::HashSet;
+use std::fs::{self, Metadata, ReadDir};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::error;
+
+/// How many entries are visited per trip to the threadpool.
+const STEP: usize = 1024;
+
+/// The size of a directory tree.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DirSize {
+    /// The total size of the files in bytes.
+    pub bytes: u64,
+    /// The number of files, which includes symbolic links that are not
+    /// followed but not directories.
+    pub files: u64,
+}
+
+/// Options for measuring the size of a directory tree, like `du`.
+///
+/// The tree is walked on the threadpool, a bounded number of entries at a
+/// time, so measuring a large tree does not hold a thread for long. Entries
+/// removed while the tree is being walked are skipped. On Unix, a file with
+/// several hard links is only counted once.
+#[derive(Clone, Debug)]
+pub struct DirSizeOptions {
+    follow_symlinks: bool,
+    apparent: bool,
+}
+
+impl DirSizeOptions {
+    /// Creates options that count the apparent size of files without
+    /// following symbolic links.
+    pub fn new() -> DirSizeOptions {
+        DirSizeOptions {
+            follow_symlinks: false,
+            apparent: true,
+        }
+    }
+
+    /// Sets whether symbolic links are followed, counting what they point to
+    /// instead of the links themselves.
+    ///
+    /// On Unix, a directory reached more than once, such as through a link
+    /// cycle, is only walked once. Other platforms do not detect cycles.
+    pub fn follow_symlinks(&mut self, follow: bool) -> &mut DirSizeOptions {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Sets whether the apparent size of files is counted, rather than the
+    /// space allocated for them on disk.
+    ///
+    /// The allocated size differs for sparse and compressed files, and
+    /// includes the unused part of the last block. It is only known on Unix,
+    /// and other platforms always count the apparent size.
+    pub fn apparent(&mut self, apparent: bool) -> &mut DirSizeOptions {
+        self.apparent = apparent;
+        self
+    }
+
+    /// Measures the tree at `path`, which may also be a single file.
+    pub fn size<P>(&self, path: P) -> impl Future<Item = DirSize, Error = io::Error>
+    where
+        P: Into<PathBuf>,
+    {
+        self.progress(path)
+            .fold(DirSize::default(), |_, size| Ok::<_, io::Error>(size))
+    }
+
+    /// Measures the tree at `path`, yielding the running total after every
+    /// trip to the threadpool.
+    ///
+    /// The last item is the size of the whole tree.
+    pub fn progress<P>(&self, path: P) -> impl Stream<Item = DirSize, Error = io::Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let walk = Walk {
+            root: Some(path.into()),
+            current: None,
+            pending: Vec::new(),
+            seen: HashSet::new(),
+            total: DirSize::default(),
+            opts: self.clone(),
+        };
+        stream::unfold(Some(walk), |walk| {
+            let mut walk = walk?;
+            Some(crate::blocking(move || {
+                walk.step()?;
+                let total = walk.total;
+                let done = walk.done();
+                Ok((total, if done { None } else { Some(walk) }))
+            }))
+        })
+    }
+}
+
+impl Default for DirSizeOptions {
+    fn default() -> DirSizeOptions {
+        DirSizeOptions::new()
+    }
+}
+
+/// Measures the apparent size of the tree at `path`, without following
+/// symbolic links.
+///
+/// See [`DirSizeOptions`] for details and more control.
+///
+/// [`DirSizeOptions`]: struct.DirSizeOptions.html
+pub fn dir_size<P>(path: P) -> impl Future<Item = DirSize, Error = io::Error>
+where
+    P: Into<PathBuf>,
+{
+    DirSizeOptions::new().size(path)
+}
+
+struct Walk {
+    root: Option<PathBuf>,
+    // The directory being read when the last step ended, if it had more
+    // entries left.
+    current: Option<(PathBuf, ReadDir)>,
+    pending: Vec<PathBuf>,
+    seen: HashSet<(u64, u64)>,
+    total: DirSize,
+    opts: DirSizeOptions,
+}
+
+impl Walk {
+    /// Visits the next few entries, resuming a directory left unfinished by
+    /// the last step.
+    fn step(&mut self) -> io::Result<()> {
+        if let Some(root) = self.root.take() {
+            let metadata = self
+                .metadata(&root)
+                .map_err(|err| error::with_path(err, "stat", &root))?;
+            self.visit(root, metadata);
+            return Ok(());
+        }
+
+        let mut visited = 0;
+        while visited < STEP {
+            let (dir, mut entries) = match self.current.take() {
+                Some(current) => current,
+                None => {
+                    let dir = match self.pending.pop() {
+                        Some(dir) => dir,
+                        None => break,
+                    };
+                    // Opening a directory counts as a visit, so a step
+                    // through many empty directories ends as well.
+                    visited += 1;
+                    match fs::read_dir(&dir) {
+                        Ok(entries) => (dir, entries),
+                        Err(ref err) if err.kind() == ErrorKind::NotFound => continue,
+                        Err(err) => return Err(error::with_path(err, "read directory", &dir)),
+                    }
+                }
+            };
+            while visited < STEP {
+                let path = match entries.next() {
+                    Some(entry) => entry
+                        .map_err(|err| error::with_path(err, "read directory", &dir))?
+                        .path(),
+                    None => break,
+                };
+                match self.metadata(&path) {
+                    Ok(metadata) => self.visit(path, metadata),
+                    Err(ref err) if err.kind() == ErrorKind::NotFound => {}
+                    Err(err) => return Err(error::with_path(err, "stat", &path)),
+                }
+                visited += 1;
+            }
+            if visited == STEP {
+                self.current = Some((dir, entries));
+            }
+        }
+        Ok(())
+    }
+
+    fn done(&self) -> bool {
+        self.root.is_none() && self.current.is_none() && self.pending.is_empty()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        if !self.opts.follow_symlinks {
+            return fs::symlink_metadata(path);
+        }
+        match fs::metadata(path) {
+            // A dangling link counts as itself.
+            Err(ref err) if err.kind() == ErrorKind::NotFound => fs::symlink_metadata(path),
+            res => res,
+        }
+    }
+
+    fn visit(&mut self, path: PathBuf, metadata: Metadata) {
+        if !self.first_visit(&metadata) {
+            return;
+        }
+        if metadata.is_dir() {
+            self.pending.push(path);
+            return;
+        }
+        self.total.files += 1;
+        self.total.bytes += if self.opts.apparent {
+            metadata.len()
+        } else {
+            allocated(&metadata)
+        };
+    }
+
+    #[cfg(unix)]
+    fn first_visit(&mut self, metadata: &Metadata) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        let linked = if metadata.is_dir() {
+            self.opts.follow_symlinks
+        } else {
+            metadata.nlink() > 1
+        };
+        !linked || self.seen.insert((metadata.dev(), metadata.ino()))
+    }
+
+    #[cfg(not(unix))]
+    fn first_visit(&mut self, _: &Metadata) -> bool {
+        true
+    }
+}
+
+#[cfg(unix)]
+fn allocated(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    // `st_blocks` is always in units of 512 bytes.
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated(metadata: &Metadata) -> u64 {
+    metadata.len()
+}
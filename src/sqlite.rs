@@ -0,0 +1,65 @@
+use futures::Future;
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+
+use std::ffi::OsString;
+use std::fs::{self, File as StdFile};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const PAGES_PER_STEP: i32 = 1024;
+
+/// Copies the SQLite database at `db_path` to `dest` consistently, even while
+/// other connections are writing to it.
+///
+/// Copying the database file directly may capture a half-written
+/// transaction, and misses everything that still lives in the write-ahead
+/// log. Instead this uses SQLite's online backup API, which copies a
+/// consistent snapshot including the contents of the log, and restarts if
+/// the database changes part way through.
+///
+/// The copy is written next to `dest`, synced to disk, and then renamed over
+/// `dest`, so an interrupted backup never leaves a partial database behind.
+///
+/// This is only available with the `sqlite` feature.
+pub fn backup_sqlite<P, Q>(db_path: P, dest: Q) -> impl Future<Item = (), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+    Q: AsRef<Path> + Send + 'static,
+{
+    crate::blocking(move || backup(db_path.as_ref(), dest.as_ref()))
+}
+
+fn backup(db_path: &Path, dest: &Path) -> io::Result<()> {
+    // SQLite reports a missing database as a generic failure to open.
+    fs::metadata(db_path)?;
+
+    let tmp = tmp_path(dest);
+    let res = copy(db_path, &tmp).and_then(|_| {
+        StdFile::open(&tmp)?.sync_all()?;
+        fs::rename(&tmp, dest)
+    });
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    res
+}
+
+fn copy(db_path: &Path, tmp: &Path) -> io::Result<()> {
+    let src = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(crate::blocking_err)?;
+    let mut dst = Connection::open(tmp).map_err(crate::blocking_err)?;
+    Backup::new(&src, &mut dst)
+        .and_then(|backup| {
+            backup.run_to_completion(PAGES_PER_STEP, Duration::from_millis(10), None)
+        })
+        .map_err(crate::blocking_err)?;
+    dst.close().map_err(|(_, err)| crate::blocking_err(err))
+}
+
+fn tmp_path(dest: &Path) -> PathBuf {
+    let mut name = OsString::from(dest.file_name().unwrap_or_default());
+    name.push(".tmp");
+    dest.with_file_name(name)
+}
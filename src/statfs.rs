@@ -0,0 +1,126 @@
+use futures::Future;
+
+use std::io;
+use std::path::Path;
+
+/// Space and inode usage of a filesystem.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FsStats {
+    /// The size of the filesystem in bytes.
+    pub total: u64,
+    /// The number of free bytes.
+    pub free: u64,
+    /// The number of free bytes available to the current user, which may be
+    /// less than `free` because of reserved blocks or quotas.
+    pub available: u64,
+    /// The total number of inodes, where the platform reports it.
+    pub inodes: Option<u64>,
+    /// The number of free inodes, where the platform reports it.
+    pub free_inodes: Option<u64>,
+}
+
+/// Reports the space and inode usage of the filesystem holding `path`.
+///
+/// This uses `statvfs` on Unix and `GetDiskFreeSpaceExW` on Windows, which
+/// does not report inodes.
+pub fn statfs<P>(path: P) -> impl Future<Item = FsStats, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    crate::blocking(move || {
+        let path = path.as_ref();
+        sys::statfs(path).map_err(|err| crate::error::with_path(err, "statfs", path))
+    })
+}
+
+#[cfg(unix)]
+pub(crate) mod sys {
+    use std::ffi::CString;
+    use std::fs::File as StdFile;
+    use std::io;
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    use super::FsStats;
+
+    pub fn statfs(path: &Path) -> io::Result<FsStats> {
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))?;
+        let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+        if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(stats(unsafe { stat.assume_init() }))
+    }
+
+    pub fn fstatfs(std: &StdFile) -> io::Result<FsStats> {
+        let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+        if unsafe { libc::fstatvfs(std.as_raw_fd(), stat.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(stats(unsafe { stat.assume_init() }))
+    }
+
+    // The field types differ between platforms.
+    #[allow(clippy::unnecessary_cast)]
+    fn stats(stat: libc::statvfs) -> FsStats {
+        let block = stat.f_frsize as u64;
+        FsStats {
+            total: (stat.f_blocks as u64).saturating_mul(block),
+            free: (stat.f_bfree as u64).saturating_mul(block),
+            available: (stat.f_bavail as u64).saturating_mul(block),
+            inodes: Some(stat.f_files as u64),
+            free_inodes: Some(stat.f_ffree as u64),
+        }
+    }
+}
+
+#[cfg(windows)]
+pub(crate) mod sys {
+    use std::io;
+    use std::iter;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    use super::FsStats;
+
+    pub fn statfs(path: &Path) -> io::Result<FsStats> {
+        // Only directories are accepted.
+        let dir = match path.parent() {
+            Some(parent) if path.is_file() => parent,
+            _ => path,
+        };
+        let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(iter::once(0)).collect();
+        let (mut available, mut total, mut free) = (0, 0, 0);
+        if unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, &mut total, &mut free) } == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(FsStats {
+            total,
+            free,
+            available,
+            inodes: None,
+            free_inodes: None,
+        })
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) mod sys {
+    use std::io;
+    use std::path::Path;
+
+    use super::FsStats;
+
+    pub fn statfs(_: &Path) -> io::Result<FsStats> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "filesystem statistics are not supported on this platform",
+        ))
+    }
+}
@@ -0,0 +1,202 @@
+use futures::future::{self, Either, Loop};
+use futures::{stream, Future, Stream};
+use tokio_timer::Interval;
+
+use std::fs::{self, File as StdFile, Metadata};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Options for following a file as it grows.
+#[derive(Clone, Debug)]
+pub struct TailOptions {
+    interval: Duration,
+    from_start: bool,
+}
+
+impl TailOptions {
+    /// Creates options that start at the end of the file and check it for
+    /// new data every 250 milliseconds.
+    pub fn new() -> TailOptions {
+        TailOptions {
+            interval: Duration::from_millis(250),
+            from_start: false,
+        }
+    }
+
+    /// Sets how often the file is checked for new data.
+    ///
+    /// With the `watch` feature, changes are also picked up as soon as they
+    /// are reported by the operating system, and this only bounds how long
+    /// a missed event can delay them.
+    pub fn interval(&mut self, interval: Duration) -> &mut TailOptions {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets whether the data already in the file is emitted first, rather
+    /// than only what is appended later.
+    pub fn from_start(&mut self, from_start: bool) -> &mut TailOptions {
+        self.from_start = from_start;
+        self
+    }
+
+    /// Returns a stream of the data appended to the file at `path`.
+    ///
+    /// The stream never ends on its own. The file does not need to exist
+    /// yet, and is picked up once it is created.
+    ///
+    /// If the file is truncated, it is read again from the start. If it is
+    /// rotated, that is renamed and replaced by a new file with the same
+    /// name, the rest of the old file is read before following the new one
+    /// from its start. Rotation is detected on Unix only.
+    pub fn tail<P>(&self, path: P) -> impl Stream<Item = Vec<u8>, Error = io::Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let wake = wake(&path, self.interval);
+        let follow = Follow {
+            path,
+            file: None,
+            pos: 0,
+            seek_end: !self.from_start,
+        };
+        stream::unfold((follow, wake), |(follow, wake)| {
+            let next = future::loop_fn((follow, wake), |(follow, wake)| {
+                crate::blocking(move || follow.read()).and_then(|(data, follow)| match data {
+                    Some(data) => Either::A(future::ok(Loop::Break((data, (follow, wake))))),
+                    None => Either::B(
+                        wake.into_future()
+                            .map(|(_, wake)| Loop::Continue((follow, wake)))
+                            .map_err(|(err, _)| err),
+                    ),
+                })
+            });
+            Some(next)
+        })
+    }
+}
+
+impl Default for TailOptions {
+    fn default() -> TailOptions {
+        TailOptions::new()
+    }
+}
+
+/// Returns a stream of the data appended to the file at `path`, starting at
+/// its current end.
+///
+/// See [`TailOptions`] for details and more control.
+///
+/// [`TailOptions`]: struct.TailOptions.html
+pub fn tail<P>(path: P) -> impl Stream<Item = Vec<u8>, Error = io::Error>
+where
+    P: Into<PathBuf>,
+{
+    TailOptions::new().tail(path)
+}
+
+type Wake = Box<dyn Stream<Item = (), Error = io::Error> + Send>;
+
+/// Returns a stream that yields whenever the file may have changed.
+fn wake(path: &Path, interval: Duration) -> Wake {
+    let poll = Interval::new_interval(interval)
+        .map(|_| ())
+        .map_err(crate::blocking_err);
+    #[cfg(feature = "watch")]
+    {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+            _ => PathBuf::from("."),
+        };
+        // Watching may fail, e.g. when the directory does not exist yet,
+        // which leaves polling to do the work.
+        let events = crate::WatchOptions::new()
+            .recursive(false)
+            .delay(Duration::from_millis(10))
+            .watch(dir)
+            .map(|_| ())
+            .or_else(|_| Ok(()));
+        Box::new(poll.select(events))
+    }
+    #[cfg(not(feature = "watch"))]
+    {
+        let _ = path;
+        Box::new(poll)
+    }
+}
+
+struct Follow {
+    path: PathBuf,
+    file: Option<(StdFile, Metadata)>,
+    pos: u64,
+    seek_end: bool,
+}
+
+impl Follow {
+    /// Reads the next chunk of new data, if there is any.
+    fn read(mut self) -> io::Result<(Option<Vec<u8>>, Follow)> {
+        if self.file.is_none() {
+            let file = match StdFile::open(&self.path) {
+                Ok(file) => file,
+                Err(ref err) if err.kind() == ErrorKind::NotFound => {
+                    // Only files that already exist are skipped to their end.
+                    self.seek_end = false;
+                    return Ok((None, self));
+                }
+                Err(err) => return Err(err),
+            };
+            let metadata = file.metadata()?;
+            self.pos = if self.seek_end { metadata.len() } else { 0 };
+            self.seek_end = false;
+            self.file = Some((file, metadata));
+        }
+
+        let (file, metadata) = self.file.as_mut().unwrap();
+        if file.metadata()?.len() < self.pos {
+            // Truncated.
+            self.pos = 0;
+        }
+        let mut buf = vec![0; CHUNK_SIZE];
+        file.seek(SeekFrom::Start(self.pos))?;
+        let n = loop {
+            match file.read(&mut buf) {
+                Ok(n) => break n,
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        };
+        if n > 0 {
+            buf.truncate(n);
+            self.pos += n as u64;
+            return Ok((Some(buf), self));
+        }
+
+        // At the end of the file, switch over if it has been rotated.
+        let rotated = match fs::metadata(&self.path) {
+            Ok(current) => !same_file(metadata, &current),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => false,
+            Err(err) => return Err(err),
+        };
+        if rotated {
+            self.file = None;
+            return self.read();
+        }
+        Ok((None, self))
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(_: &Metadata, _: &Metadata) -> bool {
+    true
+}
@@ -0,0 +1,289 @@
+//! Utilities for testing code that works with the filesystem.
+//!
+//! A [`Sandbox`] is a temporary directory that is removed when dropped. It
+//! can be populated with a [`Tree`] written out as literals, and checked
+//! afterwards with [`assert_file_eq`] and [`assert_tree_matches`]:
+//!
+//! ```no_run
+//! # use actix_fs::testing::{assert_tree_matches, Sandbox, Tree};
+//! # use futures::Future;
+//! let tree = Tree::new()
+//!     .file("config.toml", "debug = true")
+//!     .dir("uploads");
+//! let test = Sandbox::with_tree(tree).map(|sandbox| {
+//!     // Run the code under test against `sandbox.path()`.
+//!     assert_tree_matches(
+//!         sandbox.path(),
+//!         &Tree::new().file("config.toml", "debug = true").dir("uploads"),
+//!     );
+//! });
+//! ```
+//!
+//! This module is only available with the `testing` feature.
+//!
+//! [`Sandbox`]: struct.Sandbox.html
+//! [`Tree`]: struct.Tree.html
+//! [`assert_file_eq`]: fn.assert_file_eq.html
+//! [`assert_tree_matches`]: fn.assert_tree_matches.html
+
+use futures::Future;
+use tempfile::TempDir;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::{error, Root};
+
+/// A temporary directory for a test, removed with everything in it when
+/// dropped.
+#[derive(Debug)]
+pub struct Sandbox {
+    dir: TempDir,
+}
+
+impl Sandbox {
+    /// Creates an empty sandbox.
+    pub fn new() -> impl Future<Item = Sandbox, Error = io::Error> {
+        crate::blocking(|| {
+            Ok(Sandbox {
+                dir: TempDir::new()?,
+            })
+        })
+    }
+
+    /// Creates a sandbox holding `tree`.
+    pub fn with_tree(tree: Tree) -> impl Future<Item = Sandbox, Error = io::Error> {
+        Sandbox::new().and_then(|sandbox| {
+            let path = sandbox.path().to_owned();
+            tree.write_to(path).map(|()| sandbox)
+        })
+    }
+
+    /// Returns the path of the sandbox.
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Returns the path of `path` within the sandbox.
+    pub fn join<P>(&self, path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        self.dir.path().join(path)
+    }
+
+    /// Returns a [`Root`] confined to the sandbox.
+    ///
+    /// [`Root`]: ../struct.Root.html
+    pub fn root(&self) -> Root {
+        Root::new(self.dir.path())
+    }
+}
+
+/// A tree of files and directories, by path relative to its root.
+///
+/// The parents of every entry are part of the tree as well, so they need
+/// not be listed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tree {
+    // The contents of each file, or `None` for a directory.
+    entries: BTreeMap<PathBuf, Option<Vec<u8>>>,
+}
+
+impl Tree {
+    /// Creates an empty tree.
+    pub fn new() -> Tree {
+        Tree::default()
+    }
+
+    /// Adds a file holding `contents` at `path`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `path` is empty, absolute, or has `.` or `..` components,
+    /// as it could not be written within the tree's root.
+    #[track_caller]
+    pub fn file<P, C>(mut self, path: P, contents: C) -> Tree
+    where
+        P: AsRef<Path>,
+        C: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+        check_relative(path);
+        self.add_parents(path);
+        self.entries
+            .insert(path.to_owned(), Some(contents.as_ref().to_vec()));
+        self
+    }
+
+    /// Adds a directory at `path`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `path` is not a plain relative path, like [`file`].
+    ///
+    /// [`file`]: #method.file
+    #[track_caller]
+    pub fn dir<P>(mut self, path: P) -> Tree
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        check_relative(path);
+        self.add_parents(path);
+        self.entries.insert(path.to_owned(), None);
+        self
+    }
+
+    /// Reads the tree at `path`.
+    ///
+    /// Entries that are neither files nor directories, such as symbolic
+    /// links, are read as files holding their target.
+    pub fn read<P>(path: P) -> impl Future<Item = Tree, Error = io::Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        crate::blocking(move || read_tree(&path))
+    }
+
+    /// Writes the tree into the directory at `path`, creating it if needed
+    /// and replacing files that already exist.
+    pub fn write_to<P>(self, path: P) -> impl Future<Item = (), Error = io::Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let root = path.into();
+        crate::blocking(move || {
+            fs::create_dir_all(&root)
+                .map_err(|err| error::with_path(err, "create directory", &root))?;
+            // Parents sort before their children.
+            for (relative, contents) in &self.entries {
+                let path = root.join(relative);
+                let res = match *contents {
+                    Some(ref contents) => fs::write(&path, contents),
+                    None => fs::create_dir_all(&path),
+                };
+                res.map_err(|err| error::with_path(err, "write", &path))?;
+            }
+            Ok(())
+        })
+    }
+
+    fn add_parents(&mut self, path: &Path) {
+        for parent in path.ancestors().skip(1) {
+            if !parent.as_os_str().is_empty() {
+                self.entries.insert(parent.to_owned(), None);
+            }
+        }
+    }
+}
+
+#[track_caller]
+fn check_relative(path: &Path) {
+    let mut components = path.components().peekable();
+    let plain = components.peek().is_some()
+        && components.all(|component| matches!(component, Component::Normal(_)));
+    if !plain {
+        panic!(
+            "tree paths must be relative without `.` or `..`, found {}",
+            path.display()
+        );
+    }
+}
+
+fn read_tree(root: &Path) -> io::Result<Tree> {
+    let mut tree = Tree::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let dir = root.join(&relative);
+        let entries =
+            fs::read_dir(&dir).map_err(|err| error::with_path(err, "read directory", &dir))?;
+        for entry in entries {
+            let entry = entry?;
+            let relative = relative.join(entry.file_name());
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            let contents = if file_type.is_dir() {
+                pending.push(relative.clone());
+                None
+            } else if file_type.is_file() {
+                Some(fs::read(&path).map_err(|err| error::with_path(err, "read", &path))?)
+            } else {
+                let target = fs::read_link(&path)?;
+                Some(target.to_string_lossy().into_owned().into_bytes())
+            };
+            tree.entries.insert(relative, contents);
+        }
+    }
+    Ok(tree)
+}
+
+/// Asserts that the file at `path` holds `expected`.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read or holds something else, showing
+/// both contents as text.
+#[track_caller]
+pub fn assert_file_eq<P, C>(path: P, expected: C)
+where
+    P: AsRef<Path>,
+    C: AsRef<[u8]>,
+{
+    let path = path.as_ref();
+    let actual = match fs::read(path) {
+        Ok(actual) => actual,
+        Err(err) => panic!("cannot read {}: {}", path.display(), err),
+    };
+    let expected = expected.as_ref();
+    if actual != expected {
+        panic!(
+            "contents of {} differ\n  expected: {:?}\n    actual: {:?}",
+            path.display(),
+            String::from_utf8_lossy(expected),
+            String::from_utf8_lossy(&actual),
+        );
+    }
+}
+
+/// Asserts that the tree at `path` is exactly `expected`.
+///
+/// # Panics
+///
+/// Panics if the tree cannot be read or differs from `expected`, listing
+/// every entry that is missing, unexpected, or different.
+#[track_caller]
+pub fn assert_tree_matches<P>(path: P, expected: &Tree)
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let actual = match read_tree(path) {
+        Ok(actual) => actual,
+        Err(err) => panic!("cannot read tree {}: {}", path.display(), err),
+    };
+    let mut diff = String::new();
+    for (relative, contents) in &expected.entries {
+        match actual.entries.get(relative) {
+            None => {
+                let _ = writeln!(diff, "  missing: {}", relative.display());
+            }
+            Some(actual) if actual != contents => {
+                let _ = writeln!(diff, "  differs: {}", relative.display());
+            }
+            Some(_) => {}
+        }
+    }
+    for relative in actual.entries.keys() {
+        if !expected.entries.contains_key(relative) {
+            let _ = writeln!(diff, "  unexpected: {}", relative.display());
+        }
+    }
+    if !diff.is_empty() {
+        panic!("tree {} does not match\n{}", path.display(), diff);
+    }
+}
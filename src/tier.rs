@@ -0,0 +1,341 @@
+use futures::Future;
+
+use std::fmt;
+use std::fs::{self, File as StdFile, FileTimes};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+use crate::hash::{self, Algorithm, Verify};
+#[cfg(any(feature = "sha2", feature = "blake3"))]
+use crate::CancellationToken;
+use crate::{error, File, OpenOptions, Root};
+
+/// How often a file is expected to be read, as a hint for which storage it
+/// belongs on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Temperature {
+    /// Read often, such as new uploads, so it belongs on fast storage.
+    Hot,
+    /// Read now and then.
+    Warm,
+    /// Rarely read, such as an archive, so it belongs on cheap storage.
+    Cold,
+}
+
+impl Temperature {
+    /// Every temperature, from hot to cold.
+    const ALL: [Temperature; 3] = [Temperature::Hot, Temperature::Warm, Temperature::Cold];
+}
+
+/// Chooses where files of each [`Temperature`] are stored, for [`Tiers`].
+///
+/// Any closure taking a temperature and the path of a file relative to the
+/// tiers, and returning a [`Root`], implements this trait.
+///
+/// [`Temperature`]: enum.Temperature.html
+/// [`Tiers`]: struct.Tiers.html
+/// [`Root`]: struct.Root.html
+pub trait TierPolicy: Send + Sync + 'static {
+    /// Returns the root a file at `path` of `temperature` is stored in.
+    ///
+    /// The policy has to return the same root for the same arguments, as
+    /// it is also asked where to look for existing files.
+    fn root(&self, temperature: Temperature, path: &Path) -> Root;
+}
+
+impl<F> TierPolicy for F
+where
+    F: Fn(Temperature, &Path) -> Root + Send + Sync + 'static,
+{
+    fn root(&self, temperature: Temperature, path: &Path) -> Root {
+        self(temperature, path)
+    }
+}
+
+/// Storage tiering, placing files on different directories or volumes by
+/// their [`Temperature`], such as new media on an NVMe mount and old media
+/// on an HDD mount.
+///
+/// Files are addressed by a path relative to the tiers, and a
+/// [`TierPolicy`] chooses the root they are stored in:
+///
+/// ```no_run
+/// # use actix_fs::{Root, Temperature, Tiers};
+/// let tiers = Tiers::new(|temperature, _: &std::path::Path| match temperature {
+///     Temperature::Hot => Root::new("/mnt/nvme/media"),
+///     Temperature::Warm | Temperature::Cold => Root::new("/mnt/hdd/media"),
+/// });
+/// let upload = tiers.create("videos/42.mp4", Temperature::Hot);
+/// // Later, once the video is no longer new:
+/// let archived = tiers.migrate("videos/42.mp4", Temperature::Cold);
+/// ```
+///
+/// Cloning a `Tiers` produces another handle to the same policy.
+///
+/// [`Temperature`]: enum.Temperature.html
+/// [`TierPolicy`]: trait.TierPolicy.html
+#[derive(Clone)]
+pub struct Tiers {
+    policy: Arc<dyn TierPolicy>,
+    #[cfg(any(feature = "sha2", feature = "blake3"))]
+    verify: Option<(Algorithm, Verify)>,
+}
+
+impl Tiers {
+    /// Creates tiers placing files as chosen by `policy`.
+    pub fn new<T>(policy: T) -> Tiers
+    where
+        T: TierPolicy,
+    {
+        Tiers {
+            policy: Arc::new(policy),
+            #[cfg(any(feature = "sha2", feature = "blake3"))]
+            verify: None,
+        }
+    }
+
+    /// Verifies files [`migrate`] copies to another filesystem with
+    /// `algorithm`, like [`hash::copy_verified`], before they replace the
+    /// original.
+    ///
+    /// A copy that does not match fails the migration with `InvalidData`
+    /// and leaves the file in its old tier. Renames within a filesystem do
+    /// not copy, so they are not verified.
+    ///
+    /// This is only available with the `sha2` or `blake3` feature.
+    ///
+    /// [`migrate`]: #method.migrate
+    /// [`hash::copy_verified`]: hash/fn.copy_verified.html
+    #[cfg(any(feature = "sha2", feature = "blake3"))]
+    pub fn verify(mut self, algorithm: Algorithm, verify: Verify) -> Tiers {
+        self.verify = Some((algorithm, verify));
+        self
+    }
+
+    /// Returns the root the policy chooses for a file at `path` of
+    /// `temperature`.
+    pub fn root<P>(&self, path: P, temperature: Temperature) -> Root
+    where
+        P: AsRef<Path>,
+    {
+        self.policy.root(temperature, path.as_ref())
+    }
+
+    /// Resolves `path` in the root the policy chooses for `temperature`,
+    /// like [`Root::resolve`].
+    ///
+    /// [`Root::resolve`]: struct.Root.html#method.resolve
+    pub fn resolve<P>(&self, path: P, temperature: Temperature) -> io::Result<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        self.root(path.as_ref(), temperature).resolve(path)
+    }
+
+    /// Opens a file at `path` of `temperature` in write-only mode, creating
+    /// it and any missing parent directories, or truncating it if it
+    /// exists.
+    ///
+    /// A copy of the file in another tier is left as it is, so write a new
+    /// file with the temperature it was found at, or [`migrate`] it first.
+    ///
+    /// [`migrate`]: #method.migrate
+    pub fn create<P>(
+        &self,
+        path: P,
+        temperature: Temperature,
+    ) -> impl Future<Item = File, Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let resolved = self.resolve(path, temperature);
+        crate::blocking(move || {
+            let path = resolved?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|err| error::with_path(err, "create directory", parent))?;
+            }
+            Ok(path)
+        })
+        .and_then(|path| {
+            let mut opts = OpenOptions::new();
+            opts.write(true).create(true).truncate(true);
+            opts.open(path)
+        })
+    }
+
+    /// Opens the file at `path` in read-only mode, from whichever tier it is
+    /// stored in.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `NotFound` if the file is in none of the tiers, and with
+    /// `InvalidInput` if it is not a regular file.
+    pub fn open<P>(&self, path: P) -> impl Future<Item = File, Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let tiers = self.clone();
+        let path = path.as_ref().to_owned();
+        crate::blocking(move || match tiers.find(&path)? {
+            Some((_, found)) => Ok(found),
+            None => Err(not_found(&path)),
+        })
+        .and_then(File::open)
+    }
+
+    /// Returns the temperature of the tier the file at `path` is stored in,
+    /// or `None` if it is in none of them.
+    ///
+    /// Tiers are checked from hot to cold, so a file left in several tiers
+    /// is found in the hottest. Temperatures whose roots the policy shares
+    /// cannot be told apart, so a file in a shared root is reported at the
+    /// hottest temperature using it, such as `Warm` for a file migrated to
+    /// `Cold` if both are stored on the same root.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `InvalidInput` if `path` is not a regular file, such as a
+    /// directory.
+    pub fn locate<P>(&self, path: P) -> impl Future<Item = Option<Temperature>, Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let tiers = self.clone();
+        let path = path.as_ref().to_owned();
+        crate::blocking(move || Ok(tiers.find(&path)?.map(|(temperature, _)| temperature)))
+    }
+
+    /// Moves the file at `path` to the tier of `temperature`.
+    ///
+    /// Nothing happens if the file is already there. Otherwise it is
+    /// renamed, or if the tiers are on different filesystems, copied to a
+    /// temporary file next to its new place, synced, renamed into place and
+    /// then removed from its old tier. Readers therefore always find a
+    /// complete copy, and a failed migration leaves the file where it was.
+    /// The modification time is kept.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `NotFound` if the file is in none of the tiers, and with
+    /// `InvalidInput` if it is not a regular file.
+    pub fn migrate<P>(
+        &self,
+        path: P,
+        temperature: Temperature,
+    ) -> impl Future<Item = (), Error = io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let tiers = self.clone();
+        let path = path.as_ref().to_owned();
+        crate::blocking(move || {
+            let from = match tiers.find(&path)? {
+                Some((_, from)) => from,
+                None => return Err(not_found(&path)),
+            };
+            let to = tiers.resolve(&path, temperature)?;
+            if from == to {
+                return Ok(());
+            }
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|err| error::with_path(err, "create directory", parent))?;
+            }
+            match fs::rename(&from, &to) {
+                Ok(()) => Ok(()),
+                Err(ref err) if err.kind() == ErrorKind::CrossesDevices => {
+                    tiers.move_across(&from, &to)
+                }
+                Err(err) => Err(error::with_paths(err, "rename", &from, &to)),
+            }
+        })
+    }
+
+    /// Finds the tier the file at `path` is stored in, and its path there.
+    ///
+    /// Each path is checked once, at the hottest temperature resolving to
+    /// it.
+    fn find(&self, path: &Path) -> io::Result<Option<(Temperature, PathBuf)>> {
+        let mut checked = Vec::with_capacity(Temperature::ALL.len());
+        for &temperature in &Temperature::ALL {
+            let resolved = self.resolve(path, temperature)?;
+            if checked.contains(&resolved) {
+                continue;
+            }
+            match fs::symlink_metadata(&resolved) {
+                Ok(ref metadata) if metadata.is_file() => {
+                    return Ok(Some((temperature, resolved)));
+                }
+                Ok(_) => {
+                    return Err(error::with_path(
+                        io::Error::new(ErrorKind::InvalidInput, "not a file"),
+                        "locate",
+                        &resolved,
+                    ));
+                }
+                Err(ref err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(error::with_path(err, "stat", &resolved)),
+            }
+            checked.push(resolved);
+        }
+        Ok(None)
+    }
+
+    /// Moves the file at `from` to `to` on another filesystem.
+    fn move_across(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut tmp = to.to_owned().into_os_string();
+        tmp.push(format!(".{}.migrate", std::process::id()));
+        let tmp = PathBuf::from(tmp);
+        let copied = (|| {
+            let modified = fs::metadata(from)?.modified()?;
+            self.copy(from, &tmp)?;
+            let copy = StdFile::options().write(true).open(&tmp)?;
+            copy.set_times(FileTimes::new().set_modified(modified))?;
+            copy.sync_all()
+        })();
+        if let Err(err) = copied {
+            let _ = fs::remove_file(&tmp);
+            return Err(error::with_paths(err, "copy", from, to));
+        }
+        fs::rename(&tmp, to).map_err(|err| {
+            let _ = fs::remove_file(&tmp);
+            error::with_paths(err, "rename", &tmp, to)
+        })?;
+        fs::remove_file(from).map_err(|err| error::with_path(err, "remove", from))
+    }
+
+    /// Copies the file at `from` to `to`, verifying the copy if asked to.
+    #[cfg(any(feature = "sha2", feature = "blake3"))]
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match self.verify {
+            Some((algorithm, verify)) => {
+                let token = CancellationToken::new();
+                hash::copy_file_verified(from, to, algorithm, verify, &token)?;
+                fs::set_permissions(to, fs::metadata(from)?.permissions())
+            }
+            None => fs::copy(from, to).map(|_| ()),
+        }
+    }
+
+    #[cfg(not(any(feature = "sha2", feature = "blake3")))]
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+impl fmt::Debug for Tiers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tiers").finish()
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    error::with_path(
+        io::Error::new(ErrorKind::NotFound, "file is in no tier"),
+        "locate",
+        path,
+    )
+}
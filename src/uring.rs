@@ -0,0 +1,389 @@
+//! The io_uring backend, enabled with the `uring` feature on Linux.
+//!
+//! Every function hands its arguments back when io_uring is unavailable,
+//! either because the feature is disabled, because the running kernel does
+//! not support every operation used here, which takes Linux 5.6, or because
+//! the ring failed, so callers can fall back to the threadpool.
+//!
+//! The kernel offers no io_uring operation for listing directories, so those
+//! always run on the threadpool.
+
+pub(crate) use self::imp::{fsync, open, read, write_all};
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
+mod imp {
+    use futures::future::{self, Either, Loop};
+    use futures::sync::oneshot;
+    use futures::Future;
+    use io_uring::{opcode, squeue, types, IoUring, Probe};
+
+    use std::collections::HashMap;
+    use std::ffi::CString;
+    use std::fs::File as StdFile;
+    use std::io::{self, ErrorKind};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+    use std::sync::{Arc, Mutex, OnceLock};
+    use std::{mem, thread};
+
+    use crate::file::Flags;
+
+    const ENTRIES: u32 = 256;
+    // `user_data` of the read that wakes the driver for new submissions.
+    const WAKE: u64 = u64::MAX;
+
+    /// Memory and descriptors an operation refers to, kept alive until the
+    /// kernel has completed it.
+    #[derive(Default)]
+    struct Resources {
+        file: Option<StdFile>,
+        buf: Vec<u8>,
+        // Only referenced by the kernel, through the submitted entry.
+        #[allow(dead_code)]
+        path: Option<CString>,
+    }
+
+    struct Op {
+        entry: squeue::Entry,
+        resources: Resources,
+        tx: oneshot::Sender<(i32, Resources)>,
+    }
+
+    struct Driver {
+        ops: Mutex<Sender<Op>>,
+        wake: RawFd,
+        // Cleared once the ring has failed, after which nothing is submitted.
+        alive: Arc<AtomicBool>,
+    }
+
+    fn driver() -> Option<&'static Driver> {
+        static DRIVER: OnceLock<Option<Driver>> = OnceLock::new();
+        DRIVER
+            .get_or_init(|| Driver::start().ok())
+            .as_ref()
+            .filter(|driver| driver.alive.load(Ordering::SeqCst))
+    }
+
+    /// Opcodes submitted by this module.
+    const OPCODES: [u8; 4] = [
+        opcode::OpenAt::CODE,
+        opcode::Read::CODE,
+        opcode::Write::CODE,
+        opcode::Fsync::CODE,
+    ];
+
+    impl Driver {
+        fn start() -> io::Result<Driver> {
+            let ring = IoUring::new(ENTRIES)?;
+            // Kernels before 5.6 can set up a ring but support neither the
+            // probe nor some of the opcodes, so those fail here.
+            let mut probe = Probe::new();
+            ring.submitter().register_probe(&mut probe)?;
+            if !OPCODES.iter().all(|&code| probe.is_supported(code)) {
+                return Err(io::Error::new(
+                    ErrorKind::Unsupported,
+                    "io_uring lacks required opcodes",
+                ));
+            }
+            let wake = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+            if wake < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let (tx, rx) = mpsc::channel();
+            let alive = Arc::new(AtomicBool::new(true));
+            let running = alive.clone();
+            thread::Builder::new()
+                .name("actix-fs-uring".to_owned())
+                .spawn(move || {
+                    run(ring, wake, rx);
+                    running.store(false, Ordering::SeqCst);
+                })?;
+            Ok(Driver {
+                ops: Mutex::new(tx),
+                wake,
+                alive,
+            })
+        }
+
+        fn submit(
+            &self,
+            entry: squeue::Entry,
+            resources: Resources,
+        ) -> impl Future<Item = (i32, Resources), Error = io::Error> {
+            let (tx, rx) = oneshot::channel();
+            let op = Op {
+                entry,
+                resources,
+                tx,
+            };
+            if self.ops.lock().unwrap().send(op).is_ok() {
+                let one: u64 = 1;
+                unsafe {
+                    libc::write(self.wake, &one as *const u64 as *const libc::c_void, 8);
+                }
+            }
+            rx.map_err(|_| io::Error::other("io_uring driver is gone"))
+        }
+    }
+
+    /// Submits operations as they arrive and completes them as the kernel
+    /// finishes them, returning if the ring fails.
+    ///
+    /// Operations in flight then fail, and those not yet submitted are
+    /// dropped, failing too.
+    fn run(mut ring: IoUring, wake: RawFd, rx: Receiver<Op>) {
+        let mut inflight: HashMap<u64, (Resources, oneshot::Sender<_>)> = HashMap::new();
+        let mut next: u64 = 0;
+        let mut counter = Box::new([0u8; 8]);
+        let arm = opcode::Read::new(types::Fd(wake), counter.as_mut_ptr(), 8)
+            .build()
+            .user_data(WAKE);
+        push(&mut ring, &arm);
+
+        loop {
+            match ring.submit_and_wait(1) {
+                Ok(_) => {}
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return abandon(inflight, counter),
+            }
+
+            let done: Vec<(u64, i32)> = ring
+                .completion()
+                .map(|cqe| (cqe.user_data(), cqe.result()))
+                .collect();
+            for (key, res) in done {
+                if key != WAKE {
+                    if let Some((resources, tx)) = inflight.remove(&key) {
+                        let _ = tx.send((res, resources));
+                    }
+                    continue;
+                }
+                loop {
+                    match rx.try_recv() {
+                        Ok(op) => {
+                            let key = next;
+                            next = (next + 1) % WAKE;
+                            push(&mut ring, &op.entry.user_data(key));
+                            inflight.insert(key, (op.resources, op.tx));
+                        }
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => return abandon(inflight, counter),
+                    }
+                }
+                push(&mut ring, &arm);
+            }
+        }
+    }
+
+    /// Fails the operations in flight by dropping their senders.
+    ///
+    /// The kernel may still refer to their buffers, and to the counter the
+    /// wake read fills, so those are never freed.
+    fn abandon(
+        inflight: HashMap<u64, (Resources, oneshot::Sender<(i32, Resources)>)>,
+        counter: Box<[u8; 8]>,
+    ) {
+        for (_, (resources, tx)) in inflight {
+            mem::forget(resources);
+            drop(tx);
+        }
+        mem::forget(counter);
+    }
+
+    fn push(ring: &mut IoUring, entry: &squeue::Entry) {
+        loop {
+            if unsafe { ring.submission().push(entry) }.is_ok() {
+                return;
+            }
+            // The submission queue is full, hand what is queued to the kernel
+            // to make room.
+            let _ = ring.submit();
+        }
+    }
+
+    fn check(res: i32) -> io::Result<usize> {
+        if res < 0 {
+            Err(io::Error::from_raw_os_error(-res))
+        } else {
+            Ok(res as usize)
+        }
+    }
+
+    /// Translates `flags` the way `std::fs::OpenOptions` does, returning
+    /// `None` for invalid combinations so `std` reports the error.
+    fn open_flags(flags: Flags) -> Option<i32> {
+        let access = match (flags.read, flags.write, flags.append) {
+            (true, false, false) => libc::O_RDONLY,
+            (false, true, false) => libc::O_WRONLY,
+            (true, true, false) => libc::O_RDWR,
+            (false, _, true) => libc::O_WRONLY | libc::O_APPEND,
+            (true, _, true) => libc::O_RDWR | libc::O_APPEND,
+            (false, false, false) => return None,
+        };
+        match (flags.write, flags.append) {
+            (true, false) => {}
+            (false, false) if flags.truncate || flags.create || flags.create_new => return None,
+            (false, false) => {}
+            (_, true) if flags.truncate && !flags.create_new => return None,
+            (_, true) => {}
+        }
+        let creation = match (flags.create, flags.truncate, flags.create_new) {
+            (false, false, false) => 0,
+            (true, false, false) => libc::O_CREAT,
+            (false, true, false) => libc::O_TRUNC,
+            (true, true, false) => libc::O_CREAT | libc::O_TRUNC,
+            (_, _, true) => libc::O_CREAT | libc::O_EXCL,
+        };
+        Some(access | creation | libc::O_CLOEXEC)
+    }
+
+    pub(crate) fn open(
+        path: &Path,
+        flags: Flags,
+    ) -> Option<impl Future<Item = StdFile, Error = io::Error>> {
+        let driver = driver()?;
+        let flags = open_flags(flags)?;
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let entry = opcode::OpenAt::new(types::Fd(libc::AT_FDCWD), path.as_ptr())
+            .flags(flags)
+            .mode(0o666)
+            .build();
+        let resources = Resources {
+            path: Some(path),
+            ..Resources::default()
+        };
+        Some(driver.submit(entry, resources).and_then(|(res, _)| {
+            let fd = check(res)?;
+            Ok(unsafe { StdFile::from_raw_fd(fd as RawFd) })
+        }))
+    }
+
+    pub(crate) fn read(
+        file: StdFile,
+        len: usize,
+    ) -> Result<impl Future<Item = (StdFile, Vec<u8>), Error = io::Error>, StdFile> {
+        let driver = match driver() {
+            Some(driver) => driver,
+            None => return Err(file),
+        };
+        let len = len.min(u32::MAX as usize);
+        let mut buf = Vec::with_capacity(len);
+        // An offset of -1 reads from, and advances, the file position.
+        let entry = opcode::Read::new(types::Fd(file.as_raw_fd()), buf.as_mut_ptr(), len as u32)
+            .offset64(-1)
+            .build();
+        let resources = Resources {
+            file: Some(file),
+            buf,
+            path: None,
+        };
+        Ok(driver
+            .submit(entry, resources)
+            .and_then(|(res, resources)| {
+                let n = check(res)?;
+                let mut buf = resources.buf;
+                unsafe { buf.set_len(n) };
+                Ok((resources.file.unwrap(), buf))
+            }))
+    }
+
+    pub(crate) fn write_all(
+        file: StdFile,
+        buf: Vec<u8>,
+    ) -> Result<impl Future<Item = StdFile, Error = io::Error>, (StdFile, Vec<u8>)> {
+        let driver = match driver() {
+            Some(driver) => driver,
+            None => return Err((file, buf)),
+        };
+        let write = future::loop_fn((file, buf, 0), move |(file, buf, written)| {
+            if written == buf.len() {
+                return Either::A(future::ok(Loop::Break(file)));
+            }
+            let len = (buf.len() - written).min(u32::MAX as usize);
+            let entry = opcode::Write::new(
+                types::Fd(file.as_raw_fd()),
+                buf[written..].as_ptr(),
+                len as u32,
+            )
+            .offset64(-1)
+            .build();
+            let resources = Resources {
+                file: Some(file),
+                buf,
+                path: None,
+            };
+            Either::B(
+                driver
+                    .submit(entry, resources)
+                    .and_then(move |(res, resources)| {
+                        let n = check(res)?;
+                        if n == 0 {
+                            return Err(io::Error::new(
+                                ErrorKind::WriteZero,
+                                "failed to write whole buffer",
+                            ));
+                        }
+                        let file = resources.file.unwrap();
+                        Ok(Loop::Continue((file, resources.buf, written + n)))
+                    }),
+            )
+        });
+        Ok(write)
+    }
+
+    pub(crate) fn fsync(
+        file: StdFile,
+    ) -> Result<impl Future<Item = StdFile, Error = io::Error>, StdFile> {
+        let driver = match driver() {
+            Some(driver) => driver,
+            None => return Err(file),
+        };
+        let entry = opcode::Fsync::new(types::Fd(file.as_raw_fd())).build();
+        let resources = Resources {
+            file: Some(file),
+            ..Resources::default()
+        };
+        Ok(driver
+            .submit(entry, resources)
+            .and_then(|(res, resources)| {
+                check(res)?;
+                Ok(resources.file.unwrap())
+            }))
+    }
+}
+
+#[cfg(not(all(feature = "uring", target_os = "linux")))]
+mod imp {
+    use futures::future::Empty;
+
+    use std::fs::File as StdFile;
+    use std::io;
+    use std::path::Path;
+
+    use crate::file::Flags;
+
+    pub(crate) fn open(_: &Path, _: Flags) -> Option<Empty<StdFile, io::Error>> {
+        None
+    }
+
+    pub(crate) fn read(
+        file: StdFile,
+        _: usize,
+    ) -> Result<Empty<(StdFile, Vec<u8>), io::Error>, StdFile> {
+        Err(file)
+    }
+
+    pub(crate) fn write_all(
+        file: StdFile,
+        buf: Vec<u8>,
+    ) -> Result<Empty<StdFile, io::Error>, (StdFile, Vec<u8>)> {
+        Err((file, buf))
+    }
+
+    pub(crate) fn fsync(file: StdFile) -> Result<Empty<StdFile, io::Error>, StdFile> {
+        Err(file)
+    }
+}
@@ -0,0 +1,188 @@
+use futures::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::{Async, Poll, Stream};
+use notify::{DebouncedEvent, RecursiveMode, Watcher as _};
+
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// A change observed on the filesystem by [`watch`].
+///
+/// [`watch`]: fn.watch.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A file or directory was created.
+    Create(PathBuf),
+    /// The contents or metadata of a file or directory changed.
+    Modify(PathBuf),
+    /// A file or directory was removed.
+    Remove(PathBuf),
+    /// A file or directory was renamed from the first path to the second.
+    Rename(PathBuf, PathBuf),
+    /// Events may have been missed, so any state derived from the watched
+    /// tree should be rebuilt.
+    Rescan,
+}
+
+/// Options which can be used to configure how a path is watched.
+///
+/// Created with [`WatchOptions::new`], which watches recursively and
+/// debounces events over 100 milliseconds.
+///
+/// [`WatchOptions::new`]: #method.new
+#[derive(Clone, Debug)]
+pub struct WatchOptions {
+    recursive: bool,
+    delay: Duration,
+}
+
+impl WatchOptions {
+    /// Creates the default set of options.
+    pub fn new() -> WatchOptions {
+        WatchOptions {
+            recursive: true,
+            delay: Duration::from_millis(100),
+        }
+    }
+
+    /// Sets whether sub-directories, including ones created later, are
+    /// watched as well.
+    pub fn recursive(&mut self, recursive: bool) -> &mut WatchOptions {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Sets how long events are collected for before being reported.
+    ///
+    /// Bursts of events on the same path within this window, such as the
+    /// several writes an editor makes when saving, are reported once.
+    pub fn delay(&mut self, delay: Duration) -> &mut WatchOptions {
+        self.delay = delay;
+        self
+    }
+
+    /// Starts watching `path` with the options specified by `self`.
+    ///
+    /// # Errors
+    ///
+    /// The returned stream yields an error if the watch could not be
+    /// installed, for example because `path` does not exist, or if the
+    /// platform backend reports a failure while watching.
+    pub fn watch<P>(&self, path: P) -> Watch
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded();
+        let opts = self.clone();
+        thread::spawn(move || bridge(opts, path.as_ref(), tx));
+        Watch { rx }
+    }
+}
+
+impl Default for WatchOptions {
+    fn default() -> WatchOptions {
+        WatchOptions::new()
+    }
+}
+
+/// Watches `path` and everything below it for changes.
+///
+/// Events are delivered by inotify, FSEvents or `ReadDirectoryChangesW`
+/// depending on the platform. Watching stops when the returned stream is
+/// dropped.
+///
+/// See [`WatchOptions`] for more control over how the path is watched.
+///
+/// [`WatchOptions`]: struct.WatchOptions.html
+pub fn watch<P>(path: P) -> Watch
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    WatchOptions::new().watch(path)
+}
+
+/// A stream of [`Event`]s for a watched path.
+///
+/// Created by [`watch`] or [`WatchOptions::watch`].
+///
+/// [`Event`]: enum.Event.html
+/// [`watch`]: fn.watch.html
+/// [`WatchOptions::watch`]: struct.WatchOptions.html#method.watch
+#[derive(Debug)]
+pub struct Watch {
+    rx: UnboundedReceiver<io::Result<Event>>,
+}
+
+impl Stream for Watch {
+    type Item = Event;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Event>, io::Error> {
+        match self.rx.poll() {
+            Ok(Async::Ready(Some(Ok(event)))) => Ok(Async::Ready(Some(event))),
+            Ok(Async::Ready(Some(Err(err)))) => Err(err),
+            Ok(Async::Ready(None)) => Ok(Async::Ready(None)),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(()) => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+// How often the bridge thread checks whether the stream was dropped while no
+// events are arriving.
+const POLL_CLOSED: Duration = Duration::from_millis(250);
+
+/// Owns the platform watcher, forwarding its events until the `Watch` is
+/// dropped.
+fn bridge(opts: WatchOptions, path: &Path, tx: UnboundedSender<io::Result<Event>>) {
+    let (raw_tx, raw_rx) = channel();
+    let mode = if opts.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    let mut watcher = match notify::watcher(raw_tx, opts.delay) {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            let _ = tx.unbounded_send(Err(notify_err(err)));
+            return;
+        }
+    };
+    if let Err(err) = watcher.watch(path, mode) {
+        let _ = tx.unbounded_send(Err(notify_err(err)));
+        return;
+    }
+
+    loop {
+        let event = match raw_rx.recv_timeout(POLL_CLOSED) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) if tx.is_closed() => return,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        let event = match event {
+            DebouncedEvent::Create(path) => Ok(Event::Create(path)),
+            DebouncedEvent::Write(path) | DebouncedEvent::Chmod(path) => Ok(Event::Modify(path)),
+            DebouncedEvent::Remove(path) => Ok(Event::Remove(path)),
+            DebouncedEvent::Rename(from, to) => Ok(Event::Rename(from, to)),
+            DebouncedEvent::Rescan => Ok(Event::Rescan),
+            DebouncedEvent::Error(err, _) => Err(notify_err(err)),
+            // Early notices are followed by the final event once the delay
+            // expires.
+            DebouncedEvent::NoticeWrite(_) | DebouncedEvent::NoticeRemove(_) => continue,
+        };
+        if tx.unbounded_send(event).is_err() {
+            return;
+        }
+    }
+}
+
+fn notify_err(err: notify::Error) -> io::Error {
+    match err {
+        notify::Error::Io(err) => err,
+        notify::Error::PathNotFound => io::Error::new(ErrorKind::NotFound, "path not found"),
+        err => io::Error::other(err.to_string()),
+    }
+}
@@ -0,0 +1,231 @@
+use futures::Future;
+
+use std::ffi::{CString, OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+
+/// Reads the extended attribute `name` of the file at `path`.
+///
+/// Resolves to `None` if the file has no such attribute. Symbolic links are
+/// followed.
+///
+/// Extended attributes are supported on Linux and macOS. On Linux, names
+/// of attributes set by applications start with `user.`.
+///
+/// This is only available on Unix.
+pub fn get_xattr<P, N>(path: P, name: N) -> impl Future<Item = Option<Vec<u8>>, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+    N: AsRef<OsStr> + Send + 'static,
+{
+    crate::blocking(move || {
+        let path = cstring(path.as_ref().as_os_str())?;
+        let name = cstring(name.as_ref())?;
+        let mut buf = Vec::new();
+        loop {
+            // Ask for the size, then read, and start over if the value grew
+            // in between.
+            let len = match size(unsafe { sys::get(path.as_ptr(), name.as_ptr(), &mut []) }) {
+                Ok(len) => len,
+                Err(ref err) if err.raw_os_error() == Some(sys::NO_ATTR) => return Ok(None),
+                Err(err) => return Err(err),
+            };
+            buf.resize(len, 0);
+            match size(unsafe { sys::get(path.as_ptr(), name.as_ptr(), &mut buf) }) {
+                Ok(len) => {
+                    buf.truncate(len);
+                    return Ok(Some(buf));
+                }
+                Err(ref err) if err.raw_os_error() == Some(sys::NO_ATTR) => return Ok(None),
+                Err(ref err) if err.raw_os_error() == Some(libc::ERANGE) => {}
+                Err(err) => return Err(err),
+            }
+        }
+    })
+}
+
+/// Sets the extended attribute `name` of the file at `path` to `value`,
+/// creating it or replacing its previous value.
+///
+/// See [`get_xattr`](fn.get_xattr.html) for details.
+pub fn set_xattr<P, N>(
+    path: P,
+    name: N,
+    value: Vec<u8>,
+) -> impl Future<Item = (), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+    N: AsRef<OsStr> + Send + 'static,
+{
+    crate::blocking(move || {
+        let path = cstring(path.as_ref().as_os_str())?;
+        let name = cstring(name.as_ref())?;
+        status(unsafe { sys::set(path.as_ptr(), name.as_ptr(), &value) })
+    })
+}
+
+/// Lists the names of the extended attributes of the file at `path`.
+///
+/// See [`get_xattr`](fn.get_xattr.html) for details.
+pub fn list_xattr<P>(path: P) -> impl Future<Item = Vec<OsString>, Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    crate::blocking(move || {
+        let path = cstring(path.as_ref().as_os_str())?;
+        let mut buf = Vec::new();
+        let len = loop {
+            buf.resize(size(unsafe { sys::list(path.as_ptr(), &mut []) })?, 0);
+            match size(unsafe { sys::list(path.as_ptr(), &mut buf) }) {
+                Ok(len) => break len,
+                Err(ref err) if err.raw_os_error() == Some(libc::ERANGE) => {}
+                Err(err) => return Err(err),
+            }
+        };
+        // Names are separated by, and end with, a NUL byte.
+        Ok(buf[..len]
+            .split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| OsString::from_vec(name.to_vec()))
+            .collect())
+    })
+}
+
+/// Removes the extended attribute `name` of the file at `path`.
+///
+/// Fails if the file has no such attribute. See
+/// [`get_xattr`](fn.get_xattr.html) for details.
+pub fn remove_xattr<P, N>(path: P, name: N) -> impl Future<Item = (), Error = io::Error>
+where
+    P: AsRef<Path> + Send + 'static,
+    N: AsRef<OsStr> + Send + 'static,
+{
+    crate::blocking(move || {
+        let path = cstring(path.as_ref().as_os_str())?;
+        let name = cstring(name.as_ref())?;
+        status(unsafe { sys::remove(path.as_ptr(), name.as_ptr()) })
+    })
+}
+
+fn cstring(s: &OsStr) -> io::Result<CString> {
+    CString::new(s.as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "name contains a NUL byte"))
+}
+
+/// Converts the result of a call returning a size or -1.
+fn size(n: isize) -> io::Result<usize> {
+    if n < 0 {
+        Err(sys::last_error())
+    } else {
+        Ok(n as usize)
+    }
+}
+
+/// Converts the result of a call returning 0 or -1.
+fn status(res: i32) -> io::Result<()> {
+    if res < 0 {
+        Err(sys::last_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod sys {
+    use libc::{c_char, c_void};
+    use std::io;
+
+    pub const NO_ATTR: i32 = libc::ENODATA;
+
+    pub unsafe fn get(path: *const c_char, name: *const c_char, buf: &mut [u8]) -> isize {
+        libc::getxattr(path, name, buf.as_mut_ptr() as *mut c_void, buf.len())
+    }
+
+    pub unsafe fn set(path: *const c_char, name: *const c_char, value: &[u8]) -> i32 {
+        libc::setxattr(path, name, value.as_ptr() as *const c_void, value.len(), 0)
+    }
+
+    pub unsafe fn list(path: *const c_char, buf: &mut [u8]) -> isize {
+        libc::listxattr(path, buf.as_mut_ptr() as *mut c_char, buf.len())
+    }
+
+    pub unsafe fn remove(path: *const c_char, name: *const c_char) -> i32 {
+        libc::removexattr(path, name)
+    }
+
+    pub fn last_error() -> io::Error {
+        io::Error::last_os_error()
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod sys {
+    use libc::{c_char, c_void};
+    use std::io;
+
+    pub const NO_ATTR: i32 = libc::ENOATTR;
+
+    pub unsafe fn get(path: *const c_char, name: *const c_char, buf: &mut [u8]) -> isize {
+        libc::getxattr(path, name, buf.as_mut_ptr() as *mut c_void, buf.len(), 0, 0)
+    }
+
+    pub unsafe fn set(path: *const c_char, name: *const c_char, value: &[u8]) -> i32 {
+        libc::setxattr(
+            path,
+            name,
+            value.as_ptr() as *const c_void,
+            value.len(),
+            0,
+            0,
+        )
+    }
+
+    pub unsafe fn list(path: *const c_char, buf: &mut [u8]) -> isize {
+        libc::listxattr(path, buf.as_mut_ptr() as *mut c_char, buf.len(), 0)
+    }
+
+    pub unsafe fn remove(path: *const c_char, name: *const c_char) -> i32 {
+        libc::removexattr(path, name, 0)
+    }
+
+    pub fn last_error() -> io::Error {
+        io::Error::last_os_error()
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+)))]
+mod sys {
+    use libc::c_char;
+    use std::io;
+
+    pub const NO_ATTR: i32 = 0;
+
+    pub unsafe fn get(_: *const c_char, _: *const c_char, _: &mut [u8]) -> isize {
+        -1
+    }
+
+    pub unsafe fn set(_: *const c_char, _: *const c_char, _: &[u8]) -> i32 {
+        -1
+    }
+
+    pub unsafe fn list(_: *const c_char, _: &mut [u8]) -> isize {
+        -1
+    }
+
+    pub unsafe fn remove(_: *const c_char, _: *const c_char) -> i32 {
+        -1
+    }
+
+    pub fn last_error() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "extended attributes are not supported on this platform",
+        )
+    }
+}
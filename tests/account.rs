@@ -0,0 +1,135 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn charges_file_operations() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo");
+    fs::write(&path, b"hello world").unwrap();
+    let accounting = Accounting::new();
+    let account = accounting.account("tenant=acme");
+
+    rt::run(
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .account(&account)
+            .open(path)
+            .and_then(|file| file.read(5))
+            .and_then(|(file, _)| file.write_all(b"!".to_vec()))
+            .and_then(|file| file.sync_all())
+            .map(|_| ()),
+    );
+
+    assert_eq!(
+        account.usage(),
+        Usage {
+            bytes_read: 5,
+            bytes_written: 1,
+            ops: 4,
+        }
+    );
+}
+
+#[test]
+fn charges_aligned_writes_and_copies() {
+    let base_dir = tempdir().unwrap();
+    let from = base_dir.path().join("from");
+    let to = base_dir.path().join("to");
+    let accounting = Accounting::new();
+    accounting.limit("background", RateLimit::new().bytes_per_sec(1000));
+    let src_account = accounting.account("background");
+    let dst_account = accounting.account("other");
+
+    let mut buf = AlignedBuf::with_capacity(1500);
+    buf.extend_from_slice(&[7; 1500]);
+    let (account, dst) = (src_account.clone(), dst_account.clone());
+    rt::run(
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .account(&account)
+            .open(from.clone())
+            .and_then(|file| file.write_aligned(buf))
+            .and_then(|(file, _)| file.close())
+            .and_then(move |()| OpenOptions::new().read(true).account(&account).open(from))
+            .join(
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .account(&dst)
+                    .open(to),
+            )
+            .and_then(|(src, dst)| copy_file_range(src, dst, 2000))
+            .map(|(_, _, copied)| assert_eq!(copied, 1500)),
+    );
+
+    assert_eq!(
+        src_account.usage(),
+        Usage {
+            bytes_read: 1500,
+            bytes_written: 1500,
+            ops: 4,
+        }
+    );
+    assert_eq!(dst_account.usage().bytes_written, 1500);
+    // Both took byte tokens, so the limited account is in debt.
+    let start = Instant::now();
+    rt::run(src_account.ready());
+    assert!(start.elapsed() >= Duration::from_millis(1000));
+}
+
+#[test]
+fn rate_limit_delays_operations() {
+    let accounting = Accounting::new();
+    accounting.limit("background", RateLimit::new().bytes_per_sec(1000));
+    let account = accounting.account("background");
+
+    // A burst of a second's worth goes through, the debt beyond it waits.
+    account.record_read(1500);
+    let start = Instant::now();
+    rt::run(account.ready());
+    assert!(start.elapsed() >= Duration::from_millis(400));
+
+    let unlimited = accounting.account("other");
+    unlimited.record_read(1_000_000);
+    let start = Instant::now();
+    rt::run(unlimited.ready());
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
+
+#[test]
+fn usage_by_label() {
+    let accounting = Accounting::new();
+    accounting.account("b").record_write(3);
+    accounting.account("a").record_op();
+    accounting.account("b").record_write(4);
+
+    assert_eq!(
+        accounting.usage(),
+        vec![
+            (
+                "a".to_owned(),
+                Usage {
+                    bytes_read: 0,
+                    bytes_written: 0,
+                    ops: 1,
+                }
+            ),
+            (
+                "b".to_owned(),
+                Usage {
+                    bytes_read: 0,
+                    bytes_written: 7,
+                    ops: 2,
+                }
+            ),
+        ]
+    );
+}
@@ -0,0 +1,121 @@
+#![cfg(feature = "actor")]
+
+use actix::{Actor, MailboxError};
+use actix_fs::*;
+use futures::{future, Future};
+use std::io::{self, ErrorKind};
+use tempfile::tempdir;
+
+mod rt;
+
+fn mailbox_err(err: MailboxError) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+#[test]
+fn write_read_remove() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+
+    rt::run(future::lazy(move || {
+        let addr = FsActor::new().start();
+        let data = b"bar".to_vec();
+        addr.send(WriteFile {
+            path: path.clone(),
+            data,
+        })
+        .map_err(mailbox_err)
+        .and_then(|res| res)
+        .and_then({
+            let addr = addr.clone();
+            let path = path.clone();
+            move |_| addr.send(ReadFile { path }).map_err(mailbox_err)
+        })
+        .and_then(|res| res)
+        .and_then(move |data| {
+            assert_eq!(data, b"bar");
+            addr.send(RemoveFile { path: path.clone() })
+                .map_err(mailbox_err)
+                .and_then(|res| res)
+                .map(move |_| assert!(!path.exists()))
+        })
+    }));
+}
+
+#[test]
+fn list_dir() {
+    let base_dir = tempdir().unwrap();
+    std::fs::write(base_dir.path().join("b"), b"").unwrap();
+    std::fs::write(base_dir.path().join("a"), b"").unwrap();
+    let dir = base_dir.path().to_owned();
+
+    rt::run(future::lazy(move || {
+        let pool = Pool::new(1);
+        FsActor::with_pool(pool)
+            .mailbox_capacity(4)
+            .start()
+            .send(ListDir { path: dir.clone() })
+            .map_err(mailbox_err)
+            .and_then(|res| res)
+            .map(move |paths| assert_eq!(paths, vec![dir.join("a"), dir.join("b")]))
+    }));
+}
+
+#[test]
+fn errors_are_returned() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("missing.txt");
+
+    rt::run(future::lazy(move || {
+        FsActor::new()
+            .start()
+            .send(ReadFile { path })
+            .map_err(mailbox_err)
+            .map(|res| assert_eq!(res.unwrap_err().kind(), ErrorKind::NotFound))
+    }));
+}
+
+#[cfg(unix)]
+#[test]
+fn max_in_flight_holds_the_mailbox() {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::thread;
+    use std::time::Duration;
+
+    let base_dir = tempdir().unwrap();
+    let fifo = base_dir.path().join("fifo");
+    let path = CString::new(fifo.as_os_str().as_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o600) }, 0);
+    let written = base_dir.path().join("written");
+
+    // Reading the FIFO blocks until the writer below opens it, so the
+    // write queued behind it must not run before then.
+    let (check, writer) = (written.clone(), fifo.clone());
+    let feeder = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(300));
+        let ran_early = check.exists();
+        std::fs::write(writer, b"fed").unwrap();
+        ran_early
+    });
+
+    rt::run(future::lazy(move || {
+        let addr = FsActor::with_pool(Pool::new(2)).max_in_flight(1).start();
+        let read = addr
+            .send(ReadFile { path: fifo })
+            .map_err(mailbox_err)
+            .and_then(|res| res);
+        let write = addr
+            .send(WriteFile {
+                path: written.clone(),
+                data: b"bar".to_vec(),
+            })
+            .map_err(mailbox_err)
+            .and_then(|res| res);
+        read.join(write).map(move |(data, ())| {
+            assert_eq!(data, b"fed");
+            assert!(written.exists());
+        })
+    }));
+    assert!(!feeder.join().unwrap());
+}
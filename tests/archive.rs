@@ -0,0 +1,68 @@
+use actix_fs::*;
+use futures::{stream, Future, Stream};
+use std::fs;
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn round_trip() {
+    let src_dir = tempdir().unwrap();
+    let dst_dir = tempdir().unwrap();
+    let large: Vec<u8> = (0..150_000u32).map(|i| i as u8).collect();
+    fs::create_dir_all(src_dir.path().join("ab/cd")).unwrap();
+    fs::create_dir_all(src_dir.path().join("empty")).unwrap();
+    fs::write(src_dir.path().join("ab/cd/blob"), &large).unwrap();
+    fs::write(src_dir.path().join("small"), b"hello").unwrap();
+    fs::write(src_dir.path().join("nothing"), b"").unwrap();
+    fs::write(src_dir.path().join("partial.tmp"), b"skipped").unwrap();
+
+    let src = src_dir.path().to_owned();
+    let dst = dst_dir.path().to_owned();
+    rt::run(import(dst.clone(), export(src)).map(move |files| {
+        assert_eq!(files, 3);
+        assert_eq!(fs::read(dst.join("ab/cd/blob")).unwrap(), large);
+        assert_eq!(fs::read(dst.join("small")).unwrap(), b"hello");
+        assert_eq!(fs::read(dst.join("nothing")).unwrap(), b"");
+        assert!(dst.join("empty").is_dir());
+        assert!(!dst.join("partial.tmp").exists());
+    }));
+}
+
+#[test]
+fn import_rejects_escaping_names() {
+    let tmp_dir = tempdir().unwrap();
+    let dir = tmp_dir.path().join("store");
+    let mut archive = b"ACTIXFS\x01F".to_vec();
+    archive.extend_from_slice(&9u16.to_be_bytes());
+    archive.extend_from_slice(b"../escape");
+    archive.extend_from_slice(&1u64.to_be_bytes());
+    archive.extend_from_slice(b"x");
+    archive.push(b'E');
+
+    let outside = tmp_dir.path().join("escape");
+    rt::run(import(dir, stream::once(Ok(archive))).then(move |res| {
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert!(!outside.exists());
+        Ok(())
+    }));
+}
+
+#[test]
+fn import_rejects_truncated_archive() {
+    let src_dir = tempdir().unwrap();
+    let dst_dir = tempdir().unwrap();
+    fs::write(src_dir.path().join("blob"), vec![7; 1000]).unwrap();
+
+    let src = src_dir.path().to_owned();
+    let dst = dst_dir.path().to_owned();
+    rt::run(export(src).concat2().and_then(|mut archive| {
+        archive.truncate(archive.len() - 100);
+        import(dst.clone(), stream::once(Ok(archive))).then(move |res| {
+            assert_eq!(res.unwrap_err().kind(), ErrorKind::InvalidData);
+            assert_eq!(fs::read_dir(dst).unwrap().count(), 0);
+            Ok(())
+        })
+    }));
+}
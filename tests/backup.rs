@@ -0,0 +1,233 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn links_unchanged_files() {
+    let src = tempdir().unwrap();
+    let backups = tempdir().unwrap();
+    fs::create_dir(src.path().join("dir")).unwrap();
+    fs::write(src.path().join("dir/same"), b"same").unwrap();
+    fs::write(src.path().join("changed"), b"old").unwrap();
+
+    let expected = backups.path().join("20190307T134500");
+    rt::run(
+        BackupOptions::new(10)
+            .now(UNIX_EPOCH + Duration::from_secs(1_551_966_300))
+            .backup(src.path().to_owned(), backups.path().to_owned())
+            .map(move |report| {
+                assert_eq!(report.snapshot, expected);
+                assert_eq!((report.copied, report.linked), (2, 0));
+            }),
+    );
+
+    fs::write(src.path().join("changed"), b"new!").unwrap();
+    rt::run(
+        backup_rotate(src.path().to_owned(), backups.path().to_owned(), 10).map(|report| {
+            assert_eq!((report.copied, report.linked), (1, 1));
+            assert_eq!(report.bytes_copied, 4);
+            let snapshot = report.snapshot;
+            assert_eq!(fs::read(snapshot.join("changed")).unwrap(), b"new!");
+            assert_eq!(fs::read(snapshot.join("dir/same")).unwrap(), b"same");
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+
+                let same = fs::metadata(snapshot.join("dir/same")).unwrap();
+                assert_eq!(same.nlink(), 2);
+            }
+        }),
+    );
+}
+
+#[test]
+fn keeps_newest_snapshots() {
+    let src = tempdir().unwrap();
+    let backups = tempdir().unwrap();
+    fs::write(src.path().join("foo"), b"foo").unwrap();
+
+    for secs in 0..3 {
+        rt::run(
+            BackupOptions::new(2)
+                .now(UNIX_EPOCH + Duration::from_secs(secs))
+                .backup(src.path().to_owned(), backups.path().to_owned())
+                .map(move |report| {
+                    assert_eq!(report.removed.len(), if secs == 2 { 1 } else { 0 });
+                }),
+        );
+    }
+
+    let mut names: Vec<_> = fs::read_dir(backups.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["19700101T000001", "19700101T000002"]);
+}
+
+#[test]
+fn missing_source() {
+    let base_dir = tempdir().unwrap();
+    let src = base_dir.path().join("missing");
+    let backups = base_dir.path().join("backups");
+
+    rt::run(backup_rotate(src, backups.clone(), 1).then(|res| {
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::NotFound);
+        Ok(())
+    }));
+    assert_eq!(fs::read_dir(backups).unwrap().count(), 0);
+}
+
+#[test]
+fn restore_snapshot() {
+    let snapshot = tempdir().unwrap();
+    let target = tempdir().unwrap();
+    fs::create_dir(snapshot.path().join("dir")).unwrap();
+    fs::write(snapshot.path().join("dir/new"), b"new").unwrap();
+    fs::write(snapshot.path().join("changed"), b"snapshot").unwrap();
+    fs::write(target.path().join("changed"), b"target").unwrap();
+    fs::create_dir_all(target.path().join("extra/nested")).unwrap();
+
+    let report = |dry_run| {
+        RestoreOptions::new()
+            .dry_run(dry_run)
+            .restore(snapshot.path().to_owned(), target.path().to_owned())
+            .map(|report| {
+                assert_eq!(report.added, vec![Path::new("dir/new")]);
+                assert_eq!(report.replaced, vec![Path::new("changed")]);
+                assert_eq!(report.removed, vec![Path::new("extra")]);
+                report.bytes
+            })
+    };
+    rt::run(report(true).map(|bytes| assert_eq!(bytes, 0)));
+    assert_eq!(fs::read(target.path().join("changed")).unwrap(), b"target");
+    assert!(target.path().join("extra").exists());
+
+    rt::run(report(false).map(|bytes| assert_eq!(bytes, 11)));
+    assert_eq!(
+        fs::read(target.path().join("changed")).unwrap(),
+        b"snapshot"
+    );
+    assert_eq!(fs::read(target.path().join("dir/new")).unwrap(), b"new");
+    assert!(!target.path().join("extra").exists());
+
+    rt::run(
+        restore(snapshot.path().to_owned(), target.path().to_owned()).map(|report| {
+            assert!(report.added.is_empty() && report.replaced.is_empty());
+            assert_eq!(report.unchanged, 2);
+        }),
+    );
+}
+
+#[test]
+fn restore_into_missing_target() {
+    let base_dir = tempdir().unwrap();
+    let snapshot = base_dir.path().join("snapshot");
+    let target = base_dir.path().join("restored/app");
+    fs::create_dir(&snapshot).unwrap();
+    fs::write(snapshot.join("foo"), b"foo").unwrap();
+
+    rt::run(restore(snapshot, target.clone()).map(|report| {
+        assert_eq!(report.added, vec![Path::new("foo")]);
+    }));
+    assert_eq!(fs::read(target.join("foo")).unwrap(), b"foo");
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn restore_verifies_recorded_digests() {
+    use actix_fs::hash::Algorithm;
+
+    let src = tempdir().unwrap();
+    let backups = tempdir().unwrap();
+    let target = tempdir().unwrap();
+    fs::write(src.path().join("same"), b"same").unwrap();
+    fs::write(src.path().join("changed"), b"old").unwrap();
+
+    let take = |secs| {
+        BackupOptions::new(2)
+            .hash(Algorithm::Sha256)
+            .now(UNIX_EPOCH + Duration::from_secs(secs))
+            .backup(src.path().to_owned(), backups.path().to_owned())
+    };
+    rt::run(take(1).map(|_| ()));
+    fs::write(src.path().join("changed"), b"new").unwrap();
+    rt::run(take(2).map(|report| assert_eq!((report.copied, report.linked), (1, 1))));
+    let snapshot = backups.path().join("19700101T000002");
+    let digests = fs::read_to_string(backups.path().join("19700101T000002.digests")).unwrap();
+    let mut lines = digests.lines();
+    assert_eq!(lines.next(), Some("sha256"));
+    assert_eq!(lines.count(), 2);
+
+    rt::run(
+        restore(snapshot.clone(), target.path().to_owned()).map(|report| {
+            assert_eq!(report.added.len(), 2);
+        }),
+    );
+    assert_eq!(fs::read(target.path().join("changed")).unwrap(), b"new");
+
+    // Damage the snapshot without changing the size, so only the recorded
+    // digest can tell.
+    fs::write(snapshot.join("same"), b"SAME").unwrap();
+    let restored = tempdir().unwrap();
+    let check = restored.path().to_owned();
+    rt::run(
+        restore(snapshot, restored.path().to_owned()).then(move |res| {
+            assert_eq!(res.unwrap_err().kind(), ErrorKind::InvalidData);
+            assert!(!check.join("same").exists());
+            Ok(())
+        }),
+    );
+    assert!(fs::read_dir(restored.path()).unwrap().all(|entry| !entry
+        .unwrap()
+        .file_name()
+        .to_string_lossy()
+        .contains(".restore")));
+
+    // The oldest snapshot goes with its digests.
+    rt::run(take(3).map(|report| assert_eq!(report.removed.len(), 1)));
+    assert!(!backups.path().join("19700101T000001.digests").exists());
+    assert!(backups.path().join("19700101T000003.digests").exists());
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn verifies_copies() {
+    use actix_fs::hash::{Algorithm, Verify};
+
+    let src = tempdir().unwrap();
+    let backups = tempdir().unwrap();
+    fs::write(src.path().join("a"), b"a").unwrap();
+    fs::write(src.path().join("b"), b"b").unwrap();
+
+    let mut hashed = BackupOptions::new(2);
+    hashed
+        .hash(Algorithm::Sha256)
+        .verify(Verify::Reread)
+        .now(UNIX_EPOCH + Duration::from_secs(1));
+    let mut compared = BackupOptions::new(2);
+    compared
+        .verify(Verify::Readback)
+        .now(UNIX_EPOCH + Duration::from_secs(2));
+    rt::run(
+        hashed
+            .backup(src.path().to_owned(), backups.path().to_owned())
+            .map(|report| assert_eq!(report.copied, 2)),
+    );
+    fs::write(src.path().join("b"), b"changed").unwrap();
+    rt::run(
+        compared
+            .backup(src.path().to_owned(), backups.path().to_owned())
+            .map(|report| assert_eq!((report.copied, report.linked), (1, 1))),
+    );
+    assert_eq!(
+        fs::read(backups.path().join("19700101T000002/b")).unwrap(),
+        b"changed"
+    );
+}
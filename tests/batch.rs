@@ -0,0 +1,64 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn closure() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path().to_owned();
+    fs::write(dir.join("a"), b"foo").unwrap();
+    fs::write(dir.join("b"), b"bar").unwrap();
+
+    rt::run(
+        batch(move || Ok((fs::read(dir.join("a"))?, fs::read(dir.join("b"))?))).map(|(a, b)| {
+            assert_eq!(a, b"foo");
+            assert_eq!(b, b"bar");
+        }),
+    );
+}
+
+#[test]
+fn results_in_order() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path();
+    fs::write(dir.join("a"), b"foo").unwrap();
+
+    let mut batch = Batch::new();
+    batch
+        .metadata(dir.join("a"))
+        .read(dir.join("missing"))
+        .create_dir_all(dir.join("x/y"))
+        .write(dir.join("x/y/b"), b"bar".to_vec())
+        .read(dir.join("x/y/b"))
+        .remove_file(dir.join("a"));
+    assert_eq!(batch.len(), 6);
+
+    rt::run(batch.run().map(|results| {
+        let mut results = results.into_iter();
+        let metadata = results.next().unwrap().unwrap().into_metadata().unwrap();
+        assert_eq!(metadata.len(), 3);
+        let err = results.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(Error::from_io(&err).unwrap().op(), "read");
+        assert!(results.next().unwrap().is_ok());
+        assert!(results.next().unwrap().is_ok());
+        let contents = results.next().unwrap().unwrap().into_contents().unwrap();
+        assert_eq!(contents, b"bar");
+        assert!(results.next().unwrap().is_ok());
+        assert!(results.next().is_none());
+    }));
+
+    assert!(!dir.join("a").exists());
+}
+
+#[test]
+fn empty() {
+    let batch = Batch::new();
+    assert!(batch.is_empty());
+
+    rt::run(batch.run().map(|results| assert!(results.is_empty())));
+}
@@ -0,0 +1,100 @@
+use actix_fs::*;
+use futures::{Future, Stream};
+use std::fs;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn buffered_reads() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    fs::write(&path, b"one\ntwo\nthree").unwrap();
+
+    rt::run({
+        File::open(path)
+            .map(|file| BufReader::with_capacity(4, file))
+            .and_then(|reader| reader.read_until(b'\n'))
+            .and_then(|(reader, line)| {
+                assert_eq!(line, b"one\n");
+                reader.read(2)
+            })
+            .and_then(|(reader, data)| {
+                assert_eq!(data, b"tw");
+                assert_eq!(reader.buffer(), b"o\n");
+                reader.read_until(b'\n')
+            })
+            .and_then(|(reader, line)| {
+                assert_eq!(line, b"o\n");
+                reader.read_until(b'\n')
+            })
+            .and_then(|(reader, line)| {
+                assert_eq!(line, b"three");
+                reader.read_until(b'\n')
+            })
+            .map(|(_, line)| assert!(line.is_empty()))
+    });
+}
+
+#[test]
+fn buffered_writes() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    let check = path.clone();
+
+    rt::run({
+        File::create(path)
+            .map(|file| BufWriter::with_capacity(8, file))
+            .and_then(|writer| writer.write(b"abc"))
+            .and_then(move |writer| {
+                assert_eq!(writer.buffer(), b"abc");
+                assert_eq!(fs::read(&check).unwrap(), b"");
+                writer.write(b"defghi")
+            })
+            .and_then(|writer| {
+                assert_eq!(writer.buffer(), b"defghi");
+                writer.write(b"0123456789")
+            })
+            .and_then(|writer| {
+                assert!(writer.buffer().is_empty());
+                writer.shutdown()
+            })
+    });
+
+    let contents = fs::read(base_dir.path().join("foo.txt")).unwrap();
+    assert_eq!(contents, b"abcdefghi0123456789");
+}
+
+#[test]
+fn large_reads_bypass_buffer() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    fs::write(&path, b"0123456789").unwrap();
+
+    rt::run({
+        File::open(path)
+            .map(|file| BufReader::with_capacity(4, file))
+            .and_then(|reader| reader.read(8))
+            .map(|(reader, data)| {
+                assert_eq!(data, b"01234567");
+                assert!(reader.buffer().is_empty());
+            })
+    });
+}
+
+#[test]
+fn split_on_delimiter() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.bin");
+    fs::write(&path, b"a\0bc\0\0def\0").unwrap();
+
+    rt::run({
+        File::open(path)
+            .map(|file| BufReader::with_capacity(3, file))
+            .and_then(|reader| reader.split(0).collect())
+            .map(|chunks| {
+                let expected: Vec<&[u8]> = vec![b"a", b"bc", b"", b"def"];
+                assert_eq!(chunks, expected);
+            })
+    });
+}
@@ -0,0 +1,61 @@
+use actix_fs::*;
+use futures::{future, stream, Future};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn timeout() {
+    let start = Instant::now();
+
+    rt::run(
+        with_timeout(future::empty::<(), io::Error>(), Duration::from_millis(50)).then(
+            move |res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::TimedOut);
+                assert!(start.elapsed() < Duration::from_secs(5));
+                Ok(())
+            },
+        ),
+    );
+}
+
+#[test]
+fn timeout_passes_through_result() {
+    rt::run(with_timeout(future::ok(42), Duration::from_secs(5)).map(|n: u32| assert_eq!(n, 42)));
+    rt::run(
+        with_timeout(
+            future::err::<(), _>(io::Error::new(ErrorKind::NotFound, "missing")),
+            Duration::from_secs(5),
+        )
+        .then(|res| {
+            assert_eq!(res.unwrap_err().kind(), ErrorKind::NotFound);
+            Ok(())
+        }),
+    );
+}
+
+#[test]
+fn cancelled_gc_removes_nothing() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("blob");
+    fs::write(&path, b"foo").unwrap();
+    let token = CancellationToken::new();
+    token.clone().cancel();
+    assert!(token.is_cancelled());
+
+    rt::run(
+        GcOptions::new()
+            .grace_period(Duration::from_secs(0))
+            .cancellation(token)
+            .gc(base_dir.path().to_owned(), stream::empty())
+            .then(|res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::Other);
+                Ok(())
+            }),
+    );
+
+    assert!(path.exists());
+}
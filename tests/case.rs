@@ -0,0 +1,84 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+mod rt;
+
+fn case_sensitive(dir: &std::path::Path) -> bool {
+    fs::write(dir.join("probe"), b"").unwrap();
+    let sensitive = !dir.join("PROBE").exists();
+    fs::remove_file(dir.join("probe")).unwrap();
+    sensitive
+}
+
+#[test]
+fn detects_collisions() {
+    let base_dir = tempdir().unwrap();
+    if !case_sensitive(base_dir.path()) {
+        return;
+    }
+    fs::create_dir_all(base_dir.path().join("a/b")).unwrap();
+    fs::write(base_dir.path().join("a/b/Photo.jpg"), b"").unwrap();
+    fs::write(base_dir.path().join("a/b/photo.JPG"), b"").unwrap();
+    fs::write(base_dir.path().join("a/b/other.jpg"), b"").unwrap();
+    fs::create_dir(base_dir.path().join("A")).unwrap();
+    let dir = base_dir.path().to_owned();
+
+    rt::run(detect_case_collisions(dir.clone()).map(move |collisions| {
+        assert_eq!(
+            collisions,
+            vec![
+                vec![dir.join("A"), dir.join("a")],
+                vec![dir.join("a/b/Photo.jpg"), dir.join("a/b/photo.JPG")],
+            ]
+        );
+    }));
+}
+
+#[test]
+fn create_guard_rejects_collision() {
+    let base_dir = tempdir().unwrap();
+    if !case_sensitive(base_dir.path()) {
+        return;
+    }
+    fs::write(base_dir.path().join("README.md"), b"").unwrap();
+    let path = base_dir.path().join("readme.md");
+    let check = path.clone();
+
+    rt::run(
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .reject_case_collisions(true)
+            .open(path)
+            .then(move |res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::AlreadyExists);
+                assert!(!check.exists());
+                Ok(())
+            }),
+    );
+}
+
+#[test]
+fn create_guard_allows_same_name() {
+    let base_dir = tempdir().unwrap();
+    fs::write(base_dir.path().join("README.md"), b"old").unwrap();
+    let path = base_dir.path().join("README.md");
+    let check = path.clone();
+
+    rt::run(
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .reject_case_collisions(true)
+            .open(path)
+            .and_then(|file| file.write_all(b"new".to_vec()))
+            .map(move |file| {
+                drop(file.into_std());
+                assert_eq!(fs::read(check).unwrap(), b"new");
+            }),
+    );
+}
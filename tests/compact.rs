@@ -0,0 +1,158 @@
+#![cfg(feature = "gzip")]
+
+use actix_fs::*;
+use flate2::read::GzDecoder;
+use futures::{Future, Stream};
+use std::fs::{self, File as StdFile};
+use std::io::Read;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tempfile::tempdir;
+
+mod rt;
+
+fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn gunzip(path: &std::path::Path) -> Vec<u8> {
+    let mut out = Vec::new();
+    GzDecoder::new(StdFile::open(path).unwrap())
+        .read_to_end(&mut out)
+        .unwrap();
+    out
+}
+
+#[test]
+fn compresses_closed_partitions_only() {
+    let base_dir = tempdir().unwrap();
+    let closed = base_dir.path().join("2019/03/07/13");
+    let open = base_dir.path().join("2019/03/07/14");
+    fs::create_dir_all(&closed).unwrap();
+    fs::create_dir_all(&open).unwrap();
+    fs::write(closed.join("data.log"), b"a\nb\n").unwrap();
+    fs::write(open.join("data.log"), b"c\n").unwrap();
+
+    let root = base_dir.path().to_path_buf();
+    rt::run({
+        CompactOptions::new(Codec::Gzip)
+            .now(at(1_551_970_000))
+            .compact(root)
+            .collect()
+            .map(|reports| {
+                assert_eq!(reports.len(), 1);
+                assert_eq!(reports[0].partition, std::path::Path::new("2019/03/07/13"));
+                assert_eq!(reports[0].bytes_in, 4);
+            })
+    });
+
+    assert!(!closed.join("data.log").exists());
+    assert_eq!(gunzip(&closed.join("data.log.gz")), b"a\nb\n");
+    assert!(open.join("data.log").exists());
+}
+
+#[test]
+fn merges_small_files() {
+    let base_dir = tempdir().unwrap();
+    let closed = base_dir.path().join("2019/03/07");
+    fs::create_dir_all(&closed).unwrap();
+    fs::write(closed.join("a.log"), b"first\n").unwrap();
+    fs::write(closed.join("b.log"), b"second\n").unwrap();
+
+    let root = base_dir.path().to_path_buf();
+    rt::run({
+        CompactOptions::new(Codec::Gzip)
+            .granularity(Granularity::Day)
+            .merge(true)
+            .now(at(1_552_100_000))
+            .compact(root)
+            .collect()
+            .map(|reports| assert_eq!(reports[0].inputs.len(), 2))
+    });
+
+    assert_eq!(gunzip(&closed.join("merged.gz")), b"first\nsecond\n");
+    assert!(!closed.join("a.log").exists());
+}
+
+#[test]
+fn skips_compacted_partitions() {
+    let base_dir = tempdir().unwrap();
+    let closed = base_dir.path().join("2019/03/07/13");
+    fs::create_dir_all(&closed).unwrap();
+    fs::write(closed.join("data.log"), b"a\n").unwrap();
+
+    let opts = CompactOptions::new(Codec::Gzip)
+        .now(at(1_551_970_000))
+        .clone();
+    let again = opts.clone();
+    let root = base_dir.path().to_path_buf();
+    rt::run({
+        opts.compact(root.clone())
+            .collect()
+            .and_then(move |_| again.compact(root).collect())
+            .map(|reports| assert!(reports.is_empty()))
+    });
+}
+
+#[test]
+fn late_records_extend_compacted_files() {
+    let separate_dir = tempdir().unwrap();
+    let merged_dir = tempdir().unwrap();
+    let separate = separate_dir.path().join("2019/03/07");
+    let merged = merged_dir.path().join("2019/03/07");
+    fs::create_dir_all(&separate).unwrap();
+    fs::create_dir_all(&merged).unwrap();
+
+    let opts = CompactOptions::new(Codec::Gzip)
+        .granularity(Granularity::Day)
+        .now(at(1_552_100_000))
+        .clone();
+    let merge = opts.clone().merge(true).clone();
+    let compact = |opts: &CompactOptions, root: &std::path::Path| {
+        rt::run(opts.compact(root.to_path_buf()).collect().map(|_| ()));
+    };
+
+    fs::write(separate.join("data.log"), b"a\n").unwrap();
+    compact(&opts, separate_dir.path());
+    fs::write(separate.join("data.log"), b"b\n").unwrap();
+    compact(&opts, separate_dir.path());
+    assert_eq!(gunzip(&separate.join("data.log.gz")), b"a\nb\n");
+    assert_eq!(fs::read_dir(&separate).unwrap().count(), 1);
+
+    fs::write(merged.join("a.log"), b"first\n").unwrap();
+    compact(&merge, merged_dir.path());
+    fs::write(merged.join("b.log"), b"second\n").unwrap();
+    compact(&merge, merged_dir.path());
+    assert_eq!(gunzip(&merged.join("merged.gz")), b"first\nsecond\n");
+    assert_eq!(fs::read_dir(&merged).unwrap().count(), 1);
+}
+
+#[test]
+fn reruns_after_interrupted_run() {
+    let base_dir = tempdir().unwrap();
+    let closed = base_dir.path().join("2019/03/07");
+    fs::create_dir_all(&closed).unwrap();
+    fs::write(closed.join("a.log"), b"a\n").unwrap();
+    fs::write(closed.join("b.log"), b"b\n").unwrap();
+    // Stop the run after `a.log` is compacted, as the temporary output of
+    // `b.log` cannot be created.
+    fs::create_dir(closed.join("b.log.gz.tmp")).unwrap();
+
+    let opts = CompactOptions::new(Codec::Gzip)
+        .granularity(Granularity::Day)
+        .now(at(1_552_100_000))
+        .clone();
+    let root = base_dir.path().to_path_buf();
+    rt::run(opts.compact(root.clone()).collect().then(|res| {
+        assert!(res.is_err());
+        Ok(())
+    }));
+    assert!(!closed.join("a.log").exists());
+
+    fs::remove_dir(closed.join("b.log.gz.tmp")).unwrap();
+    let remaining = closed.join("b.log");
+    rt::run(opts.compact(root).collect().map(move |reports| {
+        assert_eq!(reports[0].inputs, vec![remaining]);
+    }));
+    assert_eq!(gunzip(&closed.join("a.log.gz")), b"a\n");
+    assert_eq!(gunzip(&closed.join("b.log.gz")), b"b\n");
+}
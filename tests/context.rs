@@ -0,0 +1,102 @@
+#![cfg(feature = "actix-web")]
+
+use actix_fs::*;
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use std::fs;
+use std::time::Duration;
+use tempfile::tempdir;
+
+#[test]
+fn serves_from_root() {
+    let base_dir = tempdir().unwrap();
+    fs::write(base_dir.path().join("a.txt"), b"hello").unwrap();
+
+    let mut srv = test::init_service(
+        App::new()
+            .wrap(
+                FsContextOptions::new()
+                    .label("plan", "free")
+                    .middleware(Root::new(base_dir.path())),
+            )
+            .route(
+                "/{name}",
+                web::get().to_async(|ctx: FsContext, name: web::Path<String>| {
+                    assert_eq!(ctx.labels(), &[("plan".to_owned(), "free".to_owned())]);
+                    ctx.named_file(name.into_inner())
+                }),
+            ),
+    );
+
+    let res = test::call_service(
+        &mut srv,
+        test::TestRequest::get().uri("/a.txt").to_request(),
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(test::read_body(res), "hello");
+
+    let req = test::TestRequest::get().uri("/missing.txt").to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn enforces_budget_and_deadline() {
+    let base_dir = tempdir().unwrap();
+    fs::write(base_dir.path().join("a.txt"), b"hello").unwrap();
+    let root = Root::new(base_dir.path());
+
+    let mut srv = test::init_service(
+        App::new()
+            .wrap(
+                FsContextOptions::new()
+                    .budget(4)
+                    .timeout(Duration::from_secs(60))
+                    .middleware(root),
+            )
+            .route(
+                "/",
+                web::get().to_async(|ctx: FsContext| {
+                    assert!(ctx.check_deadline().is_ok());
+                    assert_eq!(ctx.remaining(), Some(4));
+                    ctx.named_file("a.txt")
+                }),
+            ),
+    );
+
+    let res = test::call_service(&mut srv, test::TestRequest::get().uri("/").to_request());
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+fn picks_root_per_request() {
+    let base_dir = tempdir().unwrap();
+    let registry = RootRegistry::new();
+    registry.insert("acme", base_dir.path(), &TenantOptions::new());
+
+    let mut srv = test::init_service(
+        App::new()
+            .wrap(FsContextOptions::new().middleware_with(move |req| {
+                let id = req.headers().get("x-tenant")?.to_str().ok()?;
+                Some(registry.get(id)?.root().clone())
+            }))
+            .route(
+                "/",
+                web::get().to(|ctx: FsContext| ctx.root().path().display().to_string()),
+            ),
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .header("x-tenant", "acme")
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .header("x-tenant", "other")
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,60 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::io::Cursor;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn copy_to_writer_in_chunks() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo");
+    fs::write(&path, b"hello world").unwrap();
+
+    rt::run(
+        File::open(path)
+            .and_then(|file| {
+                CopyOptions::new()
+                    .chunk_size(4)
+                    .copy_to(file, Cursor::new(Vec::new()))
+            })
+            .map(|(_, writer, copied)| {
+                assert_eq!(copied, 11);
+                assert_eq!(writer.into_inner(), b"hello world");
+            }),
+    );
+}
+
+#[test]
+fn copy_from_reader() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo");
+    let check = path.clone();
+
+    rt::run(
+        File::create(path)
+            .and_then(|file| copy_from(&b"hello world"[..], file))
+            .and_then(|(_, file, copied)| {
+                assert_eq!(copied, 11);
+                file.close()
+            })
+            .map(move |()| assert_eq!(fs::read(check).unwrap(), b"hello world")),
+    );
+}
+
+#[test]
+fn copy_empty_file() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("empty");
+    fs::write(&path, b"").unwrap();
+
+    rt::run(
+        File::open(path)
+            .and_then(|file| copy_to(file, Cursor::new(Vec::new())))
+            .map(|(_, writer, copied)| {
+                assert_eq!(copied, 0);
+                assert!(writer.into_inner().is_empty());
+            }),
+    );
+}
@@ -0,0 +1,211 @@
+#![cfg(feature = "webdav")]
+
+use actix_fs::*;
+use actix_web::http::{Method, StatusCode};
+use actix_web::{test, App};
+use std::fs;
+use tempfile::tempdir;
+
+macro_rules! request {
+    ($srv:expr, $method:expr, $uri:expr $(, $name:expr => $value:expr)*) => {{
+        let req = test::TestRequest::with_uri($uri)
+            .method(Method::from_bytes($method.as_bytes()).unwrap())
+            $(.header($name, $value))*
+            .to_request();
+        test::call_service(&mut $srv, req)
+    }};
+}
+
+#[test]
+fn put_get_delete() {
+    let base_dir = tempdir().unwrap();
+    let mut srv = test::init_service(App::new().service(dav("/dav", Root::new(base_dir.path()))));
+
+    let req = test::TestRequest::put()
+        .uri("/dav/foo.txt")
+        .set_payload(&b"foo"[..])
+        .to_request();
+    assert_eq!(
+        test::call_service(&mut srv, req).status(),
+        StatusCode::CREATED
+    );
+    let req = test::TestRequest::put()
+        .uri("/dav/foo.txt")
+        .set_payload(&b"bar"[..])
+        .to_request();
+    assert_eq!(
+        test::call_service(&mut srv, req).status(),
+        StatusCode::NO_CONTENT
+    );
+
+    let res = request!(srv, "GET", "/dav/foo.txt");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(test::read_body(res), "bar");
+
+    assert_eq!(
+        request!(srv, "MKCOL", "/dav/dir").status(),
+        StatusCode::CREATED
+    );
+    assert_eq!(
+        request!(srv, "MKCOL", "/dav/dir").status(),
+        StatusCode::METHOD_NOT_ALLOWED
+    );
+    assert_eq!(
+        request!(srv, "MKCOL", "/dav/a/b").status(),
+        StatusCode::CONFLICT
+    );
+    assert!(base_dir.path().join("dir").is_dir());
+
+    assert_eq!(
+        request!(srv, "DELETE", "/dav/foo.txt").status(),
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        request!(srv, "GET", "/dav/foo.txt").status(),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        request!(srv, "DELETE", "/dav/").status(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        request!(srv, "GET", "/dav/..%2Ffoo.txt").status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[test]
+fn propfind() {
+    let base_dir = tempdir().unwrap();
+    fs::create_dir(base_dir.path().join("dir")).unwrap();
+    fs::write(base_dir.path().join("a b.txt"), b"foo").unwrap();
+    let mut srv = test::init_service(App::new().service(dav("/dav", Root::new(base_dir.path()))));
+
+    let res = request!(srv, "PROPFIND", "/dav/", "Depth" => "1");
+    assert_eq!(res.status(), StatusCode::MULTI_STATUS);
+    let body = test::read_body(res);
+    let body = std::str::from_utf8(&body).unwrap();
+    assert_eq!(body.matches("<D:response>").count(), 3);
+    assert!(body.contains("<D:href>/dav/</D:href>"));
+    assert!(body.contains("<D:href>/dav/dir/</D:href>"));
+    assert!(body.contains("<D:href>/dav/a%20b.txt</D:href>"));
+    assert!(body.contains("<D:getcontentlength>3</D:getcontentlength>"));
+
+    let res = request!(srv, "PROPFIND", "/dav/dir", "Depth" => "0");
+    let body = test::read_body(res);
+    assert_eq!(
+        std::str::from_utf8(&body)
+            .unwrap()
+            .matches("<D:response>")
+            .count(),
+        1
+    );
+
+    let res = request!(srv, "PROPFIND", "/dav/", "Depth" => "infinity");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn copy_and_move() {
+    let base_dir = tempdir().unwrap();
+    fs::create_dir(base_dir.path().join("dir")).unwrap();
+    fs::write(base_dir.path().join("dir/foo"), b"foo").unwrap();
+    let mut srv = test::init_service(App::new().service(dav("/dav", Root::new(base_dir.path()))));
+
+    let res = request!(srv, "COPY", "/dav/dir", "Destination" => "http://localhost/dav/copy");
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert_eq!(fs::read(base_dir.path().join("copy/foo")).unwrap(), b"foo");
+
+    let res = request!(
+        srv, "MOVE", "/dav/copy/foo",
+        "Destination" => "/dav/dir/foo",
+        "Overwrite" => "F"
+    );
+    assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+
+    let res = request!(srv, "MOVE", "/dav/copy/foo", "Destination" => "/dav/dir/foo");
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert!(!base_dir.path().join("copy/foo").exists());
+
+    let res = request!(srv, "MOVE", "/dav/dir", "Destination" => "/dav/dir/sub");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = request!(srv, "MOVE", "/dav/dir", "Destination" => "/elsewhere/dir");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn move_into_parent_keeps_source() {
+    let base_dir = tempdir().unwrap();
+    fs::create_dir_all(base_dir.path().join("a/b")).unwrap();
+    fs::write(base_dir.path().join("a/b/foo"), b"foo").unwrap();
+    let mut srv = test::init_service(App::new().service(dav("/dav", Root::new(base_dir.path()))));
+
+    let res = request!(srv, "MOVE", "/dav/a/b", "Destination" => "/dav/a");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = request!(srv, "COPY", "/dav/a/b", "Destination" => "/dav/a/");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert_eq!(fs::read(base_dir.path().join("a/b/foo")).unwrap(), b"foo");
+}
+
+#[test]
+fn overwrite_replaces_collection() {
+    let base_dir = tempdir().unwrap();
+    fs::create_dir_all(base_dir.path().join("src/sub")).unwrap();
+    fs::write(base_dir.path().join("src/sub/new"), b"new").unwrap();
+    fs::create_dir(base_dir.path().join("dst")).unwrap();
+    fs::write(base_dir.path().join("dst/old"), b"old").unwrap();
+    let mut srv = test::init_service(App::new().service(dav("/dav", Root::new(base_dir.path()))));
+
+    let res = request!(srv, "COPY", "/dav/src", "Destination" => "/dav/dst");
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert!(!base_dir.path().join("dst/old").exists());
+    assert_eq!(fs::read(base_dir.path().join("dst/sub/new")).unwrap(), b"new");
+    assert_eq!(fs::read(base_dir.path().join("src/sub/new")).unwrap(), b"new");
+
+    // A collection is copied without its members at depth 0.
+    let res = request!(srv, "COPY", "/dav/src", "Destination" => "/dav/empty", "Depth" => "0");
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert_eq!(fs::read_dir(base_dir.path().join("empty")).unwrap().count(), 0);
+
+    // A file replaces a collection, and nothing staged is left behind.
+    let res = request!(srv, "MOVE", "/dav/src/sub/new", "Destination" => "/dav/dst");
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert_eq!(fs::read(base_dir.path().join("dst")).unwrap(), b"new");
+    let mut names: Vec<_> = fs::read_dir(base_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["dst", "empty", "src"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn refuses_symlinks() {
+    let base_dir = tempdir().unwrap();
+    let outside = tempdir().unwrap();
+    fs::write(outside.path().join("secret"), b"secret").unwrap();
+    std::os::unix::fs::symlink(outside.path(), base_dir.path().join("link")).unwrap();
+    let mut srv = test::init_service(App::new().service(dav("/dav", Root::new(base_dir.path()))));
+
+    let res = request!(srv, "GET", "/dav/link/secret");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::put()
+        .uri("/dav/link/planted")
+        .set_payload(&b"foo"[..])
+        .to_request();
+    assert_eq!(
+        test::call_service(&mut srv, req).status(),
+        StatusCode::FORBIDDEN
+    );
+    let res = request!(srv, "DELETE", "/dav/link/secret");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = request!(srv, "MOVE", "/dav/link/secret", "Destination" => "/dav/secret");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert!(outside.path().join("secret").exists());
+    assert!(!outside.path().join("planted").exists());
+
+    let res = request!(srv, "PROPFIND", "/dav/", "Depth" => "1");
+    let body = test::read_body(res);
+    assert!(!std::str::from_utf8(&body).unwrap().contains("link"));
+}
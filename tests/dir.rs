@@ -35,3 +35,86 @@ fn remove() {
 
     assert!(!new_dir.exists());
 }
+
+#[test]
+fn snapshot_tolerates_concurrent_changes() {
+    use futures::{Future, Stream};
+
+    let base_dir = tempdir().unwrap();
+    for i in 0..100 {
+        fs::write(base_dir.path().join(format!("{:03}", i)), "").unwrap();
+    }
+    let dir = base_dir.path().to_owned();
+
+    rt::run(
+        read_dir_snapshot(dir.clone())
+            .into_future()
+            .map_err(|(err, _)| err)
+            .and_then(move |(first, rest)| {
+                assert_eq!(first.unwrap().file_name(), "000");
+                fs::remove_file(dir.join("099")).unwrap();
+                fs::write(dir.join("100"), "").unwrap();
+                rest.collect()
+            })
+            .map(|rest| {
+                let names: Vec<_> = rest.iter().map(|entry| entry.file_name()).collect();
+                assert_eq!(names.len(), 98);
+                assert_eq!(names.last().unwrap(), &"098");
+            }),
+    );
+}
+
+#[test]
+fn walk_snapshot() {
+    use futures::{Future, Stream};
+    use std::path::PathBuf;
+
+    let base_dir = tempdir().unwrap();
+    fs::create_dir_all(base_dir.path().join("x/y")).unwrap();
+    fs::write(base_dir.path().join("x/y/z"), "z").unwrap();
+    fs::write(base_dir.path().join("x-1"), "1").unwrap();
+    let dir = base_dir.path().to_owned();
+
+    rt::run(
+        walk_dir_snapshot(dir.clone())
+            .collect()
+            .map(move |entries| {
+                let paths: Vec<_> = entries
+                    .iter()
+                    .map(|entry| entry.path().strip_prefix(&dir).unwrap().to_owned())
+                    .collect();
+                assert_eq!(
+                    paths,
+                    ["x", "x/y", "x/y/z", "x-1"]
+                        .iter()
+                        .map(PathBuf::from)
+                        .collect::<Vec<_>>()
+                );
+                assert!(entries[1].metadata().is_dir());
+            }),
+    );
+}
+
+#[test]
+fn collected() {
+    use futures::Future;
+
+    let base_dir = tempdir().unwrap();
+    fs::write(base_dir.path().join("a.txt"), "aaa").unwrap();
+    fs::write(base_dir.path().join("b.TXT"), "b").unwrap();
+    fs::write(base_dir.path().join("c.txt"), "cc").unwrap();
+    fs::write(base_dir.path().join("d.log"), "dddd").unwrap();
+    fs::create_dir(base_dir.path().join("e.txt")).unwrap();
+
+    let mut opts = ReadDirOptions::new();
+    opts.extension("txt")
+        .filter(|entry| entry.metadata().is_file())
+        .sort_by(SortBy::Size)
+        .reverse(true);
+    rt::run(
+        read_dir_collected(base_dir.path().to_owned(), &opts).map(|entries| {
+            let names: Vec<_> = entries.iter().map(|entry| entry.file_name()).collect();
+            assert_eq!(names, ["a.txt", "c.txt", "b.TXT"]);
+        }),
+    );
+}
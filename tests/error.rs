@@ -0,0 +1,122 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::io::{self, ErrorKind};
+use tempfile::tempdir;
+
+mod rt;
+
+fn expect_err<F>(f: F, check: fn(io::Error))
+where
+    F: Future<Error = io::Error> + Send + 'static,
+{
+    rt::run(f.then(move |res| {
+        check(res.err().expect("operation succeeded"));
+        Ok(())
+    }));
+}
+
+#[test]
+fn open_reports_path() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("missing.txt");
+
+    expect_err(File::open(path), |err| {
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        let ctx = Error::from_io(&err).unwrap();
+        assert_eq!(ctx.op(), "open");
+        assert!(ctx.path().unwrap().ends_with("missing.txt"));
+        assert!(ctx.io_error().raw_os_error().is_some());
+        assert_eq!(raw_os_error(&err), ctx.io_error().raw_os_error());
+        assert_eq!(err.raw_os_error(), None);
+        assert!(err.to_string().starts_with("open "));
+        assert!(err.to_string().contains("missing.txt: "));
+    });
+}
+
+#[test]
+fn rename_reports_both_paths() {
+    let base_dir = tempdir().unwrap();
+    let from = base_dir.path().join("from");
+    let to = base_dir.path().join("to");
+
+    expect_err(rename(from, to), |err| {
+        let ctx = Error::from_io(&err).unwrap();
+        assert_eq!(ctx.op(), "rename");
+        assert!(ctx.path().unwrap().ends_with("from"));
+        assert!(ctx.dest().unwrap().ends_with("to"));
+        assert!(err.to_string().contains(" -> "));
+    });
+}
+
+#[test]
+fn context_converts_to_io_error() {
+    let err = Error::new("remove", io::Error::from(ErrorKind::PermissionDenied))
+        .with_path("/srv/data/blob");
+    assert_eq!(err.to_string(), "remove /srv/data/blob: permission denied");
+
+    let err = io::Error::from(err);
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    assert_eq!(Error::from_io(&err).unwrap().op(), "remove");
+    assert!(Error::from_io(&io::Error::from(ErrorKind::NotFound)).is_none());
+
+    let base_dir = tempdir().unwrap();
+    fs::write(base_dir.path().join("file"), b"").unwrap();
+    expect_err(create_dir(base_dir.path().join("file")), |err| {
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(Error::from_io(&err).unwrap().op(), "create directory");
+    });
+}
+
+#[test]
+fn write_reports_path() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("read-only");
+    fs::write(&path, b"foo").unwrap();
+
+    expect_err(
+        File::open(path).and_then(|file| file.write_all(b"bar".to_vec())),
+        |err| {
+            let ctx = Error::from_io(&err).unwrap();
+            assert_eq!(ctx.op(), "write");
+            assert!(ctx.path().unwrap().ends_with("read-only"));
+            assert!(raw_os_error(&err).is_some());
+        },
+    );
+}
+
+#[test]
+fn raw_os_error_without_context() {
+    let err = io::Error::from_raw_os_error(2);
+    assert_eq!(raw_os_error(&err), Some(2));
+    assert_eq!(raw_os_error(&io::Error::from(ErrorKind::NotFound)), None);
+}
+
+#[test]
+fn file_operations_report_names() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("read-only");
+    fs::write(&path, b"foo").unwrap();
+
+    expect_err(
+        File::open(path).and_then(|file| file.allocate(4096)),
+        |err| {
+            let ctx = Error::from_io(&err).unwrap();
+            assert_eq!(ctx.op(), "allocate");
+            assert!(ctx.path().unwrap().ends_with("read-only"));
+        },
+    );
+}
+
+#[test]
+fn retried_operations_report_paths() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("missing");
+
+    expect_err(RetryOptions::new().remove_file(path), |err| {
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        let ctx = Error::from_io(&err).unwrap();
+        assert_eq!(ctx.op(), "remove");
+        assert!(ctx.path().unwrap().ends_with("missing"));
+    });
+}
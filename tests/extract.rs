@@ -0,0 +1,69 @@
+#![cfg(feature = "actix-web")]
+
+use actix_fs::*;
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use std::fs;
+use tempfile::tempdir;
+
+macro_rules! service {
+    ($root:expr) => {
+        test::init_service(
+            App::new()
+                .wrap(FsContextOptions::new().middleware(Root::new($root)))
+                .route(
+                    "/files/{tail:.*}",
+                    web::get().to(|path: SafeFilePath| path.relative().display().to_string()),
+                ),
+        )
+    };
+}
+
+macro_rules! status {
+    ($srv:expr, $uri:expr) => {
+        test::call_service($srv, test::TestRequest::get().uri($uri).to_request()).status()
+    };
+}
+
+#[test]
+fn resolves_existing_paths() {
+    let base_dir = tempdir().unwrap();
+    fs::create_dir(base_dir.path().join("sub")).unwrap();
+    fs::write(base_dir.path().join("sub/a b.txt"), b"foo").unwrap();
+
+    let mut srv = service!(base_dir.path());
+    let req = test::TestRequest::get()
+        .uri("/files/sub/a%20b.txt")
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(test::read_body(res), "sub/a b.txt");
+}
+
+#[test]
+fn rejects_traversal() {
+    let base_dir = tempdir().unwrap();
+    let root = base_dir.path().join("root");
+    fs::create_dir(&root).unwrap();
+    fs::write(base_dir.path().join("secret"), b"foo").unwrap();
+
+    let mut srv = service!(&root);
+    assert_eq!(status!(&mut srv, "/files/../secret"), StatusCode::NOT_FOUND);
+    assert_eq!(
+        status!(&mut srv, "/files/%2E%2E/secret"),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status!(&mut srv, "/files/..%2Fsecret"),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(status!(&mut srv, "/files/"), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn rejects_missing_files() {
+    let base_dir = tempdir().unwrap();
+
+    let mut srv = service!(base_dir.path());
+    assert_eq!(status!(&mut srv, "/files/missing"), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,590 @@
+use actix_fs::*;
+use futures::{Future, Stream};
+use std::fs;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn lock_exclusive_blocks_other_handles() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.lock");
+    let other = path.clone();
+
+    rt::run({
+        File::create(path)
+            .and_then(|file| file.lock_exclusive())
+            .and_then(move |file| {
+                File::open(other)
+                    .and_then(|other| other.try_lock_shared())
+                    .map(move |(_, locked)| {
+                        assert!(!locked);
+                        drop(file);
+                    })
+            })
+    });
+}
+
+#[test]
+fn lock_shared_allows_other_shared() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.lock");
+    let other = path.clone();
+
+    rt::run({
+        File::create(path)
+            .and_then(|file| file.lock_shared())
+            .and_then(move |file| {
+                File::open(other)
+                    .and_then(|other| other.try_lock_shared())
+                    .map(move |(_, locked)| {
+                        assert!(locked);
+                        drop(file);
+                    })
+            })
+    });
+}
+
+#[test]
+fn unlock_releases_lock() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.lock");
+    let other = path.clone();
+
+    rt::run({
+        File::create(path)
+            .and_then(|file| file.lock_exclusive())
+            .and_then(|file| file.unlock())
+            .and_then(move |file| {
+                File::open(other)
+                    .and_then(|other| other.try_lock_exclusive())
+                    .map(move |(_, locked)| {
+                        assert!(locked);
+                        drop(file);
+                    })
+            })
+    });
+}
+
+#[test]
+fn write_then_read() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    let read_path = path.clone();
+
+    rt::run({
+        File::create(path)
+            .and_then(|file| file.write_all(b"hello world".to_vec()))
+            .and_then(|file| file.sync_all())
+            .and_then(move |_| File::open(read_path))
+            .and_then(|file| file.read(5))
+            .and_then(|(file, head)| {
+                assert_eq!(head, b"hello");
+                file.read(64)
+            })
+            .and_then(|(file, tail)| {
+                assert_eq!(tail, b" world");
+                file.read(64)
+            })
+            .map(|(_, eof)| assert!(eof.is_empty()))
+    });
+}
+
+#[test]
+fn create_new_existing_file() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    std::fs::write(&path, b"taken").unwrap();
+
+    rt::run({
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .then(|res| {
+                assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+                Ok(())
+            })
+    });
+}
+
+#[test]
+fn lines() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.ndjson");
+    fs::write(&path, b"{\"a\":1}\r\n{\"b\":2}\n\n{\"c\":3}").unwrap();
+
+    rt::run({
+        File::open(path)
+            .and_then(|file| file.lines().collect())
+            .map(|lines| assert_eq!(lines, vec!["{\"a\":1}", "{\"b\":2}", "", "{\"c\":3}"]))
+    });
+}
+
+#[test]
+fn close_on_pool() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    let check = path.clone();
+    let pool = Pool::new(1);
+
+    rt::run({
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .pool(&pool)
+            .open(path)
+            .and_then(|file| file.write_all(b"bar".to_vec()))
+            .and_then(|file| file.close())
+            .map(move |_| assert_eq!(fs::read(check).unwrap(), b"bar"))
+    });
+}
+
+#[test]
+fn try_clone_shares_file() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    let check = path.clone();
+
+    rt::run({
+        File::create(path)
+            .and_then(|file| file.try_clone())
+            .and_then(|(file, clone)| {
+                #[cfg(unix)]
+                {
+                    use std::os::unix::io::AsRawFd;
+                    assert_ne!(file.as_raw_fd(), clone.as_raw_fd());
+                }
+                file.write_all(b"foo".to_vec())
+                    .and_then(|_| clone.write_all(b"bar".to_vec()))
+            })
+            .map(move |file| {
+                drop(file.into_std());
+                assert_eq!(fs::read(check).unwrap(), b"foobar");
+            })
+    });
+}
+
+#[test]
+fn allocate_extends_but_never_shrinks() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    let check = path.clone();
+
+    rt::run({
+        File::create(path)
+            .and_then(|file| file.write_all(b"foo".to_vec()))
+            .and_then(|file| file.allocate(1 << 20))
+            .and_then(|file| file.allocate(16))
+            .map(move |file| {
+                drop(file.into_std());
+                let contents = fs::read(check).unwrap();
+                assert_eq!(contents.len(), 1 << 20);
+                assert_eq!(&contents[..3], b"foo");
+                assert!(contents[3..].iter().all(|&b| b == 0));
+            })
+    });
+}
+
+#[test]
+fn read_with_reopen_on_stale() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    fs::write(&path, b"foobar").unwrap();
+
+    rt::run({
+        OpenOptions::new()
+            .read(true)
+            .reopen_on_stale(3)
+            .open(path)
+            .and_then(|file| file.read(3))
+            .and_then(|(file, buf)| {
+                assert_eq!(buf, b"foo");
+                file.read(16)
+            })
+            .map(|(_, buf)| assert_eq!(buf, b"bar"))
+    });
+}
+
+#[test]
+fn advise_keeps_contents() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    fs::write(&path, b"foobar").unwrap();
+
+    rt::run({
+        File::open(path)
+            .and_then(|file| file.advise(0, 0, Advice::Sequential))
+            .and_then(|file| file.read(3))
+            .and_then(|(file, buf)| {
+                assert_eq!(buf, b"foo");
+                file.advise(0, 3, Advice::DontNeed)
+            })
+            .and_then(|file| file.read(16))
+            .map(|(_, buf)| assert_eq!(buf, b"bar"))
+    });
+}
+
+#[test]
+fn copy_file_range_advances_both() {
+    let base_dir = tempdir().unwrap();
+    let from = base_dir.path().join("from.txt");
+    let to = base_dir.path().join("to.txt");
+    fs::write(&from, b"foobarbaz").unwrap();
+    let check = to.clone();
+
+    rt::run({
+        File::open(from)
+            .join(File::create(to))
+            .and_then(|(src, dst)| src.read(3).map(move |(src, _)| (src, dst)))
+            .and_then(|(src, dst)| copy_file_range(src, dst, 3))
+            .and_then(|(src, dst, copied)| {
+                assert_eq!(copied, 3);
+                copy_file_range(src, dst, 100)
+            })
+            .map(move |(_, dst, copied)| {
+                assert_eq!(copied, 3);
+                drop(dst.into_std());
+                assert_eq!(fs::read(check).unwrap(), b"barbaz");
+            })
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn sendfile_to_socket() {
+    use std::io::Read;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    fs::write(&path, b"foobarbaz").unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let receiver = thread::spawn(move || {
+        let mut received = Vec::new();
+        let (mut conn, _) = listener.accept().unwrap();
+        conn.read_to_end(&mut received).unwrap();
+        received
+    });
+    let socket = TcpStream::connect(addr).unwrap();
+
+    rt::run({
+        File::open(path)
+            .and_then(move |file| file.sendfile(socket, 3, 100))
+            .map(|(_, _, sent)| assert_eq!(sent, 6))
+    });
+
+    assert_eq!(receiver.join().unwrap(), b"barbaz");
+}
+
+#[test]
+fn set_times() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    let check = path.clone();
+    let accessed = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let modified = UNIX_EPOCH + Duration::from_secs(2_000_000);
+
+    rt::run({
+        File::create(path.clone())
+            .and_then(move |file| file.set_times(Some(accessed), Some(modified)))
+            .and_then(move |_| set_file_times(path, None, Some(accessed)))
+            .map(move |_| {
+                let metadata = fs::metadata(check).unwrap();
+                assert_eq!(metadata.accessed().unwrap(), accessed);
+                assert_eq!(metadata.modified().unwrap(), accessed);
+            })
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn set_times_without_opening() {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let base_dir = tempdir().unwrap();
+    let write_only = base_dir.path().join("write-only");
+    fs::write(&write_only, b"").unwrap();
+    fs::set_permissions(&write_only, fs::Permissions::from_mode(0o200)).unwrap();
+    // Opening a FIFO for reading would block until a writer shows up.
+    let fifo = base_dir.path().join("fifo");
+    let c_fifo = CString::new(fifo.as_os_str().as_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(c_fifo.as_ptr(), 0o600) }, 0);
+    let before_epoch = UNIX_EPOCH - Duration::from_millis(1500);
+    let modified = UNIX_EPOCH + Duration::from_secs(2_000_000);
+    let (check_write_only, check_fifo) = (write_only.clone(), fifo.clone());
+
+    rt::run({
+        set_file_times(write_only, None, Some(modified))
+            .join(set_file_times(fifo, Some(before_epoch), Some(modified)))
+            .map(move |_| {
+                let metadata = fs::metadata(check_write_only).unwrap();
+                assert_eq!(metadata.modified().unwrap(), modified);
+                let metadata = fs::symlink_metadata(check_fifo).unwrap();
+                assert!(metadata.file_type().is_fifo());
+                assert_eq!(metadata.accessed().unwrap(), before_epoch);
+                assert_eq!(metadata.modified().unwrap(), modified);
+            })
+    });
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn punch_hole_and_seek() {
+    use std::io::ErrorKind;
+
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("sparse");
+    let check = path.clone();
+    fs::write(&path, vec![b'a'; 3 * 4096]).unwrap();
+
+    rt::run({
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .and_then(|file| file.punch_hole(4096, 4096))
+            .and_then(|file| file.seek_hole(0))
+            .and_then(|(file, hole)| {
+                // Filesystems without holes report only the end of the file.
+                let hole = hole.unwrap();
+                assert!(hole == 4096 || hole == 3 * 4096);
+                file.seek_data(hole).map(move |(file, data)| {
+                    assert_eq!(data, if hole == 4096 { Some(2 * 4096) } else { None });
+                    file
+                })
+            })
+            .and_then(|file| file.seek_hole(3 * 4096))
+            .then(move |res| {
+                match res {
+                    Ok((_, hole)) => assert_eq!(hole, None),
+                    Err(ref err) if err.kind() == ErrorKind::Unsupported => return Ok(()),
+                    Err(err) => return Err(err),
+                }
+                let contents = fs::read(check).unwrap();
+                assert_eq!(contents.len(), 3 * 4096);
+                assert!(contents[4096..2 * 4096].iter().all(|&b| b == 0));
+                assert!(contents[2 * 4096..].iter().all(|&b| b == b'a'));
+                Ok(())
+            })
+    });
+}
+
+#[test]
+fn direct_round_trip() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("direct");
+    let data: Vec<u8> = (0..5000).map(|i| i as u8).collect();
+    let expected = data.clone();
+
+    let mut buf = AlignedBuf::with_capacity(6000);
+    assert_eq!(buf.capacity(), 2 * AlignedBuf::ALIGNMENT);
+    assert_eq!(buf.extend_from_slice(&data), 5000);
+    rt::run({
+        let read = path.clone();
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .direct(true)
+            .open(path)
+            .and_then(|file| file.write_aligned(buf))
+            .and_then(|(file, _)| file.close())
+            .and_then(|()| OpenOptions::new().read(true).direct(true).open(read))
+            .and_then(|file| file.read_aligned(AlignedBuf::with_capacity(8192)))
+            .map(move |(_, buf)| {
+                assert_eq!(buf.as_ptr() as usize % AlignedBuf::ALIGNMENT, 0);
+                assert_eq!(&buf[..], &expected[..]);
+            })
+    });
+}
+
+#[test]
+fn errors_keep_os_code() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("missing");
+
+    rt::run(File::open(path).then(|res| {
+        let err = res.unwrap_err();
+        assert_eq!(raw_os_error(&err), Some(2));
+        assert_eq!(Error::from_io(&err).unwrap().raw_os_error(), Some(2));
+        Ok(())
+    }));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn direct_unaligned_writes_leave_flags_alone() {
+    use std::os::unix::io::AsRawFd;
+
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("direct");
+    let mut expected = b"head".to_vec();
+    let data: Vec<u8> = (0..2 * AlignedBuf::ALIGNMENT).map(|i| i as u8).collect();
+    expected.extend_from_slice(&data);
+
+    let mut head = AlignedBuf::with_capacity(4);
+    head.extend_from_slice(b"head");
+    let mut buf = AlignedBuf::with_capacity(data.len());
+    buf.extend_from_slice(&data);
+    rt::run(
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .direct(true)
+            .open(path.clone())
+            .and_then(|file| file.try_clone())
+            .and_then(|(file, clone)| {
+                let flags = unsafe { libc::fcntl(clone.into_std().as_raw_fd(), libc::F_GETFL) };
+                // The blocks then start at an unaligned position, so they
+                // are written through the page cache, once.
+                file.write_aligned(head)
+                    .and_then(|(file, _)| file.write_aligned(buf))
+                    .map(move |(file, _)| (file.into_std(), flags))
+            })
+            .map(|(std, flags)| {
+                assert_eq!(
+                    unsafe { libc::fcntl(std.as_raw_fd(), libc::F_GETFL) },
+                    flags
+                );
+            }),
+    );
+    assert_eq!(fs::read(path).unwrap(), expected);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn seek_sparse_edges() {
+    use std::io::ErrorKind;
+
+    let base_dir = tempdir().unwrap();
+    let empty = base_dir.path().join("empty");
+    let path = base_dir.path().join("foo");
+    fs::write(&empty, b"").unwrap();
+    fs::write(&path, b"hello").unwrap();
+
+    let empty = File::open(empty)
+        .and_then(|file| file.seek_data(0))
+        .and_then(|(file, data)| {
+            assert_eq!(data, None);
+            file.seek_hole(0)
+        })
+        .map(|(_, hole)| assert_eq!(hole, None));
+    let edges = File::open(path)
+        .and_then(|file| file.seek_hole(0))
+        .and_then(|(file, hole)| {
+            assert_eq!(hole, Some(5));
+            file.seek_data(1)
+        })
+        .and_then(|(file, data)| {
+            assert_eq!(data, Some(1));
+            file.seek_data(5)
+        })
+        .and_then(|(file, data)| {
+            assert_eq!(data, None);
+            file.seek_hole(100)
+        })
+        .and_then(|(file, hole)| {
+            assert_eq!(hole, None);
+            // Neither of the last two seeks moved the position.
+            file.read(16)
+        })
+        .and_then(|(file, contents)| {
+            assert_eq!(contents, b"ello");
+            file.seek_data(u64::MAX).then(|res| {
+                assert_eq!(res.err().unwrap().kind(), ErrorKind::InvalidInput);
+                Ok(())
+            })
+        });
+    rt::run(empty.join(edges).map(|_| ()));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn punch_hole_edges() {
+    use std::io::ErrorKind;
+
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("sparse");
+    let check = path.clone();
+    let read_only = path.clone();
+    fs::write(&path, vec![b'a'; 2 * 4096]).unwrap();
+
+    let punched = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .and_then(|file| file.punch_hole(0, 0))
+        .and_then(|file| file.punch_hole(4096, 1 << 20))
+        .and_then(|file| {
+            file.punch_hole(u64::MAX, 1).then(|res| {
+                assert_eq!(res.err().unwrap().kind(), ErrorKind::InvalidInput);
+                Ok(())
+            })
+        })
+        .and_then(|()| File::open(read_only))
+        .and_then(|file| file.punch_hole(0, 4096).then(|res| Ok(res.is_err())));
+    rt::run(punched.then(move |res| {
+        match res {
+            Ok(failed) => assert!(failed, "punched a hole through a read-only handle"),
+            Err(ref err) if err.kind() == ErrorKind::Unsupported => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        let contents = fs::read(check).unwrap();
+        assert_eq!(contents.len(), 2 * 4096);
+        assert!(contents[..4096].iter().all(|&b| b == b'a'));
+        assert!(contents[4096..].iter().all(|&b| b == 0));
+        Ok(())
+    }));
+}
+
+#[cfg(feature = "uring")]
+#[test]
+fn writes_keep_reopen_on_stale() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+
+    rt::run({
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .reopen_on_stale(3)
+            .open(path)
+            .and_then(|file| file.write_all(b"foo".to_vec()))
+            .and_then(|file| file.sync_all())
+            .map(|file| assert!(format!("{:?}", file).contains("retries: 3")))
+    });
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn direct_unaligned_reads_and_writes() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("direct");
+    let reopened = path.clone();
+
+    rt::run({
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .direct(true)
+            .open(path)
+            .and_then(|file| file.write_all(b"foo\nbar\n".to_vec()))
+            .and_then(|file| file.write_all(b"baz\n".to_vec()))
+            .and_then(move |_| OpenOptions::new().read(true).direct(true).open(reopened))
+            .and_then(|file| file.read(2))
+            .and_then(|(file, buf)| {
+                assert_eq!(buf, b"fo");
+                file.lines().collect()
+            })
+            .map(|lines| assert_eq!(lines, vec!["o", "bar", "baz"]))
+    });
+}
@@ -0,0 +1,49 @@
+use actix_fs::*;
+use std::io::ErrorKind;
+
+fn invalid(name: &str, platform: Platform) -> bool {
+    match validate_filename(name, platform) {
+        Ok(()) => false,
+        Err(err) => {
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+            true
+        }
+    }
+}
+
+#[test]
+fn unix_rules() {
+    assert!(!invalid("report: final?.txt", Platform::Unix));
+    assert!(!invalid("CON", Platform::Unix));
+    assert!(!invalid("trailing. ", Platform::Unix));
+    assert!(invalid("", Platform::Unix));
+    assert!(invalid("..", Platform::Unix));
+    assert!(invalid("a/b", Platform::Unix));
+    assert!(invalid("a\0b", Platform::Unix));
+    assert!(invalid(&"x".repeat(256), Platform::Unix));
+}
+
+#[test]
+fn windows_rules() {
+    assert!(!invalid("report final.txt", Platform::Windows));
+    assert!(!invalid("CONSOLE.txt", Platform::Windows));
+    assert!(!invalid(&"é".repeat(255), Platform::Windows));
+    assert!(invalid("report: final.txt", Platform::Windows));
+    assert!(invalid("a\\b", Platform::Windows));
+    assert!(invalid("tab\there", Platform::Windows));
+    assert!(invalid("con", Platform::Windows));
+    assert!(invalid("nul.tar.gz", Platform::Windows));
+    assert!(invalid("LPT1 .txt", Platform::Windows));
+    assert!(invalid("trailing.", Platform::Windows));
+    assert!(invalid("trailing ", Platform::Windows));
+    assert!(invalid(&"x".repeat(256), Platform::Windows));
+}
+
+#[test]
+fn portable_rules() {
+    assert!(!invalid("photo-01.jpg", Platform::Portable));
+    assert!(invalid("aux", Platform::Portable));
+    assert!(invalid("a/b", Platform::Portable));
+    // Fine in UTF-16, but more than 255 bytes in UTF-8.
+    assert!(invalid(&"é".repeat(200), Platform::Portable));
+}
@@ -0,0 +1,97 @@
+use actix_fs::*;
+use futures::{stream, Future};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tempfile::tempdir;
+
+mod rt;
+
+fn blob(dir: &Path, key: &str, age: Duration) {
+    let path = dir.join(key);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, key).unwrap();
+    let file = fs::File::options().write(true).open(&path).unwrap();
+    file.set_modified(SystemTime::now() - age).unwrap();
+}
+
+fn refs(keys: &[&str]) -> impl futures::Stream<Item = String, Error = std::io::Error> {
+    let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+    stream::iter_ok(keys)
+}
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[test]
+fn removes_unreferenced_blobs() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path().to_owned();
+    blob(&dir, "ab/cdef", DAY);
+    blob(&dir, "ab/0123", DAY);
+    blob(&dir, "ff/9999", DAY);
+
+    rt::run({
+        gc(dir.clone(), refs(&["ab/cdef"])).map(move |report| {
+            assert_eq!(report.scanned, 3);
+            assert_eq!(report.removed, vec!["ab/0123", "ff/9999"]);
+            assert_eq!(report.bytes_freed, 14);
+            assert!(dir.join("ab/cdef").exists());
+            assert!(!dir.join("ab/0123").exists());
+        })
+    });
+}
+
+#[test]
+fn keeps_blobs_within_grace_period() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path().to_owned();
+    blob(&dir, "old", DAY);
+    blob(&dir, "new", Duration::from_secs(60));
+    fs::write(dir.join("upload.tmp"), b"partial").unwrap();
+
+    rt::run({
+        gc(dir.clone(), refs(&[])).map(move |report| {
+            assert_eq!(report.removed, vec!["old"]);
+            assert_eq!(report.recent, 1);
+            assert!(dir.join("new").exists());
+            assert!(dir.join("upload.tmp").exists());
+        })
+    });
+}
+
+#[test]
+fn dry_run_removes_nothing() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path().to_owned();
+    blob(&dir, "old", DAY);
+
+    rt::run({
+        GcOptions::new()
+            .grace_period(Duration::from_secs(1))
+            .dry_run(true)
+            .gc(dir.clone(), refs(&[]))
+            .map(move |report| {
+                assert_eq!(report.removed, vec!["old"]);
+                assert!(dir.join("old").exists());
+            })
+    });
+}
+
+#[test]
+fn recheck_keeps_blobs_referenced_meanwhile() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path().to_owned();
+    blob(&dir, "ab/cdef", DAY);
+    blob(&dir, "ab/0123", DAY);
+
+    rt::run({
+        GcOptions::new()
+            .recheck(|key| Ok(key == "ab/cdef"))
+            .gc(dir.clone(), refs(&[]))
+            .map(move |report| {
+                assert_eq!(report.removed, vec!["ab/0123"]);
+                assert_eq!(report.recent, 1);
+                assert!(dir.join("ab/cdef").exists());
+            })
+    });
+}
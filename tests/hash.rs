@@ -0,0 +1,178 @@
+#![cfg(all(feature = "sha2", feature = "blake3"))]
+
+use actix_fs::hash::{self, Algorithm};
+use actix_fs::{copy_file_range_verified, CancellationToken, File};
+use futures::Future;
+use std::fs;
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+mod rt;
+
+const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+#[test]
+fn sha256_of_file() {
+    let tmp_dir = tempdir().unwrap();
+    let path = tmp_dir.path().join("abc");
+    fs::write(&path, b"abc").unwrap();
+
+    rt::run(hash::sha256(path).map(|digest| {
+        assert_eq!(digest.algorithm(), Algorithm::Sha256);
+        assert_eq!(digest.to_string(), ABC_SHA256);
+    }));
+}
+
+#[test]
+fn blake3_spans_chunks() {
+    let tmp_dir = tempdir().unwrap();
+    let path = tmp_dir.path().join("large");
+    let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+    fs::write(&path, &data).unwrap();
+    let expected = *blake3::hash(&data).as_bytes();
+
+    rt::run(hash::blake3(path).map(move |digest| {
+        assert_eq!(digest.as_bytes(), &expected);
+    }));
+}
+
+#[test]
+fn copy_hashes_contents() {
+    let tmp_dir = tempdir().unwrap();
+    let from = tmp_dir.path().join("from");
+    let to = tmp_dir.path().join("to");
+    fs::write(&from, b"abc").unwrap();
+
+    let check = to.clone();
+    rt::run(
+        hash::copy(from, to, Algorithm::Sha256).map(move |(len, digest)| {
+            assert_eq!(len, 3);
+            assert_eq!(format!("{:x}", digest), ABC_SHA256);
+            assert_eq!(fs::read(check).unwrap(), b"abc");
+        }),
+    );
+}
+
+#[test]
+fn copy_verified_reads_back() {
+    let tmp_dir = tempdir().unwrap();
+    let from = tmp_dir.path().join("from");
+    let to = tmp_dir.path().join("to");
+    fs::write(&from, b"abc").unwrap();
+
+    for &verify in &[hash::Verify::Readback, hash::Verify::Reread] {
+        rt::run(
+            hash::copy_verified(from.clone(), to.clone(), Algorithm::Sha256, verify).map(
+                |(len, digest)| {
+                    assert_eq!(len, 3);
+                    assert_eq!(format!("{:x}", digest), ABC_SHA256);
+                },
+            ),
+        );
+    }
+    assert_eq!(fs::read(to).unwrap(), b"abc");
+}
+
+#[test]
+fn errors_report_paths() {
+    let tmp_dir = tempdir().unwrap();
+    let from = tmp_dir.path().join("missing");
+    let to = tmp_dir.path().join("copy");
+
+    let digest = hash::sha256(from.clone()).then(|res| {
+        let err = res.unwrap_err();
+        let ctx = actix_fs::Error::from_io(&err).unwrap();
+        assert_eq!(ctx.op(), "hash");
+        assert!(ctx.path().unwrap().ends_with("missing"));
+        Ok(())
+    });
+    let copy = hash::copy(from, to, Algorithm::Sha256).then(|res| {
+        let err = res.unwrap_err();
+        let ctx = actix_fs::Error::from_io(&err).unwrap();
+        assert_eq!(ctx.op(), "copy");
+        assert!(ctx.dest().unwrap().ends_with("copy"));
+        assert_eq!(actix_fs::raw_os_error(&err), Some(2));
+        Ok(())
+    });
+    rt::run(digest.join(copy).map(|_| ()));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn copy_verified_detects_changed_source() {
+    // Every read of this file returns a new UUID, as if it changed between
+    // the copy and the check.
+    let uuid = "/proc/sys/kernel/random/uuid";
+    let tmp_dir = tempdir().unwrap();
+    let to = tmp_dir.path().join("to");
+
+    rt::run(
+        hash::copy_verified(uuid, to.clone(), Algorithm::Sha256, hash::Verify::Readback)
+            .and_then(move |_| {
+                hash::copy_verified(uuid, to, Algorithm::Sha256, hash::Verify::Reread)
+            })
+            .then(|res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::InvalidData);
+                Ok(())
+            }),
+    );
+}
+
+#[test]
+fn copy_verified_cancellable_stops() {
+    let tmp_dir = tempdir().unwrap();
+    let from = tmp_dir.path().join("from");
+    fs::write(&from, b"abc").unwrap();
+    let token = CancellationToken::new();
+    token.cancel();
+
+    rt::run(
+        hash::copy_verified_cancellable(
+            from,
+            tmp_dir.path().join("to"),
+            Algorithm::Sha256,
+            hash::Verify::Readback,
+            token,
+        )
+        .then(|res| {
+            assert!(res.is_err());
+            Ok(())
+        }),
+    );
+}
+
+#[test]
+fn copy_file_range_verified_checks_range() {
+    use std::io::{Seek, SeekFrom};
+
+    let tmp_dir = tempdir().unwrap();
+    let from = tmp_dir.path().join("from");
+    let to = tmp_dir.path().join("to");
+    fs::write(&from, b"xxabcxx").unwrap();
+    fs::write(&to, b"--").unwrap();
+    let mut src = fs::File::open(&from).unwrap();
+    src.seek(SeekFrom::Start(2)).unwrap();
+    let mut dst = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&to)
+        .unwrap();
+    dst.seek(SeekFrom::End(0)).unwrap();
+
+    rt::run(
+        copy_file_range_verified(
+            File::from_std(src),
+            File::from_std(dst),
+            3,
+            Algorithm::Sha256,
+        )
+        .map(|(src, dst, copied, digest)| {
+            assert_eq!(copied, 3);
+            assert_eq!(digest.to_string(), ABC_SHA256);
+            let (mut src, mut dst) = (src.into_std(), dst.into_std());
+            assert_eq!(src.stream_position().unwrap(), 5);
+            assert_eq!(dst.stream_position().unwrap(), 5);
+        }),
+    );
+    assert_eq!(fs::read(to).unwrap(), b"--abc");
+}
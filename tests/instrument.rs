@@ -0,0 +1,55 @@
+use actix_fs::*;
+use futures::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tempfile::tempdir;
+
+mod rt;
+
+// Metrics are global, so everything is checked in a single test.
+#[test]
+fn reports_operations() {
+    let base_dir = tempdir().unwrap();
+    let foo = base_dir.path().join("foo");
+    let bar = base_dir.path().join("bar");
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let record = seen.clone();
+    set_metrics(move |op: &Operation| {
+        let path = op.path.map(PathBuf::from);
+        record
+            .lock()
+            .unwrap()
+            .push((op.name, path, op.bytes, op.ok));
+    });
+    rt::run({
+        let (read, from, to, missing) = (foo.clone(), foo.clone(), bar.clone(), foo.clone());
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .pool(&Pool::new(1))
+            .open(foo.clone())
+            .and_then(|file| file.write_all(b"hello".to_vec()))
+            .and_then(|file| file.close())
+            .and_then(move |()| File::open(read))
+            .and_then(|file| file.read(16))
+            .and_then(move |_| rename(from, to))
+            .and_then(move |()| {
+                remove_file(missing).then(|res| {
+                    assert!(res.is_err());
+                    Ok(())
+                })
+            })
+    });
+    clear_metrics();
+    rt::run(remove_file(bar));
+
+    let seen = seen.lock().unwrap();
+    let find = |name| seen.iter().find(|op| op.0 == name).unwrap();
+    assert_eq!(find("open").1.as_ref(), Some(&foo));
+    assert_eq!(find("write").2, 5);
+    assert_eq!(find("rename").1.as_ref(), Some(&foo));
+    let removed = find("remove");
+    assert_eq!((removed.1.as_ref(), removed.3), (Some(&foo), false));
+    assert_eq!(seen.iter().filter(|op| op.0 == "remove").count(), 1);
+}
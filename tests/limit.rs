@@ -0,0 +1,66 @@
+use actix_fs::*;
+use futures::{stream, Future};
+use std::fs;
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+mod rt;
+
+fn chunks() -> impl futures::Stream<Item = Vec<u8>, Error = std::io::Error> {
+    stream::iter_ok(vec![b"foo".to_vec(), b"bar".to_vec(), b"baz".to_vec()])
+}
+
+#[test]
+fn writes_within_limit() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("upload");
+
+    rt::run(
+        File::create(path.clone())
+            .and_then(|file| file.write_stream_limited(chunks(), 9))
+            .map(|(_, written)| assert_eq!(written, 9)),
+    );
+
+    assert_eq!(fs::read(path).unwrap(), b"foobarbaz");
+}
+
+#[test]
+fn stops_at_limit() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("upload");
+
+    rt::run(
+        File::create(path.clone())
+            .and_then(|file| LimitedWriter::new(8).write(file, chunks()))
+            .then(|res| {
+                let err = res.unwrap_err();
+                assert_eq!(err.kind(), ErrorKind::FileTooLarge);
+                assert_eq!(LimitExceeded::from_io(&err).unwrap().limit(), 8);
+                Ok(())
+            }),
+    );
+
+    assert_eq!(fs::read(path).unwrap(), b"foobar");
+}
+
+#[cfg(unix)]
+#[test]
+fn checks_space() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("upload");
+
+    rt::run(
+        File::create(path.clone())
+            .and_then(|file| {
+                LimitedWriter::new(u64::MAX)
+                    .check_space(true)
+                    .write(file, chunks())
+            })
+            .then(|res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::StorageFull);
+                Ok(())
+            }),
+    );
+
+    assert_eq!(fs::read(path).unwrap(), b"");
+}
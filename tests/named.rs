@@ -0,0 +1,202 @@
+#![cfg(feature = "actix-web")]
+
+use actix_fs::*;
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::{test, web, App, HttpRequest};
+use futures::Future;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tempfile::tempdir;
+
+macro_rules! service {
+    ($path:expr) => {{
+        let path = $path;
+        test::init_service(App::new().route(
+            "/",
+            web::get().to_async(move || NamedFile::open(path.clone())),
+        ))
+    }};
+}
+
+#[test]
+fn serves_with_metadata() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("index.html");
+    fs::write(&path, b"<p>hello</p>").unwrap();
+
+    let mut srv = service!(path);
+    let res = test::call_service(&mut srv, test::TestRequest::get().uri("/").to_request());
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "text/html"
+    );
+    let etag = res.headers().get(header::ETAG).unwrap().clone();
+    assert!(res.headers().contains_key(header::LAST_MODIFIED));
+    assert_eq!(test::read_body(res), "<p>hello</p>");
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .header(header::IF_NONE_MATCH, etag)
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[test]
+fn serves_ranges() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("data.bin");
+    fs::write(&path, b"0123456789").unwrap();
+
+    let mut srv = service!(path);
+    let req = test::TestRequest::get()
+        .uri("/")
+        .header(header::RANGE, "bytes=2-5")
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        res.headers().get(header::CONTENT_RANGE).unwrap(),
+        "bytes 2-5/10"
+    );
+    assert_eq!(test::read_body(res), "2345");
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .header(header::RANGE, "bytes=-3")
+        .to_request();
+    assert_eq!(test::read_body(test::call_service(&mut srv, req)), "789");
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .header(header::RANGE, "bytes=20-")
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+}
+
+#[test]
+fn missing_file_is_not_found() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("missing");
+
+    let mut srv = service!(path);
+    let res = test::call_service(&mut srv, test::TestRequest::get().uri("/").to_request());
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn serve_file_negotiates_precompressed() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("app.js");
+    fs::write(&path, b"plain").unwrap();
+    fs::write(base_dir.path().join("app.js.gz"), b"gzipped").unwrap();
+    fs::write(base_dir.path().join("app.js.br"), b"brotli").unwrap();
+
+    let mut srv = test::init_service(App::new().route(
+        "/",
+        web::get().to_async(move |req| serve_file(&req, path.clone(), &ServeOptions::new())),
+    ));
+    let get = |accept: &str| {
+        test::TestRequest::get()
+            .uri("/")
+            .header(header::ACCEPT_ENCODING, accept)
+            .to_request()
+    };
+
+    let res = test::call_service(&mut srv, get("gzip, br"));
+    assert_eq!(res.headers().get(header::CONTENT_ENCODING).unwrap(), "br");
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "text/javascript"
+    );
+    assert_eq!(res.headers().get(header::VARY).unwrap(), "Accept-Encoding");
+    assert_eq!(test::read_body(res), "brotli");
+
+    let res = test::call_service(&mut srv, get("br;q=0.5, gzip"));
+    assert_eq!(test::read_body(res), "gzipped");
+
+    let res = test::call_service(&mut srv, get("br;q=0, gzip;q=0"));
+    assert!(!res.headers().contains_key(header::CONTENT_ENCODING));
+    assert_eq!(test::read_body(res), "plain");
+}
+
+#[test]
+fn serve_file_skips_stale_variants() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("app.js");
+    fs::write(&path, b"plain").unwrap();
+    let gz = fs::File::create(base_dir.path().join("app.js.gz")).unwrap();
+    gz.set_modified(SystemTime::now() - Duration::from_secs(60))
+        .unwrap();
+    fs::write(base_dir.path().join("app.js.br"), b"brotli").unwrap();
+
+    let mut srv = test::init_service(App::new().route(
+        "/",
+        web::get().to_async(move |req| serve_file(&req, path.clone(), &ServeOptions::new())),
+    ));
+    let req = test::TestRequest::get()
+        .uri("/")
+        .header(header::ACCEPT_ENCODING, "gzip")
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert!(!res.headers().contains_key(header::CONTENT_ENCODING));
+    assert_eq!(test::read_body(res), "plain");
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .header(header::ACCEPT_ENCODING, "br")
+        .to_request();
+    assert_eq!(test::read_body(test::call_service(&mut srv, req)), "brotli");
+}
+
+#[test]
+fn serve_file_charges_bytes_sent() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("data.bin");
+    fs::write(&path, b"0123456789").unwrap();
+    let left = Arc::new(Mutex::new(Vec::new()));
+
+    let recorded = left.clone();
+    let mut srv = test::init_service(
+        App::new()
+            .wrap(
+                FsContextOptions::new()
+                    .budget(100)
+                    .middleware(Root::new(base_dir.path())),
+            )
+            .route(
+                "/",
+                web::route().to_async(move |req: HttpRequest, ctx: FsContext| {
+                    let recorded = recorded.clone();
+                    serve_file(&req, path.clone(), &ServeOptions::new()).map(move |res| {
+                        recorded.lock().unwrap().push(ctx.remaining().unwrap());
+                        res
+                    })
+                }),
+            ),
+    );
+
+    let res = test::call_service(&mut srv, test::TestRequest::get().uri("/").to_request());
+    let etag = res.headers().get(header::ETAG).unwrap().clone();
+    let req = test::TestRequest::get()
+        .uri("/")
+        .header(header::RANGE, "bytes=2-5")
+        .to_request();
+    test::call_service(&mut srv, req);
+    let req = test::TestRequest::default()
+        .method(Method::HEAD)
+        .uri("/")
+        .to_request();
+    test::call_service(&mut srv, req);
+    let req = test::TestRequest::get()
+        .uri("/")
+        .header(header::IF_NONE_MATCH, etag)
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    assert_eq!(*left.lock().unwrap(), [90, 96, 100, 100]);
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "unicode")]
+
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use tempfile::tempdir;
+
+mod rt;
+
+const NFC: &str = "caf\u{e9}.txt";
+const NFD: &str = "cafe\u{301}.txt";
+
+#[test]
+fn finds_decomposed_entry() {
+    let base_dir = tempdir().unwrap();
+    fs::write(base_dir.path().join(NFD), b"").unwrap();
+    let expected = base_dir.path().join(NFD);
+    if base_dir.path().join(NFC).exists() {
+        // The filesystem normalizes names itself.
+        return;
+    }
+
+    rt::run(
+        find_entry_normalized(base_dir.path().to_owned(), NFC)
+            .map(move |found| assert_eq!(found, Some(expected))),
+    );
+}
+
+#[test]
+fn prefers_exact_match() {
+    let base_dir = tempdir().unwrap();
+    fs::write(base_dir.path().join(NFC), b"").unwrap();
+    fs::write(base_dir.path().join(NFD), b"").unwrap();
+    let expected = base_dir.path().join(NFD);
+
+    rt::run(
+        find_entry_normalized(base_dir.path().to_owned(), NFD)
+            .map(move |found| assert_eq!(found, Some(expected))),
+    );
+}
+
+#[test]
+fn missing_entry() {
+    let base_dir = tempdir().unwrap();
+    fs::write(base_dir.path().join("cafe.txt"), b"").unwrap();
+
+    rt::run(
+        find_entry_normalized(base_dir.path().to_owned(), NFC).map(|found| assert_eq!(found, None)),
+    );
+}
+
+#[test]
+fn rejects_paths() {
+    let base_dir = tempdir().unwrap();
+    fs::create_dir(base_dir.path().join("sub")).unwrap();
+    fs::write(base_dir.path().join("sub").join(NFC), b"").unwrap();
+
+    for &name in &["", ".", "..", "../x", "sub/caf\u{e9}.txt", "/etc", "sub/"] {
+        rt::run(
+            find_entry_normalized(base_dir.path().to_owned(), name).then(move |res| {
+                assert_eq!(
+                    res.unwrap_err().kind(),
+                    std::io::ErrorKind::InvalidInput,
+                    "{:?}",
+                    name
+                );
+                Ok(())
+            }),
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tempfile::tempdir;
+
+mod rt;
+
+fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[test]
+fn partition_layout() {
+    // 2019-03-07T13:45:00Z
+    let time = at(1_551_966_300);
+
+    assert_eq!(
+        Granularity::Hour.partition(time),
+        Path::new("2019/03/07/13")
+    );
+    assert_eq!(Granularity::Day.partition(time), Path::new("2019/03/07"));
+    assert_eq!(Granularity::Day.partition(at(0)), Path::new("1970/01/01"));
+    // 2020-02-29T23:59:59Z
+    assert_eq!(
+        Granularity::Hour.partition(at(1_583_020_799)),
+        Path::new("2020/02/29/23")
+    );
+}
+
+#[test]
+fn partition_before_epoch() {
+    let before = |secs: u64, nanos: u32| UNIX_EPOCH - Duration::new(secs, nanos);
+
+    assert_eq!(
+        Granularity::Hour.partition(before(3_600, 0)),
+        Path::new("1969/12/31/23")
+    );
+    assert_eq!(
+        Granularity::Hour.partition(before(3_600, 1)),
+        Path::new("1969/12/31/22")
+    );
+    assert_eq!(
+        Granularity::Day.partition(before(86_400, 0)),
+        Path::new("1969/12/31")
+    );
+    assert_eq!(
+        Granularity::Day.partition(before(0, 1)),
+        Path::new("1969/12/31")
+    );
+}
+
+#[test]
+fn rolls_over_at_boundaries() {
+    let base_dir = tempdir().unwrap();
+    let writer = PartitionedWriter::new(base_dir.path());
+    let second = writer.clone();
+    let third = writer.clone();
+
+    rt::run({
+        writer
+            .append_at(at(1_551_966_300), b"a\n".to_vec())
+            .and_then(move |_| second.append_at(at(1_551_966_400), b"b\n".to_vec()))
+            .and_then(move |_| third.append_at(at(1_551_970_000), b"c\n".to_vec()))
+            .map(|path| assert!(path.ends_with("2019/03/07/14/data.log")))
+    });
+
+    let first = base_dir.path().join("2019/03/07/13/data.log");
+    let next = base_dir.path().join("2019/03/07/14/data.log");
+    assert_eq!(fs::read(first).unwrap(), b"a\nb\n");
+    assert_eq!(fs::read(next).unwrap(), b"c\n");
+}
+
+#[test]
+fn reopens_existing_partition() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path().join("2019/03/07");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("events.ndjson"), b"old\n").unwrap();
+
+    let writer = PartitionOptions::new()
+        .granularity(Granularity::Day)
+        .file_name("events.ndjson")
+        .open(base_dir.path());
+    let flush = writer.clone();
+
+    rt::run({
+        writer
+            .append_at(at(1_551_966_300), b"new\n".to_vec())
+            .and_then(move |_| flush.flush())
+    });
+
+    assert_eq!(fs::read(dir.join("events.ndjson")).unwrap(), b"old\nnew\n");
+}
@@ -0,0 +1,120 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempdir;
+
+mod rt;
+
+fn upper(source: &Path, tmp: &Path) -> io::Result<()> {
+    let data = fs::read(source)?;
+    fs::write(tmp, data.to_ascii_uppercase())
+}
+
+#[test]
+fn generates_derivatives_next_to_source() {
+    let base_dir = tempdir().unwrap();
+    let source = base_dir.path().join("foo.txt");
+    fs::write(&source, b"hello").unwrap();
+
+    let pipeline = Pipeline::builder()
+        .derivative("upper", upper)
+        .derivative("len", |source: &Path, tmp: &Path| {
+            let len = fs::metadata(source)?.len();
+            fs::write(tmp, len.to_string())
+        })
+        .build();
+
+    rt::run({
+        pipeline.process(source).map(|derivatives| {
+            assert_eq!(derivatives.len(), 2);
+            assert!(derivatives.iter().all(|d| d.error.is_none()));
+            assert_eq!(fs::read(&derivatives[0].path).unwrap(), b"HELLO");
+            assert_eq!(fs::read(&derivatives[1].path).unwrap(), b"5");
+            assert!(derivatives[0].path.ends_with("foo.txt.upper"));
+        })
+    });
+}
+
+#[test]
+fn retries_failed_generators() {
+    let base_dir = tempdir().unwrap();
+    let source = base_dir.path().join("foo.txt");
+    fs::write(&source, b"hello").unwrap();
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let pipeline = Pipeline::builder()
+        .retry_delay(Duration::from_millis(1))
+        .derivative("upper", move |source: &Path, tmp: &Path| {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                fs::write(tmp, b"partial")?;
+                return Err(io::Error::other("transient"));
+            }
+            upper(source, tmp)
+        })
+        .build();
+
+    rt::run({
+        pipeline.process(source).map(|derivatives| {
+            assert_eq!(derivatives[0].attempts, 2);
+            assert!(derivatives[0].error.is_none());
+            assert_eq!(fs::read(&derivatives[0].path).unwrap(), b"HELLO");
+        })
+    });
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn reports_exhausted_retries() {
+    let base_dir = tempdir().unwrap();
+    let source = base_dir.path().join("foo.txt");
+    fs::write(&source, b"hello").unwrap();
+    let dir = base_dir.path().to_owned();
+
+    let pipeline = Pipeline::builder()
+        .retries(1)
+        .retry_delay(Duration::from_millis(1))
+        .derivative("broken", |_: &Path, tmp: &Path| {
+            fs::write(tmp, b"partial")?;
+            Err(io::Error::new(ErrorKind::InvalidData, "corrupt source"))
+        })
+        .build();
+
+    rt::run({
+        pipeline.process(source).map(move |derivatives| {
+            let derivative = &derivatives[0];
+            assert_eq!(derivative.attempts, 2);
+            let err = derivative.error.as_ref().unwrap();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            assert_eq!(fs::read_dir(dir).unwrap().count(), 1);
+        })
+    });
+}
+
+#[test]
+fn caps_retry_delay() {
+    let base_dir = tempdir().unwrap();
+    let source = base_dir.path().join("foo.txt");
+    fs::write(&source, b"hello").unwrap();
+
+    let pipeline = Pipeline::builder()
+        .retries(40)
+        .retry_delay(Duration::from_secs(u64::MAX))
+        .max_retry_delay(Duration::from_millis(1))
+        .derivative("broken", |_: &Path, _: &Path| {
+            Err(io::Error::new(ErrorKind::InvalidData, "corrupt source"))
+        })
+        .build();
+
+    rt::run({
+        pipeline.process(source).map(|derivatives| {
+            assert_eq!(derivatives[0].attempts, 41);
+            assert!(derivatives[0].error.is_some());
+        })
+    });
+}
@@ -0,0 +1,127 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs::File as StdFile;
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn open_on_pool() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo");
+    let pool = Pool::new(1);
+
+    rt::run({
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .pool(&pool)
+            .open(path)
+            .and_then(|file| file.lock_exclusive())
+            .map(|_| ())
+    });
+
+    assert_eq!(pool.pending(), 0);
+}
+
+#[test]
+fn full_queue_is_rejected() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo");
+    let holder = StdFile::create(&path).unwrap();
+    holder.lock().unwrap();
+
+    let pool = Pool::builder().threads(1).queue_depth(1).build();
+    let mut opts = OpenOptions::new();
+    opts.read(true).pool(&pool);
+
+    rt::run({
+        let second = opts.clone();
+        opts.open(path.clone())
+            .and_then(move |waiting| second.open(path).map(|rejected| (waiting, rejected)))
+            .and_then(move |(waiting, rejected)| {
+                // Occupies the only thread until `holder` releases its lock.
+                let waiting = waiting.lock_exclusive();
+                let rejected = rejected.unlock().then(move |res| {
+                    assert_eq!(res.unwrap_err().kind(), ErrorKind::WouldBlock);
+                    drop(holder);
+                    Ok(())
+                });
+                waiting.join(rejected).map(|_| ())
+            })
+    });
+}
+
+#[test]
+fn spawn_blocking() {
+    let pool = Pool::new(1);
+
+    rt::run(
+        spawn_blocking_fs(|| Ok(1))
+            .join(pool.spawn_blocking(|| Ok(2)))
+            .map(|res| assert_eq!(res, (1, 2))),
+    );
+    rt::run(
+        spawn_blocking_fs(|| -> std::io::Result<()> {
+            Err(std::io::Error::new(ErrorKind::NotFound, "custom"))
+        })
+        .then(|res| {
+            let err = res.unwrap_err();
+            assert_eq!(
+                (err.kind(), err.to_string()),
+                (ErrorKind::NotFound, "custom".into())
+            );
+            Ok(())
+        }),
+    );
+    assert_eq!(pool.pending(), 0);
+}
+
+#[test]
+fn path_operations_on_pool() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path().join("a/b");
+    let pool = Pool::new(1);
+    // Rejects everything, so any operation reaching it fails.
+    let full = Pool::builder().threads(1).queue_depth(0).build();
+
+    let (p1, p2, p3, p4, p5) = (
+        pool.clone(),
+        pool.clone(),
+        pool.clone(),
+        pool.clone(),
+        pool.clone(),
+    );
+    let (file, renamed) = (dir.join("file"), dir.join("renamed"));
+    let (check, listed) = (renamed.clone(), dir.clone());
+    rt::run(
+        create_dir_all_on(&pool, dir.clone())
+            .and_then(move |()| {
+                std::fs::write(&file, b"x").unwrap();
+                rename_on(&p1, file, renamed)
+            })
+            .and_then(move |()| read_dir_collected_on(&p2, listed, &ReadDirOptions::new()))
+            .and_then(move |entries| {
+                assert_eq!(entries.len(), 1);
+                remove_file_on(&p3, check)
+            })
+            .and_then(move |()| create_dir_on(&p4, dir.join("c")).map(|()| dir))
+            .and_then(move |dir| remove_dir_on(&p5, dir.join("c")).map(|()| dir))
+            .and_then(move |dir| {
+                create_dir_on(&full, dir.join("d"))
+                    .join(remove_file_on(&full, dir.join("missing")))
+                    .then(|res| {
+                        assert_eq!(res.unwrap_err().kind(), ErrorKind::WouldBlock);
+                        Ok(())
+                    })
+            }),
+    );
+    assert_eq!(pool.pending(), 0);
+    assert_eq!(
+        std::fs::read_dir(base_dir.path().join("a/b"))
+            .unwrap()
+            .count(),
+        0
+    );
+}
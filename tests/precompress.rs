@@ -0,0 +1,129 @@
+#![cfg(all(feature = "gzip", feature = "brotli"))]
+
+use actix_fs::*;
+use flate2::read::GzDecoder;
+use futures::Future;
+use std::fs::{self, File as StdFile};
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn writes_variants() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path().to_owned();
+    fs::create_dir(dir.join("js")).unwrap();
+    fs::write(dir.join("js/app.js"), b"console.log('hello');").unwrap();
+
+    rt::run(
+        precompress(dir.clone(), &[Codec::Gzip, Codec::Brotli]).map(|report| {
+            assert_eq!(
+                report.written,
+                vec![PathBuf::from("js/app.js.br"), PathBuf::from("js/app.js.gz")]
+            );
+        }),
+    );
+
+    let mut gz = String::new();
+    GzDecoder::new(StdFile::open(dir.join("js/app.js.gz")).unwrap())
+        .read_to_string(&mut gz)
+        .unwrap();
+    assert_eq!(gz, "console.log('hello');");
+    let mut br = String::new();
+    brotli::Decompressor::new(StdFile::open(dir.join("js/app.js.br")).unwrap(), 4096)
+        .read_to_string(&mut br)
+        .unwrap();
+    assert_eq!(br, "console.log('hello');");
+}
+
+#[test]
+fn skips_fresh_variants() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path().to_owned();
+    let asset = dir.join("style.css");
+    fs::write(&asset, b"body {}").unwrap();
+
+    let again = dir.clone();
+    let touched = asset.clone();
+    rt::run(
+        precompress(dir.clone(), &[Codec::Gzip])
+            .and_then(move |_| precompress(again, &[Codec::Gzip]))
+            .map(|report| {
+                assert!(report.written.is_empty());
+                assert_eq!(report.fresh, 1);
+            })
+            .and_then(move |()| {
+                fs::write(&touched, b"body { margin: 0 }").unwrap();
+                let file = fs::OpenOptions::new().write(true).open(&touched).unwrap();
+                file.set_modified(SystemTime::now() + Duration::from_secs(10))
+                    .unwrap();
+                precompress(dir, &[Codec::Gzip])
+            })
+            .map(|report| assert_eq!(report.written, vec![PathBuf::from("style.css.gz")])),
+    );
+}
+
+#[test]
+fn removes_orphans() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path().to_owned();
+    fs::write(dir.join("gone.txt"), b"soon removed").unwrap();
+    fs::write(dir.join("tiny.txt"), b"soon truncated").unwrap();
+    fs::write(dir.join("kept.txt"), b"stays as it is").unwrap();
+    fs::write(dir.join("bundle.tar.gz"), b"a real archive").unwrap();
+    fs::write(dir.join("other.txt.br"), b"not ours").unwrap();
+
+    let again = dir.clone();
+    rt::run(
+        PrecompressOptions::new(&[Codec::Gzip])
+            .min_size(10)
+            .precompress(dir.clone())
+            .map(|report| assert_eq!(report.written.len(), 3))
+            .and_then(move |()| {
+                fs::remove_file(again.join("gone.txt")).unwrap();
+                fs::write(again.join("tiny.txt"), b"x").unwrap();
+                PrecompressOptions::new(&[Codec::Gzip])
+                    .min_size(10)
+                    .precompress(again)
+            })
+            .map(|report| {
+                assert!(report.written.is_empty());
+                assert_eq!(report.fresh, 1);
+                assert_eq!(
+                    report.removed,
+                    vec![PathBuf::from("gone.txt.gz"), PathBuf::from("tiny.txt.gz")]
+                );
+            }),
+    );
+
+    assert!(dir.join("kept.txt.gz").exists());
+    assert!(dir.join("bundle.tar.gz").exists());
+    assert!(dir.join("other.txt.br").exists());
+    assert!(!dir.join(".precompressed.gz").exists());
+}
+
+#[test]
+fn manifest_cannot_remove_other_files() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path().to_owned();
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("asset.js"), b"asset").unwrap();
+    fs::write(dir.join("download.tar.gz"), b"archive").unwrap();
+    fs::write(
+        dir.join("sub/.precompressed"),
+        b"../asset.js\n../download.tar.gz\nasset.js\n",
+    )
+    .unwrap();
+
+    rt::run(precompress(dir.clone(), &[Codec::Gzip]).map(|report| {
+        assert_eq!(report.written, vec![PathBuf::from("asset.js.gz")]);
+        assert!(report.removed.is_empty());
+    }));
+
+    assert!(dir.join("asset.js").exists());
+    assert!(dir.join("download.tar.gz").exists());
+    assert!(!dir.join("sub/.precompressed").exists());
+}
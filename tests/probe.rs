@@ -0,0 +1,134 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::time::Duration;
+use tempfile::tempdir;
+
+mod rt;
+
+fn probe_bytes(bytes: Vec<u8>) -> io::Result<MediaInfo> {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("media");
+    fs::write(&path, bytes).unwrap();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    rt::run(probe(path).then(move |res| {
+        tx.send(res).unwrap();
+        Ok(())
+    }));
+    rx.recv().unwrap()
+}
+
+fn dimensions(bytes: Vec<u8>) -> (MediaFormat, (u32, u32)) {
+    let info = probe_bytes(bytes).unwrap();
+    (info.format, info.dimensions.unwrap())
+}
+
+#[test]
+fn image_dimensions() {
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    png.extend_from_slice(&640u32.to_be_bytes());
+    png.extend_from_slice(&480u32.to_be_bytes());
+    assert_eq!(dimensions(png), (MediaFormat::Png, (640, 480)));
+
+    let gif = b"GIF89a\x20\x03\x58\x02\0\0".to_vec();
+    assert_eq!(dimensions(gif), (MediaFormat::Gif, (800, 600)));
+
+    let mut bmp = b"BM".to_vec();
+    bmp.extend_from_slice(&[0; 12]);
+    bmp.extend_from_slice(&40u32.to_le_bytes());
+    bmp.extend_from_slice(&100i32.to_le_bytes());
+    bmp.extend_from_slice(&(-50i32).to_le_bytes());
+    assert_eq!(dimensions(bmp), (MediaFormat::Bmp, (100, 50)));
+
+    let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0".to_vec();
+    webp.extend_from_slice(&[0xff, 0x03, 0x00, 0xff, 0x01, 0x00]);
+    assert_eq!(dimensions(webp), (MediaFormat::WebP, (1024, 512)));
+
+    // SOI, an APP0 segment to skip, then a baseline frame header.
+    let mut jpeg = b"\xff\xd8\xff\xe0\0\x04\0\0".to_vec();
+    jpeg.extend_from_slice(b"\xff\xc0\0\x11\x08\x01\xe0\x02\x80");
+    assert_eq!(dimensions(jpeg), (MediaFormat::Jpeg, (640, 480)));
+}
+
+#[test]
+fn media_durations() {
+    // 8 kHz mono 16-bit PCM, so 16000 bytes per second.
+    let mut wav = b"RIFF\0\0\0\0WAVEfmt \x10\0\0\0\x01\0\x01\0".to_vec();
+    wav.extend_from_slice(&8000u32.to_le_bytes());
+    wav.extend_from_slice(&16000u32.to_le_bytes());
+    wav.extend_from_slice(b"\x02\0\x10\0data");
+    wav.extend_from_slice(&24000u32.to_le_bytes());
+    let info = probe_bytes(wav).unwrap();
+    assert_eq!(info.format, MediaFormat::Wav);
+    assert_eq!(info.duration, Some(Duration::from_millis(1500)));
+
+    let mut mp4 = b"\0\0\0\x10ftypisom\0\0\0\0".to_vec();
+    mp4.extend_from_slice(b"\0\0\0\x10free\0\0\0\0\0\0\0\0");
+    mp4.extend_from_slice(b"\0\0\0\x24moov\0\0\0\x1cmvhd\0\0\0\0\0\0\0\0\0\0\0\0");
+    mp4.extend_from_slice(&1000u32.to_be_bytes());
+    mp4.extend_from_slice(&90_500u32.to_be_bytes());
+    let info = probe_bytes(mp4).unwrap();
+    assert_eq!(info.format, MediaFormat::Mp4);
+    assert_eq!(info.duration, Some(Duration::from_millis(90_500)));
+}
+
+#[test]
+fn unrecognized_or_truncated() {
+    let err = probe_bytes(b"hello world".to_vec()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+    let err = probe_bytes(b"\x89PNG\r\n\x1a\n\0\0".to_vec()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn mp4_64_bit_boxes() {
+    // A 64-bit sized moov box holding a version 1 movie header.
+    let mut mp4 = b"\0\0\0\x08ftyp\0\0\0\x01moov".to_vec();
+    mp4.extend_from_slice(&56u64.to_be_bytes());
+    mp4.extend_from_slice(b"\0\0\0\x28mvhd\x01\0\0\0");
+    mp4.extend_from_slice(&[0; 16]);
+    mp4.extend_from_slice(&1000u32.to_be_bytes());
+    mp4.extend_from_slice(&(3u64 << 32).to_be_bytes());
+    let info = probe_bytes(mp4).unwrap();
+    assert_eq!(info.duration, Some(Duration::from_millis(3 << 32)));
+
+    // A duration whose nanoseconds do not fit in 64 bits.
+    let mut mp4 = b"\0\0\0\x08ftyp\0\0\0\x01moov".to_vec();
+    mp4.extend_from_slice(&56u64.to_be_bytes());
+    mp4.extend_from_slice(b"\0\0\0\x28mvhd\x01\0\0\0");
+    mp4.extend_from_slice(&[0; 16]);
+    mp4.extend_from_slice(&3u32.to_be_bytes());
+    mp4.extend_from_slice(&u64::MAX.to_be_bytes());
+    let info = probe_bytes(mp4).unwrap();
+    assert_eq!(info.duration, Some(Duration::from_secs(u64::MAX / 3)));
+
+    // Sizes that would overflow the offset, or end inside their own header.
+    for &size in &[u64::MAX, u64::MAX - 7, 8, 0] {
+        let mut mp4 = b"\0\0\0\x08ftyp\0\0\0\x01moov".to_vec();
+        mp4.extend_from_slice(&size.to_be_bytes());
+        mp4.extend_from_slice(&[0; 32]);
+        let err = probe_bytes(mp4).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
+
+#[test]
+fn wav_skips_padded_chunks() {
+    // An odd sized LIST chunk, padded to an even size, before the data.
+    let mut wav = b"RIFF\0\0\0\0WAVEfmt \x10\0\0\0\x01\0\x01\0".to_vec();
+    wav.extend_from_slice(&8000u32.to_le_bytes());
+    wav.extend_from_slice(&8000u32.to_le_bytes());
+    wav.extend_from_slice(b"\x01\0\x08\0LIST\x03\0\0\0abc\0data");
+    wav.extend_from_slice(&4000u32.to_le_bytes());
+    let info = probe_bytes(wav).unwrap();
+    assert_eq!(info.duration, Some(Duration::from_millis(500)));
+
+    // A chunk claiming to run past the end of the file.
+    let mut wav = b"RIFF\0\0\0\0WAVEJUNK".to_vec();
+    wav.extend_from_slice(&u32::MAX.to_le_bytes());
+    let err = probe_bytes(wav).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
@@ -0,0 +1,183 @@
+#![cfg(feature = "actix-web")]
+
+use actix_fs::*;
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, web, App, HttpRequest, HttpResponse};
+use futures::Future;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::time::Duration;
+use tempfile::tempdir;
+
+macro_rules! service {
+    ($dest:expr, $opts:expr) => {{
+        let dest = $dest;
+        let opts = $opts;
+        test::init_service(App::new().route(
+            "/",
+            web::post().to_async(move |req: HttpRequest, payload: web::Payload| {
+                receive_file(&req, payload, dest.clone(), &opts)
+                    .map(|received| HttpResponse::Ok().body(received.len.to_string()))
+            }),
+        ))
+    }};
+}
+
+#[test]
+fn stores_body() {
+    let base_dir = tempdir().unwrap();
+    let dest = base_dir.path().join("upload.png");
+
+    let mut opts = ReceiveOptions::new();
+    opts.max_size(16)
+        .allow_content_type("image/*".parse().unwrap());
+    let mut srv = service!(dest.clone(), opts);
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .header(header::CONTENT_TYPE, "image/png")
+        .set_payload(&b"png data"[..])
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(test::read_body(res), "8");
+    assert_eq!(fs::read(&dest).unwrap(), b"png data");
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .header(header::CONTENT_TYPE, "text/plain")
+        .set_payload(&b"text"[..])
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[test]
+fn rejects_large_bodies() {
+    let base_dir = tempdir().unwrap();
+    let dest = base_dir.path().join("upload");
+    fs::write(&dest, b"old").unwrap();
+
+    let mut opts = ReceiveOptions::new();
+    opts.max_size(4);
+    let mut srv = service!(dest.clone(), opts);
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_payload(&b"too large"[..])
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    assert_eq!(fs::read(&dest).unwrap(), b"old");
+    assert_eq!(fs::read_dir(base_dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn scan_rejects_files() {
+    let base_dir = tempdir().unwrap();
+    let dest = base_dir.path().join("upload");
+
+    let mut opts = ReceiveOptions::new();
+    opts.scan(|path| {
+        if fs::read(path)?.starts_with(b"EICAR") {
+            return Err(io::Error::new(ErrorKind::InvalidData, "infected"));
+        }
+        Ok(())
+    });
+    let mut srv = service!(dest.clone(), opts);
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_payload(&b"EICAR test"[..])
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(!dest.exists());
+    assert_eq!(fs::read_dir(base_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn deadline_stops_late_renames() {
+    let base_dir = tempdir().unwrap();
+    let dest = base_dir.path().join("upload");
+    let root = Root::new(base_dir.path());
+
+    let mut opts = ReceiveOptions::new();
+    opts.scan(|_| {
+        std::thread::sleep(Duration::from_millis(300));
+        Ok(())
+    });
+    let to = dest.clone();
+    let mut srv = test::init_service(
+        App::new()
+            .wrap(
+                FsContextOptions::new()
+                    .timeout(Duration::from_millis(100))
+                    .middleware(root),
+            )
+            .route(
+                "/",
+                web::post().to_async(move |req: HttpRequest, payload: web::Payload| {
+                    receive_file(&req, payload, to.clone(), &opts)
+                        .map(|received| HttpResponse::Ok().body(received.len.to_string()))
+                }),
+            ),
+    );
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_payload(&b"slow"[..])
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    std::thread::sleep(Duration::from_millis(400));
+    assert!(!dest.exists());
+    assert_eq!(fs::read_dir(base_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn charges_budget_before_storing() {
+    let base_dir = tempdir().unwrap();
+    let dest = base_dir.path().join("upload");
+    fs::write(&dest, b"old").unwrap();
+    let root = Root::new(base_dir.path());
+
+    let opts = ReceiveOptions::new();
+    let to = dest.clone();
+    let mut srv = test::init_service(
+        App::new()
+            .wrap(FsContextOptions::new().budget(8).middleware(root))
+            .route(
+                "/",
+                web::post().to_async(move |req: HttpRequest, payload: web::Payload| {
+                    let ctx = req.extensions().get::<FsContext>().cloned().unwrap();
+                    let received = receive_file(&req, payload, to.clone(), &opts);
+                    // Spend most of the budget while the body is received.
+                    ctx.charge(6).unwrap();
+                    received.map(move |received| {
+                        assert_eq!(ctx.remaining(), Some(0));
+                        HttpResponse::Ok().body(received.len.to_string())
+                    })
+                }),
+            ),
+    );
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_payload(&b"new"[..])
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(fs::read(&dest).unwrap(), b"old");
+    assert_eq!(fs::read_dir(base_dir.path()).unwrap().count(), 1);
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_payload(&b"ok"[..])
+        .to_request();
+    let res = test::call_service(&mut srv, req);
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(fs::read(&dest).unwrap(), b"ok");
+}
@@ -0,0 +1,144 @@
+use actix_fs::*;
+use futures::{future, Future};
+use std::fs;
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn tenants_are_isolated() {
+    let base_dir = tempdir().unwrap();
+    let registry = RootRegistry::new();
+    registry.insert("a", base_dir.path().join("a"), &TenantOptions::new());
+    registry.insert(
+        "b",
+        base_dir.path().join("b"),
+        TenantOptions::new().label("plan", "free"),
+    );
+    assert_eq!(registry.ids(), ["a", "b"]);
+    let a = registry.get("a").unwrap();
+    let b = registry.get("b").unwrap();
+    assert_eq!(b.labels(), [("plan".to_owned(), "free".to_owned())]);
+
+    rt::run(
+        a.write("x/doc.txt", b"from a".to_vec())
+            .and_then(move |_| b.write("../a/x/doc.txt", b"from b".to_vec()))
+            .then(|res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::PermissionDenied);
+                Ok(())
+            }),
+    );
+
+    assert_eq!(
+        fs::read(base_dir.path().join("a/x/doc.txt")).unwrap(),
+        b"from a"
+    );
+    assert!(registry.remove("a").is_some());
+    assert!(registry.get("a").is_none());
+}
+
+#[test]
+fn quota_is_enforced() {
+    let base_dir = tempdir().unwrap();
+    fs::create_dir(base_dir.path().join("t")).unwrap();
+    fs::write(base_dir.path().join("t/existing"), vec![0; 40]).unwrap();
+    let registry = RootRegistry::new();
+    let tenant = registry.insert(
+        "t",
+        base_dir.path().join("t"),
+        TenantOptions::new().quota(100),
+    );
+
+    let (t1, t2, t3, t4) = (
+        tenant.clone(),
+        tenant.clone(),
+        tenant.clone(),
+        tenant.clone(),
+    );
+    rt::run(
+        tenant
+            .refresh_usage()
+            .and_then(move |used| {
+                assert_eq!(used, 40);
+                t1.write("new", vec![0; 50])
+            })
+            .and_then(move |_| t2.write("big", vec![0; 20]))
+            .then(move |res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::StorageFull);
+                assert_eq!(t3.used(), 90);
+                // Replacing a file only counts the difference.
+                t3.write("new", vec![0; 60])
+            })
+            .and_then(move |_| t4.remove_file("existing")),
+    );
+    assert_eq!(tenant.used(), 60);
+    assert!(!base_dir.path().join("big").exists());
+}
+
+#[test]
+fn concurrent_writes_respect_quota() {
+    let base_dir = tempdir().unwrap();
+    let registry = RootRegistry::new();
+    let tenant = registry.insert("t", base_dir.path(), TenantOptions::new().quota(100));
+
+    let writes: Vec<_> = (0..10)
+        .map(|i| {
+            tenant
+                .write(format!("{}", i), vec![0; 30])
+                .then(|res| Ok::<_, std::io::Error>(res.is_ok()))
+        })
+        .collect();
+    rt::run(future::join_all(writes).map(|written| {
+        assert_eq!(written.iter().filter(|&&ok| ok).count(), 3);
+    }));
+    assert_eq!(tenant.used(), 90);
+
+    // Replacing the same file concurrently counts it once.
+    let rewrites: Vec<_> = (0..10).map(|_| tenant.write("0", vec![0; 10])).collect();
+    rt::run(future::join_all(rewrites).map(|_| ()));
+    assert_eq!(tenant.used(), 70);
+    let on_disk: u64 = fs::read_dir(base_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().metadata().unwrap().len())
+        .sum();
+    assert_eq!(on_disk, 70);
+}
+
+#[test]
+fn policies() {
+    let base_dir = tempdir().unwrap();
+    fs::create_dir(base_dir.path().join("ro")).unwrap();
+    fs::write(base_dir.path().join("ro/doc"), b"doc").unwrap();
+    let registry = RootRegistry::new();
+    let ro = registry.insert(
+        "ro",
+        base_dir.path().join("ro"),
+        TenantOptions::new().policy(Policy::ReadOnly),
+    );
+    let once = registry.insert(
+        "once",
+        base_dir.path().join("once"),
+        TenantOptions::new().policy(Policy::WriteOnce),
+    );
+
+    let (once1, once2) = (once.clone(), once.clone());
+    rt::run(
+        ro.write("doc", b"new".to_vec())
+            .then(move |res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::PermissionDenied);
+                once.write("log", b"1".to_vec())
+            })
+            .and_then(move |_| once1.write("log", b"2".to_vec()))
+            .then(move |res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::AlreadyExists);
+                assert_eq!(once2.used(), 1);
+                once2.remove_file("log")
+            })
+            .then(|res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::PermissionDenied);
+                Ok(())
+            }),
+    );
+    assert_eq!(fs::read(base_dir.path().join("once/log")).unwrap(), b"1");
+}
@@ -0,0 +1,68 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn rename() {
+    let base_dir = tempdir().unwrap();
+    let from = base_dir.path().join("from");
+    let to = base_dir.path().join("to");
+    fs::write(&from, b"foo").unwrap();
+    fs::write(&to, b"old").unwrap();
+
+    rt::run(RetryOptions::new().rename(from.clone(), to.clone()));
+
+    assert!(!from.exists());
+    assert_eq!(fs::read(to).unwrap(), b"foo");
+}
+
+#[test]
+fn remove_file() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo");
+    fs::write(&path, b"foo").unwrap();
+
+    rt::run(RetryOptions::new().remove_file(path.clone()));
+
+    assert!(!path.exists());
+}
+
+#[test]
+fn permanent_errors_are_not_retried() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("missing");
+    let start = Instant::now();
+
+    rt::run(
+        RetryOptions::new()
+            .delay(Duration::from_secs(5))
+            .remove_file(path)
+            .then(move |res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::NotFound);
+                assert!(start.elapsed() < Duration::from_secs(5));
+                Ok(())
+            }),
+    );
+}
+
+#[test]
+fn huge_durations_do_not_overflow() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo");
+    fs::write(&path, b"foo").unwrap();
+
+    rt::run(
+        RetryOptions::new()
+            .timeout(Duration::MAX)
+            .delay(Duration::MAX)
+            .max_delay(Duration::MAX)
+            .remove_file(path.clone()),
+    );
+
+    assert!(!path.exists());
+}
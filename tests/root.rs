@@ -0,0 +1,59 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn resolve_rejects_escapes() {
+    let root = Root::new("/srv/data");
+
+    assert_eq!(
+        root.resolve("a/./b/../c").unwrap(),
+        root.path().join("a").join("c")
+    );
+    for path in &["../etc/passwd", "a/../../b", "/etc/passwd"] {
+        let err = root.resolve(path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    }
+}
+
+#[test]
+fn write_once_refuses_overwrite() {
+    let base_dir = tempdir().unwrap();
+    let audit = WriteOnce::new(base_dir.path());
+    let again = audit.clone();
+
+    rt::run({
+        audit
+            .create_dir_all("2019")
+            .and_then(move |_| audit.write("2019/1.log", b"first".to_vec()))
+            .and_then(move |_| {
+                again.write("2019/1.log", b"second".to_vec()).then(|res| {
+                    assert_eq!(res.unwrap_err().kind(), ErrorKind::AlreadyExists);
+                    Ok(())
+                })
+            })
+    });
+
+    let contents = fs::read(base_dir.path().join("2019").join("1.log")).unwrap();
+    assert_eq!(contents, b"first");
+}
+
+#[test]
+fn root_rename_stays_inside() {
+    let base_dir = tempdir().unwrap();
+    let root = Root::new(base_dir.path());
+    fs::write(base_dir.path().join("a"), b"a").unwrap();
+
+    rt::run({
+        root.rename("a", "../b").then(move |res| {
+            assert_eq!(res.unwrap_err().kind(), ErrorKind::PermissionDenied);
+            root.rename("a", "b")
+        })
+    });
+
+    assert!(base_dir.path().join("b").exists());
+}
@@ -0,0 +1,76 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tempfile::tempdir;
+
+mod rt;
+
+/// Returns the contents of the rotated files in `dir`, oldest first.
+fn rotated(dir: &Path) -> Vec<Vec<u8>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.file_name().unwrap() != "app.log")
+        .collect();
+    paths.sort();
+    paths.iter().map(|path| fs::read(path).unwrap()).collect()
+}
+
+#[test]
+fn rotates_by_size() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("app.log");
+    let file = RotateOptions::new().max_size(5).open(path.clone());
+
+    rt::run(
+        file.write(b"abc".to_vec())
+            .and_then({
+                let file = file.clone();
+                move |()| file.write(&b"def"[..])
+            })
+            .and_then(move |()| file.write("gh")),
+    );
+
+    assert_eq!(fs::read(&path).unwrap(), b"defgh");
+    assert_eq!(rotated(base_dir.path()), vec![b"abc".to_vec()]);
+}
+
+#[test]
+fn keeps_newest() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("app.log");
+    let file = RotateOptions::new().max_size(1).keep(2).open(path.clone());
+
+    let writes = (0..5).fold(
+        Box::new(futures::future::ok(())) as Box<dyn Future<Item = (), Error = _> + Send>,
+        |prev, i| {
+            let file = file.clone();
+            Box::new(prev.and_then(move |()| file.write(i.to_string())))
+        },
+    );
+    rt::run(writes);
+
+    assert_eq!(fs::read(&path).unwrap(), b"4");
+    assert_eq!(rotated(base_dir.path()), vec![b"2".to_vec(), b"3".to_vec()]);
+}
+
+#[test]
+fn rotates_at_boundaries() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("app.log");
+    fs::write(&path, b"yesterday").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(2 * 86_400))
+        .unwrap();
+
+    let file = RotatingFile::new(path.clone());
+    rt::run(file.write("today").and_then(move |()| file.flush()));
+
+    assert_eq!(fs::read(&path).unwrap(), b"today");
+    assert_eq!(rotated(base_dir.path()), vec![b"yesterday".to_vec()]);
+}
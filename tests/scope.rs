@@ -0,0 +1,68 @@
+use actix_fs::*;
+use futures::{future, Future};
+use std::fs;
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn awaits_all_futures() {
+    let base_dir = tempdir().unwrap();
+    let (foo, bar) = (base_dir.path().join("foo"), base_dir.path().join("bar"));
+    let (file, dir) = (foo.clone(), bar.clone());
+
+    rt::run(scope(move |s| {
+        s.spawn(File::create(file));
+        s.spawn(create_dir(dir));
+    }));
+    assert!(foo.is_file() && bar.is_dir());
+}
+
+#[test]
+fn first_error_cancels_siblings() {
+    let base_dir = tempdir().unwrap();
+    let missing = base_dir.path().join("missing");
+    let token = CancellationToken::new();
+
+    rt::run(
+        ScopeOptions::new()
+            .cancellation(token.clone())
+            .run(move |s| {
+                s.spawn(future::empty::<(), _>());
+                s.spawn(remove_file(missing));
+            })
+            .then(|res| {
+                let err = res.unwrap_err();
+                assert_eq!(err.kind(), ErrorKind::NotFound);
+                assert_eq!(ScopeError::from_io(&err).unwrap().errors().len(), 1);
+                Ok(())
+            }),
+    );
+    assert!(token.is_cancelled());
+}
+
+#[test]
+fn keep_going_aggregates_errors() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().to_owned();
+    let foo = path.join("foo");
+    let created = foo.clone();
+    fs::write(path.join("a"), b"a").unwrap();
+
+    rt::run(
+        ScopeOptions::new()
+            .keep_going(true)
+            .run(move |s| {
+                s.spawn(remove_file(path.join("missing")));
+                s.spawn(File::create(created));
+                s.spawn(remove_dir(path.join("a")));
+            })
+            .then(move |res| {
+                let err = res.unwrap_err();
+                assert_eq!(ScopeError::from_io(&err).unwrap().errors().len(), 2);
+                assert!(foo.is_file());
+                Ok(())
+            }),
+    );
+}
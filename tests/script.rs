@@ -0,0 +1,117 @@
+use actix_fs::*;
+use futures::{stream, Future};
+use std::fs;
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn runs_steps() {
+    let base_dir = tempdir().unwrap();
+    fs::write(base_dir.path().join("old"), b"old").unwrap();
+    let root = Root::new(base_dir.path());
+
+    let steps = vec![
+        Step::Mkdir("a/b".into()),
+        Step::put(
+            "a/b/foo",
+            stream::iter_ok(vec![b"foo".to_vec(), b"bar".to_vec()]),
+        ),
+        Step::Chmod("a/b/foo".into(), 0o600),
+        Step::Rename("a/b/foo".into(), "a/bar".into()),
+        Step::Remove("old".into()),
+        Step::Remove("a/b".into()),
+    ];
+    rt::run(run_script(&root, steps).map(|outcomes| {
+        assert_eq!(outcomes.len(), 6);
+        assert!(outcomes.iter().all(StepOutcome::is_done));
+    }));
+
+    assert_eq!(fs::read(base_dir.path().join("a/bar")).unwrap(), b"foobar");
+    assert!(!base_dir.path().join("a/b").exists());
+    assert!(!base_dir.path().join("old").exists());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let metadata = fs::metadata(base_dir.path().join("a/bar")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn stops_on_error() {
+    let base_dir = tempdir().unwrap();
+    let root = Root::new(base_dir.path());
+
+    let steps = vec![
+        Step::Mkdir("a".into()),
+        Step::Remove("missing".into()),
+        Step::Mkdir("b".into()),
+    ];
+    rt::run(run_script(&root, steps).map(|outcomes| {
+        assert!(outcomes[0].is_done());
+        assert_eq!(outcomes[1].err().unwrap().kind(), ErrorKind::NotFound);
+        assert!(matches!(outcomes[2], StepOutcome::Skipped));
+    }));
+
+    assert!(!base_dir.path().join("b").exists());
+}
+
+#[test]
+fn keeps_going() {
+    let base_dir = tempdir().unwrap();
+    let root = Root::new(base_dir.path());
+
+    let steps = vec![
+        Step::Mkdir("../escape".into()),
+        Step::put("foo", stream::once(Ok(b"foo".to_vec()))),
+    ];
+    rt::run(
+        ScriptOptions::new()
+            .keep_going(true)
+            .run(&root, steps)
+            .map(|outcomes| {
+                assert_eq!(
+                    outcomes[0].err().unwrap().kind(),
+                    ErrorKind::PermissionDenied
+                );
+                assert!(outcomes[1].is_done());
+            }),
+    );
+
+    assert_eq!(fs::read(base_dir.path().join("foo")).unwrap(), b"foo");
+    assert!(!base_dir.path().join("foo.tmp").exists());
+}
+
+#[test]
+fn refuses_the_root() {
+    let base_dir = tempdir().unwrap();
+    let root_dir = base_dir.path().join("root");
+    fs::create_dir(&root_dir).unwrap();
+    fs::write(root_dir.join("foo"), b"foo").unwrap();
+    let root = Root::new(&root_dir);
+
+    let steps = vec![
+        Step::Remove("".into()),
+        Step::Remove(".".into()),
+        Step::Remove("a/..".into()),
+        Step::Rename(".".into(), "moved".into()),
+        Step::Rename("foo".into(), "".into()),
+        Step::Chmod("".into(), 0o000),
+    ];
+    rt::run(
+        ScriptOptions::new()
+            .keep_going(true)
+            .run(&root, steps)
+            .map(|outcomes| {
+                for outcome in outcomes {
+                    assert_eq!(outcome.err().unwrap().kind(), ErrorKind::PermissionDenied);
+                }
+            }),
+    );
+
+    assert_eq!(fs::read(root_dir.join("foo")).unwrap(), b"foo");
+    assert!(!root_dir.join("moved").exists());
+}
@@ -0,0 +1,125 @@
+use actix_fs::*;
+use futures::{Future, Stream};
+use std::fs;
+use std::time::Duration;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn verify_unchanged() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("app.conf");
+    fs::write(&path, b"listen = 8080").unwrap();
+
+    let sentinel = Sentinel::new();
+    let verify = sentinel.clone();
+    let check = path.clone();
+
+    rt::run(
+        sentinel
+            .register(path)
+            .and_then(move |_| verify.verify(check).map(|alert| assert_eq!(alert, None))),
+    );
+}
+
+#[test]
+fn verify_detects_change_and_removal() {
+    let base_dir = tempdir().unwrap();
+    let changed = base_dir.path().join("app.conf");
+    let removed = base_dir.path().join("app.key");
+    fs::write(&changed, b"listen = 8080").unwrap();
+    fs::write(&removed, b"secret").unwrap();
+
+    let sentinel = Sentinel::new();
+    rt::run(
+        sentinel
+            .register(changed.clone())
+            .join(sentinel.register(removed.clone()))
+            .map(|_| ()),
+    );
+
+    fs::write(&changed, b"listen = 8081").unwrap();
+    fs::remove_file(&removed).unwrap();
+
+    let (changed_alert, removed_alert) = (changed.clone(), removed.clone());
+    rt::run(
+        sentinel
+            .verify(changed)
+            .join(sentinel.verify(removed))
+            .map(move |(a, b)| {
+                match a {
+                    Some(Alert::Changed { path, .. }) => assert_eq!(path, changed_alert),
+                    other => panic!("unexpected alert: {:?}", other),
+                }
+                assert_eq!(
+                    b,
+                    Some(Alert::Missing {
+                        path: removed_alert
+                    })
+                );
+            }),
+    );
+}
+
+#[test]
+fn watch_reports_change_once() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("app.conf");
+    fs::write(&path, b"listen = 8080").unwrap();
+
+    let sentinel = Sentinel::new();
+    rt::run(sentinel.register(path.clone()).map(|_| ()));
+    fs::write(&path, b"listen = 8081").unwrap();
+
+    rt::run(
+        sentinel
+            .watch(Duration::from_millis(10))
+            .take(1)
+            .collect()
+            .and_then(move |alerts| {
+                assert_eq!(alerts.len(), 1);
+                sentinel.verify_all()
+            })
+            .map(|alerts| assert_eq!(alerts.len(), 1)),
+    );
+}
+
+#[test]
+fn watch_reports_unreadable_files_and_continues() {
+    let base_dir = tempdir().unwrap();
+    let broken = base_dir.path().join("broken.conf");
+    let changed = base_dir.path().join("app.conf");
+    fs::write(&broken, b"user = www").unwrap();
+    fs::write(&changed, b"listen = 8080").unwrap();
+
+    let sentinel = Sentinel::new();
+    rt::run(
+        sentinel
+            .register(broken.clone())
+            .join(sentinel.register(changed.clone()))
+            .map(|_| ()),
+    );
+    // Opening a directory succeeds but reading it fails.
+    fs::remove_file(&broken).unwrap();
+    fs::create_dir(&broken).unwrap();
+    fs::write(&changed, b"listen = 8081").unwrap();
+
+    rt::run(
+        sentinel
+            .watch(Duration::from_millis(10))
+            .take(2)
+            .collect()
+            .map(move |alerts| {
+                let unreadable = alerts.iter().any(|alert| match alert {
+                    Alert::Unreadable { path, .. } => *path == broken,
+                    _ => false,
+                });
+                let changed = alerts.iter().any(|alert| match alert {
+                    Alert::Changed { path, .. } => *path == changed,
+                    _ => false,
+                });
+                assert!(unreadable && changed);
+            }),
+    );
+}
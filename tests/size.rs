@@ -0,0 +1,91 @@
+use actix_fs::*;
+use futures::{Future, Stream};
+use std::fs;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn counts_files() {
+    let base_dir = tempdir().unwrap();
+    fs::create_dir_all(base_dir.path().join("a/b")).unwrap();
+    fs::write(base_dir.path().join("foo"), b"foo").unwrap();
+    fs::write(base_dir.path().join("a/bar"), b"bar").unwrap();
+    fs::write(base_dir.path().join("a/b/baz"), b"bazbaz").unwrap();
+
+    rt::run(dir_size(base_dir.path().to_owned()).map(|size| {
+        assert_eq!(
+            size,
+            DirSize {
+                bytes: 12,
+                files: 3
+            }
+        );
+    }));
+}
+
+#[cfg(unix)]
+#[test]
+fn follows_symlinks() {
+    let base_dir = tempdir().unwrap();
+    let outside = tempdir().unwrap();
+    fs::write(outside.path().join("foo"), b"foo").unwrap();
+    std::os::unix::fs::symlink(outside.path(), base_dir.path().join("link")).unwrap();
+    // A cycle back to the root is only walked once.
+    std::os::unix::fs::symlink(base_dir.path(), base_dir.path().join("loop")).unwrap();
+
+    let not_followed = dir_size(base_dir.path().to_owned()).map(|size| assert_eq!(size.files, 2));
+    let followed = DirSizeOptions::new()
+        .follow_symlinks(true)
+        .size(base_dir.path().to_owned())
+        .map(|size| assert_eq!(size, DirSize { bytes: 3, files: 1 }));
+    rt::run(not_followed.join(followed).map(|_| ()));
+}
+
+#[test]
+fn reports_progress() {
+    let base_dir = tempdir().unwrap();
+    for i in 0..2000 {
+        fs::write(base_dir.path().join(i.to_string()), b"x").unwrap();
+    }
+
+    rt::run(
+        DirSizeOptions::new()
+            .progress(base_dir.path().to_owned())
+            .collect()
+            .map(|totals| {
+                assert!(totals.len() > 1);
+                assert!(totals.windows(2).all(|pair| pair[0].files <= pair[1].files));
+                assert_eq!(
+                    totals.last(),
+                    Some(&DirSize {
+                        bytes: 2000,
+                        files: 2000
+                    })
+                );
+            }),
+    );
+}
+
+#[test]
+fn pages_large_directories() {
+    let base_dir = tempdir().unwrap();
+    let dir = base_dir.path().join("big");
+    fs::create_dir(&dir).unwrap();
+    for i in 0..3000 {
+        fs::write(dir.join(i.to_string()), b"x").unwrap();
+    }
+
+    rt::run(
+        DirSizeOptions::new()
+            .progress(base_dir.path().to_owned())
+            .collect()
+            .map(|totals| {
+                assert!(totals.len() > 3);
+                assert!(totals
+                    .windows(2)
+                    .all(|pair| pair[1].files - pair[0].files <= 1024));
+                assert_eq!(totals.last().unwrap().files, 3000);
+            }),
+    );
+}
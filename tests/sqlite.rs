@@ -0,0 +1,70 @@
+#![cfg(feature = "sqlite")]
+
+use actix_fs::*;
+use futures::Future;
+use rusqlite::Connection;
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+mod rt;
+
+fn count(path: &std::path::Path) -> i64 {
+    let conn = Connection::open(path).unwrap();
+    conn.query_row("SELECT COUNT(*) FROM events", [], |row| row.get(0))
+        .unwrap()
+}
+
+#[test]
+fn backup_live_wal_database() {
+    let base_dir = tempdir().unwrap();
+    let db = base_dir.path().join("app.db");
+    let dest = base_dir.path().join("backup.db");
+
+    // Keep the connection open so the rows stay in the write-ahead log.
+    let conn = Connection::open(&db).unwrap();
+    conn.pragma_update(None, "journal_mode", "wal").unwrap();
+    conn.execute_batch("PRAGMA wal_autocheckpoint = 0; CREATE TABLE events (id INTEGER);")
+        .unwrap();
+    for id in 0..100 {
+        conn.execute("INSERT INTO events VALUES (?1)", [id])
+            .unwrap();
+    }
+
+    rt::run(backup_sqlite(db, dest.clone()));
+
+    assert_eq!(count(&dest), 100);
+    drop(conn);
+}
+
+#[test]
+fn backup_replaces_destination() {
+    let base_dir = tempdir().unwrap();
+    let db = base_dir.path().join("app.db");
+    let dest = base_dir.path().join("backup.db");
+    std::fs::write(&dest, b"stale").unwrap();
+
+    let conn = Connection::open(&db).unwrap();
+    conn.execute_batch("CREATE TABLE events (id INTEGER); INSERT INTO events VALUES (1);")
+        .unwrap();
+
+    rt::run(backup_sqlite(db, dest.clone()));
+
+    assert_eq!(count(&dest), 1);
+    assert!(!base_dir.path().join("backup.db.tmp").exists());
+}
+
+#[test]
+fn backup_missing_database() {
+    let base_dir = tempdir().unwrap();
+    let db = base_dir.path().join("missing.db");
+    let dest = base_dir.path().join("backup.db");
+    let check = dest.clone();
+
+    rt::run({
+        backup_sqlite(db, dest).then(move |res| {
+            assert_eq!(res.unwrap_err().kind(), ErrorKind::NotFound);
+            assert!(!check.exists());
+            Ok(())
+        })
+    });
+}
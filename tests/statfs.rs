@@ -0,0 +1,39 @@
+use actix_fs::*;
+use futures::Future;
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn reports_space() {
+    let base_dir = tempdir().unwrap();
+
+    rt::run(statfs(base_dir.path().to_owned()).map(|stats| {
+        assert!(stats.total > 0);
+        assert!(stats.free <= stats.total);
+        assert!(stats.available <= stats.free);
+    }));
+}
+
+#[test]
+fn accepts_files() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo");
+    std::fs::write(&path, b"foo").unwrap();
+
+    rt::run(statfs(path).map(|stats| assert!(stats.total > 0)));
+}
+
+#[test]
+fn missing_path() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("missing");
+
+    rt::run(statfs(path.clone()).then(move |res| {
+        let err = res.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(Error::from_io(&err).unwrap().path(), Some(path.as_path()));
+        Ok(())
+    }));
+}
@@ -0,0 +1,102 @@
+use actix_fs::*;
+use futures::future::{self, Loop};
+use futures::{Future, Stream};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+mod rt;
+
+/// Collects data from `stream` until it has `len` bytes.
+fn collect<S>(stream: S, len: usize) -> impl Future<Item = Vec<u8>, Error = io::Error>
+where
+    S: Stream<Item = Vec<u8>, Error = io::Error>,
+{
+    future::loop_fn((stream, Vec::new()), move |(stream, mut buf)| {
+        stream
+            .into_future()
+            .map_err(|(err, _)| err)
+            .map(move |(data, stream)| {
+                buf.extend(data.unwrap());
+                if buf.len() >= len {
+                    Loop::Break(buf)
+                } else {
+                    Loop::Continue((stream, buf))
+                }
+            })
+    })
+}
+
+fn append(path: &Path, data: &[u8]) {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap();
+    file.write_all(data).unwrap();
+}
+
+/// Runs `f` on another thread after the stream had time to start.
+fn later<F>(path: PathBuf, f: F)
+where
+    F: FnOnce(&Path) + Send + 'static,
+{
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        f(&path);
+    });
+}
+
+#[test]
+fn follows_appended_data() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("app.log");
+    fs::write(&path, b"old\n").unwrap();
+
+    later(path.clone(), |path| {
+        append(path, b"one\n");
+        thread::sleep(Duration::from_millis(50));
+        append(path, b"two\n");
+    });
+    let stream = TailOptions::new()
+        .interval(Duration::from_millis(10))
+        .tail(path);
+    rt::run(collect(stream, 8).map(|data| assert_eq!(data, b"one\ntwo\n")));
+}
+
+#[test]
+fn rereads_truncated_files() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("app.log");
+    fs::write(&path, b"first\n").unwrap();
+
+    later(path.clone(), |path| fs::write(path, b"new\n").unwrap());
+    let stream = TailOptions::new()
+        .interval(Duration::from_millis(10))
+        .from_start(true)
+        .tail(path);
+    rt::run(collect(stream, 10).map(|data| assert_eq!(data, b"first\nnew\n")));
+}
+
+#[cfg(unix)]
+#[test]
+fn follows_rotated_files() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("app.log");
+    fs::write(&path, b"").unwrap();
+
+    later(path.clone(), |path| {
+        append(path, b"a\n");
+        thread::sleep(Duration::from_millis(50));
+        fs::rename(path, path.with_extension("log.1")).unwrap();
+        append(&path.with_extension("log.1"), b"b\n");
+        fs::write(path, b"c\n").unwrap();
+    });
+    let stream = TailOptions::new()
+        .interval(Duration::from_millis(10))
+        .tail(path);
+    rt::run(collect(stream, 6).map(|data| assert_eq!(data, b"a\nb\nc\n")));
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "testing")]
+
+use actix_fs::testing::*;
+use futures::Future;
+use std::fs;
+use std::panic;
+
+mod rt;
+
+#[test]
+fn sandbox_with_tree() {
+    let tree = Tree::new().file("a/b.txt", "b").dir("empty");
+    rt::run(Sandbox::with_tree(tree.clone()).map(move |sandbox| {
+        assert_file_eq(sandbox.join("a/b.txt"), "b");
+        assert_tree_matches(sandbox.path(), &tree);
+        assert!(sandbox.root().resolve("a/b.txt").is_ok());
+
+        let path = sandbox.path().to_owned();
+        drop(sandbox);
+        assert!(!path.exists());
+    }));
+}
+
+#[test]
+fn tree_reads_back() {
+    rt::run(
+        Sandbox::new()
+            .and_then(|sandbox| {
+                fs::create_dir(sandbox.join("dir")).unwrap();
+                fs::write(sandbox.join("dir/foo"), b"foo").unwrap();
+                Tree::read(sandbox.path().to_owned()).map(|tree| (sandbox, tree))
+            })
+            .map(|(_sandbox, tree)| {
+                assert_eq!(tree, Tree::new().file("dir/foo", "foo"));
+            }),
+    );
+}
+
+#[test]
+fn mismatches_panic() {
+    rt::run(
+        Sandbox::with_tree(Tree::new().file("foo", "foo")).map(|sandbox| {
+            let path = sandbox.join("foo");
+            assert!(panic::catch_unwind(|| assert_file_eq(&path, "bar")).is_err());
+
+            let root = sandbox.path();
+            let extra = Tree::new().file("foo", "foo").file("bar", "bar");
+            assert!(panic::catch_unwind(|| assert_tree_matches(root, &extra)).is_err());
+            let fewer = Tree::new();
+            assert!(panic::catch_unwind(|| assert_tree_matches(root, &fewer)).is_err());
+        }),
+    );
+}
+
+#[test]
+fn tree_rejects_escaping_paths() {
+    for path in &["", "/etc/passwd", "../outside", "a/../../outside", "./a"] {
+        assert!(panic::catch_unwind(|| Tree::new().file(path, "x")).is_err());
+        assert!(panic::catch_unwind(|| Tree::new().dir(path)).is_err());
+    }
+    assert_eq!(
+        Tree::new().file("a/b", "x"),
+        Tree::new().dir("a").file("a/b", "x")
+    );
+}
@@ -0,0 +1,154 @@
+use actix_fs::*;
+use futures::Future;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod rt;
+
+fn tiers(base: &Path) -> Tiers {
+    let hot = Root::new(base.join("hot"));
+    let cold = Root::new(base.join("cold"));
+    Tiers::new(move |temperature, _: &Path| match temperature {
+        Temperature::Hot => hot.clone(),
+        Temperature::Warm | Temperature::Cold => cold.clone(),
+    })
+}
+
+#[test]
+fn create_in_chosen_tier() {
+    let base_dir = tempdir().unwrap();
+    let tiers = tiers(base_dir.path());
+    let check = base_dir.path().join("hot/videos/42.mp4");
+
+    rt::run(
+        tiers
+            .create("videos/42.mp4", Temperature::Hot)
+            .and_then(|file| file.write_all(b"hello".to_vec()))
+            .and_then(|file| file.close())
+            .and_then(move |()| tiers.locate("videos/42.mp4"))
+            .map(|found| assert_eq!(found, Some(Temperature::Hot))),
+    );
+
+    assert_eq!(fs::read(check).unwrap(), b"hello");
+}
+
+#[test]
+fn migrate_between_tiers() {
+    let base_dir = tempdir().unwrap();
+    let tiers = tiers(base_dir.path());
+    fs::create_dir_all(base_dir.path().join("hot/videos")).unwrap();
+    fs::write(base_dir.path().join("hot/videos/42.mp4"), b"hello").unwrap();
+    let reader = tiers.clone();
+
+    rt::run(
+        tiers
+            .migrate("videos/42.mp4", Temperature::Cold)
+            .and_then(move |()| tiers.migrate("videos/42.mp4", Temperature::Warm))
+            .and_then(move |()| reader.open("videos/42.mp4"))
+            .and_then(|file| file.read(16))
+            .map(|(_, buf)| assert_eq!(buf, b"hello")),
+    );
+
+    assert!(!base_dir.path().join("hot/videos/42.mp4").exists());
+    assert!(base_dir.path().join("cold/videos/42.mp4").exists());
+}
+
+#[test]
+fn migrate_missing_file() {
+    let base_dir = tempdir().unwrap();
+    let tiers = tiers(base_dir.path());
+
+    rt::run(tiers.migrate("missing", Temperature::Cold).then(|res| {
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::NotFound);
+        Ok(())
+    }));
+}
+
+#[test]
+fn shared_root_reports_hottest_temperature() {
+    let base_dir = tempdir().unwrap();
+    let tiers = tiers(base_dir.path());
+    fs::create_dir_all(base_dir.path().join("cold")).unwrap();
+    fs::write(base_dir.path().join("cold/doc"), b"").unwrap();
+
+    rt::run(
+        tiers
+            .locate("doc")
+            .map(|found| assert_eq!(found, Some(Temperature::Warm))),
+    );
+}
+
+#[test]
+fn directories_are_not_files() {
+    let base_dir = tempdir().unwrap();
+    let tiers = tiers(base_dir.path());
+    fs::create_dir_all(base_dir.path().join("hot/videos")).unwrap();
+    let locator = tiers.clone();
+
+    rt::run(
+        tiers
+            .migrate("videos", Temperature::Cold)
+            .then(move |res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::InvalidInput);
+                locator.locate("videos")
+            })
+            .then(|res| {
+                assert_eq!(res.unwrap_err().kind(), ErrorKind::InvalidInput);
+                Ok(())
+            }),
+    );
+    assert!(base_dir.path().join("hot/videos").is_dir());
+    assert!(!base_dir.path().join("cold/videos").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn migrate_across_filesystems() {
+    use std::os::unix::fs::MetadataExt;
+    use std::time::{Duration, SystemTime};
+
+    // Needs a second filesystem, such as the tmpfs usually at /dev/shm.
+    let hot_dir = match tempfile::tempdir_in("/dev/shm") {
+        Ok(dir) => dir,
+        Err(_) => return,
+    };
+    let cold_dir = tempdir().unwrap();
+    let dev = |path: &Path| fs::metadata(path).unwrap().dev();
+    if dev(hot_dir.path()) == dev(cold_dir.path()) {
+        return;
+    }
+    let hot = Root::new(hot_dir.path());
+    let cold = Root::new(cold_dir.path());
+    let tiers = Tiers::new(move |temperature, _: &Path| match temperature {
+        Temperature::Hot => hot.clone(),
+        Temperature::Warm | Temperature::Cold => cold.clone(),
+    });
+    #[cfg(feature = "sha2")]
+    let tiers = tiers.verify(hash::Algorithm::Sha256, hash::Verify::Readback);
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    fs::create_dir(hot_dir.path().join("videos")).unwrap();
+    let file = fs::File::create(hot_dir.path().join("videos/42.mp4")).unwrap();
+    fs::write(hot_dir.path().join("videos/42.mp4"), b"hello").unwrap();
+    file.set_modified(modified).unwrap();
+    let locator = tiers.clone();
+
+    rt::run(
+        tiers
+            .migrate("videos/42.mp4", Temperature::Cold)
+            .and_then(move |()| locator.locate("videos/42.mp4"))
+            .map(|found| assert_eq!(found, Some(Temperature::Warm))),
+    );
+
+    let moved = cold_dir.path().join("videos/42.mp4");
+    assert_eq!(fs::read(&moved).unwrap(), b"hello");
+    assert_eq!(fs::metadata(&moved).unwrap().modified().unwrap(), modified);
+    assert!(!hot_dir.path().join("videos/42.mp4").exists());
+    assert_eq!(
+        fs::read_dir(cold_dir.path().join("videos"))
+            .unwrap()
+            .count(),
+        1
+    );
+}
@@ -0,0 +1,42 @@
+#![cfg(feature = "watch")]
+
+use actix_fs::*;
+use futures::{Future, Stream};
+use std::time::Duration;
+use std::{fs, thread};
+use tempfile::tempdir;
+
+mod rt;
+
+#[test]
+fn reports_created_file() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("template.html");
+    let expected = path.clone();
+
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(500));
+        fs::write(path, b"<html>").unwrap();
+    });
+
+    rt::run({
+        WatchOptions::new()
+            .delay(Duration::from_millis(50))
+            .watch(base_dir.path().to_path_buf())
+            .filter(|event| matches!(event, Event::Create(_)))
+            .take(1)
+            .collect()
+            .map(move |events| assert_eq!(events, vec![Event::Create(expected)]))
+    });
+}
+
+#[test]
+fn missing_path_is_an_error() {
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("missing");
+
+    let mut sys = actix_rt::System::new("test");
+    let res = sys.block_on(watch(path).into_future().map_err(|(err, _)| err));
+
+    assert!(res.is_err());
+}
@@ -0,0 +1,66 @@
+#![cfg(unix)]
+
+use actix_fs::*;
+use futures::Future;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tempfile::tempdir;
+
+mod rt;
+
+/// Runs `f` unless the filesystem of the temporary directory does not support
+/// user extended attributes.
+fn with_xattrs<F, R>(f: F)
+where
+    F: FnOnce(std::path::PathBuf) -> R,
+    R: Future<Item = (), Error = io::Error> + Send + 'static,
+{
+    let base_dir = tempdir().unwrap();
+    let path = base_dir.path().join("foo.txt");
+    fs::write(&path, b"foo").unwrap();
+
+    let supported = Arc::new(AtomicBool::new(false));
+    let probe = supported.clone();
+    rt::run(
+        set_xattr(path.clone(), "user.probe", Vec::new()).then(move |res| {
+            probe.store(res.is_ok(), Ordering::SeqCst);
+            Ok(())
+        }),
+    );
+    if supported.load(Ordering::SeqCst) {
+        rt::run(f(path));
+    }
+}
+
+#[test]
+fn set_and_get() {
+    with_xattrs(|path| {
+        let get = path.clone();
+        set_xattr(path, "user.origin", b"https://example.com/foo".to_vec())
+            .and_then(move |_| get_xattr(get, "user.origin"))
+            .map(|value| assert_eq!(value.unwrap(), b"https://example.com/foo"))
+    });
+}
+
+#[test]
+fn missing_attribute() {
+    with_xattrs(|path| get_xattr(path, "user.missing").map(|value| assert_eq!(value, None)));
+}
+
+#[test]
+fn list_and_remove() {
+    with_xattrs(|path| {
+        let (list, remove, relist) = (path.clone(), path.clone(), path.clone());
+        set_xattr(path, "user.sha256", b"abc".to_vec())
+            .and_then(move |_| list_xattr(list))
+            .and_then(move |names| {
+                assert!(names.contains(&OsString::from("user.sha256")));
+                remove_xattr(remove, "user.sha256")
+            })
+            .and_then(move |_| list_xattr(relist))
+            .map(|names| assert!(!names.contains(&OsString::from("user.sha256"))))
+    });
+}